@@ -0,0 +1,251 @@
+use crate::config::ReviewConfig;
+use crate::{Chat, dump_message};
+use log::{info, warn};
+use openai::Credentials;
+use openai::chat::{ChatCompletion, ChatCompletionMessage, ChatCompletionMessageRole};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::sync::Mutex;
+
+/// Agents the conversion is split between, each with its own instructions, conversation and
+/// functions.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Role {
+    /// analyzes the source project and owns the conversion plan
+    Planner,
+    /// writes the destination project following the plan
+    Coder,
+    /// critiques the changes of the coder before they are written
+    Reviewer,
+}
+
+impl Role {
+    /// Instructions added to the system prompt of the agent.
+    fn instructions(self) -> &'static str {
+        match self {
+            Role::Planner => {
+                "You are the planner. Analyze the source project and write the conversion plan \
+                with plan_write; you own the plan. You can't change the destination project, the \
+                coder converts the modules in the order of your plan."
+            }
+            Role::Coder => {
+                "You are the coder. Convert the modules of the conversion plan written by the \
+                planner and mark them done with plan_update; you can't rewrite the plan. A \
+                reviewer checks every change of a file before it's written, if it sends comments \
+                back, address them and write the file again."
+            }
+            Role::Reviewer => {
+                "You are the reviewer of a conversion of a project to Rust. You get the proposed \
+                contents of a file of the Rust project and the change against the current file as \
+                a unified diff. Check the correctness: the logic, the edge cases and the error \
+                handling, and that nothing is left out or replaced by a placeholder. Check that \
+                it's idiomatic Rust: ownership instead of needless clones, `Result` instead of \
+                panics, iterators, the standard traits. Check the unsafe code: it must be \
+                necessary, as small as possible and explained with a `SAFETY:` comment. Start the \
+                reply with APPROVE if the change can be written as it is, or with REJECT followed \
+                by a short list of the problems that must be fixed. Don't reject a change for \
+                matters of taste."
+            }
+        }
+    }
+
+    /// Whether the agent may call a function, given its name and whether it changes the
+    /// destination project.
+    pub fn allows(self, name: &str, mutating: bool) -> bool {
+        match self {
+            Role::Planner => !mutating,
+            Role::Coder => name != "plan_write",
+            // The reviewer gets the changes in its messages.
+            Role::Reviewer => false,
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Role::Planner => "planner",
+            Role::Coder => "coder",
+            Role::Reviewer => "reviewer",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Hands the chat over between the planner and the coder. The functions stay registered in the
+/// chat, only the access to them changes; the conversations are kept apart.
+pub struct Agents {
+    system_prompt: String,
+    role: Option<Role>,
+    conversations: HashMap<Role, Vec<ChatCompletionMessage>>,
+}
+
+impl Agents {
+    pub fn new(system_prompt: String) -> Self {
+        Agents {
+            system_prompt,
+            role: None,
+            conversations: HashMap::new(),
+        }
+    }
+
+    /// Switches the chat to the conversation and the functions of the agent. The first agent
+    /// takes over the conversation of a resumed run.
+    pub fn hand_over(&mut self, chat: &mut Chat, role: Role) {
+        if self.role == Some(role) {
+            return;
+        }
+        info!("Handing over to the {role}");
+        let messages = std::mem::take(&mut chat.messages);
+        chat.messages = match self.role {
+            None if messages.len() > 1 => messages,
+            current => {
+                if let Some(current) = current {
+                    self.conversations.insert(current, messages);
+                }
+                self.conversations.remove(&role).unwrap_or_else(|| {
+                    let system_message = ChatCompletionMessage {
+                        role: ChatCompletionMessageRole::System,
+                        content: Some(format!("{} {}", self.system_prompt, role.instructions())),
+                        ..Default::default()
+                    };
+                    dump_message(&system_message);
+                    vec![system_message]
+                })
+            }
+        };
+        chat.functions
+            .set_access(move |name, mutating| role.allows(name, mutating));
+        self.role = Some(role);
+    }
+}
+
+/// The reviewer agent, possibly a different model than the one converting the project. Each
+/// change is reviewed in a conversation of its own.
+pub struct Reviewer {
+    model: String,
+    credentials: Credentials,
+    /// how many times the changes of a file are sent back before they are accepted anyway, so
+    /// that the coder doesn't get stuck on a disagreement
+    max_rejections: usize,
+    /// how many times the changes of each file were sent back
+    rejections: Mutex<HashMap<String, usize>>,
+}
+
+impl Reviewer {
+    pub fn from_env(config: &ReviewConfig) -> Self {
+        Reviewer {
+            model: config
+                .model
+                .clone()
+                .unwrap_or_else(|| env::var("MODEL").unwrap()),
+            credentials: Credentials::from_env(),
+            max_rejections: config.max_rejections,
+            rejections: Mutex::default(),
+        }
+    }
+
+    /// Reviews a change of a destination file. Returns the comments if the change should be
+    /// reworked. A failed request accepts the change.
+    pub fn review(&self, path: &str, old: &str, new: &str) -> Option<String> {
+        let rejections = self.rejections.lock().unwrap().get(path).copied();
+        if rejections.unwrap_or(0) >= self.max_rejections {
+            return None;
+        }
+        let messages = vec![
+            ChatCompletionMessage {
+                role: ChatCompletionMessageRole::System,
+                content: Some(Role::Reviewer.instructions().to_string()),
+                ..Default::default()
+            },
+            ChatCompletionMessage {
+                role: ChatCompletionMessageRole::User,
+                content: Some(review_request(path, old, new)),
+                ..Default::default()
+            },
+        ];
+        let request = ChatCompletion::builder(&self.model, messages)
+            .credentials(self.credentials.clone())
+            .create();
+        // Functions are synchronous, but they are called from the chat loop running in tokio.
+        let response =
+            tokio::task::block_in_place(|| tokio::runtime::Handle::current().block_on(request));
+        let reply = match response {
+            Ok(completion) => completion.choices.first()?.message.content.clone()?,
+            Err(e) => {
+                warn!("Cannot review the change of {path}: {}", e.message);
+                return None;
+            }
+        };
+        let comments = verdict(&reply)?;
+        info!("The reviewer sent the change of {path} back:\n{comments}");
+        *self
+            .rejections
+            .lock()
+            .unwrap()
+            .entry(path.to_string())
+            .or_default() += 1;
+        Some(comments)
+    }
+}
+
+/// Asks for the review of the proposed contents of a file, with the diff if the file exists.
+fn review_request(path: &str, old: &str, new: &str) -> String {
+    let mut request = format!("Review the proposed contents of `{path}`:\n```\n{new}```\n");
+    if !old.is_empty() {
+        let diff = diffy::create_patch(old, new);
+        request += &format!("The change against the current file:\n```diff\n{diff}```\n");
+    }
+    request
+}
+
+/// Returns the comments of a reply rejecting the change, or none if it's approved.
+fn verdict(reply: &str) -> Option<String> {
+    let reply = reply.trim();
+    if reply.starts_with("APPROVE") {
+        return None;
+    }
+    Some(
+        reply
+            .strip_prefix("REJECT")
+            .unwrap_or(reply)
+            .trim()
+            .to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roles() {
+        assert!(Role::Planner.allows("plan_write", false));
+        assert!(!Role::Planner.allows("dst_write_file", true));
+        assert!(Role::Coder.allows("dst_write_file", true));
+        assert!(Role::Coder.allows("plan_update", false));
+        assert!(!Role::Coder.allows("plan_write", false));
+        assert!(!Role::Reviewer.allows("src_read_file", false));
+
+        let request = review_request("src/lib.rs", "", "fn f() {}\n");
+        assert_eq!(
+            request,
+            "Review the proposed contents of `src/lib.rs`:\n```\nfn f() {}\n```\n"
+        );
+        let request = review_request("src/lib.rs", "fn f() {}\n", "fn g() {}\n");
+        assert!(request.contains(
+            "```diff\n--- original\n+++ modified\n@@ -1 +1 @@\n-fn f() {}\n+fn g() {}\n```"
+        ));
+
+        assert_eq!(verdict("APPROVE\nLooks fine."), None);
+        assert_eq!(
+            verdict("REJECT\n- `parse` is missing").as_deref(),
+            Some("- `parse` is missing")
+        );
+        assert_eq!(
+            verdict("The loop never ends.").as_deref(),
+            Some("The loop never ends.")
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use crate::items::rust_items;
+use crate::language::detect_language;
+use crate::layout::snake_case;
+use crate::python::SHIM_DIR;
+use crate::skeleton::type_name;
+use crate::symbols::file_symbols;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::Path;
+
+const HEADER_EXTENSIONS: &[&str] = &["h", "hh", "hpp", "hxx"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKind {
+    Function,
+    Type,
+    Constant,
+}
+
+/// An item of the public API of the source project and the name of its port.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ApiItem {
+    pub kind: ApiKind,
+    /// name in the source project, e.g. `parseConfig`
+    pub name: String,
+    /// file of the source project defining it
+    pub source: String,
+    /// name the crate must expose it under, e.g. `parse_config`
+    pub rust_name: String,
+}
+
+impl fmt::Display for ApiItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            ApiKind::Function => "fn",
+            ApiKind::Type => "type",
+            ApiKind::Constant => "const",
+        };
+        write!(
+            f,
+            "- {kind} `{}` for `{}` of `{}`",
+            self.rust_name, self.name, self.source
+        )
+    }
+}
+
+/// The public API of a library in the source project, which the crate must keep exposing.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ApiManifest {
+    pub items: Vec<ApiItem>,
+}
+
+impl ApiManifest {
+    /// Extracts the public functions, types and constants of the source files: the ones declared
+    /// in C and C++ headers, the exported ones of Go and JavaScript, the `public` ones of Java,
+    /// and the ones of Python without a leading underscore. Tests and examples are skipped.
+    pub fn extract(root: &Path, files: &[String]) -> Self {
+        let sources: Vec<(&String, String)> = files
+            .iter()
+            .filter(|file| !is_test_file(file))
+            .filter_map(|file| Some((file, std::fs::read_to_string(root.join(file)).ok()?)))
+            .collect();
+        let header_words: BTreeSet<&str> = sources
+            .iter()
+            .filter(|(file, _)| is_header(file))
+            .flat_map(|(_, source)| source.split(|c: char| !c.is_alphanumeric() && c != '_'))
+            .collect();
+
+        let mut manifest = ApiManifest::default();
+        for (file, source) in &sources {
+            let path = Path::new(file.as_str());
+            let (Some(language), Some(symbols)) =
+                (detect_language(path), file_symbols(path, source))
+            else {
+                continue;
+            };
+            let lines: Vec<&str> = source.lines().collect();
+            for symbol in symbols {
+                let line = lines.get(symbol.line - 1).map_or("", |line| line.trim());
+                // C++ methods defined outside the class are qualified with it.
+                let name = symbol.name.rsplit("::").next().unwrap_or(&symbol.name);
+                let kind = symbol.kind.as_str();
+                let constant = !name.chars().any(|c| c.is_lowercase());
+                let api_kind = match language {
+                    "C" | "C++" if is_header(file) => match kind {
+                        "function" | "method" => Some(ApiKind::Function),
+                        "struct" | "enum" | "type" | "class" => Some(ApiKind::Type),
+                        // Function-like macros and include guards aren't constants.
+                        "macro" if !line.contains(&format!("{name}(")) && !is_guard(name) => {
+                            Some(ApiKind::Constant)
+                        }
+                        _ => None,
+                    },
+                    "C" | "C++" => match kind {
+                        "function" | "method"
+                            if header_words.contains(name) && !line.starts_with("static") =>
+                        {
+                            Some(ApiKind::Function)
+                        }
+                        _ => None,
+                    },
+                    "Python" if !name.starts_with('_') => match kind {
+                        "function" => Some(ApiKind::Function),
+                        "class" => Some(ApiKind::Type),
+                        "global" if constant => Some(ApiKind::Constant),
+                        _ => None,
+                    },
+                    "Go" if name.starts_with(char::is_uppercase) => match kind {
+                        "function" | "method" => Some(ApiKind::Function),
+                        "type" => Some(ApiKind::Type),
+                        "global" => Some(ApiKind::Constant),
+                        _ => None,
+                    },
+                    "Java" if line.split_whitespace().any(|word| word == "public") => match kind {
+                        "method" => Some(ApiKind::Function),
+                        "class" | "interface" | "enum" => Some(ApiKind::Type),
+                        "field" if line.contains("static final") => Some(ApiKind::Constant),
+                        _ => None,
+                    },
+                    "JavaScript" if line.starts_with("export ") => match kind {
+                        "function" => Some(ApiKind::Function),
+                        "class" => Some(ApiKind::Type),
+                        "global" if constant => Some(ApiKind::Constant),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                let Some(api_kind) = api_kind else {
+                    continue;
+                };
+                let rust_name = match api_kind {
+                    ApiKind::Function => snake_case(name),
+                    ApiKind::Type => type_name(name),
+                    ApiKind::Constant => snake_case(name).to_uppercase(),
+                };
+                // Declarations in headers come with definitions in the sources.
+                if manifest
+                    .items
+                    .iter()
+                    .any(|item| item.kind == api_kind && item.rust_name == rust_name)
+                {
+                    continue;
+                }
+                manifest.items.push(ApiItem {
+                    kind: api_kind,
+                    name: name.to_string(),
+                    source: file.to_string(),
+                    rust_name,
+                });
+            }
+        }
+        manifest
+    }
+
+    pub fn load(path: &Path) -> Option<Self> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, contents).map_err(|e| format!("Cannot write {}: {e}", path.display()))
+    }
+
+    /// Returns the items defined in the given source files.
+    pub fn items_of(&self, sources: &[String]) -> Vec<&ApiItem> {
+        self.items
+            .iter()
+            .filter(|item| sources.contains(&item.source))
+            .collect()
+    }
+
+    /// Returns the items the Rust files of the crate don't expose as public items of the same
+    /// kind and name. Names are compared without case and underscores, so `HttpClient` matches
+    /// `HTTPClient`; tests, examples and benchmarks don't count.
+    pub fn missing(&self, root: &Path, files: &[String]) -> Vec<&ApiItem> {
+        let mut exposed = BTreeSet::new();
+        for file in files {
+            let top = file.split('/').next().unwrap_or_default();
+            if !file.ends_with(".rs") || ["tests", "examples", "benches", SHIM_DIR].contains(&top) {
+                continue;
+            }
+            let Ok(source) = std::fs::read_to_string(root.join(file)) else {
+                continue;
+            };
+            for item in rust_items(&source).items {
+                let kind = match item.kind.as_str() {
+                    "fn" | "method" => ApiKind::Function,
+                    "struct" | "enum" | "union" | "type" | "trait" => ApiKind::Type,
+                    "const" | "static" => ApiKind::Constant,
+                    _ => continue,
+                };
+                let name = item.name.rsplit("::").next().unwrap_or(&item.name);
+                exposed.insert((kind, normalize(name)));
+            }
+        }
+        self.items
+            .iter()
+            .filter(|item| !exposed.contains(&(item.kind, normalize(&item.rust_name))))
+            .collect()
+    }
+}
+
+/// Lists API items for the model, one per line.
+pub fn format_items(items: &[&ApiItem]) -> String {
+    items
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn is_header(file: &str) -> bool {
+    Path::new(file)
+        .extension()
+        .is_some_and(|ext| HEADER_EXTENSIONS.contains(&ext.to_string_lossy().as_ref()))
+}
+
+/// Include guards, e.g. `SHAPE_H` or `PARSER_HPP_INCLUDED`.
+fn is_guard(name: &str) -> bool {
+    ["_H", "_H_", "_HPP", "_INCLUDED"]
+        .iter()
+        .any(|suffix| name.ends_with(suffix))
+}
+
+fn is_test_file(file: &str) -> bool {
+    let path = Path::new(file);
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    file.split('/').any(|part| {
+        ["test", "tests", "testdata", "examples", "docs"].contains(&part) || part.starts_with('_')
+    }) || stem.starts_with("test_")
+        || stem.ends_with("_test")
+        || stem.ends_with("Test")
+        || stem.ends_with("Tests")
+        || stem.ends_with(".test")
+        || stem.ends_with(".spec")
+        || stem == "setup"
+        || stem == "conftest"
+}
+
+fn normalize(name: &str) -> String {
+    name.to_lowercase().replace('_', "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn manifest() {
+        let dir = TempDir::new("api");
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(
+            dir.join("shape.h"),
+            "#ifndef SHAPE_H\n#define SHAPE_H\n#define MAX_POINTS 64\n#define SQUARE(x) ((x) * (x))\n\
+            typedef struct shape shape_t;\ndouble shape_area(const shape_t *shape);\n#endif\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("shape.c"),
+            "static double square(double x) { return x * x; }\n\
+            double shape_area(const shape_t *shape) { return 0; }\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("client.go"),
+            "package client\n\nconst MaxRetries = 3\n\ntype Client struct{}\n\n\
+            func NewClient() *Client { return nil }\n\nfunc (c *Client) Fetch(url string) {}\n\n\
+            func helper() {}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("client_test.go"),
+            "package client\n\nfunc TestFetch() {}\n",
+        )
+        .unwrap();
+        let files: Vec<String> = ["client.go", "client_test.go", "shape.c", "shape.h"]
+            .iter()
+            .map(|file| file.to_string())
+            .collect();
+
+        let manifest = ApiManifest::extract(&dir, &files);
+        let names: Vec<(ApiKind, &str)> = manifest
+            .items
+            .iter()
+            .map(|item| (item.kind, item.rust_name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (ApiKind::Constant, "MAX_RETRIES"),
+                (ApiKind::Type, "Client"),
+                (ApiKind::Function, "new_client"),
+                (ApiKind::Function, "fetch"),
+                (ApiKind::Function, "shape_area"),
+                (ApiKind::Constant, "MAX_POINTS"),
+                (ApiKind::Type, "Shape"),
+            ]
+        );
+        assert_eq!(
+            manifest.items_of(&["client.go".to_string()])[2].to_string(),
+            "- fn `new_client` for `NewClient` of `client.go`"
+        );
+
+        std::fs::write(
+            dir.join("src/lib.rs"),
+            "pub const MAX_RETRIES: u32 = 3;\npub const MAX_POINTS: usize = 64;\n\
+            pub struct Client;\nimpl Client {\n    pub fn new() -> Self { Client }\n    \
+            pub fn fetch(&self, url: &str) {}\n}\nfn shape_area() {}\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("src/shape.rs"), "pub struct Shape;\n").unwrap();
+        let files = vec!["src/lib.rs".to_string(), "src/shape.rs".to_string()];
+        let missing = manifest.missing(&dir, &files);
+        assert_eq!(
+            format_items(&missing),
+            "- fn `new_client` for `NewClient` of `client.go`\n\
+            - fn `shape_area` for `shape_area` of `shape.c`"
+        );
+
+        let path = dir.join("api.json");
+        manifest.save(&path).unwrap();
+        assert_eq!(ApiManifest::load(&path).unwrap().items, manifest.items);
+    }
+}
@@ -0,0 +1,215 @@
+use crate::cargo::CargoOptions;
+use crate::process::{CommandOutput, run_command};
+use serde_json::Value;
+use std::path::Path;
+use std::process::Command;
+
+/// A problem with a dependency reported by cargo audit or cargo deny.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DependencyProblem {
+    /// the crate and its version, e.g. "time 0.1.45", if known
+    pub package: Option<String>,
+    /// e.g. "vulnerability", "unmaintained" or a code of cargo deny like "rejected"
+    pub kind: String,
+    /// the advisory, e.g. "RUSTSEC-2020-0071: Potential segfault in the time crate"
+    pub message: String,
+    /// how to fix it, e.g. the patched versions
+    pub fix: Option<String>,
+}
+
+/// Checks `Cargo.lock` against the RustSec advisory database with `cargo audit`.
+pub fn run_audit(dir: &Path, options: &CargoOptions) -> Result<Vec<DependencyProblem>, String> {
+    let output = run_cargo_plugin(dir, &["audit", "--json"], options)?;
+    let report: Value = serde_json::from_str(&output.stdout)
+        .map_err(|_| format!("Unexpected output of cargo audit: {}", output.stderr.trim()))?;
+    Ok(parse_audit(&report))
+}
+
+/// Checks the dependencies against the policy in `policy`, a `deny.toml`, with `cargo deny`.
+pub fn run_deny(
+    dir: &Path,
+    policy: &Path,
+    options: &CargoOptions,
+) -> Result<Vec<DependencyProblem>, String> {
+    let policy = policy.to_string_lossy();
+    let args = ["deny", "--format", "json", "check", "--config", &policy];
+    let output = run_cargo_plugin(dir, &args, options)?;
+    let problems = parse_deny(&output.stderr);
+    if !output.success && problems.is_empty() {
+        return Err(format!("cargo deny failed: {}", output.stderr.trim()));
+    }
+    Ok(problems)
+}
+
+fn run_cargo_plugin(
+    dir: &Path,
+    args: &[&str],
+    options: &CargoOptions,
+) -> Result<CommandOutput, String> {
+    let output = run_command(
+        Command::new("cargo").args(args).current_dir(dir),
+        None,
+        options.timeout,
+        options.max_output,
+    )?;
+    if output.stderr.contains("no such command") {
+        return Err(format!(
+            "cargo {} is not installed, install it with `cargo install cargo-{}`.",
+            args[0], args[0]
+        ));
+    }
+    if output.timed_out {
+        return Err(format!("cargo {} was killed after the timeout.", args[0]));
+    }
+    Ok(output)
+}
+
+/// Extracts the vulnerabilities and the warnings, like unmaintained or yanked crates, from the
+/// JSON report of cargo audit.
+fn parse_audit(report: &Value) -> Vec<DependencyProblem> {
+    let mut problems = vec![];
+    let vulnerabilities = report["vulnerabilities"]["list"].as_array();
+    for vulnerability in vulnerabilities.into_iter().flatten() {
+        let patched: Vec<&str> = vulnerability["versions"]["patched"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect();
+        problems.push(DependencyProblem {
+            package: package(&vulnerability["package"]),
+            kind: "vulnerability".to_string(),
+            message: advisory(&vulnerability["advisory"]),
+            fix: Some(if patched.is_empty() {
+                "no patched version, use another crate".to_string()
+            } else {
+                format!("upgrade to {}", patched.join(" or "))
+            }),
+        });
+    }
+    let warnings = report["warnings"].as_object();
+    for (kind, warnings) in warnings.into_iter().flatten() {
+        for warning in warnings.as_array().into_iter().flatten() {
+            problems.push(DependencyProblem {
+                package: package(&warning["package"]),
+                kind: kind.clone(),
+                message: advisory(&warning["advisory"]),
+                fix: None,
+            });
+        }
+    }
+    problems
+}
+
+fn package(package: &Value) -> Option<String> {
+    let name = package["name"].as_str()?;
+    Some(match package["version"].as_str() {
+        Some(version) => format!("{name} {version}"),
+        None => name.to_string(),
+    })
+}
+
+fn advisory(advisory: &Value) -> String {
+    match (advisory["id"].as_str(), advisory["title"].as_str()) {
+        (Some(id), Some(title)) => format!("{id}: {title}"),
+        (Some(id), None) => id.to_string(),
+        _ => "no advisory".to_string(),
+    }
+}
+
+/// Extracts the errors from the JSON lines cargo deny prints to stderr.
+fn parse_deny(stderr: &str) -> Vec<DependencyProblem> {
+    stderr
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|line| line["type"] == "diagnostic" && line["fields"]["severity"] == "error")
+        .map(|line| {
+            let fields = &line["fields"];
+            let mut message = fields["message"].as_str().unwrap_or_default().to_string();
+            for label in fields["labels"].as_array().into_iter().flatten() {
+                if let (Some(text), Some(span)) =
+                    (label["message"].as_str(), label["span"].as_str())
+                {
+                    message += &format!("; {text}: {span}");
+                }
+            }
+            DependencyProblem {
+                package: package(&fields["graphs"][0]["Krate"]),
+                kind: fields["code"].as_str().unwrap_or("deny").to_string(),
+                message,
+                fix: None,
+            }
+        })
+        .collect()
+}
+
+/// Lists the problems for the model.
+pub fn format_problems(problems: &[DependencyProblem]) -> String {
+    let mut report = String::new();
+    for problem in problems {
+        report += &format!(
+            "- {} ({}): {}",
+            problem.package.as_deref().unwrap_or("the dependencies"),
+            problem.kind,
+            problem.message
+        );
+        if let Some(fix) = &problem.fix {
+            report += &format!(" ({fix})");
+        }
+        report += "\n";
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn audit() {
+        let report = serde_json::json!({
+            "vulnerabilities": {
+                "found": true,
+                "count": 1,
+                "list": [{
+                    "advisory": {
+                        "id": "RUSTSEC-2020-0071",
+                        "package": "time",
+                        "title": "Potential segfault in the time crate"
+                    },
+                    "versions": {"patched": [">=0.2.23"], "unaffected": ["=0.2.0"]},
+                    "package": {"name": "time", "version": "0.1.45"}
+                }]
+            },
+            "warnings": {
+                "yanked": [{"kind": "yanked", "package": {"name": "foo", "version": "1.0.1"}, "advisory": null}]
+            }
+        });
+        let problems = parse_audit(&report);
+        assert_eq!(
+            format_problems(&problems),
+            "- time 0.1.45 (vulnerability): RUSTSEC-2020-0071: Potential segfault in the time \
+            crate (upgrade to >=0.2.23)\n\
+            - foo 1.0.1 (yanked): no advisory\n"
+        );
+    }
+
+    #[test]
+    fn deny() {
+        let stderr = r#"{"type":"diagnostic","fields":{"severity":"error","code":"rejected","message":"failed to satisfy license requirements","labels":[{"message":"rejected: license is not explicitly allowed","span":"GPL-3.0","line":4,"column":12}],"graphs":[{"Krate":{"name":"gpl-crate","version":"0.3.0"}}]}}
+{"type":"diagnostic","fields":{"severity":"warning","code":"duplicate","message":"found 2 duplicate entries for crate 'bitflags'","labels":[]}}
+{"type":"summary","fields":{"bans":{"errors":0},"licenses":{"errors":1}}}
+"#;
+        assert_eq!(
+            parse_deny(stderr),
+            [DependencyProblem {
+                package: Some("gpl-crate 0.3.0".to_string()),
+                kind: "rejected".to_string(),
+                message: "failed to satisfy license requirements; rejected: license is not \
+                    explicitly allowed: GPL-3.0"
+                    .to_string(),
+                fix: None,
+            }]
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use crate::config::BenchmarkConfig;
+use crate::process::run_command;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// The output of the benchmarks is only needed for the error messages.
+const MAX_CAPTURED_OUTPUT: usize = 64 * 1024;
+
+/// Median times of a benchmark, or why it couldn't be run.
+pub struct BenchmarkResult {
+    pub name: String,
+    pub original: Result<Duration, String>,
+    pub ported: Result<Duration, String>,
+}
+
+impl BenchmarkResult {
+    /// How many times faster the port is, below 1 for a regression.
+    pub fn speedup(&self) -> Option<f64> {
+        let (Ok(original), Ok(ported)) = (&self.original, &self.ported) else {
+            return None;
+        };
+        Some(original.as_secs_f64() / ported.as_secs_f64().max(1e-9))
+    }
+}
+
+/// Times every case with both programs. The commands run in `source_copy`, the copy of the
+/// source project, with the path of the destination project in `RIIR_DESTINATION`.
+pub fn run_benchmarks(
+    config: &BenchmarkConfig,
+    source_copy: &Path,
+    destination: &Path,
+) -> Vec<BenchmarkResult> {
+    let timeout = Some(Duration::from_secs(config.timeout)).filter(|timeout| !timeout.is_zero());
+    let time =
+        |command: &str| time_command(command, source_copy, destination, config.runs, timeout);
+    config
+        .cases
+        .iter()
+        .map(|case| BenchmarkResult {
+            name: case.name.clone(),
+            original: time(&case.original),
+            ported: time(&case.ported),
+        })
+        .collect()
+}
+
+/// Runs the command once to warm up the caches, then `runs` more times, and returns the median
+/// wall time.
+fn time_command(
+    command: &str,
+    dir: &Path,
+    destination: &Path,
+    runs: usize,
+    timeout: Option<Duration>,
+) -> Result<Duration, String> {
+    let mut times = vec![];
+    for _ in 0..=runs.max(1) {
+        let started = Instant::now();
+        let output = run_command(
+            Command::new("sh")
+                .args(["-c", command])
+                .env("RIIR_DESTINATION", destination)
+                .current_dir(dir),
+            None,
+            timeout,
+            Some(MAX_CAPTURED_OUTPUT),
+        )?;
+        let elapsed = started.elapsed();
+        if output.timed_out {
+            return Err("timed out".to_string());
+        }
+        if !output.success {
+            let stderr = output.stderr.trim();
+            return Err(match output.exit_code {
+                Some(code) => format!("exit code {code}: {stderr}"),
+                None => format!("killed by a signal: {stderr}"),
+            });
+        }
+        times.push(elapsed);
+    }
+    // The first run only warms up.
+    times.remove(0);
+    times.sort();
+    Ok(times[times.len() / 2])
+}
+
+/// Formats the results as a table with the speedup of each benchmark.
+pub fn format_table(results: &[BenchmarkResult]) -> String {
+    let width = results
+        .iter()
+        .map(|result| result.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("benchmark".len());
+    let mut table = format!(
+        "{:width$}  {:>10}  {:>10}  {:>8}\n",
+        "benchmark", "original", "port", "speedup"
+    );
+    let mut errors = String::new();
+    for result in results {
+        let time = |time: &Result<Duration, String>| match time {
+            Ok(time) => format!("{:.3}s", time.as_secs_f64()),
+            Err(_) => "failed".to_string(),
+        };
+        let speedup = result
+            .speedup()
+            .map_or("-".to_string(), |speedup| format!("{speedup:.2}x"));
+        table += &format!(
+            "{:width$}  {:>10}  {:>10}  {:>8}\n",
+            result.name,
+            time(&result.original),
+            time(&result.ported),
+            speedup
+        );
+        for (program, time) in [("original", &result.original), ("port", &result.ported)] {
+            if let Err(e) = time {
+                errors += &format!("{} ({program}): {e}\n", result.name);
+            }
+        }
+    }
+    table + &errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BenchmarkCase;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn benchmarks() {
+        let dir = TempDir::new("benchmark");
+        let case = |name: &str, original: &str, ported: &str| BenchmarkCase {
+            name: name.to_string(),
+            original: original.to_string(),
+            ported: ported.to_string(),
+        };
+        let config = BenchmarkConfig {
+            runs: 3,
+            cases: vec![
+                case("sleep", "sleep 0.2", "test -n \"$RIIR_DESTINATION\""),
+                case("broken", "true", "echo oops >&2; exit 2"),
+            ],
+            ..Default::default()
+        };
+
+        let results = run_benchmarks(&config, &dir, Path::new("/dest"));
+        assert!(results[0].original.as_ref().unwrap() >= &Duration::from_millis(200));
+        assert!(results[0].speedup().unwrap() > 1.0);
+        assert_eq!(results[1].ported.as_ref().unwrap_err(), "exit code 2: oops");
+        assert_eq!(results[1].speedup(), None);
+
+        let table = format_table(&results);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "benchmark    original        port   speedup");
+        assert!(lines[2].starts_with("broken   "));
+        assert!(lines[2].ends_with("failed         -"));
+        assert_eq!(lines[3], "broken (port): exit code 2: oops");
+    }
+}
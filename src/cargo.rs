@@ -0,0 +1,1087 @@
+use crate::config::ClippyConfig;
+use crate::process::run_command;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// A message of the compiler from cargo's JSON output.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// "error", "warning" and so on
+    pub level: String,
+    /// e.g. "E0308" or "unused_variables"
+    pub code: Option<String>,
+    pub message: String,
+    /// file of the primary span, relative to the project directory
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    /// the message as rustc prints it, with the source snippet and notes
+    pub rendered: String,
+    /// the workspace member the file belongs to, if the project has several members
+    pub member: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn is_error(&self) -> bool {
+        self.level.starts_with("error")
+    }
+}
+
+/// A failed test from the output of `cargo test`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TestFailure {
+    /// e.g. "tests::parse" or "src/lib.rs - parse (line 10)" for a doctest
+    pub name: String,
+    pub panic_message: Option<String>,
+    /// the captured output of the test, including the panic
+    pub output: String,
+}
+
+/// Settings of the cargo processes. The limits make sure that e.g. a build script looping forever
+/// doesn't hang riir.
+#[derive(Clone, Default)]
+pub struct CargoOptions {
+    /// rustup toolchain like "1.78" or "nightly", passed as `+toolchain`
+    pub toolchain: Option<String>,
+    /// cargo is killed after running this long
+    pub timeout: Option<Duration>,
+    /// the captured stdout and stderr are each truncated to this many bytes
+    pub max_output: Option<usize>,
+}
+
+/// Result of a cargo command run with `--message-format=json`.
+pub struct CargoOutput {
+    pub success: bool,
+    pub diagnostics: Vec<Diagnostic>,
+    /// failed tests, if the command ran tests
+    pub test_failures: Vec<TestFailure>,
+    /// names of the tests that passed
+    pub passed_tests: Vec<String>,
+    /// set if some messages are missing because the output exceeded the limit
+    pub truncated: bool,
+    /// what cargo printed itself, e.g. an error in the manifest
+    pub stderr: String,
+}
+
+impl CargoOutput {
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter().filter(|d| d.is_error())
+    }
+
+    /// Returns the errors and the warnings of clippy, without the ones of the compiler.
+    pub fn clippy_lints(&self) -> Vec<&Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|d| {
+                d.is_error() || d.code.as_deref().is_some_and(|c| c.starts_with("clippy::"))
+            })
+            .collect()
+    }
+
+    /// Returns the files with errors and the number of problems in each, starting with the file
+    /// whose errors likely cause the others.
+    pub fn files_with_errors(&self) -> Vec<(&str, usize)> {
+        let errors: Vec<&Diagnostic> = self.errors().collect();
+        let mut files: Vec<(&str, usize)> = vec![];
+        for problem in group_problems(&errors) {
+            let Some(file) = problem.first.file.as_deref() else {
+                continue;
+            };
+            match files.iter_mut().find(|(f, _)| *f == file) {
+                Some((_, count)) => *count += 1,
+                None => files.push((file, 1)),
+            }
+        }
+        files
+    }
+
+    /// Formats the errors for the model, grouped by file. Cargo's own output is returned
+    /// instead if the compiler reported no errors.
+    pub fn report(&self) -> String {
+        let errors: Vec<&Diagnostic> = self.errors().collect();
+        if errors.is_empty() {
+            return self.stderr.clone();
+        }
+
+        let mut report = format_diagnostics(&errors);
+        let warnings = self.diagnostics.len() - errors.len();
+        if warnings > 0 {
+            report += &format!("There are also {warnings} warning(s), not shown.\n");
+        }
+        if self.truncated {
+            report += "The output of cargo was too long, so some messages are missing.\n";
+        }
+        report
+    }
+
+    /// Formats the failures of `cargo test` for the model: compiler errors if the tests don't
+    /// build, otherwise the failed tests with their output.
+    pub fn test_report(&self) -> String {
+        if self.errors().next().is_some() || self.test_failures.is_empty() {
+            return self.report();
+        }
+        let mut report = String::new();
+        for failure in &self.test_failures {
+            report += &format!("==== {} ====\n", failure.name);
+            if failure.output.is_empty() {
+                report += "(no output)\n";
+            } else {
+                report += failure.output.trim_end();
+                report += "\n";
+            }
+            report += "\n";
+        }
+        report
+    }
+}
+
+/// The report of a cargo command run by the model is truncated to this many bytes, so that it
+/// leaves room for the model to work.
+const MAX_TOOL_REPORT: usize = 16 * 1024;
+
+/// Result of a cargo command the model runs itself to verify its work.
+#[derive(Serialize, Deserialize)]
+pub struct CargoRunResult {
+    pub error: Option<String>,
+    pub success: bool,
+    pub errors: usize,
+    pub warnings: usize,
+    /// the problems formatted like after the model's changes, possibly truncated
+    pub report: String,
+    /// names of the failed tests
+    pub failed_tests: Vec<String>,
+    /// number of the tests that passed
+    pub passed_tests: usize,
+}
+
+impl CargoRunResult {
+    /// Summarizes the output of a cargo command, with `report` formatting the problems.
+    pub fn new<F>(output: Result<CargoOutput, String>, report: F) -> Self
+    where
+        F: Fn(&CargoOutput) -> String,
+    {
+        let output = match output {
+            Ok(output) => output,
+            Err(e) => {
+                return CargoRunResult {
+                    error: Some(e),
+                    success: false,
+                    errors: 0,
+                    warnings: 0,
+                    report: String::new(),
+                    failed_tests: vec![],
+                    passed_tests: 0,
+                };
+            }
+        };
+        let errors = output.errors().count();
+        let mut report = report(&output);
+        if report.len() > MAX_TOOL_REPORT {
+            let mut end = MAX_TOOL_REPORT;
+            while !report.is_char_boundary(end) {
+                end -= 1;
+            }
+            report.truncate(end);
+            report += "\n[the report is truncated]";
+        }
+        CargoRunResult {
+            error: None,
+            success: output.success,
+            errors,
+            warnings: output.diagnostics.len() - errors,
+            report,
+            failed_tests: output
+                .test_failures
+                .iter()
+                .map(|failure| failure.name.clone())
+                .collect(),
+            passed_tests: output.passed_tests.len(),
+        }
+    }
+}
+
+/// How many distinct problems are sent to the model at once. Fixing the first ones often fixes
+/// the rest, and a huge report would only bury them.
+const MAX_REPORTED_PROBLEMS: usize = 20;
+
+/// Codes of errors about unresolved names, which usually cause many other errors.
+const NAME_ERRORS: &[&str] = &[
+    "E0405", "E0407", "E0412", "E0422", "E0423", "E0425", "E0432", "E0433", "E0583",
+];
+
+/// Codes of borrow checker errors, which rustc reports only once the types are right.
+const BORROW_ERRORS: &[&str] = &[
+    "E0382", "E0499", "E0502", "E0505", "E0506", "E0507", "E0597", "E0716",
+];
+
+/// The same diagnostic reported at one or more places.
+struct Problem<'a> {
+    first: &'a Diagnostic,
+    others: Vec<&'a Diagnostic>,
+}
+
+/// Formats diagnostics for the model, grouped by file.
+///
+/// Duplicates are dropped, the same message about the same item at several places is shown
+/// once, and only the most actionable problems are included.
+pub fn format_diagnostics(diagnostics: &[&Diagnostic]) -> String {
+    let problems = group_problems(diagnostics);
+    let shown = &problems[..problems.len().min(MAX_REPORTED_PROBLEMS)];
+
+    let mut files: Vec<Option<&str>> = vec![];
+    for problem in shown {
+        if !files.contains(&problem.first.file.as_deref()) {
+            files.push(problem.first.file.as_deref());
+        }
+    }
+    let mut report = String::new();
+    for file in files {
+        let file_problems: Vec<&Problem> = shown
+            .iter()
+            .filter(|problem| problem.first.file.as_deref() == file)
+            .collect();
+        let kind = if file_problems.iter().all(|p| p.first.is_error()) {
+            "error(s)"
+        } else {
+            "problem(s)"
+        };
+        let member = match &file_problems[0].first.member {
+            Some(member) => format!(" (crate `{member}`)"),
+            None => String::new(),
+        };
+        report += &format!(
+            "==== {}{member}: {} {kind} ====\n",
+            file.unwrap_or("no file"),
+            file_problems.len()
+        );
+        for problem in file_problems {
+            report += problem.first.rendered.trim_end();
+            report += "\n";
+            if !problem.others.is_empty() {
+                let places: Vec<String> = problem.others.iter().map(|d| location(d)).collect();
+                report += &format!("The same problem also occurs at {}.\n", places.join(", "));
+            }
+            report += "\n";
+        }
+    }
+    if problems.len() > shown.len() {
+        report += &format!(
+            "{} more problem(s) not shown, fix these first.\n",
+            problems.len() - shown.len()
+        );
+    }
+    report
+}
+
+/// Merges the diagnostics into problems, ordered so that the problems likely causing others come
+/// first.
+fn group_problems<'a>(diagnostics: &[&'a Diagnostic]) -> Vec<Problem<'a>> {
+    let mut problems: Vec<Problem> = vec![];
+    for &diagnostic in diagnostics {
+        // Messages naming an item, like "cannot find type `Foo` in this scope", have one cause.
+        // Generic ones like "mismatched types" are only merged if they are exact duplicates,
+        // e.g. from the library and its tests.
+        let same = |other: &Diagnostic| {
+            other.rendered == diagnostic.rendered
+                || (diagnostic.message.contains('`')
+                    && other.level == diagnostic.level
+                    && other.code == diagnostic.code
+                    && other.message == diagnostic.message)
+        };
+        match problems.iter_mut().find(|problem| same(problem.first)) {
+            Some(problem) => {
+                let duplicate = problem.first.rendered == diagnostic.rendered
+                    || problem
+                        .others
+                        .iter()
+                        .any(|d| d.rendered == diagnostic.rendered);
+                if !duplicate {
+                    problem.others.push(diagnostic);
+                }
+            }
+            None => problems.push(Problem {
+                first: diagnostic,
+                others: vec![],
+            }),
+        }
+    }
+    problems.sort_by_key(|problem| priority(problem.first));
+    problems
+}
+
+fn priority(diagnostic: &Diagnostic) -> u8 {
+    if !diagnostic.is_error() {
+        return 4;
+    }
+    match diagnostic.code.as_deref() {
+        // Syntax errors stop the compiler before anything else.
+        None => 0,
+        Some(code) if NAME_ERRORS.contains(&code) => 1,
+        Some(code) if BORROW_ERRORS.contains(&code) => 3,
+        Some(_) => 2,
+    }
+}
+
+fn location(diagnostic: &Diagnostic) -> String {
+    match (&diagnostic.file, diagnostic.line, diagnostic.column) {
+        (Some(file), Some(line), Some(column)) => format!("{file}:{line}:{column}"),
+        (Some(file), _, _) => file.clone(),
+        _ => "an unknown place".to_string(),
+    }
+}
+
+/// Arguments of `cargo clippy` checking the lints chosen in the configuration.
+pub fn clippy_args(config: &ClippyConfig) -> Vec<&str> {
+    let mut args = vec!["clippy", "--all-targets", "--"];
+    if config.pedantic {
+        args.extend(["-W", "clippy::pedantic"]);
+    }
+    for lint in &config.allow {
+        args.extend(["-A", lint]);
+    }
+    if config.deny_warnings {
+        args.extend(["-D", "warnings"]);
+    }
+    args
+}
+
+/// Runs a cargo subcommand with JSON messages in the project directory, e.g. `run_cargo(dir,
+/// &["check"])`. The arguments after the subcommand may include `--` and compiler flags.
+pub fn run_cargo(dir: &Path, args: &[&str], options: &CargoOptions) -> Result<CargoOutput, String> {
+    run_cargo_with_env(dir, args, &[], options)
+}
+
+/// Runs cargo like `run_cargo` with additional environment variables, which e.g. the tests see.
+pub fn run_cargo_with_env(
+    dir: &Path,
+    args: &[&str],
+    env: &[(&str, String)],
+    options: &CargoOptions,
+) -> Result<CargoOutput, String> {
+    let (subcommand, args) = args.split_first().ok_or("No cargo subcommand.")?;
+    let mut command = Command::new("cargo");
+    if let Some(toolchain) = &options.toolchain {
+        command.arg(format!("+{toolchain}"));
+    }
+    command
+        .arg(subcommand)
+        .args(["--message-format=json", "--color", "never"])
+        .args(args)
+        .current_dir(dir)
+        .envs(env.iter().map(|(name, value)| (name, value)))
+        // Keep the build in the project, where `target` is hidden from the model.
+        .env_remove("CARGO_TARGET_DIR");
+    let output = run_command(&mut command, None, options.timeout, options.max_output)?;
+
+    let mut stderr = output.stderr;
+    if output.stderr_truncated {
+        let max_output = options.max_output.unwrap_or_default();
+        stderr += &format!("\n[the output was truncated to {max_output} bytes]\n");
+    }
+    if output.timed_out {
+        stderr += &format!(
+            "\n[cargo {subcommand} was killed after running for {} seconds]\n",
+            options.timeout.unwrap_or_default().as_secs_f64()
+        );
+    }
+    // Test binaries print their results to stdout between the JSON messages.
+    Ok(CargoOutput {
+        success: output.success,
+        diagnostics: parse_diagnostics(&output.stdout),
+        test_failures: parse_test_failures(&output.stdout),
+        passed_tests: parse_passed_tests(&output.stdout),
+        truncated: output.stdout_truncated,
+        stderr,
+    })
+}
+
+/// Returns the version of the compiler, e.g. "rustc 1.78.0 (9b00956e5 2024-04-29)".
+pub fn rustc_version(toolchain: Option<&str>) -> Result<String, String> {
+    let mut command = Command::new("rustc");
+    if let Some(toolchain) = toolchain {
+        command.arg(format!("+{toolchain}"));
+    }
+    let output = command
+        .arg("--version")
+        .output()
+        .map_err(|e| format!("Cannot run rustc: {e}"))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// A package of a cargo workspace.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Member {
+    pub name: String,
+    /// directory of the package relative to the workspace root, empty for the root package
+    pub dir: PathBuf,
+    /// names of the packages it depends on, including the ones outside the workspace
+    pub dependencies: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Metadata {
+    packages: Vec<Package>,
+    workspace_root: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    name: String,
+    manifest_path: PathBuf,
+    dependencies: Vec<Dependency>,
+}
+
+#[derive(Deserialize)]
+struct Dependency {
+    name: String,
+}
+
+/// Lists the packages of the workspace in the directory, a single one if it's not a workspace.
+pub fn workspace_members(dir: &Path, options: &CargoOptions) -> Result<Vec<Member>, String> {
+    let mut command = Command::new("cargo");
+    if let Some(toolchain) = &options.toolchain {
+        command.arg(format!("+{toolchain}"));
+    }
+    command
+        .args([
+            "metadata",
+            "--no-deps",
+            "--format-version",
+            "1",
+            "--offline",
+        ])
+        .current_dir(dir);
+    let output = run_command(&mut command, None, options.timeout, options.max_output)?;
+    if output.timed_out {
+        return Err(format!(
+            "cargo metadata was killed after running for {} seconds",
+            options.timeout.unwrap_or_default().as_secs_f64()
+        ));
+    }
+    if !output.success {
+        return Err(output.stderr.trim().to_string());
+    }
+    // Truncated metadata doesn't parse and is reported as such.
+    let metadata: Metadata = serde_json::from_str(&output.stdout)
+        .map_err(|e| format!("Cannot parse cargo metadata: {e}"))?;
+    Ok(metadata
+        .packages
+        .into_iter()
+        .map(|package| Member {
+            dir: package
+                .manifest_path
+                .parent()
+                .and_then(|dir| dir.strip_prefix(&metadata.workspace_root).ok())
+                .unwrap_or(Path::new(""))
+                .to_path_buf(),
+            name: package.name,
+            dependencies: package
+                .dependencies
+                .into_iter()
+                .map(|dependency| dependency.name)
+                .collect(),
+        })
+        .collect())
+}
+
+/// Runs `cargo check` for the given members of a workspace separately, so that errors in one
+/// member don't hide the ones of the others, and tells which member each error belongs to.
+pub fn check_workspace(
+    dir: &Path,
+    members: &[Member],
+    checked: &[&Member],
+    options: &CargoOptions,
+) -> Result<CargoOutput, String> {
+    let mut result = CargoOutput {
+        success: true,
+        diagnostics: vec![],
+        test_failures: vec![],
+        passed_tests: vec![],
+        truncated: false,
+        stderr: String::new(),
+    };
+    for member in checked {
+        let output = run_cargo(dir, &["check", "-p", &member.name], options)?;
+        result.success &= output.success;
+        result.truncated |= output.truncated;
+        for mut diagnostic in output.diagnostics {
+            diagnostic.member = Some(
+                member_of(members, diagnostic.file.as_deref())
+                    .unwrap_or(&member.name)
+                    .to_string(),
+            );
+            // Errors of a shared dependency are reported by each member using it.
+            if !result.diagnostics.contains(&diagnostic) {
+                result.diagnostics.push(diagnostic);
+            }
+        }
+        if !output.success {
+            result.stderr += &format!("==== {} ====\n{}", member.name, output.stderr);
+        }
+    }
+    Ok(result)
+}
+
+/// Files outside the crate sources that affect the build.
+const BUILD_FILES: &[&str] = &[
+    "Cargo.toml",
+    "Cargo.lock",
+    "build.rs",
+    "rust-toolchain.toml",
+];
+
+/// Directories of a package whose files may end up in the build, e.g. with `include_str!`.
+const SOURCE_DIRS: &[&str] = &["src", "tests", "benches", "examples", ".cargo"];
+
+/// Tells whether a change of the file can change the result of a build, unlike e.g. a change of
+/// `README.md`. The path is relative to the project directory.
+pub fn affects_build(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "rs" || ext == "toml")
+        || path
+            .file_name()
+            .is_some_and(|name| BUILD_FILES.iter().any(|file| name == *file))
+        || path
+            .components()
+            .any(|component| SOURCE_DIRS.iter().any(|dir| component.as_os_str() == *dir))
+}
+
+/// Finds the members that have to be checked again after the files changed: the ones containing
+/// them and the ones depending on those. A file outside all members, e.g. `Cargo.lock`, affects
+/// every member, and so does an empty list, which means that nothing is known about the changes.
+pub fn affected_members<'a>(members: &'a [Member], changed: &[&Path]) -> Vec<&'a Member> {
+    if changed.is_empty() {
+        return members.iter().collect();
+    }
+    let mut affected: Vec<&Member> = vec![];
+    for path in changed {
+        let member = path
+            .to_str()
+            .and_then(|path| member_of(members, Some(path)))
+            .and_then(|name| members.iter().find(|member| member.name == name))
+            // The root package owns the top-level files, but they affect the whole workspace.
+            .filter(|member| !member.dir.as_os_str().is_empty() || path.starts_with("src"));
+        match member {
+            Some(member) if !affected.contains(&member) => affected.push(member),
+            Some(_) => {}
+            None => return members.iter().collect(),
+        }
+    }
+    loop {
+        let dependents: Vec<&Member> = members
+            .iter()
+            .filter(|member| !affected.contains(member))
+            .filter(|member| {
+                affected
+                    .iter()
+                    .any(|dependency| member.dependencies.contains(&dependency.name))
+            })
+            .collect();
+        if dependents.is_empty() {
+            return affected;
+        }
+        affected.extend(dependents);
+    }
+}
+
+/// Finds the member with the longest directory containing the file.
+fn member_of<'a>(members: &'a [Member], file: Option<&str>) -> Option<&'a str> {
+    let file = Path::new(file?);
+    members
+        .iter()
+        .filter(|member| file.starts_with(&member.dir))
+        .max_by_key(|member| member.dir.components().count())
+        .map(|member| member.name.as_str())
+}
+
+/// Formats Rust code with rustfmt. Returns rustfmt's error, e.g. about a syntax error, if the
+/// code can't be formatted.
+///
+/// The code is passed through stdin, so that rustfmt doesn't format the modules it declares.
+pub fn rustfmt(code: &str, edition: &str) -> Result<String, String> {
+    let mut child = Command::new("rustfmt")
+        .args(["--edition", edition, "--color", "never", "--emit", "stdout"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Cannot run rustfmt: {e}"))?;
+    let mut stdin = child.stdin.take().unwrap();
+    let code = code.to_string();
+    // Writing from another thread avoids a deadlock when rustfmt fills the output pipe first.
+    let writer = std::thread::spawn(move || stdin.write_all(code.as_bytes()));
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Cannot run rustfmt: {e}"))?;
+    let _ = writer.join();
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    String::from_utf8(output.stdout).map_err(|_| "rustfmt returned invalid UTF-8.".to_string())
+}
+
+#[derive(Deserialize)]
+struct Message {
+    reason: String,
+    message: Option<CompilerMessage>,
+}
+
+#[derive(Deserialize)]
+struct CompilerMessage {
+    message: String,
+    code: Option<Code>,
+    level: String,
+    spans: Vec<Span>,
+    rendered: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Code {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct Span {
+    file_name: String,
+    line_start: usize,
+    column_start: usize,
+    is_primary: bool,
+}
+
+/// Extracts the compiler messages from cargo's JSON output, skipping the summaries like
+/// "aborting due to 2 previous errors".
+pub fn parse_diagnostics(output: &str) -> Vec<Diagnostic> {
+    output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Message>(line).ok())
+        .filter(|message| message.reason == "compiler-message")
+        .filter_map(|message| message.message)
+        .filter(|message| !message.spans.is_empty() || !is_summary(&message.message))
+        .map(|message| {
+            let span = message.spans.iter().find(|span| span.is_primary);
+            Diagnostic {
+                level: message.level,
+                code: message.code.map(|code| code.code),
+                file: span.map(|span| span.file_name.clone()),
+                line: span.map(|span| span.line_start),
+                column: span.map(|span| span.column_start),
+                rendered: message.rendered.unwrap_or_else(|| message.message.clone()),
+                message: message.message,
+                member: None,
+            }
+        })
+        .collect()
+}
+
+/// Extracts the failed tests from the output of libtest.
+pub fn parse_test_failures(output: &str) -> Vec<TestFailure> {
+    let mut failures: Vec<TestFailure> = output
+        .lines()
+        .filter_map(|line| line.strip_prefix("test ")?.strip_suffix(" ... FAILED"))
+        .map(|name| TestFailure {
+            name: name.to_string(),
+            panic_message: None,
+            output: String::new(),
+        })
+        .collect();
+
+    // The captured output of each failed test follows in a `---- name stdout ----` section.
+    let mut current: Option<usize> = None;
+    for line in output.lines() {
+        if let Some(name) = line
+            .strip_prefix("---- ")
+            .and_then(|line| line.strip_suffix(" stdout ----"))
+        {
+            current = failures.iter().position(|failure| failure.name == name);
+            continue;
+        }
+        if line == "failures:" || line.starts_with("test result: ") {
+            current = None;
+        }
+        if let Some(index) = current {
+            failures[index].output += line;
+            failures[index].output += "\n";
+        }
+    }
+    for failure in &mut failures {
+        failure.panic_message = panic_message(&failure.output);
+    }
+    failures
+}
+
+/// Extracts the names of the passed tests from the output of libtest.
+pub fn parse_passed_tests(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.strip_prefix("test ")?.strip_suffix(" ... ok"))
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// Finds the message in the output of a test like "thread 'x' panicked at src/lib.rs:3:5:\n
+/// assertion failed\nnote: run with `RUST_BACKTRACE=1`".
+fn panic_message(output: &str) -> Option<String> {
+    let mut lines = output
+        .lines()
+        .skip_while(|line| !(line.starts_with("thread '") && line.contains("' panicked at ")));
+    let panicked = lines.next()?;
+    let message: Vec<&str> = lines
+        .take_while(|line| !line.starts_with("note: ") && !line.starts_with("stack backtrace:"))
+        .collect();
+    if message.is_empty() {
+        // Older versions print the message on the same line.
+        return Some(panicked.to_string());
+    }
+    Some(message.join("\n").trim_end().to_string())
+}
+
+fn is_summary(message: &str) -> bool {
+    message.starts_with("aborting due to")
+        || message.ends_with("warning emitted")
+        || message.ends_with("warnings emitted")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use std::time::Instant;
+
+    #[test]
+    fn parse() {
+        let output = r#"{"reason":"compiler-artifact","package_id":"check 0.1.0"}
+{"reason":"compiler-message","message":{"rendered":"warning: unused variable: `x`\n","message":"unused variable: `x`","code":{"code":"unused_variables","explanation":null},"level":"warning","spans":[{"file_name":"src/lib.rs","line_start":2,"column_start":9,"is_primary":true}],"children":[]}}
+{"reason":"compiler-message","message":{"rendered":"error[E0308]: mismatched types\n","message":"mismatched types","code":{"code":"E0308","explanation":"..."},"level":"error","spans":[{"file_name":"src/main.rs","line_start":1,"column_start":5,"is_primary":false},{"file_name":"src/lib.rs","line_start":3,"column_start":5,"is_primary":true}],"children":[]}}
+{"reason":"compiler-message","message":{"rendered":"error: aborting due to 1 previous error\n","message":"aborting due to 1 previous error","code":null,"level":"error","spans":[],"children":[]}}
+{"reason":"build-finished","success":false}"#;
+        let diagnostics = parse_diagnostics(output);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[1],
+            Diagnostic {
+                level: "error".to_string(),
+                code: Some("E0308".to_string()),
+                message: "mismatched types".to_string(),
+                file: Some("src/lib.rs".to_string()),
+                line: Some(3),
+                column: Some(5),
+                rendered: "error[E0308]: mismatched types\n".to_string(),
+                member: None,
+            }
+        );
+
+        let output = CargoOutput {
+            success: false,
+            diagnostics,
+            test_failures: vec![],
+            passed_tests: vec![],
+            truncated: false,
+            stderr: "error: could not compile `check`\n".to_string(),
+        };
+        assert_eq!(
+            output.report(),
+            "==== src/lib.rs: 1 error(s) ====\nerror[E0308]: mismatched types\n\n\
+            There are also 1 warning(s), not shown.\n"
+        );
+    }
+
+    #[test]
+    fn format_code() {
+        assert_eq!(rustfmt("fn  main( ){}", "2024").unwrap(), "fn main() {}\n");
+        assert!(
+            rustfmt("fn main( {}", "2024")
+                .unwrap_err()
+                .contains("error")
+        );
+    }
+
+    #[test]
+    fn clippy_command() {
+        assert_eq!(
+            clippy_args(&ClippyConfig {
+                enabled: true,
+                deny_warnings: true,
+                pedantic: true,
+                allow: vec!["clippy::module_name_repetitions".to_string()],
+            }),
+            [
+                "clippy",
+                "--all-targets",
+                "--",
+                "-W",
+                "clippy::pedantic",
+                "-A",
+                "clippy::module_name_repetitions",
+                "-D",
+                "warnings"
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_tests() {
+        let output = r#"{"reason":"build-finished","success":true}
+
+running 3 tests
+test tests::ok ... ok
+test tests::sum ... FAILED
+test tests::empty ... FAILED
+
+failures:
+
+---- tests::sum stdout ----
+computing
+thread 'tests::sum' panicked at src/lib.rs:12:9:
+assertion `left == right` failed
+  left: 3
+ right: 4
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+
+---- tests::empty stdout ----
+thread 'tests::empty' panicked at src/lib.rs:17:5:
+not implemented
+
+failures:
+    tests::empty
+    tests::sum
+
+test result: FAILED. 1 passed; 2 failed; 0 ignored; 0 measured; 0 filtered out
+"#;
+        assert_eq!(parse_passed_tests(output), ["tests::ok"]);
+        let failures = parse_test_failures(output);
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].name, "tests::sum");
+        assert_eq!(
+            failures[0].panic_message.as_deref(),
+            Some("assertion `left == right` failed\n  left: 3\n right: 4")
+        );
+        assert!(failures[0].output.starts_with("computing\nthread"));
+        assert_eq!(failures[1].name, "tests::empty");
+        assert_eq!(
+            failures[1].panic_message.as_deref(),
+            Some("not implemented")
+        );
+    }
+
+    #[test]
+    fn run_result() {
+        let output = CargoOutput {
+            success: false,
+            diagnostics: parse_diagnostics(&format!(
+                r#"{{"reason":"compiler-message","message":{{"rendered":"{}","message":"mismatched types","code":{{"code":"E0308","explanation":null}},"level":"error","spans":[],"children":[]}}}}"#,
+                "x".repeat(MAX_TOOL_REPORT)
+            )),
+            test_failures: vec![],
+            passed_tests: vec!["tests::parse".to_string()],
+            truncated: false,
+            stderr: String::new(),
+        };
+        let result = CargoRunResult::new(Ok(output), CargoOutput::report);
+        assert!(!result.success);
+        assert_eq!(
+            (result.errors, result.warnings, result.passed_tests),
+            (1, 0, 1)
+        );
+        assert!(result.report.len() < MAX_TOOL_REPORT + 100);
+        assert!(result.report.ends_with("[the report is truncated]"));
+
+        let result = CargoRunResult::new(Err("cargo not found".to_string()), CargoOutput::report);
+        assert_eq!(result.error.as_deref(), Some("cargo not found"));
+    }
+
+    #[test]
+    fn build_files() {
+        assert!(affects_build(Path::new("src/main.rs")));
+        assert!(affects_build(Path::new("core/Cargo.toml")));
+        assert!(affects_build(Path::new("Cargo.lock")));
+        assert!(affects_build(Path::new("src/data/table.txt")));
+        assert!(affects_build(Path::new(".cargo/config")));
+        assert!(!affects_build(Path::new("README.md")));
+        assert!(!affects_build(Path::new("docs/design.md")));
+        assert!(!affects_build(Path::new("LICENSE")));
+    }
+
+    #[test]
+    fn prioritize() {
+        let diagnostic = |code: &str, message: &str, file: &str, line: usize| Diagnostic {
+            level: "error".to_string(),
+            code: Some(code.to_string()).filter(|code| !code.is_empty()),
+            message: message.to_string(),
+            file: Some(file.to_string()),
+            line: Some(line),
+            column: Some(1),
+            rendered: format!("error: {message}\n --> {file}:{line}:1\n"),
+            member: None,
+        };
+        let diagnostics = [
+            diagnostic("E0502", "cannot borrow `v` as mutable", "src/a.rs", 9),
+            diagnostic("E0308", "mismatched types", "src/a.rs", 3),
+            diagnostic("E0308", "mismatched types", "src/a.rs", 5),
+            diagnostic(
+                "E0412",
+                "cannot find type `Foo` in this scope",
+                "src/b.rs",
+                1,
+            ),
+            diagnostic(
+                "E0412",
+                "cannot find type `Foo` in this scope",
+                "src/a.rs",
+                2,
+            ),
+            diagnostic("E0308", "mismatched types", "src/a.rs", 3),
+        ];
+        let output = CargoOutput {
+            success: false,
+            diagnostics: diagnostics.to_vec(),
+            test_failures: vec![],
+            passed_tests: vec![],
+            truncated: false,
+            stderr: String::new(),
+        };
+        assert_eq!(
+            output.files_with_errors(),
+            [("src/b.rs", 1), ("src/a.rs", 3)]
+        );
+        let refs: Vec<&Diagnostic> = diagnostics.iter().collect();
+        let problems = group_problems(&refs);
+        let order: Vec<(usize, usize)> = problems
+            .iter()
+            .map(|problem| (problem.first.line.unwrap(), problem.others.len()))
+            .collect();
+        assert_eq!(order, [(1, 1), (3, 0), (5, 0), (9, 0)]);
+        assert!(format_diagnostics(&refs).starts_with(
+            "==== src/b.rs: 1 error(s) ====\nerror: cannot find type `Foo` in \
+                    this scope\n --> src/b.rs:1:1\nThe same problem also occurs at src/a.rs:2:1.\n"
+        ));
+
+        let many: Vec<Diagnostic> = (1..=25)
+            .map(|line| diagnostic("E0308", "mismatched types", "src/a.rs", line))
+            .collect();
+        let refs: Vec<&Diagnostic> = many.iter().collect();
+        assert!(
+            format_diagnostics(&refs).ends_with("5 more problem(s) not shown, fix these first.\n")
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let dir = TempDir::new("limits");
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(
+            dir.join("Cargo.toml"),
+            "[package]\nname = \"limits\"\nversion = \"0.1.0\"\nedition = \"2024\"\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("src/lib.rs"), "").unwrap();
+        std::fs::write(
+            dir.join("build.rs"),
+            "fn main() { std::thread::sleep(std::time::Duration::from_secs(5)); }\n",
+        )
+        .unwrap();
+
+        let options = CargoOptions {
+            toolchain: None,
+            timeout: Some(Duration::from_millis(500)),
+            max_output: Some(10),
+        };
+        let started = Instant::now();
+        let output = run_cargo(&dir, &["check"], &options).unwrap();
+        // The build script alone would take longer.
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(!output.success);
+        assert!(
+            output
+                .stderr
+                .contains("[the output was truncated to 10 bytes]")
+        );
+        assert!(
+            output
+                .stderr
+                .ends_with("[cargo check was killed after running for 0.5 seconds]\n")
+        );
+    }
+
+    #[test]
+    fn toolchain_version() {
+        assert!(rustc_version(None).unwrap().starts_with("rustc "));
+        assert!(rustc_version(Some("no-such-toolchain")).is_err());
+    }
+
+    #[test]
+    fn workspace() {
+        let dir = TempDir::new("workspace");
+        let package = |name: &str, code: &str| {
+            std::fs::create_dir_all(dir.join(name).join("src")).unwrap();
+            std::fs::write(
+                dir.join(name).join("Cargo.toml"),
+                format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nedition = \"2024\"\n"),
+            )
+            .unwrap();
+            std::fs::write(dir.join(name).join("src/lib.rs"), code).unwrap();
+        };
+        package("core", "pub fn f() -> u32 { \"\" }\n");
+        package("cli", "pub fn g() { h() }\n");
+        package("tool", "");
+        let manifest = dir.join("tool/Cargo.toml");
+        let contents = std::fs::read_to_string(&manifest).unwrap();
+        std::fs::write(
+            &manifest,
+            contents + "[dependencies]\ncore = { path = \"../core\" }\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"core\", \"cli\", \"tool\"]\n",
+        )
+        .unwrap();
+
+        let members = workspace_members(&dir, &CargoOptions::default()).unwrap();
+        let names: Vec<&str> = members.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names.len(), 3);
+        assert!(names.contains(&"core") && names.contains(&"cli"));
+        assert_eq!(member_of(&members, Some("cli/src/lib.rs")), Some("cli"));
+
+        let affected = |changed: &[&str]| {
+            let changed: Vec<&Path> = changed.iter().map(Path::new).collect();
+            let mut names: Vec<&str> = affected_members(&members, &changed)
+                .into_iter()
+                .map(|member| member.name.as_str())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(affected(&["core/src/lib.rs"]), ["core", "tool"]);
+        assert_eq!(affected(&["cli/src/lib.rs"]), ["cli"]);
+        assert_eq!(affected(&["Cargo.lock"]), ["cli", "core", "tool"]);
+        assert_eq!(affected(&[]), ["cli", "core", "tool"]);
+
+        let checked: Vec<&Member> = members.iter().filter(|m| m.name != "tool").collect();
+        let output = check_workspace(&dir, &members, &checked, &CargoOptions::default()).unwrap();
+        assert!(!output.success);
+        let errors: Vec<(&str, &str)> = output
+            .errors()
+            .map(|e| (e.file.as_deref().unwrap(), e.member.as_deref().unwrap()))
+            .collect();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.contains(&("core/src/lib.rs", "core")));
+        assert!(errors.contains(&("cli/src/lib.rs", "cli")));
+        assert!(
+            output
+                .report()
+                .contains("==== cli/src/lib.rs (crate `cli`): 1 error(s) ====")
+        );
+
+        // A check without known changes, e.g. the one the model asks for, covers every member.
+        let checked = affected_members(&members, &[]);
+        let output = check_workspace(&dir, &members, &checked, &CargoOptions::default()).unwrap();
+        assert!(!output.success);
+        assert!(output.errors().any(|e| e.member.as_deref() == Some("core")));
+    }
+}
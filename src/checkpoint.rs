@@ -0,0 +1,153 @@
+use log::warn;
+use openai::chat::ChatCompletionMessage;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Name of the checkpoint file in the session directory.
+pub const CHECKPOINT_FILE: &str = "checkpoint.json";
+
+/// State of the run that isn't kept anywhere else, so that an interrupted conversion can be
+/// resumed with `riir resume`. The plan, the todo list and the notes have their own files.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct CheckpointState {
+    /// working directory of the run, the arguments may be relative to it
+    pub dir: PathBuf,
+    /// command line arguments of the run, without the program name
+    pub args: Vec<String>,
+    pub phase: Phase,
+    /// conversation with the model
+    pub messages: Vec<ChatCompletionMessage>,
+    /// destination files changed since the last successful verification
+    pub unverified: Vec<PathBuf>,
+    /// tokens spent on the model so far
+    pub tokens: u64,
+}
+
+/// How far the run got.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    #[default]
+    Analysis,
+    Planning,
+    Conversion,
+    Done,
+}
+
+/// Checkpoint of the run, saved on every change.
+pub struct Checkpoint {
+    path: PathBuf,
+    state: Mutex<CheckpointState>,
+}
+
+impl Checkpoint {
+    /// Starts a new checkpoint for a run with the given arguments.
+    pub fn new(path: PathBuf, dir: PathBuf, args: Vec<String>) -> Self {
+        Checkpoint {
+            path,
+            state: Mutex::new(CheckpointState {
+                dir,
+                args,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Loads the checkpoint of an interrupted run.
+    pub fn load(path: PathBuf) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Cannot read {}: {e}", path.display()))?;
+        let state = serde_json::from_str(&contents)
+            .map_err(|e| format!("Cannot parse {}: {e}", path.display()))?;
+        Ok(Checkpoint {
+            path,
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn state(&self) -> CheckpointState {
+        self.state.lock().unwrap().clone()
+    }
+
+    pub fn set_phase(&self, phase: Phase) {
+        self.update(|state| state.phase = phase);
+    }
+
+    pub fn set_messages(&self, messages: &[ChatCompletionMessage], tokens: u64) {
+        self.update(|state| {
+            state.messages = messages.to_vec();
+            state.tokens = tokens;
+        });
+    }
+
+    pub fn set_unverified<'a>(&self, unverified: impl IntoIterator<Item = &'a PathBuf>) {
+        let unverified = unverified.into_iter().cloned().collect();
+        self.update(|state| state.unverified = unverified);
+    }
+
+    fn update(&self, change: impl FnOnce(&mut CheckpointState)) {
+        let mut state = self.state.lock().unwrap();
+        change(&mut state);
+        // Losing the checkpoint only means starting from scratch, so the run goes on.
+        if let Err(e) = save(&self.path, &state) {
+            warn!("{e}");
+        }
+    }
+}
+
+fn save(path: &Path, state: &CheckpointState) -> Result<(), String> {
+    let contents = serde_json::to_string(state).map_err(|e| e.to_string())?;
+    // Writing to a temporary file first, a crash in the middle leaves the old checkpoint intact.
+    let temp = path.with_extension("json.tmp");
+    std::fs::write(&temp, contents)
+        .and_then(|_| std::fs::rename(&temp, path))
+        .map_err(|e| format!("Cannot save the checkpoint to {}: {e}", path.display()))
+}
+
+/// Finds the checkpoint of a session given its directory or the destination project directory.
+pub fn checkpoint_path(session: &Path) -> PathBuf {
+    let nested = session.join(crate::session::SESSION_DIR);
+    if nested.is_dir() {
+        nested.join(CHECKPOINT_FILE)
+    } else {
+        session.join(CHECKPOINT_FILE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use openai::chat::ChatCompletionMessageRole;
+
+    #[test]
+    fn resume() {
+        let dir = TempDir::new("checkpoint");
+        let session = dir.join(crate::session::SESSION_DIR);
+        std::fs::create_dir_all(&session).unwrap();
+        let path = checkpoint_path(&dir);
+        assert_eq!(path, session.join(CHECKPOINT_FILE));
+        assert_eq!(checkpoint_path(&session), path);
+        assert!(Checkpoint::load(path.clone()).is_err());
+
+        let args = vec!["src".to_string(), "dst".to_string()];
+        let checkpoint = Checkpoint::new(path.clone(), dir.to_path_buf(), args.clone());
+        checkpoint.set_phase(Phase::Conversion);
+        let message = ChatCompletionMessage {
+            role: ChatCompletionMessageRole::User,
+            content: Some("Convert".to_string()),
+            ..Default::default()
+        };
+        checkpoint.set_messages(std::slice::from_ref(&message), 1200);
+        checkpoint.set_unverified(&[PathBuf::from("src/lib.rs")]);
+
+        let state = Checkpoint::load(path).unwrap().state();
+        assert_eq!(state.dir, *dir);
+        assert_eq!(state.args, args);
+        assert_eq!(state.phase, Phase::Conversion);
+        assert_eq!(state.messages, [message]);
+        assert_eq!(state.unverified, [PathBuf::from("src/lib.rs")]);
+        assert_eq!(state.tokens, 1200);
+    }
+}
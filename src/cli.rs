@@ -0,0 +1,395 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// Integration tests of the destination project comparing its command line with the original.
+pub const TEST_FILE: &str = "tests/cli_compat.rs";
+/// Name of the test target of `TEST_FILE`.
+pub const TEST_TARGET: &str = "cli_compat";
+
+/// A flag or a list of flags in the option column of `--help`, e.g. `-o FILE, --output=FILE`.
+static FLAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|[\s,\[])(--?[A-Za-z0-9][\w-]*)").unwrap());
+/// A call defining options in argparse, optparse, click, typer, commander, yargs or picocli.
+static OPTION_CALL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(add_argument|add_option|option|requiredOption|Option)\s*\(").unwrap()
+});
+static STRING: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#""([^"\\]*)"|'([^'\\]*)'"#).unwrap());
+/// A subcommand of argparse, click, commander or yargs.
+static SUBCOMMAND: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\b(?:add_parser|command)\(\s*["']([\w-]+)"#).unwrap());
+/// A cobra command other than the root one.
+static COBRA_COMMAND: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(\w+)\s*:?=\s*&cobra\.Command\{[^}]*?Use:\s*"([\w-]+)"#).unwrap()
+});
+/// A flag of the Go `flag` package, e.g. `flag.Bool("verbose", ...)`.
+static GO_FLAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\bflag\.\w+\(\s*(?:&\w+,\s*)?"([\w-]+)""#).unwrap());
+/// A flag of cobra (pflag), e.g. `Flags().BoolP("verbose", "v", ...)`.
+static PFLAG: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"Flags\(\)\.\w+?(P?)\(\s*(?:&\w+,\s*)?"([\w-]+)"(?:,\s*"(\w)")?"#).unwrap()
+});
+/// An entry of the `struct option` array of `getopt_long`, e.g. `{"verbose", no_argument, ...}`.
+static LONG_OPTION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"\{\s*"([\w-]+)"\s*,\s*(?:no_argument|required_argument|optional_argument)"#)
+        .unwrap()
+});
+/// The short options of `getopt`, e.g. `getopt(argc, argv, "vo:")`.
+static SHORT_OPTIONS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\bgetopt(?:_long)?\([^,]+,[^,]+,\s*"([^"]*)""#).unwrap());
+
+/// Flags and subcommands of the original program, which the port has to accept too.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CliInterface {
+    /// e.g. `-v` and `--verbose`
+    pub flags: BTreeSet<String>,
+    pub subcommands: BTreeSet<String>,
+}
+
+impl CliInterface {
+    /// Finds the flags and subcommands defined with the common argument parsers: argparse,
+    /// click, typer, commander, yargs, picocli, the Go `flag` package, cobra and getopt.
+    pub fn from_sources(root: &Path, files: &[String]) -> Self {
+        let mut interface = CliInterface::default();
+        for file in files {
+            let Ok(source) = std::fs::read_to_string(root.join(file)) else {
+                continue;
+            };
+            for line in source.lines().filter(|line| OPTION_CALL.is_match(line)) {
+                for literal in STRING.captures_iter(line) {
+                    let literal = literal.get(1).or(literal.get(2)).unwrap().as_str();
+                    // Commander defines several flags in one string, e.g. "-o, --output <file>".
+                    if literal.starts_with('-') {
+                        interface.add_flags(literal);
+                    }
+                }
+            }
+            for captures in SUBCOMMAND.captures_iter(&source) {
+                interface.subcommands.insert(captures[1].to_string());
+            }
+            for captures in COBRA_COMMAND.captures_iter(&source) {
+                if !captures[1].starts_with("root") {
+                    interface.subcommands.insert(captures[2].to_string());
+                }
+            }
+            for captures in GO_FLAG.captures_iter(&source) {
+                interface.flags.insert(format!("-{}", &captures[1]));
+            }
+            for captures in PFLAG.captures_iter(&source) {
+                interface.flags.insert(format!("--{}", &captures[2]));
+                if let Some(short) = captures.get(3).filter(|_| !captures[1].is_empty()) {
+                    interface.flags.insert(format!("-{}", short.as_str()));
+                }
+            }
+            for captures in LONG_OPTION.captures_iter(&source) {
+                interface.flags.insert(format!("--{}", &captures[1]));
+            }
+            for captures in SHORT_OPTIONS.captures_iter(&source) {
+                for c in captures[1].chars().filter(char::is_ascii_alphanumeric) {
+                    interface.flags.insert(format!("-{c}"));
+                }
+            }
+        }
+        interface.remove_help();
+        interface
+    }
+
+    /// Parses the `--help` output of the original program: the flags of the lines starting with
+    /// `-`, and the subcommands listed in a section like `Commands:` or in braces by argparse.
+    pub fn from_help(help: &str) -> Self {
+        let mut interface = CliInterface::default();
+        let mut in_commands = false;
+        for line in help.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            if !line.starts_with(char::is_whitespace) {
+                in_commands = trimmed.ends_with(':') && trimmed.to_lowercase().contains("command");
+                continue;
+            }
+            if trimmed.starts_with('-') {
+                // The description starts after a wider gap.
+                let column = trimmed.split("  ").next().unwrap_or(trimmed);
+                interface.add_flags(column);
+            } else if let Some(list) = trimmed
+                .split_whitespace()
+                .next()
+                .and_then(|word| word.strip_prefix('{')?.strip_suffix('}'))
+            {
+                interface
+                    .subcommands
+                    .extend(list.split(',').map(str::to_string));
+            } else if in_commands
+                && let Some(name) = trimmed.split_whitespace().next()
+                && name.starts_with(|c: char| c.is_ascii_lowercase())
+                && name
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+            {
+                interface.subcommands.insert(name.to_string());
+            }
+        }
+        interface.remove_help();
+        interface
+    }
+
+    pub fn load(path: &Path) -> Option<Self> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, contents).map_err(|e| format!("Cannot write {}: {e}", path.display()))
+    }
+
+    pub fn merge(&mut self, other: CliInterface) {
+        self.flags.extend(other.flags);
+        self.subcommands.extend(other.subcommands);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flags.is_empty() && self.subcommands.is_empty()
+    }
+
+    /// Tells the model to keep the interface when it ports the entry point.
+    pub fn instructions(&self) -> String {
+        let list = |names: &BTreeSet<String>| {
+            names
+                .iter()
+                .map(|name| format!("`{name}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let mut instructions = "The program is a command line tool, and its users rely on its \
+            interface: parse the arguments with clap, or argh if the crate already uses it, and \
+            accept the same flags and subcommands with the same meaning as the original"
+            .to_string();
+        if !self.flags.is_empty() {
+            instructions += &format!("; flags: {}", list(&self.flags));
+        }
+        if !self.subcommands.is_empty() {
+            instructions += &format!("; subcommands: {}", list(&self.subcommands));
+        }
+        instructions + "."
+    }
+
+    /// Generates the integration tests of `TEST_FILE`: the `--help` of the port lists the flags
+    /// and subcommands, and both programs succeed or fail alike with the same arguments. The
+    /// original program is only run if `RIIR_ORIGINAL` is set to its command, in the directory
+    /// `RIIR_ORIGINAL_DIR`.
+    pub fn test_file(&self, binary: &str) -> String {
+        let mut cases = vec![vec!["--help".to_string()]];
+        cases.extend(
+            self.subcommands
+                .iter()
+                .map(|subcommand| vec![subcommand.clone(), "--help".to_string()]),
+        );
+        if self.flags.contains("--version") {
+            cases.push(vec!["--version".to_string()]);
+        }
+        cases.push(vec![UNKNOWN_FLAG.to_string()]);
+        let cases: String = cases
+            .iter()
+            .map(|args| format!("    &{args:?},\n"))
+            .collect();
+        format!(
+            "//! Checks that the command line interface of the port matches the original program.\n\
+            //! Generated by riir; set `RIIR_ORIGINAL` to the command of the original program and\n\
+            //! `RIIR_ORIGINAL_DIR` to its working directory to compare both programs.\n\
+            \n\
+            use std::process::{{Command, Output}};\n\
+            \n\
+            const FLAGS: &[&str] = &{flags:?};\n\
+            const SUBCOMMANDS: &[&str] = &{subcommands:?};\n\
+            /// Arguments both programs run with.\n\
+            const CASES: &[&[&str]] = &[\n\
+            {cases}];\n\
+            const UNKNOWN_FLAG: &str = {UNKNOWN_FLAG:?};\n\
+            \n\
+            fn ported(args: &[&str]) -> Output {{\n\
+            \x20   Command::new(env!(\"CARGO_BIN_EXE_{binary}\"))\n\
+            \x20       .args(args)\n\
+            \x20       .output()\n\
+            \x20       .unwrap()\n\
+            }}\n\
+            \n\
+            fn original(args: &[&str]) -> Option<Output> {{\n\
+            \x20   let command = std::env::var(\"RIIR_ORIGINAL\").ok()?;\n\
+            \x20   let mut command = command.split_whitespace();\n\
+            \x20   let mut original = Command::new(command.next()?);\n\
+            \x20   original.args(command).args(args);\n\
+            \x20   if let Ok(dir) = std::env::var(\"RIIR_ORIGINAL_DIR\") {{\n\
+            \x20       original.current_dir(dir);\n\
+            \x20   }}\n\
+            \x20   Some(original.output().expect(\"cannot run the original program\"))\n\
+            }}\n\
+            \n\
+            #[test]\n\
+            fn help_lists_the_original_interface() {{\n\
+            \x20   let output = ported(&[\"--help\"]);\n\
+            \x20   let help = String::from_utf8_lossy(&output.stdout);\n\
+            \x20   let words: Vec<&str> = help\n\
+            \x20       .split(|c: char| c.is_whitespace() || \",[]<>=|\".contains(c))\n\
+            \x20       .collect();\n\
+            \x20   let missing: Vec<&str> = FLAGS\n\
+            \x20       .iter()\n\
+            \x20       .chain(SUBCOMMANDS)\n\
+            \x20       .copied()\n\
+            \x20       .filter(|name| !words.contains(name))\n\
+            \x20       .collect();\n\
+            \x20   assert!(missing.is_empty(), \"`--help` doesn't list {{missing:?}}\");\n\
+            }}\n\
+            \n\
+            #[test]\n\
+            fn accepts_the_same_arguments() {{\n\
+            \x20   for args in CASES {{\n\
+            \x20       let ported = ported(args).status.success();\n\
+            \x20       let expected = match original(args) {{\n\
+            \x20           Some(original) => original.status.success(),\n\
+            \x20           None => !args.contains(&UNKNOWN_FLAG),\n\
+            \x20       }};\n\
+            \x20       assert_eq!(ported, expected, \"the port succeeds: {{ported}}, with {{args:?}}\");\n\
+            \x20   }}\n\
+            }}\n",
+            flags = self.flags.iter().collect::<Vec<_>>(),
+            subcommands = self.subcommands.iter().collect::<Vec<_>>(),
+        )
+    }
+
+    fn add_flags(&mut self, column: &str) {
+        for captures in FLAG.captures_iter(column) {
+            self.flags.insert(captures[1].to_string());
+        }
+    }
+
+    /// Every parser has its own help flag.
+    fn remove_help(&mut self) {
+        self.flags.remove("-h");
+        self.flags.remove("--help");
+        self.subcommands.remove("help");
+    }
+}
+
+/// A flag neither program should accept.
+const UNKNOWN_FLAG: &str = "--riir-unknown-flag";
+
+/// Returns the environment telling the generated tests how to run the original program: its
+/// command with the program resolved like the equivalence cases do, and the copy of the source
+/// project to run it in.
+pub fn original_env(command: &[String], source_copy: &Path) -> Vec<(&'static str, String)> {
+    let Some((program, args)) = command.split_first() else {
+        return vec![];
+    };
+    let path = Path::new(program);
+    let program = if path.is_relative() && path.components().count() > 1 {
+        source_copy.join(path).to_string_lossy().to_string()
+    } else {
+        program.clone()
+    };
+    let command = std::iter::once(program)
+        .chain(args.iter().cloned())
+        .collect::<Vec<_>>()
+        .join(" ");
+    vec![
+        ("RIIR_ORIGINAL", command),
+        (
+            "RIIR_ORIGINAL_DIR",
+            source_copy.to_string_lossy().to_string(),
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn interface() {
+        let help = "usage: tool [-h] [-v] [-o FILE] {add,remove} ...\n\
+            \n\
+            positional arguments:\n\
+            \x20 {add,remove}\n\
+            \n\
+            options:\n\
+            \x20 -h, --help            show this help message and exit\n\
+            \x20 -v, --verbose         print more\n\
+            \x20 -o FILE, --output=FILE  write to FILE, e.g. --output -\n\
+            \n\
+            Commands:\n\
+            \x20 list       list the items\n";
+        let mut interface = CliInterface::from_help(help);
+        assert_eq!(
+            interface.flags.iter().collect::<Vec<_>>(),
+            ["--output", "--verbose", "-o", "-v"]
+        );
+        assert_eq!(
+            interface.subcommands.iter().collect::<Vec<_>>(),
+            ["add", "list", "remove"]
+        );
+
+        let dir = TempDir::new("cli");
+        std::fs::write(
+            dir.join("cli.py"),
+            "parser.add_argument('-n', '--count', type=int)\nsub.add_parser('show')\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("main.go"),
+            "var rootCmd = &cobra.Command{\n\tUse: \"tool\",\n}\n\
+            var syncCmd = &cobra.Command{\n\tUse: \"sync\",\n}\n\
+            func init() {\n\tsyncCmd.Flags().BoolP(\"dry-run\", \"d\", false, \"\")\n\
+            \tflag.IntVar(&depth, \"depth\", 1, \"\")\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("main.c"),
+            "static struct option options[] = {\n\
+            \x20   {\"quiet\", no_argument, 0, 'q'},\n};\n\
+            while ((c = getopt_long(argc, argv, \"qh\", options, NULL)) != -1) {}\n",
+        )
+        .unwrap();
+        let files = ["cli.py", "main.c", "main.go"].map(String::from);
+        interface.merge(CliInterface::from_sources(&dir, &files));
+        assert_eq!(
+            interface.flags.iter().collect::<Vec<_>>(),
+            [
+                "--count",
+                "--dry-run",
+                "--output",
+                "--quiet",
+                "--verbose",
+                "-d",
+                "-depth",
+                "-n",
+                "-o",
+                "-q",
+                "-v"
+            ]
+        );
+        assert_eq!(
+            interface.subcommands.iter().collect::<Vec<_>>(),
+            ["add", "list", "remove", "show", "sync"]
+        );
+
+        let tests = interface.test_file("tool");
+        assert!(tests.contains("env!(\"CARGO_BIN_EXE_tool\")"));
+        assert!(tests.contains("    &[\"sync\", \"--help\"],\n"));
+        assert!(tests.contains("const UNKNOWN_FLAG: &str = \"--riir-unknown-flag\";"));
+        assert_eq!(
+            original_env(
+                &["./tool".to_string(), "-x".to_string()],
+                Path::new("/copy")
+            ),
+            [
+                ("RIIR_ORIGINAL", "/copy/./tool -x".to_string()),
+                ("RIIR_ORIGINAL_DIR", "/copy".to_string()),
+            ]
+        );
+    }
+}
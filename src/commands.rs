@@ -0,0 +1,85 @@
+use crate::config::VerifyConfig;
+use crate::process::run_command;
+use crate::reference::tail;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+/// The captured stdout and stderr of a command are each truncated to this many bytes.
+const MAX_CAPTURED_OUTPUT: usize = 4 * 1024 * 1024;
+
+/// A verification command that failed.
+pub struct CommandFailure {
+    pub command: String,
+    /// e.g. "exit code 1"
+    pub status: String,
+    /// the end of what the command printed, stdout first
+    pub output: String,
+}
+
+/// Runs the verification commands with `sh -c` in the destination project and returns the
+/// first one that fails, if any.
+pub fn run_verify_commands(
+    config: &VerifyConfig,
+    dir: &Path,
+) -> Result<Option<CommandFailure>, String> {
+    let timeout = Some(Duration::from_secs(config.timeout)).filter(|timeout| !timeout.is_zero());
+    for command in &config.commands {
+        let output = run_command(
+            Command::new("sh").args(["-c", command]).current_dir(dir),
+            None,
+            timeout,
+            Some(MAX_CAPTURED_OUTPUT),
+        )?;
+        if output.success {
+            continue;
+        }
+        let status = if output.timed_out {
+            format!("killed after running for {} seconds", config.timeout)
+        } else {
+            match output.exit_code {
+                Some(code) => format!("exit code {code}"),
+                None => "killed by a signal".to_string(),
+            }
+        };
+        let printed = format!("{}{}", output.stdout, output.stderr);
+        return Ok(Some(CommandFailure {
+            command: command.clone(),
+            status,
+            output: tail(printed.trim_end()),
+        }));
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn verify_commands() {
+        let dir = TempDir::new("commands");
+        std::fs::write(dir.join("schema.sql"), "").unwrap();
+        let config = |commands: &[&str]| VerifyConfig {
+            commands: commands.iter().map(|command| command.to_string()).collect(),
+            ..Default::default()
+        };
+
+        let passing = config(&["test -f schema.sql", "true"]);
+        assert!(run_verify_commands(&passing, &dir).unwrap().is_none());
+
+        let failing = config(&[
+            "true",
+            "echo checking; echo missing query >&2; exit 2",
+            "false",
+        ]);
+        let failure = run_verify_commands(&failing, &dir).unwrap().unwrap();
+        assert_eq!(
+            failure.command,
+            "echo checking; echo missing query >&2; exit 2"
+        );
+        assert_eq!(failure.status, "exit code 2");
+        assert_eq!(failure.output, "checking\nmissing query");
+    }
+}
@@ -0,0 +1,143 @@
+use crate::items::all_rust_items;
+use crate::symbols::{Symbol, file_symbols};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct CompareFilesArgs {
+    /// a relative path to the file in the source project directory
+    pub source_path: String,
+    /// a relative path to the ported Rust file in the destination project directory
+    pub destination_path: String,
+    /// include a unified diff of the two files, false by default
+    pub diff: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct CompareFilesResult {
+    pub error: Option<String>,
+    /// set if the symbols of the source file can't be compared
+    pub note: Option<String>,
+    /// source symbols without a Rust item of a similar name, they may have been dropped
+    pub missing: Vec<Symbol>,
+    /// number of source symbols found in the Rust file
+    pub ported: usize,
+    /// Rust items without a source symbol of a similar name
+    pub rust_only: Vec<String>,
+    pub diff: Option<String>,
+}
+
+impl CompareFilesResult {
+    pub fn error(error: String) -> Self {
+        CompareFilesResult {
+            error: Some(error),
+            ..Default::default()
+        }
+    }
+}
+
+/// Compares the symbols defined in a source file with the items of its Rust counterpart.
+///
+/// Names are compared ignoring case and underscores, so `parseConfig` matches `parse_config`.
+pub fn compare_files(
+    source_path: &str,
+    source: &str,
+    destination: &str,
+    diff: bool,
+) -> CompareFilesResult {
+    let mut result = CompareFilesResult::default();
+    if diff {
+        result.diff = Some(diffy::create_patch(source, destination).to_string());
+    }
+
+    let rust = all_rust_items(destination);
+    if let Some(error) = rust.error {
+        result.error = Some(error);
+        return result;
+    }
+    let Some(symbols) = file_symbols(Path::new(source_path), source) else {
+        result.note = Some("Symbols of this language are not supported.".to_string());
+        return result;
+    };
+
+    let rust_names: HashSet<String> = rust
+        .items
+        .iter()
+        .map(|item| normalize(&item.name))
+        .collect();
+    let source_names: HashSet<String> = symbols
+        .iter()
+        .map(|symbol| normalize(&symbol.name))
+        .collect();
+    for symbol in symbols {
+        if is_include_guard(&symbol) {
+            continue;
+        }
+        if rust_names.contains(&normalize(&symbol.name)) {
+            result.ported += 1;
+        } else {
+            result.missing.push(symbol);
+        }
+    }
+    result.rust_only = rust
+        .items
+        .into_iter()
+        .filter(|item| item.kind != "use" && item.kind != "impl")
+        .filter(|item| !source_names.contains(&normalize(&item.name)))
+        .map(|item| item.name)
+        .collect();
+    result
+}
+
+/// Reduces a name to its last path segment in lower case without underscores.
+fn normalize(name: &str) -> String {
+    let name = name.rsplit([':', '.']).next().unwrap_or(name);
+    name.chars()
+        .filter(|&c| c != '_')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn is_include_guard(symbol: &Symbol) -> bool {
+    symbol.kind == "macro"
+        && ["_H", "_H_", "_HPP", "_INCLUDED"]
+            .iter()
+            .any(|suffix| symbol.name.ends_with(suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare() {
+        let source = "#ifndef LIST_H\n#define LIST_H\n#define MAX_ITEMS 16\n\
+            struct list { int len; };\n\
+            int listLength(struct list *l) { return l->len; }\n\
+            void list_free(struct list *l) {}\n#endif\n";
+        let destination = "pub const MAX_ITEMS: usize = 16;\n\
+            pub struct List { len: i32 }\n\
+            impl List {\n    pub fn list_length(&self) -> i32 { self.len }\n}\n\
+            fn helper() {}\n";
+
+        let result = compare_files("list.h", source, destination, true);
+        assert!(result.error.is_none());
+        let missing: Vec<_> = result.missing.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(missing, ["list_free"]);
+        assert_eq!(result.ported, 3);
+        assert_eq!(result.rust_only, ["helper"]);
+        assert!(
+            result
+                .diff
+                .unwrap()
+                .contains("+pub struct List { len: i32 }")
+        );
+
+        let result = compare_files("list.h", source, "fn (", false);
+        assert!(result.error.is_some());
+        let result = compare_files("list.lua", source, destination, false);
+        assert!(result.note.is_some());
+    }
+}
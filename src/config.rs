@@ -0,0 +1,418 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Name of the configuration file looked up in the current directory.
+pub const CONFIG_FILE: &str = "riir.toml";
+
+/// Configuration loaded from `riir.toml`.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub ignore: IgnoreConfig,
+    pub clippy: ClippyConfig,
+    pub reference: ReferenceConfig,
+    pub equivalence: EquivalenceConfig,
+    pub fuzz: FuzzConfig,
+    pub benchmark: BenchmarkConfig,
+    pub audit: AuditConfig,
+    pub escalation: EscalationConfig,
+    pub warnings: WarningsConfig,
+    pub verify: VerifyConfig,
+    pub review: ReviewConfig,
+    pub layout: LayoutConfig,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read {}: {e}", path.display()))?;
+        toml::from_str(&contents).map_err(|e| format!("Cannot parse {}: {e}", path.display()))
+    }
+}
+
+/// Files hidden from the model in both projects.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IgnoreConfig {
+    /// whether the built-in patterns (`.git`, `target`, `Cargo.lock`, ...) are used
+    pub use_defaults: bool,
+    /// glob patterns of paths relative to the project root, e.g. `tests/fixtures/**`
+    pub patterns: Vec<String>,
+}
+
+impl Default for IgnoreConfig {
+    fn default() -> Self {
+        IgnoreConfig {
+            use_defaults: true,
+            patterns: vec![],
+        }
+    }
+}
+
+/// The optional clippy pass run after the destination project compiles.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClippyConfig {
+    pub enabled: bool,
+    /// whether all warnings, including the ones of the compiler, must be fixed; otherwise only
+    /// the clippy lints are
+    pub deny_warnings: bool,
+    /// whether the `clippy::pedantic` lints are enabled
+    pub pedantic: bool,
+    /// lints that are allowed, e.g. `clippy::too_many_arguments`
+    pub allow: Vec<String>,
+}
+
+impl Default for ClippyConfig {
+    fn default() -> Self {
+        ClippyConfig {
+            enabled: false,
+            deny_warnings: true,
+            pedantic: false,
+            allow: vec![],
+        }
+    }
+}
+
+/// The optional build and tests of the source project, run once at the start so that the model
+/// knows how the original behaves.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReferenceConfig {
+    pub enabled: bool,
+    /// shell commands per language of the source project, e.g. `Python = ["pytest -q"]`; they
+    /// replace the built-in ones for that language
+    pub commands: BTreeMap<String, Vec<String>>,
+    /// maximum time in seconds each command may take
+    pub timeout: u64,
+}
+
+impl Default for ReferenceConfig {
+    fn default() -> Self {
+        ReferenceConfig {
+            enabled: false,
+            commands: BTreeMap::new(),
+            timeout: 600,
+        }
+    }
+}
+
+/// Cases run with both the original and the ported program once the destination project builds,
+/// comparing their output.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EquivalenceConfig {
+    /// command running the original program, e.g. `["./mytool"]`; a relative path is resolved in
+    /// the copy of the source project
+    pub original: Vec<String>,
+    /// command running the ported program, e.g. `["target/debug/mytool"]`; a relative path is
+    /// resolved in the destination project
+    pub ported: Vec<String>,
+    /// whether stderr has to match too, not only stdout and the exit code
+    pub compare_stderr: bool,
+    /// maximum time in seconds each run may take
+    pub timeout: u64,
+    pub cases: Vec<EquivalenceCase>,
+}
+
+impl Default for EquivalenceConfig {
+    fn default() -> Self {
+        EquivalenceConfig {
+            original: vec![],
+            ported: vec![],
+            compare_stderr: true,
+            timeout: 60,
+            cases: vec![],
+        }
+    }
+}
+
+/// Input of both programs. They run in the copy of the source project, so the arguments may
+/// refer to its files.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EquivalenceCase {
+    pub name: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// text written to stdin
+    pub stdin: Option<String>,
+    /// file of the source project written to stdin
+    pub stdin_file: Option<PathBuf>,
+}
+
+/// Random inputs fed to harnesses calling the original library and its port once the conversion
+/// is done.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FuzzConfig {
+    pub enabled: bool,
+    /// number of random inputs in each round
+    pub iterations: usize,
+    /// maximum size of an input in bytes
+    pub max_input_size: usize,
+    /// seed of the random inputs, the same seed gives the same inputs
+    pub seed: u64,
+    /// maximum time in seconds each harness may take for all inputs
+    pub timeout: u64,
+}
+
+impl Default for FuzzConfig {
+    fn default() -> Self {
+        FuzzConfig {
+            enabled: false,
+            iterations: 1000,
+            max_input_size: 64,
+            seed: 1,
+            timeout: 300,
+        }
+    }
+}
+
+/// Commands timed with both the original and the ported program at the end of the conversion.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BenchmarkConfig {
+    /// how many times each command is timed after a warm-up run; the median is reported
+    pub runs: usize,
+    /// maximum time in seconds each run may take
+    pub timeout: u64,
+    pub cases: Vec<BenchmarkCase>,
+}
+
+impl Default for BenchmarkConfig {
+    fn default() -> Self {
+        BenchmarkConfig {
+            runs: 5,
+            timeout: 600,
+            cases: vec![],
+        }
+    }
+}
+
+/// Shell commands running the same workload with both programs. They run in the copy of the
+/// source project, and the destination project is in `$RIIR_DESTINATION`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BenchmarkCase {
+    pub name: String,
+    pub original: String,
+    pub ported: String,
+}
+
+/// Checks of the dependencies of the destination project, run whenever they change.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditConfig {
+    /// whether `cargo audit` looks for crates with known vulnerabilities
+    pub enabled: bool,
+    /// a `deny.toml` with the policy checked by `cargo deny`, e.g. of the allowed licenses
+    pub deny_policy: Option<PathBuf>,
+}
+
+/// The optional review of every change of a destination file before it's written.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReviewConfig {
+    pub enabled: bool,
+    /// model doing the review, ideally a different one than the model converting the project;
+    /// the same model by default
+    pub model: Option<String>,
+    /// how many times the changes of a file are sent back before they are written anyway
+    pub max_rejections: usize,
+}
+
+impl Default for ReviewConfig {
+    fn default() -> Self {
+        ReviewConfig {
+            enabled: false,
+            model: None,
+            max_rejections: 2,
+        }
+    }
+}
+
+/// What happens when the model fails to fix the same problem again and again.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EscalationConfig {
+    /// how many failed verifications in a row, blamed on the same file or stage, trigger the
+    /// next step
+    pub max_failures: usize,
+    /// the steps taken one after another; the problem is flagged after the last one anyway
+    pub steps: Vec<EscalationStep>,
+    /// model used after the `stronger_model` step
+    pub stronger_model: Option<String>,
+}
+
+impl Default for EscalationConfig {
+    fn default() -> Self {
+        EscalationConfig {
+            max_failures: 5,
+            steps: vec![EscalationStep::Reset, EscalationStep::Flag],
+            stronger_model: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EscalationStep {
+    /// switch to the stronger model
+    StrongerModel,
+    /// start a new conversation with a summary of the state
+    Reset,
+    /// give up on the problem and leave it to the operator
+    Flag,
+}
+
+/// What happens to the compiler warnings once the destination project compiles.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WarningsConfig {
+    pub policy: WarningPolicy,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningPolicy {
+    /// ignore the warnings
+    #[default]
+    Allow,
+    /// log the warnings but accept the changes
+    Report,
+    /// have the model fix the warnings like errors
+    Deny,
+}
+
+/// Commands verifying the destination project besides cargo, e.g. `cargo sqlx prepare --check`
+/// or `wasm-pack build`.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VerifyConfig {
+    /// shell commands run in the destination project in this order once it compiles
+    pub commands: Vec<String>,
+    /// maximum time in seconds each command may take
+    pub timeout: u64,
+}
+
+impl Default for VerifyConfig {
+    fn default() -> Self {
+        VerifyConfig {
+            commands: vec![],
+            timeout: 600,
+        }
+    }
+}
+
+/// Where the ported files go in the destination project.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutConfig {
+    /// the first rule matching a source file decides its destination
+    pub rules: Vec<LayoutRule>,
+    /// glob patterns of Rust files the model may write besides the destinations of the rules,
+    /// e.g. `src/error.rs`
+    pub allow: Vec<String>,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutRule {
+    /// glob pattern of source files, e.g. `src/**/*.py`
+    pub source: String,
+    /// destination of the matching files, e.g. `src/{path}.rs`, where `{path}` is the path after
+    /// the directory of the pattern and `{name}` the file name, both without the extension; a
+    /// directory ending with `/` stands for `<directory>/{path}.rs`
+    pub destination: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_config() {
+        let config: Config = toml::from_str(
+            r#"
+            [ignore]
+            patterns = ["data/**", "*.csv"]
+            "#,
+        )
+        .unwrap();
+        assert!(config.ignore.use_defaults);
+        assert_eq!(config.ignore.patterns, ["data/**", "*.csv"]);
+        assert!(!config.clippy.enabled);
+
+        let config: Config = toml::from_str(
+            r#"
+            [clippy]
+            enabled = true
+            allow = ["clippy::too_many_arguments"]
+            "#,
+        )
+        .unwrap();
+        assert!(config.clippy.enabled && config.clippy.deny_warnings);
+        assert_eq!(config.clippy.allow, ["clippy::too_many_arguments"]);
+
+        let config: Config = toml::from_str(
+            r#"
+            [reference]
+            enabled = true
+            commands = { Python = ["pytest -q"] }
+            "#,
+        )
+        .unwrap();
+        assert!(config.reference.enabled);
+        assert_eq!(config.reference.commands["Python"], ["pytest -q"]);
+        assert_eq!(config.reference.timeout, 600);
+
+        let config: Config = toml::from_str(
+            r#"
+            [equivalence]
+            original = ["./wc"]
+            ported = ["target/debug/wc"]
+
+            [[equivalence.cases]]
+            name = "count lines"
+            args = ["-l"]
+            stdin = "a\nb\n"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.equivalence.cases[0].args, ["-l"]);
+        assert!(config.equivalence.compare_stderr);
+
+        let config: Config = toml::from_str("warnings = { policy = \"deny\" }").unwrap();
+        assert_eq!(config.warnings.policy, WarningPolicy::Deny);
+        assert!(toml::from_str::<Config>("warnings = { policy = \"strict\" }").is_err());
+
+        let config: Config = toml::from_str(
+            r#"
+            [review]
+            enabled = true
+            model = "other-model"
+            "#,
+        )
+        .unwrap();
+        assert!(config.review.enabled);
+        assert_eq!(config.review.model.as_deref(), Some("other-model"));
+        assert_eq!(config.review.max_rejections, 2);
+
+        let config: Config = toml::from_str(
+            r#"
+            [layout]
+            rules = [
+                { source = "src/**/*.py", destination = "src/{path}.rs" },
+                { source = "tests/**", destination = "tests/" },
+            ]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.layout.rules[1].destination, "tests/");
+        assert!(config.layout.allow.is_empty());
+
+        assert!(toml::from_str::<Config>("unknown = 1").is_err());
+    }
+}
@@ -0,0 +1,287 @@
+use crate::notes::Note;
+use crate::plan::{DependencyMapping, ModuleStatus, PlanContents, PlanModule};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+/// Notes on the conversion written to the destination project at the end of each run.
+pub const CONVERSION_FILE: &str = "CONVERSION.md";
+
+/// Markers of unfinished code in the Rust files.
+const MARKERS: &[&str] = &["todo!(", "unimplemented!(", "// TODO", "// FIXME", "// XXX"];
+
+/// What the conversion decided and left open, assembled from the plan, the notes of the model,
+/// the todo list and the files of the destination project.
+#[derive(Default)]
+pub struct ConversionNotes {
+    pub modules: Vec<PlanModule>,
+    pub dependencies: Vec<DependencyMapping>,
+    /// dependencies of `Cargo.toml` with their version
+    pub crates: Vec<(String, String)>,
+    /// first paragraph of the `//!` comment of each Rust file
+    pub summaries: BTreeMap<String, String>,
+    /// decisions recorded with note_write
+    pub decisions: Vec<Note>,
+    /// source files that weren't converted and problems left to the operator
+    pub gaps: Vec<String>,
+    /// unfinished tasks of the todo list
+    pub todos: Vec<String>,
+    /// lines of the Rust files with a marker of unfinished code, e.g. `src/lib.rs:12: todo!()`
+    pub markers: Vec<String>,
+}
+
+impl ConversionNotes {
+    /// Collects the plan and what the files of the destination project tell about the port.
+    pub fn collect(plan: PlanContents, root: &Path, files: &[String]) -> Self {
+        let mut notes = ConversionNotes {
+            modules: plan.modules,
+            dependencies: plan.dependencies,
+            crates: manifest_crates(root),
+            ..Default::default()
+        };
+        for file in files.iter().filter(|file| file.ends_with(".rs")) {
+            let Ok(source) = std::fs::read_to_string(root.join(file)) else {
+                continue;
+            };
+            let summary: Vec<&str> = source
+                .lines()
+                .map_while(|line| line.strip_prefix("//!"))
+                .map(str::trim)
+                .take_while(|line| !line.is_empty())
+                .collect();
+            if !summary.is_empty() {
+                notes.summaries.insert(file.clone(), summary.join(" "));
+            }
+            for (number, line) in source.lines().enumerate() {
+                if MARKERS.iter().any(|marker| line.contains(marker)) {
+                    notes
+                        .markers
+                        .push(format!("`{file}:{}`: `{}`", number + 1, line.trim()));
+                }
+            }
+        }
+        notes
+    }
+}
+
+impl fmt::Display for ConversionNotes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "# Conversion notes\n\n\
+            Generated by riir from the conversion plan and the state of the port at the end of the \
+            last run; changes to this file are overwritten."
+        )?;
+
+        let cell = |text: &str| text.replace('|', "\\|").replace('\n', " ");
+        let files = |files: &[String]| {
+            files
+                .iter()
+                .map(|file| format!("`{file}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        if !self.modules.is_empty() {
+            writeln!(
+                f,
+                "\n## Modules\n\n\
+                | Rust file | Source files | Status | Description |\n\
+                | --- | --- | --- | --- |"
+            )?;
+            for module in &self.modules {
+                let status = match module.status {
+                    ModuleStatus::Pending => "pending",
+                    ModuleStatus::Done => "done",
+                    ModuleStatus::Skipped => "skipped",
+                };
+                let description = self
+                    .summaries
+                    .get(&module.destination)
+                    .filter(|_| module.description.is_empty())
+                    .unwrap_or(&module.description);
+                writeln!(
+                    f,
+                    "| `{}` | {} | {status} | {} |",
+                    module.destination,
+                    files(&module.source),
+                    cell(description)
+                )?;
+            }
+        } else if !self.summaries.is_empty() {
+            writeln!(
+                f,
+                "\n## Modules\n\n| Rust file | Description |\n| --- | --- |"
+            )?;
+            for (file, summary) in &self.summaries {
+                writeln!(f, "| `{file}` | {} |", cell(summary))?;
+            }
+        }
+
+        if !self.dependencies.is_empty() || !self.crates.is_empty() {
+            writeln!(f, "\n## Dependencies")?;
+        }
+        if !self.dependencies.is_empty() {
+            writeln!(
+                f,
+                "\n| Original | Rust | Notes |\n\
+                | --- | --- | --- |"
+            )?;
+            for dependency in &self.dependencies {
+                writeln!(
+                    f,
+                    "| `{}` | {} | {} |",
+                    dependency.source,
+                    dependency
+                        .rust
+                        .as_ref()
+                        .map_or("none".to_string(), |rust| format!("`{rust}`")),
+                    cell(dependency.notes.as_deref().unwrap_or_default())
+                )?;
+            }
+        }
+        if !self.crates.is_empty() {
+            let crates: Vec<String> = self
+                .crates
+                .iter()
+                .map(|(name, version)| format!("`{name}` {version}"))
+                .collect();
+            writeln!(f, "\nCrates in `Cargo.toml`: {}.", crates.join(", "))?;
+        }
+
+        if !self.decisions.is_empty() {
+            writeln!(f, "\n## Decisions")?;
+            for note in &self.decisions {
+                writeln!(f, "\n### {}\n\n{}", note.topic, note.text.trim())?;
+            }
+        }
+
+        if !self.gaps.is_empty() {
+            writeln!(f, "\n## Known gaps\n")?;
+            for gap in &self.gaps {
+                writeln!(f, "- {gap}")?;
+            }
+        }
+
+        if !self.todos.is_empty() || !self.markers.is_empty() {
+            writeln!(f, "\n## TODOs\n")?;
+            for todo in self.todos.iter().chain(&self.markers) {
+                writeln!(f, "- {todo}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Lists the dependencies of the manifest with their version, or `path` or `git` if they aren't
+/// from crates.io.
+fn manifest_crates(root: &Path) -> Vec<(String, String)> {
+    let Ok(manifest) = std::fs::read_to_string(root.join("Cargo.toml")) else {
+        return vec![];
+    };
+    let Ok(manifest) = manifest.parse::<toml::Table>() else {
+        return vec![];
+    };
+    let Some(dependencies) = manifest.get("dependencies").and_then(|d| d.as_table()) else {
+        return vec![];
+    };
+    dependencies
+        .iter()
+        .map(|(name, dependency)| {
+            let version = match dependency {
+                toml::Value::String(version) => version.clone(),
+                toml::Value::Table(table) => match table.get("version") {
+                    Some(version) => version.as_str().unwrap_or_default().to_string(),
+                    None if table.contains_key("path") => "(path)".to_string(),
+                    None if table.contains_key("git") => "(git)".to_string(),
+                    None => String::new(),
+                },
+                _ => String::new(),
+            };
+            (name.clone(), version)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn conversion_notes() {
+        let dir = TempDir::new("conversion");
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(
+            dir.join("Cargo.toml"),
+            "[package]\nname = \"shapes\"\n\n[dependencies]\nclap = { version = \"4\", features \
+            = [\"derive\"] }\nshapes-sys = { path = \"shapes-sys\" }\nthiserror = \"2\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("src/lib.rs"),
+            "//! Areas of shapes.\n//! Circles only.\n//!\n//! More.\n\npub fn area() -> f64 {\n    \
+            todo!()\n}\n",
+        )
+        .unwrap();
+        let plan = PlanContents {
+            modules: vec![
+                PlanModule {
+                    source: vec!["shape.c".to_string(), "shape.h".to_string()],
+                    destination: "src/lib.rs".to_string(),
+                    description: String::new(),
+                    status: ModuleStatus::Done,
+                },
+                PlanModule {
+                    source: vec!["list.c".to_string()],
+                    destination: "src/list.rs".to_string(),
+                    description: "Linked list | replaced by Vec".to_string(),
+                    status: ModuleStatus::Skipped,
+                },
+            ],
+            dependencies: vec![DependencyMapping {
+                source: "getopt".to_string(),
+                rust: Some("clap".to_string()),
+                notes: None,
+            }],
+        };
+        let files = vec!["Cargo.toml".to_string(), "src/lib.rs".to_string()];
+        let mut notes = ConversionNotes::collect(plan, &dir, &files);
+        notes.decisions.push(Note {
+            topic: "errors".to_string(),
+            text: "One error enum per module.\n".to_string(),
+        });
+        notes.gaps.push("`draw.c` isn't converted".to_string());
+        notes.todos.push("[ ] 2. Port the benchmarks".to_string());
+
+        assert_eq!(
+            notes.to_string(),
+            "# Conversion notes\n\n\
+            Generated by riir from the conversion plan and the state of the port at the end of the \
+            last run; changes to this file are overwritten.\n\
+            \n\
+            ## Modules\n\n\
+            | Rust file | Source files | Status | Description |\n\
+            | --- | --- | --- | --- |\n\
+            | `src/lib.rs` | `shape.c`, `shape.h` | done | Areas of shapes. Circles only. |\n\
+            | `src/list.rs` | `list.c` | skipped | Linked list \\| replaced by Vec |\n\
+            \n\
+            ## Dependencies\n\n\
+            | Original | Rust | Notes |\n\
+            | --- | --- | --- |\n\
+            | `getopt` | `clap` |  |\n\
+            \n\
+            Crates in `Cargo.toml`: `clap` 4, `shapes-sys` (path), `thiserror` 2.\n\
+            \n\
+            ## Decisions\n\n\
+            ### errors\n\n\
+            One error enum per module.\n\
+            \n\
+            ## Known gaps\n\n\
+            - `draw.c` isn't converted\n\
+            \n\
+            ## TODOs\n\n\
+            - [ ] 2. Port the benchmarks\n\
+            - `src/lib.rs:7`: `todo!()`\n"
+        );
+    }
+}
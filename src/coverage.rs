@@ -0,0 +1,197 @@
+use crate::language::detect_language;
+use crate::plan::{ModuleStatus, PlanContents, PlanModule};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::Path;
+
+/// Languages of the source files that are expected to be converted; build files, configuration
+/// and documentation are not.
+const CODE_LANGUAGES: &[&str] = &[
+    "C",
+    "C++",
+    "C#",
+    "Go",
+    "Java",
+    "Kotlin",
+    "JavaScript",
+    "TypeScript",
+    "Python",
+    "Ruby",
+    "Swift",
+    "Assembly",
+];
+
+/// Rust files that don't correspond to a particular source file.
+const ROOT_FILES: &[&str] = &["main.rs", "lib.rs", "mod.rs", "build.rs"];
+
+/// Which source files were read and converted, and which destination files have no source.
+#[derive(Debug, Default, PartialEq)]
+pub struct Coverage {
+    /// number of source code files
+    pub total: usize,
+    /// source code files the model never read
+    pub unread: Vec<String>,
+    /// source code files without a counterpart in the destination project
+    pub unconverted: Vec<String>,
+    /// Rust files of the destination project that no source file accounts for
+    pub dangling: Vec<String>,
+}
+
+/// Matches the source files with the destination files. A source file is converted if a done
+/// module of the plan lists it and the module's file exists, if a skipped module lists it, or if a
+/// Rust file has the same name, e.g. `src/parser.rs` for `lib/Parser.java`.
+pub fn coverage(
+    source_files: &[String],
+    read: &BTreeSet<String>,
+    destination_files: &[String],
+    plan: &PlanContents,
+) -> Coverage {
+    let sources: Vec<&String> = source_files
+        .iter()
+        .filter(|file| {
+            detect_language(Path::new(file)).is_some_and(|lang| CODE_LANGUAGES.contains(&lang))
+        })
+        .collect();
+    let rust_files: Vec<&String> = destination_files
+        .iter()
+        .filter(|file| file.ends_with(".rs"))
+        .collect();
+    let planned: Vec<&PlanModule> = plan
+        .modules
+        .iter()
+        .filter(|module| module.status != ModuleStatus::Pending)
+        .collect();
+
+    let mut coverage = Coverage {
+        total: sources.len(),
+        ..Default::default()
+    };
+    for source in &sources {
+        if !read.contains(*source) {
+            coverage.unread.push(source.to_string());
+        }
+        let in_plan = planned.iter().any(|module| {
+            module.source.contains(source)
+                && (module.status == ModuleStatus::Skipped
+                    || destination_files.contains(&module.destination))
+        });
+        let same_name = rust_files
+            .iter()
+            .any(|file| normalized_stem(file) == normalized_stem(source));
+        if !in_plan && !same_name {
+            coverage.unconverted.push(source.to_string());
+        }
+    }
+    for file in rust_files {
+        let name = Path::new(file)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        let is_aux = ROOT_FILES.contains(&name)
+            || ["tests/", "benches/", "examples/"]
+                .iter()
+                .any(|dir| file.starts_with(dir));
+        let in_plan = planned.iter().any(|module| &module.destination == file);
+        let same_name = sources
+            .iter()
+            .any(|source| normalized_stem(source) == normalized_stem(file));
+        if !is_aux && !in_plan && !same_name {
+            coverage.dangling.push(file.clone());
+        }
+    }
+    coverage
+}
+
+/// Reduces a file name to lower case letters and digits, so that `ParserUtils.java`,
+/// `parser_utils.rs` and `parser-utils.ts` match.
+fn normalized_stem(file: &str) -> String {
+    let name = Path::new(file)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let stem = name.split('.').next().unwrap_or(name);
+    stem.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} of {} source files converted, {} read",
+            self.total - self.unconverted.len(),
+            self.total,
+            self.total - self.unread.len()
+        )?;
+        for (title, files) in [
+            ("Not converted", &self.unconverted),
+            ("Never read", &self.unread),
+            ("Destination files without a source", &self.dangling),
+        ] {
+            if !files.is_empty() {
+                writeln!(f, "{title}:")?;
+                for file in files {
+                    writeln!(f, "  {file}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gaps() {
+        let strings =
+            |files: &[&str]| -> Vec<String> { files.iter().map(|file| file.to_string()).collect() };
+        let sources = strings(&[
+            "src/main.c",
+            "src/util.c",
+            "src/util.h",
+            "lib/ParserUtils.java",
+            "src/extra.c",
+            "Makefile",
+        ]);
+        let read: BTreeSet<String> = strings(&["src/main.c", "src/util.c", "src/extra.c"])
+            .into_iter()
+            .collect();
+        let destination = strings(&[
+            "Cargo.toml",
+            "src/main.rs",
+            "src/helpers.rs",
+            "src/parser_utils.rs",
+            "src/leftover.rs",
+            "tests/cli.rs",
+        ]);
+        let plan = PlanContents {
+            modules: vec![PlanModule {
+                source: strings(&["src/util.c", "src/util.h"]),
+                destination: "src/helpers.rs".to_string(),
+                description: String::new(),
+                status: ModuleStatus::Done,
+            }],
+            dependencies: vec![],
+        };
+
+        let coverage = coverage(&sources, &read, &destination, &plan);
+        assert_eq!(
+            coverage,
+            Coverage {
+                total: 5,
+                unread: strings(&["src/util.h", "lib/ParserUtils.java"]),
+                unconverted: strings(&["src/extra.c"]),
+                dangling: strings(&["src/leftover.rs"]),
+            }
+        );
+        assert!(
+            coverage.to_string().starts_with(
+                "4 of 5 source files converted, 3 read\nNot converted:\n  src/extra.c\n"
+            )
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use crate::function::TransientError;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+const CRATES_API: &str = "https://crates.io/api/v1/crates";
+/// crates.io rejects requests without a user agent identifying the client.
+pub const USER_AGENT: &str = concat!("riir/", env!("CARGO_PKG_VERSION"));
+const DEFAULT_MAX_RESULTS: usize = 10;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct CratesSearchArgs {
+    /// crate name or keywords, e.g. "sha256" or "command line parser"
+    pub query: String,
+    /// maximum number of crates to return, 10 by default
+    pub max_results: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CratesSearchResult {
+    pub error: Option<String>,
+    /// matching crates, the most relevant first
+    pub crates: Vec<CrateInfo>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CrateInfo {
+    pub name: String,
+    pub description: Option<String>,
+    /// the latest stable version, or the latest one if there are no stable releases
+    pub latest_version: String,
+    pub downloads: u64,
+    /// downloads in the last 90 days
+    pub recent_downloads: Option<u64>,
+}
+
+/// A crate as returned by the crates.io API.
+#[derive(Deserialize)]
+struct ApiCrate {
+    name: String,
+    description: Option<String>,
+    max_version: String,
+    max_stable_version: Option<String>,
+    downloads: u64,
+    recent_downloads: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ApiSearchResponse {
+    crates: Vec<ApiCrate>,
+}
+
+impl From<ApiCrate> for CrateInfo {
+    fn from(api: ApiCrate) -> Self {
+        CrateInfo {
+            name: api.name,
+            description: api.description.map(|d| d.trim().to_string()),
+            latest_version: api.max_stable_version.unwrap_or(api.max_version),
+            downloads: api.downloads,
+            recent_downloads: api.recent_downloads,
+        }
+    }
+}
+
+/// Searches crates.io. Must be called within a multi-threaded runtime.
+pub fn search_crates(args: &CratesSearchArgs) -> Result<CratesSearchResult, TransientError> {
+    let per_page = args
+        .max_results
+        .unwrap_or(DEFAULT_MAX_RESULTS)
+        .clamp(1, 100);
+    let request = async {
+        reqwest::Client::new()
+            .get(CRATES_API)
+            .header(reqwest::header::USER_AGENT, USER_AGENT)
+            .query(&[
+                ("q", args.query.as_str()),
+                ("per_page", &per_page.to_string()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<ApiSearchResponse>()
+            .await
+    };
+    // Functions are synchronous, but they are called from the chat loop running in tokio.
+    let response =
+        tokio::task::block_in_place(|| tokio::runtime::Handle::current().block_on(request));
+    match response {
+        Ok(response) => Ok(CratesSearchResult {
+            error: None,
+            crates: response.crates.into_iter().map(CrateInfo::from).collect(),
+        }),
+        Err(e) if is_transient(&e) => Err(TransientError(format!("Cannot reach crates.io: {e}"))),
+        Err(e) => Ok(CratesSearchResult {
+            error: Some(format!("Cannot search crates.io: {e}")),
+            crates: vec![],
+        }),
+    }
+}
+
+/// Returns the name of the most relevant crate for the query, if crates.io can be reached.
+pub fn best_crate(query: &str) -> Option<String> {
+    let args = CratesSearchArgs {
+        query: query.to_string(),
+        max_results: Some(1),
+    };
+    let result = search_crates(&args).ok()?;
+    result.crates.into_iter().next().map(|info| info.name)
+}
+
+/// Returns the latest version of the crate with exactly this name.
+pub fn crate_version(name: &str) -> Option<String> {
+    let args = CratesSearchArgs {
+        query: name.to_string(),
+        max_results: None,
+    };
+    let result = search_crates(&args).ok()?;
+    let info = result.crates.into_iter().find(|info| info.name == name)?;
+    Some(info.latest_version)
+}
+
+pub fn is_transient(error: &reqwest::Error) -> bool {
+    error.is_timeout()
+        || error.is_connect()
+        || error
+            .status()
+            .is_some_and(|status| status.is_server_error() || status.as_u16() == 429)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_response() {
+        let response: ApiSearchResponse = serde_json::from_str(
+            r#"{
+                "crates": [
+                    {"name": "sha2", "description": "SHA-2 hash functions\n", "max_version": "0.11.0-rc.0",
+                     "max_stable_version": "0.10.8", "downloads": 300000000, "recent_downloads": 50000000,
+                     "id": "sha2"},
+                    {"name": "sha2-asm", "description": null, "max_version": "0.6.4",
+                     "max_stable_version": null, "downloads": 1000, "recent_downloads": null}
+                ],
+                "meta": {"total": 2}
+            }"#,
+        )
+        .unwrap();
+        let crates: Vec<CrateInfo> = response.crates.into_iter().map(CrateInfo::from).collect();
+        assert_eq!(crates[0].latest_version, "0.10.8");
+        assert_eq!(
+            crates[0].description.as_deref(),
+            Some("SHA-2 hash functions")
+        );
+        assert_eq!(crates[1].latest_version, "0.6.4");
+    }
+}
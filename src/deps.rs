@@ -0,0 +1,310 @@
+use crate::language::detect_language;
+use crate::text::{decode_text, is_binary};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::LazyLock;
+
+static C_INCLUDE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?m)^\s*#\s*include\s*[<"]([^>"]+)[>"]"#).unwrap());
+static PYTHON_IMPORT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^\s*(?:from\s+(\.*[\w.]*)\s+import\s+([\w, ]+)|import\s+([\w.]+))").unwrap()
+});
+static GO_IMPORT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#""([\w./-]+)""#).unwrap());
+static GO_IMPORT_BLOCK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?ms)^import\s*(?:\((.*?)\)|([^\n]*))"#).unwrap());
+static JAVA_IMPORT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^\s*import\s+(?:static\s+)?([\w.]+)\s*;").unwrap());
+static JS_IMPORT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?:\bfrom\s*|\bimport\s*\(?\s*|\brequire\s*\(\s*)['"](\.{1,2}/[^'"]+)['"]"#)
+        .unwrap()
+});
+
+#[derive(Serialize, Deserialize)]
+pub struct DependencyGraphResult {
+    /// project files each file depends on through includes or imports
+    pub dependencies: BTreeMap<String, Vec<String>>,
+    /// files ordered so that dependencies go before the files using them
+    pub order: Vec<String>,
+    /// files in or depending on circular dependencies, they are at the end of `order`
+    pub cycles: Vec<String>,
+}
+
+/// Finds which project files each file includes or imports.
+///
+/// `files` are paths relative to `root`. Only dependencies between the project files are kept,
+/// system headers and third-party packages are ignored.
+pub fn dependency_graph(root: &Path, files: &[String]) -> DependencyGraphResult {
+    let known: HashSet<&str> = files.iter().map(String::as_str).collect();
+    let mut dependencies = BTreeMap::new();
+    for file in files {
+        let Some(language) = detect_language(Path::new(file)) else {
+            continue;
+        };
+        let Ok(data) = std::fs::read(root.join(file)) else {
+            continue;
+        };
+        if is_binary(&data) {
+            continue;
+        }
+        let source = decode_text(data).contents;
+        let mut deps: BTreeSet<String> = BTreeSet::new();
+        for candidate in candidates(language, file, &source) {
+            if let Some(dep) = resolve(&candidate, &known, files)
+                && dep != *file
+            {
+                deps.insert(dep);
+            }
+        }
+        dependencies.insert(file.clone(), deps.into_iter().collect());
+    }
+
+    let (order, cycles) = topological_order(&dependencies);
+    DependencyGraphResult {
+        dependencies,
+        order,
+        cycles,
+    }
+}
+
+/// A possible location of an imported file.
+enum Candidate {
+    /// exact path relative to the project root
+    Exact(PathBuf),
+    /// a file whose path ends with the given one, e.g. for include directories
+    Suffix(PathBuf),
+    /// a Go package import path, which ends with the package directory
+    GoPackage(String),
+}
+
+fn candidates(language: &str, file: &str, source: &str) -> Vec<Candidate> {
+    let dir = Path::new(file).parent().unwrap_or(Path::new(""));
+    let mut result = vec![];
+    match language {
+        "C" | "C++" => {
+            for include in C_INCLUDE.captures_iter(source) {
+                result.push(Candidate::Exact(dir.join(&include[1])));
+                result.push(Candidate::Suffix(PathBuf::from(&include[1])));
+            }
+        }
+        "Python" => {
+            for import in PYTHON_IMPORT.captures_iter(source) {
+                if let Some(module) = import.get(3) {
+                    python_module(&mut result, dir, module.as_str());
+                    continue;
+                }
+                let module = &import[1];
+                let names = import[2].split(',').map(str::trim);
+                if module.chars().all(|c| c == '.') {
+                    // `from . import a, b` imports sibling modules.
+                    for name in names {
+                        python_module(&mut result, dir, &format!("{module}{name}"));
+                    }
+                } else {
+                    python_module(&mut result, dir, module);
+                    for name in names {
+                        python_module(&mut result, dir, &format!("{module}.{name}"));
+                    }
+                }
+            }
+        }
+        "Go" => {
+            for block in GO_IMPORT_BLOCK.captures_iter(source) {
+                let specs = block.get(1).or(block.get(2)).map_or("", |m| m.as_str());
+                for import in GO_IMPORT.captures_iter(specs) {
+                    result.push(Candidate::GoPackage(import[1].to_string()));
+                }
+            }
+        }
+        "Java" | "Kotlin" => {
+            for import in JAVA_IMPORT.captures_iter(source) {
+                let path = import[1].replace('.', "/");
+                result.push(Candidate::Suffix(PathBuf::from(format!("{path}.java"))));
+                result.push(Candidate::Suffix(PathBuf::from(format!("{path}.kt"))));
+            }
+        }
+        "JavaScript" | "TypeScript" => {
+            for import in JS_IMPORT.captures_iter(source) {
+                let path = dir.join(&import[1]);
+                result.push(Candidate::Exact(path.clone()));
+                for extension in ["js", "mjs", "jsx", "ts", "tsx"] {
+                    let mut with_extension = path.clone().into_os_string();
+                    with_extension.push(format!(".{extension}"));
+                    result.push(Candidate::Exact(with_extension.into()));
+                    result.push(Candidate::Exact(path.join(format!("index.{extension}"))));
+                }
+            }
+        }
+        _ => {}
+    }
+    result
+}
+
+/// Adds the possible files of a Python module, absolute or relative to `dir`.
+fn python_module(result: &mut Vec<Candidate>, dir: &Path, module: &str) {
+    let level = module.chars().take_while(|&c| c == '.').count();
+    let path = module[level..].replace('.', "/");
+    for file in [format!("{path}.py"), format!("{path}/__init__.py")] {
+        let file = file.trim_start_matches('/');
+        if level == 0 {
+            result.push(Candidate::Exact(dir.join(file)));
+            result.push(Candidate::Suffix(PathBuf::from(file)));
+        } else {
+            // Each dot after the first one goes one package up.
+            let package = (1..level).fold(dir.to_path_buf(), |package, _| package.join(".."));
+            result.push(Candidate::Exact(package.join(file)));
+        }
+    }
+}
+
+fn resolve(candidate: &Candidate, known: &HashSet<&str>, files: &[String]) -> Option<String> {
+    match candidate {
+        Candidate::Exact(path) => {
+            let path = normalize(path)?;
+            known.contains(path.as_str()).then_some(path)
+        }
+        Candidate::Suffix(path) => {
+            let path = normalize(path)?;
+            let (dir, name) = match path.rsplit_once('/') {
+                Some((dir, name)) => (format!("/{dir}/"), name),
+                None => ("/".to_string(), path.as_str()),
+            };
+            files
+                .iter()
+                .find(|file| {
+                    let file = format!("/{file}");
+                    let Some(index) = file.rfind('/') else {
+                        return false;
+                    };
+                    let (file_dir, file_name) = file.split_at(index + 1);
+                    file_dir.ends_with(&dir) && file_name == name
+                })
+                .cloned()
+        }
+        Candidate::GoPackage(import) => {
+            // Packages are directories, any of their files will do.
+            let import = format!("/{import}/");
+            files
+                .iter()
+                .find(|file| {
+                    let Some((dir, name)) = file.rsplit_once('/') else {
+                        return false;
+                    };
+                    import.ends_with(&format!("/{dir}/"))
+                        && name.ends_with(".go")
+                        && !name.ends_with("_test.go")
+                })
+                .cloned()
+        }
+    }
+}
+
+/// Removes `.` and `..` components, failing if the path leaves the project root.
+fn normalize(path: &Path) -> Option<String> {
+    let mut parts: Vec<&str> = vec![];
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_str()?),
+            Component::ParentDir => {
+                parts.pop()?;
+            }
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// Orders the files so that dependencies go first. Files in cycles are appended at the end
+/// and returned separately.
+pub fn topological_order(
+    dependencies: &BTreeMap<String, Vec<String>>,
+) -> (Vec<String>, Vec<String>) {
+    let mut remaining: BTreeMap<&str, usize> = dependencies
+        .iter()
+        .map(|(file, deps)| (file.as_str(), deps.len()))
+        .collect();
+    let mut order: Vec<String> = vec![];
+    loop {
+        let ready: Vec<&str> = remaining
+            .iter()
+            .filter(|&(_, &count)| count == 0)
+            .map(|(&file, _)| file)
+            .collect();
+        if ready.is_empty() {
+            break;
+        }
+        for file in ready {
+            remaining.remove(file);
+            order.push(file.to_string());
+            for (dependent, deps) in dependencies {
+                if deps.iter().any(|dep| dep == file)
+                    && let Some(count) = remaining.get_mut(dependent.as_str())
+                {
+                    *count -= 1;
+                }
+            }
+        }
+    }
+
+    let cycles: Vec<String> = remaining.keys().map(|file| file.to_string()).collect();
+    order.extend(cycles.iter().cloned());
+    (order, cycles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn include_graph() {
+        let dir = TempDir::new("deps");
+        let sources = [
+            ("include/util.h", "#include <stdio.h>\n"),
+            ("src/util.c", "#include \"util.h\"\n"),
+            (
+                "src/main.c",
+                "#include \"util.h\"\n#include \"../src/a.h\"\n",
+            ),
+            ("src/a.h", "#include \"b.h\"\n"),
+            ("src/b.h", "#include \"a.h\"\n"),
+            (
+                "app/main.py",
+                "import app.models\nfrom .views import render\n",
+            ),
+            ("app/models.py", "import os\n"),
+            ("app/views.py", "from . import models\n"),
+            ("web/index.js", "const api = require('./lib/api');\n"),
+            ("web/lib/api.js", "import { x } from \"../util\";\n"),
+            ("web/util.js", ""),
+            (
+                "cmd/main.go",
+                "package main\nimport (\n\t\"fmt\"\n\t\"example.com/tool/pkg/log\"\n)\n",
+            ),
+            ("pkg/log/log.go", "package log\nimport \"os\"\n"),
+        ];
+        for (file, contents) in sources {
+            std::fs::create_dir_all(dir.join(file).parent().unwrap()).unwrap();
+            std::fs::write(dir.join(file), contents).unwrap();
+        }
+        let files: Vec<String> = sources.iter().map(|(file, _)| file.to_string()).collect();
+
+        let graph = dependency_graph(&dir, &files);
+        let deps = |file: &str| graph.dependencies[file].clone();
+        assert_eq!(deps("src/util.c"), ["include/util.h"]);
+        assert_eq!(deps("src/main.c"), ["include/util.h", "src/a.h"]);
+        assert_eq!(deps("app/main.py"), ["app/models.py", "app/views.py"]);
+        assert_eq!(deps("app/views.py"), ["app/models.py"]);
+        assert_eq!(deps("web/index.js"), ["web/lib/api.js"]);
+        assert_eq!(deps("web/lib/api.js"), ["web/util.js"]);
+        assert_eq!(deps("cmd/main.go"), ["pkg/log/log.go"]);
+        assert_eq!(graph.cycles, ["src/a.h", "src/b.h", "src/main.c"]);
+
+        let position = |file: &str| graph.order.iter().position(|f| f == file).unwrap();
+        assert!(position("include/util.h") < position("src/util.c"));
+        assert!(position("app/models.py") < position("app/views.py"));
+        assert!(position("app/views.py") < position("app/main.py"));
+    }
+}
@@ -0,0 +1,276 @@
+use crate::crates::{USER_AGENT, is_transient};
+use crate::function::TransientError;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+const DOCS_RS: &str = "https://docs.rs";
+
+/// Links to the items on the `all.html` page of a crate.
+static ITEM_LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"<a href="([^"]+\.html)">([\w:]+)</a>"#).unwrap());
+static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<[^>]*>").unwrap());
+static BLOCK_END: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)<br\s*/?>|</(p|div|h\d|li|pre|tr|summary|section|details)>").unwrap()
+});
+static BLANK_LINES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\n\s*\n\s*\n+").unwrap());
+
+#[derive(Deserialize, JsonSchema)]
+pub struct DocsLookupArgs {
+    /// path of the item starting with the crate name, e.g. "tokio::sync::Mutex" or
+    /// "std::vec::Vec::retain"
+    pub path: String,
+    /// version of the crate, the latest one by default
+    pub version: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DocsLookupResult {
+    pub error: Option<String>,
+    pub url: Option<String>,
+    /// signature and documentation of the item as plain text
+    pub documentation: Option<String>,
+}
+
+impl DocsLookupResult {
+    fn error(error: String) -> Self {
+        DocsLookupResult {
+            error: Some(error),
+            url: None,
+            documentation: None,
+        }
+    }
+}
+
+/// Looks up the documentation of an item on docs.rs, or on doc.rust-lang.org for the standard
+/// library. Must be called within a multi-threaded runtime.
+pub fn lookup_docs(args: &DocsLookupArgs) -> Result<DocsLookupResult, TransientError> {
+    let segments: Vec<&str> = args.path.trim().split("::").collect();
+    if segments.iter().any(|s| s.is_empty() || !is_identifier(s)) {
+        return Ok(DocsLookupResult::error("Invalid item path.".to_string()));
+    }
+    let krate = segments[0];
+    let version = args.version.as_deref().unwrap_or("latest");
+    let base = match krate {
+        "std" | "core" | "alloc" => format!("https://doc.rust-lang.org/{krate}"),
+        _ => format!("{DOCS_RS}/{krate}/{version}/{}", krate.replace('-', "_")),
+    };
+
+    let (page, anchor) = if segments.len() == 1 {
+        ("index.html".to_string(), None)
+    } else {
+        let all = match fetch(&format!("{base}/all.html"))? {
+            Ok(all) => all,
+            Err(e) => return Ok(DocsLookupResult::error(e)),
+        };
+        match find_item(&all, &segments[1..]) {
+            Some(item) => item,
+            None => {
+                return Ok(DocsLookupResult::error(format!(
+                    "{} is not found in the documentation.",
+                    args.path
+                )));
+            }
+        }
+    };
+
+    let url = format!("{base}/{page}");
+    let html = match fetch(&url)? {
+        Ok(html) => html,
+        Err(e) => return Ok(DocsLookupResult::error(e)),
+    };
+    let documentation = match &anchor {
+        Some(anchor) => member_section(&html, anchor),
+        None => main_content(&html),
+    };
+    Ok(DocsLookupResult {
+        error: None,
+        url: Some(match anchor {
+            Some(anchor) => format!("{url}#{anchor}"),
+            None => url,
+        }),
+        documentation: documentation.map(|html| html_to_text(&html)),
+    })
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ExplainErrorArgs {
+    /// error code reported by the compiler, e.g. "E0502"
+    pub code: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExplainErrorResult {
+    pub error: Option<String>,
+    /// the explanation from `rustc --explain` with examples of erroneous and fixed code
+    pub explanation: Option<String>,
+}
+
+/// Returns the explanation of a compiler error code.
+pub fn explain_error(args: &ExplainErrorArgs) -> ExplainErrorResult {
+    let code = args.code.trim().trim_start_matches(['E', 'e']);
+    if code.is_empty() || code.len() > 4 || !code.chars().all(|c| c.is_ascii_digit()) {
+        return ExplainErrorResult {
+            error: Some("Invalid error code.".to_string()),
+            explanation: None,
+        };
+    }
+    let code = format!("E{code:0>4}");
+    let output = std::process::Command::new("rustc")
+        .arg("--explain")
+        .arg(&code)
+        .output();
+    match output {
+        Ok(output) if output.status.success() => ExplainErrorResult {
+            error: None,
+            explanation: Some(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+        },
+        Ok(output) => ExplainErrorResult {
+            error: Some(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+            explanation: None,
+        },
+        Err(e) => ExplainErrorResult {
+            error: Some(format!("Cannot run rustc: {e}")),
+            explanation: None,
+        },
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    s.chars()
+        .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Fetches a page, returning an error message if it doesn't exist.
+fn fetch(url: &str) -> Result<Result<String, String>, TransientError> {
+    let request = async {
+        reqwest::Client::new()
+            .get(url)
+            .header(reqwest::header::USER_AGENT, USER_AGENT)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await
+    };
+    // Functions are synchronous, but they are called from the chat loop running in tokio.
+    match tokio::task::block_in_place(|| tokio::runtime::Handle::current().block_on(request)) {
+        Ok(text) => Ok(Ok(text)),
+        Err(e) if is_transient(&e) => Err(TransientError(format!("Cannot fetch {url}: {e}"))),
+        Err(e) => Ok(Err(format!("Cannot fetch {url}: {e}"))),
+    }
+}
+
+/// Finds the page of an item in the list of all items of a crate. A method or another member
+/// of a type is found on the page of the type, with its anchor.
+fn find_item(all: &str, path: &[&str]) -> Option<(String, Option<String>)> {
+    let find = |path: &str| {
+        ITEM_LINK
+            .captures_iter(all)
+            .find(|link| &link[2] == path)
+            .map(|link| link[1].to_string())
+    };
+    if let Some(page) = find(&path.join("::")) {
+        return Some((page, None));
+    }
+    let (member, parent) = path.split_last()?;
+    let page = find(&parent.join("::"))?;
+    Some((page, Some(member.to_string())))
+}
+
+fn main_content(html: &str) -> Option<String> {
+    let start = html.find(r#"<section id="main-content""#)?;
+    let end = html[start..]
+        .find("</main>")
+        .map_or(html.len(), |end| start + end);
+    Some(html[start..end].to_string())
+}
+
+/// Extracts the documentation of a member of a type, e.g. a method.
+fn member_section(html: &str, member: &str) -> Option<String> {
+    let start = [
+        "method",
+        "tymethod",
+        "structfield",
+        "variant",
+        "associatedtype",
+    ]
+    .iter()
+    .find_map(|kind| html.find(&format!(r#"id="{kind}.{member}""#)))?;
+    let start = html[..start].rfind('<').unwrap_or(start);
+    let end = html[start..]
+        .find("</details>")
+        .map_or(html.len(), |end| start + end);
+    Some(html[start..end].to_string())
+}
+
+fn html_to_text(html: &str) -> String {
+    let text = BLOCK_END.replace_all(html, "\n");
+    let text = TAG.replace_all(&text, "");
+    let text = text
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&");
+    BLANK_LINES.replace_all(&text, "\n\n").trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_items() {
+        let all = r#"<h3 id="structs">Structs</h3><ul class="all-items">
+            <li><a href="sync/struct.Mutex.html">sync::Mutex</a></li>
+            <li><a href="struct.Runtime.html">Runtime</a></li></ul>"#;
+        assert_eq!(
+            find_item(all, &["sync", "Mutex"]),
+            Some(("sync/struct.Mutex.html".to_string(), None))
+        );
+        assert_eq!(
+            find_item(all, &["sync", "Mutex", "lock"]),
+            Some((
+                "sync/struct.Mutex.html".to_string(),
+                Some("lock".to_string())
+            ))
+        );
+        assert_eq!(find_item(all, &["Mutex"]), None);
+    }
+
+    #[test]
+    fn explain() {
+        let args = |code: &str| ExplainErrorArgs {
+            code: code.to_string(),
+        };
+        let explanation = explain_error(&args("E0502")).explanation.unwrap();
+        assert!(explanation.contains("borrow"));
+        assert!(explain_error(&args("502")).explanation.is_some());
+        assert!(explain_error(&args("E05x2")).error.is_some());
+        assert!(explain_error(&args("E9999")).error.is_some());
+    }
+
+    #[test]
+    fn extract_text() {
+        let html = r#"<main><section id="main-content" class="content"><h1>Struct Mutex</h1>
+            <pre class="rust item-decl"><code>pub struct Mutex&lt;T: ?Sized&gt; { /* private fields */ }</code></pre>
+            <details class="toggle method-toggle" open><summary><section id="method.lock" class="method">
+            <h4 class="code-header">pub async fn <a class="fn">lock</a>(&amp;self) -&gt; MutexGuard&lt;'_, T&gt;</h4>
+            </section></summary><div class="docblock"><p>Locks this mutex.</p></div></details>
+            <details class="toggle method-toggle" open><summary><section id="method.try_lock" class="method">
+            </section></summary></details></section></main>"#;
+
+        let lock = html_to_text(&member_section(html, "lock").unwrap());
+        assert_eq!(
+            lock,
+            "pub async fn lock(&self) -> MutexGuard<'_, T>\n\nLocks this mutex."
+        );
+        let main = html_to_text(&main_content(html).unwrap());
+        assert!(main.starts_with("Struct Mutex\n"));
+        assert!(main.contains("pub struct Mutex<T: ?Sized>"));
+        assert!(member_section(html, "unlock").is_none());
+    }
+}
@@ -0,0 +1,235 @@
+use crate::config::{EquivalenceCase, EquivalenceConfig};
+use crate::process::{CommandOutput, run_command};
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+/// The captured stdout and stderr of each run are truncated to this many bytes.
+const MAX_CAPTURED_OUTPUT: usize = 1024 * 1024;
+
+/// How many mismatching cases are reported to the model at once.
+const MAX_REPORTED_MISMATCHES: usize = 5;
+
+/// Longer diffs of the outputs are truncated in the report.
+const MAX_DIFF_SIZE: usize = 4 * 1024;
+
+/// A case in which the ported program behaves differently from the original.
+pub struct Mismatch {
+    pub case: String,
+    /// the arguments and stdin of the case
+    pub input: String,
+    /// what differs, e.g. a diff of stdout
+    pub differences: Vec<String>,
+}
+
+/// Runs every case with the original and the ported program and returns the ones whose exit
+/// codes or outputs differ. Both programs run in `source_copy`, the copy of the source project,
+/// and relative paths of the ported program are resolved in `destination`.
+pub fn compare_programs(
+    config: &EquivalenceConfig,
+    source_copy: &Path,
+    destination: &Path,
+) -> Result<Vec<Mismatch>, String> {
+    let timeout = Some(Duration::from_secs(config.timeout)).filter(|timeout| !timeout.is_zero());
+    let mut mismatches = vec![];
+    for case in &config.cases {
+        let input = match (&case.stdin, &case.stdin_file) {
+            (Some(stdin), _) => Some(stdin.as_bytes().to_vec()),
+            (None, Some(file)) => Some(
+                std::fs::read(source_copy.join(file))
+                    .map_err(|e| format!("Cannot read {}: {e}", file.display()))?,
+            ),
+            (None, None) => None,
+        };
+        let run = |command: &[String], dir: &Path| {
+            run_command(
+                &mut program_command(command, dir, case, source_copy),
+                input.as_deref(),
+                timeout,
+                Some(MAX_CAPTURED_OUTPUT),
+            )
+        };
+        let original = run(&config.original, source_copy)?;
+        let differences = match run(&config.ported, destination) {
+            Ok(ported) => compare_outputs(&original, &ported, config),
+            Err(e) => vec![format!("The ported program can't be started: {e}")],
+        };
+        if !differences.is_empty() {
+            mismatches.push(Mismatch {
+                case: case.name.clone(),
+                input: describe_input(case),
+                differences,
+            });
+        }
+    }
+    Ok(mismatches)
+}
+
+/// Runs the original program with the given arguments in the copy of the source project, e.g. to
+/// read its `--help`.
+pub fn run_original(
+    config: &EquivalenceConfig,
+    source_copy: &Path,
+    args: &[String],
+) -> Result<CommandOutput, String> {
+    let case = EquivalenceCase {
+        name: String::new(),
+        args: args.to_vec(),
+        stdin: None,
+        stdin_file: None,
+    };
+    let timeout = Some(Duration::from_secs(config.timeout)).filter(|timeout| !timeout.is_zero());
+    run_command(
+        &mut program_command(&config.original, source_copy, &case, source_copy),
+        None,
+        timeout,
+        Some(MAX_CAPTURED_OUTPUT),
+    )
+}
+
+/// Builds the command running a program with the arguments of the case. A relative path with a
+/// directory, like `./tool`, is resolved in `dir`, a bare name is looked up in `PATH`.
+fn program_command(command: &[String], dir: &Path, case: &EquivalenceCase, cwd: &Path) -> Command {
+    let (program, args) = command.split_first().expect("the program is configured");
+    let path = Path::new(program);
+    let mut command = if path.is_relative() && path.components().count() > 1 {
+        Command::new(dir.join(path))
+    } else {
+        Command::new(path)
+    };
+    command.args(args).args(&case.args).current_dir(cwd);
+    command
+}
+
+fn compare_outputs(
+    original: &CommandOutput,
+    ported: &CommandOutput,
+    config: &EquivalenceConfig,
+) -> Vec<String> {
+    let mut differences = vec![];
+    if original.exit_code != ported.exit_code || original.timed_out != ported.timed_out {
+        differences.push(format!(
+            "The exit status differs: the original {}, the port {}.",
+            describe_status(original, config.timeout),
+            describe_status(ported, config.timeout)
+        ));
+    }
+    let mut streams = vec![("stdout", &original.stdout, &ported.stdout)];
+    if config.compare_stderr {
+        streams.push(("stderr", &original.stderr, &ported.stderr));
+    }
+    for (name, original, ported) in streams {
+        if original != ported {
+            let mut diff = diffy::create_patch(original, ported).to_string();
+            if diff.len() > MAX_DIFF_SIZE {
+                let mut end = MAX_DIFF_SIZE;
+                while !diff.is_char_boundary(end) {
+                    end -= 1;
+                }
+                diff.truncate(end);
+                diff += "\n[the rest of the diff is omitted]\n";
+            }
+            differences.push(format!(
+                "The {name} differs (`-` lines are the original's, `+` lines the port's):\n{diff}"
+            ));
+        }
+    }
+    differences
+}
+
+fn describe_status(output: &CommandOutput, timeout: u64) -> String {
+    match output.exit_code {
+        _ if output.timed_out => format!("was killed after {timeout} seconds"),
+        Some(code) => format!("exited with {code}"),
+        None => "was killed by a signal".to_string(),
+    }
+}
+
+fn describe_input(case: &EquivalenceCase) -> String {
+    let mut input = format!("arguments {:?}", case.args);
+    if let Some(stdin) = &case.stdin {
+        let mut stdin = stdin.clone();
+        if stdin.len() > 200 {
+            stdin = stdin.chars().take(200).collect::<String>() + "...";
+        }
+        input += &format!(", stdin {stdin:?}");
+    }
+    if let Some(file) = &case.stdin_file {
+        input += &format!(", stdin from `{}`", file.display());
+    }
+    input
+}
+
+/// Describes the mismatches for the model, the first few of them in full.
+pub fn format_mismatches(mismatches: &[Mismatch], cases: usize) -> String {
+    let mut report = format!(
+        "The ported program behaves differently from the original in {} of {cases} cases.\n",
+        mismatches.len()
+    );
+    for mismatch in mismatches.iter().take(MAX_REPORTED_MISMATCHES) {
+        report += &format!(
+            "\n==== {} ({}) ====\n{}\n",
+            mismatch.case,
+            mismatch.input,
+            mismatch.differences.join("\n")
+        );
+    }
+    if mismatches.len() > MAX_REPORTED_MISMATCHES {
+        let others: Vec<&str> = mismatches[MAX_REPORTED_MISMATCHES..]
+            .iter()
+            .map(|mismatch| mismatch.case.as_str())
+            .collect();
+        report += &format!("\nThe other mismatching cases: {}.\n", others.join(", "));
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn compare() {
+        let dir = TempDir::new("equivalence");
+        let source = dir.join("source");
+        let destination = dir.join("destination");
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::create_dir_all(&destination).unwrap();
+        std::fs::write(source.join("input.txt"), "a\nb\n").unwrap();
+        // The port is correct, except for the line count of empty input.
+        std::fs::write(source.join("count.sh"), "wc -l | tr -d ' '\n").unwrap();
+        std::fs::write(
+            destination.join("count.sh"),
+            "n=$(wc -l | tr -d ' '); [ $n = 0 ] && exit 1; echo $n\n",
+        )
+        .unwrap();
+
+        let case = |name: &str, stdin: Option<&str>, stdin_file: Option<&str>| EquivalenceCase {
+            name: name.to_string(),
+            args: vec!["count.sh".to_string()],
+            stdin: stdin.map(String::from),
+            stdin_file: stdin_file.map(Into::into),
+        };
+        let config = EquivalenceConfig {
+            original: vec!["sh".to_string()],
+            ported: vec!["sh".to_string(), "../destination/count.sh".to_string()],
+            cases: vec![
+                case("file", None, Some("input.txt")),
+                case("empty", Some(""), None),
+            ],
+            ..Default::default()
+        };
+        // The ported command ignores the `count.sh` argument of the case.
+        let mismatches = compare_programs(&config, &source, &destination).unwrap();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].case, "empty");
+        assert_eq!(mismatches[0].differences.len(), 2);
+        assert!(mismatches[0].differences[0].contains("the original exited with 0"));
+        assert!(mismatches[0].differences[1].contains("-0\n"));
+
+        let report = format_mismatches(&mismatches, 2);
+        assert!(report.starts_with("The ported program behaves differently"));
+        assert!(report.contains("==== empty (arguments [\"count.sh\"], stdin \"\") ===="));
+    }
+}
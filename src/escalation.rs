@@ -0,0 +1,204 @@
+use crate::cargo::Diagnostic;
+use crate::config::{EscalationConfig, EscalationStep};
+use log::warn;
+use regex::Regex;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+
+/// Code blocks, e.g. the quoted contents of a file, and numbers, e.g. lines and durations.
+static VOLATILE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)```.*?```|\d+").unwrap());
+
+/// Counts the failed verifications of each file or stage and decides when the model needs
+/// help. Problems given up on are persisted in the session directory.
+pub struct Escalation {
+    config: EscalationConfig,
+    path: PathBuf,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// failed verifications in a row and the number of steps taken, per problem
+    failures: HashMap<String, (usize, usize)>,
+    /// fingerprints of the failures since the last successful verification, per problem
+    history: HashMap<String, Vec<u64>>,
+    flagged: BTreeSet<String>,
+}
+
+impl Escalation {
+    /// Loads the flagged problems from the file, starting with none if it doesn't exist.
+    pub fn load(config: EscalationConfig, path: PathBuf) -> Self {
+        let flagged = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        Escalation {
+            config,
+            path,
+            state: Mutex::new(State {
+                failures: HashMap::new(),
+                history: HashMap::new(),
+                flagged,
+            }),
+        }
+    }
+
+    pub fn stronger_model(&self) -> Option<&str> {
+        self.config.stronger_model.as_deref()
+    }
+
+    /// Records a failed verification blamed on `subject`, a file or a stage like "cargo test",
+    /// and returns the step to take if the model has failed too many times. A flagged problem is
+    /// flagged again right away.
+    ///
+    /// The `fingerprint` of the failure tells whether the fixes go in circles: if a failure
+    /// comes back after a different one, the next step is taken right away instead of waiting
+    /// for more failures.
+    pub fn record_failure(&self, subject: &str, fingerprint: u64) -> Option<EscalationStep> {
+        let mut state = self.state.lock().unwrap();
+        if state.flagged.contains(subject) {
+            return Some(EscalationStep::Flag);
+        }
+        let history = state.history.entry(subject.to_string()).or_default();
+        let oscillating = history.last() != Some(&fingerprint) && history.contains(&fingerprint);
+        history.push(fingerprint);
+        if oscillating {
+            warn!("The same failure of `{subject}` came back after it was fixed");
+            history.clear();
+        }
+        let (failures, steps) = state.failures.entry(subject.to_string()).or_default();
+        *failures += 1;
+        if *failures < self.config.max_failures.max(1) && !oscillating {
+            return None;
+        }
+        *failures = 0;
+        let step = self
+            .config
+            .steps
+            .get(*steps)
+            .copied()
+            .unwrap_or(EscalationStep::Flag);
+        *steps += 1;
+        if step == EscalationStep::Flag {
+            state.flagged.insert(subject.to_string());
+            // Losing the list only means the problem is retried in the next session.
+            if let Ok(contents) = serde_json::to_string_pretty(&state.flagged) {
+                let _ = std::fs::write(&self.path, contents);
+            }
+        }
+        Some(step)
+    }
+
+    /// Starts counting the failures over after a successful verification. The steps already
+    /// taken are kept.
+    pub fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        for (failures, _) in state.failures.values_mut() {
+            *failures = 0;
+        }
+        state.history.clear();
+    }
+
+    /// Returns the problems left to the operator.
+    pub fn flagged(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state.flagged.iter().cloned().collect()
+    }
+}
+
+/// Identifies a failure by its message, without the parts that change with every edit even if
+/// the problem stays the same.
+pub fn fingerprint(message: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    VOLATILE.replace_all(message, "").hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Identifies a failure by its diagnostics alone: their codes, files and messages, in any order
+/// and without the lines they point at. The prompt built from them also lists the changed files,
+/// which differ after every edit.
+pub fn diagnostics_fingerprint(diagnostics: &[&Diagnostic]) -> u64 {
+    let mut keys: Vec<_> = diagnostics
+        .iter()
+        .map(|d| (&d.code, &d.file, VOLATILE.replace_all(&d.message, "")))
+        .collect();
+    keys.sort();
+    let mut hasher = DefaultHasher::new();
+    keys.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn escalate() {
+        let dir = TempDir::new("flagged");
+        let path = dir.join("flagged.json");
+        let config = EscalationConfig {
+            max_failures: 2,
+            steps: vec![EscalationStep::StrongerModel, EscalationStep::Reset],
+            stronger_model: Some("big".to_string()),
+        };
+        let escalation = Escalation::load(config.clone(), path.clone());
+
+        assert_eq!(escalation.record_failure("src/a.rs", 1), None);
+        assert_eq!(
+            escalation.record_failure("src/a.rs", 1),
+            Some(EscalationStep::StrongerModel)
+        );
+        // Other problems are counted separately, and a success starts over.
+        assert_eq!(escalation.record_failure("src/b.rs", 1), None);
+        escalation.reset();
+        assert_eq!(escalation.record_failure("src/a.rs", 1), None);
+        assert_eq!(
+            escalation.record_failure("src/a.rs", 1),
+            Some(EscalationStep::Reset)
+        );
+        assert_eq!(escalation.record_failure("src/a.rs", 1), None);
+        assert_eq!(
+            escalation.record_failure("src/a.rs", 1),
+            Some(EscalationStep::Flag)
+        );
+        assert_eq!(escalation.flagged(), ["src/a.rs"]);
+
+        let escalation = Escalation::load(config, path.clone());
+        assert_eq!(
+            escalation.record_failure("src/a.rs", 1),
+            Some(EscalationStep::Flag)
+        );
+    }
+
+    #[test]
+    fn oscillation() {
+        let dir = TempDir::new("cycle");
+        let path = dir.join("cycle.json");
+        let config = EscalationConfig {
+            max_failures: 5,
+            steps: vec![EscalationStep::Reset],
+            stronger_model: None,
+        };
+        let escalation = Escalation::load(config, path);
+        let a = fingerprint("error[E0308]: mismatched types\n --> src/a.rs:10:5\n```\nfn a()\n```");
+        let b = fingerprint("error[E0382]: use of moved value\n --> src/a.rs:12:9");
+        assert_eq!(
+            a,
+            fingerprint("error[E0308]: mismatched types\n --> src/a.rs:11:5\n```\nfn b()\n```")
+        );
+        assert_ne!(a, b);
+
+        // The same failure again isn't a cycle, A -> B -> A is.
+        assert_eq!(escalation.record_failure("src/a.rs", a), None);
+        assert_eq!(escalation.record_failure("src/a.rs", a), None);
+        assert_eq!(escalation.record_failure("src/a.rs", b), None);
+        assert_eq!(
+            escalation.record_failure("src/a.rs", a),
+            Some(EscalationStep::Reset)
+        );
+        assert_eq!(escalation.record_failure("src/a.rs", b), None);
+    }
+}
@@ -0,0 +1,311 @@
+use crate::language::detect_language;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Extensions of the C and C++ headers the bindings are generated from.
+const HEADER_EXTENSIONS: &[&str] = &["h", "hh", "hpp", "hxx"];
+
+/// Directory of the `-sys` crate the original files are copied to.
+const NATIVE_DIR: &str = "csrc";
+
+/// Returns the C and C++ files of the source project.
+pub fn native_files(files: &[String]) -> Vec<String> {
+    files
+        .iter()
+        .filter(|file| matches!(detect_language(Path::new(file)), Some("C" | "C++")))
+        .cloned()
+        .collect()
+}
+
+fn is_header(file: &str) -> bool {
+    Path::new(file)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| HEADER_EXTENSIONS.contains(&ext))
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SysCrateState {
+    /// name of the crate, e.g. `foo-sys`, which is also its directory in the destination project
+    name: String,
+    /// C and C++ files still compiled into the crate, relative to the source project
+    sources: Vec<String>,
+    /// headers the bindings are still generated from
+    headers: Vec<String>,
+    /// include directories, relative to the crate
+    include_dirs: Vec<String>,
+}
+
+/// The `-sys` crate of the FFI-first conversion: the original C and C++ code compiled with `cc`
+/// and bound with bindgen, so that the Rust project links against it from the start. The files
+/// of a module are retired from the crate once the module is ported, and the crate is removed
+/// when nothing is left.
+pub struct SysCrate {
+    /// where the state is saved
+    path: PathBuf,
+    /// directory of the destination project
+    root: PathBuf,
+    state: Mutex<SysCrateState>,
+}
+
+impl SysCrate {
+    /// Loads the crate created by an earlier run, if any.
+    pub fn load(path: PathBuf, root: &Path) -> Option<Self> {
+        let contents = std::fs::read_to_string(&path).ok()?;
+        let state = serde_json::from_str(&contents).ok()?;
+        Some(SysCrate {
+            path,
+            root: root.to_path_buf(),
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Creates the crate in the destination project, copying the C and C++ files of the source
+    /// project into it.
+    pub fn create(
+        path: PathBuf,
+        root: &Path,
+        name: &str,
+        source_root: &Path,
+        files: &[String],
+    ) -> Result<Self, String> {
+        let dir = root.join(name);
+        for file in files {
+            let copy = dir.join(NATIVE_DIR).join(file);
+            std::fs::create_dir_all(copy.parent().unwrap())
+                .and_then(|_| std::fs::copy(source_root.join(file), &copy))
+                .map_err(|e| format!("Cannot copy {file} to {}: {e}", copy.display()))?;
+        }
+        let (headers, sources): (Vec<String>, Vec<String>) =
+            files.iter().cloned().partition(|file| is_header(file));
+        let mut include_dirs = vec![NATIVE_DIR.to_string()];
+        for header in &headers {
+            if let Some(parent) = Path::new(header).parent()
+                && parent != Path::new("")
+            {
+                let include_dir = format!("{NATIVE_DIR}/{}", parent.display());
+                if !include_dirs.contains(&include_dir) {
+                    include_dirs.push(include_dir);
+                }
+            }
+        }
+        let manifest = format!(
+            "[package]\n\
+            name = \"{name}\"\n\
+            version = \"0.1.0\"\n\
+            edition = \"2021\"\n\
+            publish = false\n\
+            \n\
+            [build-dependencies]\n\
+            bindgen = \"0.71\"\n\
+            cc = \"1\"\n"
+        );
+        let lib = "//! Bindings to the original code that isn't ported to Rust yet, generated by riir.\n\
+            #![allow(non_upper_case_globals, non_camel_case_types, non_snake_case, dead_code)]\n\
+            \n\
+            include!(concat!(env!(\"OUT_DIR\"), \"/bindings.rs\"));\n";
+        std::fs::create_dir_all(dir.join("src"))
+            .and_then(|_| std::fs::write(dir.join("Cargo.toml"), manifest))
+            .and_then(|_| std::fs::write(dir.join("src/lib.rs"), lib))
+            .map_err(|e| format!("Cannot create {}: {e}", dir.display()))?;
+
+        let sys_crate = SysCrate {
+            path,
+            root: root.to_path_buf(),
+            state: Mutex::new(SysCrateState {
+                name: name.to_string(),
+                sources,
+                headers,
+                include_dirs,
+            }),
+        };
+        sys_crate.update(|_| ())?;
+        Ok(sys_crate)
+    }
+
+    pub fn name(&self) -> String {
+        self.state.lock().unwrap().name.clone()
+    }
+
+    /// Whether all the original code is ported.
+    pub fn is_ported(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.sources.is_empty() && state.headers.is_empty()
+    }
+
+    /// Tells the model how to use the crate while the modules are ported.
+    pub fn instructions(&self) -> String {
+        let name = self.name();
+        format!(
+            "The original C and C++ code is compiled by the `{name}` crate in the `{name}` \
+            directory of the destination project, with bindings generated by bindgen. Depend on it \
+            with `{name} = {{ path = \"{name}\" }}` and call the functions of the modules that \
+            aren't converted yet through `{}`. Once a module is converted its original code is \
+            removed from `{name}`, so switch all callers of its bindings to the Rust code.",
+            name.replace('-', "_")
+        )
+    }
+
+    /// Removes the files of a ported module from the crate. Returns the removed files.
+    pub fn retire(&self, files: &[String]) -> Result<Vec<String>, String> {
+        let mut retired = vec![];
+        self.update(|state| {
+            for list in [&mut state.sources, &mut state.headers] {
+                list.retain(|file| {
+                    let ported = files.contains(file);
+                    if ported {
+                        retired.push(file.clone());
+                    }
+                    !ported
+                });
+            }
+        })?;
+        Ok(retired)
+    }
+
+    /// Puts retired files back, when the project doesn't work without them.
+    pub fn restore(&self, files: &[String]) -> Result<(), String> {
+        self.update(|state| {
+            for file in files {
+                let list = if is_header(file) {
+                    &mut state.headers
+                } else {
+                    &mut state.sources
+                };
+                if !list.contains(file) {
+                    list.push(file.clone());
+                }
+            }
+        })
+    }
+
+    /// Deletes the crate once nothing is left in it.
+    pub fn remove(&self) -> Result<(), String> {
+        let dir = self.root.join(self.name());
+        std::fs::remove_dir_all(&dir)
+            .map_err(|e| format!("Cannot remove {}: {e}", dir.display()))?;
+        std::fs::remove_file(&self.path)
+            .map_err(|e| format!("Cannot remove {}: {e}", self.path.display()))
+    }
+
+    /// Changes the state, then saves it and regenerates the build script.
+    fn update(&self, change: impl FnOnce(&mut SysCrateState)) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        change(&mut state);
+        let build_script = self.root.join(&state.name).join("build.rs");
+        std::fs::write(&build_script, build_script_contents(&state))
+            .map_err(|e| format!("Cannot write {}: {e}", build_script.display()))?;
+        let contents = serde_json::to_string_pretty(&*state).map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, contents)
+            .map_err(|e| format!("Cannot write {}: {e}", self.path.display()))
+    }
+}
+
+/// Generates the build script compiling the remaining sources and binding the remaining headers.
+fn build_script_contents(state: &SysCrateState) -> String {
+    let in_crate = |files: &mut dyn Iterator<Item = &String>| -> Vec<String> {
+        files.map(|file| format!("{NATIVE_DIR}/{file}")).collect()
+    };
+    let (cpp_sources, c_sources): (Vec<&String>, Vec<&String>) = state
+        .sources
+        .iter()
+        .partition(|file| detect_language(Path::new(file)) == Some("C++"));
+    let cpp = !cpp_sources.is_empty()
+        || state
+            .headers
+            .iter()
+            .any(|file| detect_language(Path::new(file)) == Some("C++"));
+    format!(
+        "// Generated by riir: builds the original code that isn't ported to Rust yet.\n\
+        \n\
+        const C_SOURCES: &[&str] = &{:?};\n\
+        const CPP_SOURCES: &[&str] = &{:?};\n\
+        const HEADERS: &[&str] = &{:?};\n\
+        const INCLUDE_DIRS: &[&str] = &{:?};\n\
+        \n\
+        fn main() {{\n\
+        \x20   for (sources, cpp, name) in [(C_SOURCES, false, \"original_c\"), (CPP_SOURCES, true, \"original_cpp\")] {{\n\
+        \x20       if !sources.is_empty() {{\n\
+        \x20           cc::Build::new().cpp(cpp).includes(INCLUDE_DIRS).files(sources).warnings(false).compile(name);\n\
+        \x20       }}\n\
+        \x20   }}\n\
+        \x20   for file in C_SOURCES.iter().chain(CPP_SOURCES).chain(HEADERS) {{\n\
+        \x20       println!(\"cargo:rerun-if-changed={{file}}\");\n\
+        \x20   }}\n\
+        \x20   let mut bindings = String::new();\n\
+        \x20   if !HEADERS.is_empty() {{\n\
+        \x20       let mut builder = bindgen::Builder::default()\n\
+        \x20           .clang_args(INCLUDE_DIRS.iter().map(|dir| format!(\"-I{{dir}}\")));\n\
+        \x20       if {cpp} {{\n\
+        \x20           builder = builder.clang_arg(\"-xc++\");\n\
+        \x20       }}\n\
+        \x20       for header in HEADERS {{\n\
+        \x20           builder = builder.header(*header);\n\
+        \x20       }}\n\
+        \x20       bindings = builder.generate().expect(\"cannot generate the bindings\").to_string();\n\
+        \x20   }}\n\
+        \x20   let out_dir = std::path::PathBuf::from(std::env::var(\"OUT_DIR\").unwrap());\n\
+        \x20   std::fs::write(out_dir.join(\"bindings.rs\"), bindings).unwrap();\n\
+        }}\n",
+        in_crate(&mut c_sources.into_iter()),
+        in_crate(&mut cpp_sources.into_iter()),
+        in_crate(&mut state.headers.iter()),
+        state.include_dirs,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn retire_modules() {
+        let dir = TempDir::new("ffi");
+        let source = dir.join("source");
+        let destination = dir.join("destination");
+        std::fs::create_dir_all(source.join("include")).unwrap();
+        std::fs::create_dir_all(&destination).unwrap();
+        std::fs::write(source.join("main.c"), "int main() {}\n").unwrap();
+        std::fs::write(source.join("util.c"), "int f() {}\n").unwrap();
+        std::fs::write(source.join("include/util.h"), "int f();\n").unwrap();
+        std::fs::write(source.join("README.md"), "# Foo\n").unwrap();
+        let files: Vec<String> = ["README.md", "include/util.h", "main.c", "util.c"]
+            .iter()
+            .map(|file| file.to_string())
+            .collect();
+        let files = native_files(&files);
+        assert_eq!(files, ["include/util.h", "main.c", "util.c"]);
+
+        let path = dir.join("ffi.json");
+        let sys_crate =
+            SysCrate::create(path.clone(), &destination, "foo-sys", &source, &files).unwrap();
+        let crate_dir = destination.join("foo-sys");
+        assert!(crate_dir.join("csrc/include/util.h").is_file());
+        assert!(crate_dir.join("src/lib.rs").is_file());
+        let build_script = std::fs::read_to_string(crate_dir.join("build.rs")).unwrap();
+        assert!(build_script.contains(
+            "const C_SOURCES: &[&str] = &[\"csrc/main.c\", \"csrc/util.c\"];\n\
+            const CPP_SOURCES: &[&str] = &[];\n\
+            const HEADERS: &[&str] = &[\"csrc/include/util.h\"];\n\
+            const INCLUDE_DIRS: &[&str] = &[\"csrc\", \"csrc/include\"];\n"
+        ));
+        assert!(sys_crate.instructions().contains("through `foo_sys`"));
+
+        let module = vec!["util.c".to_string(), "include/util.h".to_string()];
+        let retired = sys_crate.retire(&module).unwrap();
+        assert_eq!(retired, ["util.c", "include/util.h"]);
+        let build_script = std::fs::read_to_string(crate_dir.join("build.rs")).unwrap();
+        assert!(build_script.contains("const C_SOURCES: &[&str] = &[\"csrc/main.c\"];"));
+        assert!(build_script.contains("const HEADERS: &[&str] = &[];"));
+
+        sys_crate.restore(&retired).unwrap();
+        let sys_crate = SysCrate::load(path, &destination).unwrap();
+        assert!(!sys_crate.is_ported());
+        sys_crate.retire(&files).unwrap();
+        assert!(sys_crate.is_ported());
+        sys_crate.remove().unwrap();
+        assert!(!crate_dir.exists());
+    }
+}
@@ -2,24 +2,89 @@ use openai::chat::{
     ChatCompletionFunctionCall, ChatCompletionFunctionDefinition, ChatCompletionMessage,
     ChatCompletionMessageRole,
 };
-use schemars::{JsonSchema, schema_for};
+use schemars::JsonSchema;
+use schemars::r#gen::SchemaSettings;
 use serde::Serialize;
 use serde::de::DeserializeOwned;
-use serde_json::Value;
-use std::any::TypeId;
-use std::sync::Arc;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
-type GenericCallableFn = Arc<dyn Fn(&str) -> Result<String, DispatchError>>;
+type GenericCallableFn = Arc<dyn Fn(&str) -> Result<Value, DispatchError>>;
+type ResultFilterFn = Box<dyn Fn(&str) -> String>;
+type ConfirmationFn = Box<dyn Fn(&str, &str) -> bool>;
+type AccessFn = Box<dyn Fn(&str, bool) -> bool>;
+
+/// Registers a function in a [`CallableFunctionList`].
+///
+/// The function name is taken from the identifier and the description from the doc comment,
+/// so that they don't have to be written by hand:
+///
+/// ```ignore
+/// add_function!(list,
+///     /// Reads the contents of a file.
+///     fn read_file(args: ReadFileArgs) => project.read_file(&args.path)
+/// );
+/// ```
+///
+/// Functions returning `Result<_, TransientError>` are registered with `retry fn` instead.
+/// A name computed at runtime is given in brackets: `fn [format!("{prefix}_read")](...)`.
+macro_rules! add_function {
+    ($list:expr, $(#[doc = $doc:literal])+ fn $name:ident($args:tt: $ty:ty) => $body:expr) => {
+        $list.add_function(
+            stringify!($name),
+            &$crate::function::doc_description(&[$($doc),+]),
+            move |$args: $ty| $body,
+        )
+    };
+    ($list:expr, $(#[doc = $doc:literal])+ retry fn $name:ident($args:tt: $ty:ty) => $body:expr) => {
+        $list.add_retryable_function(
+            stringify!($name),
+            &$crate::function::doc_description(&[$($doc),+]),
+            move |$args: $ty| $body,
+        )
+    };
+    ($list:expr, $(#[doc = $doc:literal])+ fn [$name:expr]($args:tt: $ty:ty) => $body:expr) => {
+        $list.add_function(
+            &$name,
+            &$crate::function::doc_description(&[$($doc),+]),
+            move |$args: $ty| $body,
+        )
+    };
+    ($list:expr, $(#[doc = $doc:literal])+ retry fn [$name:expr]($args:tt: $ty:ty) => $body:expr) => {
+        $list.add_retryable_function(
+            &$name,
+            &$crate::function::doc_description(&[$($doc),+]),
+            move |$args: $ty| $body,
+        )
+    };
+}
+pub(crate) use add_function;
+
+/// Joins doc comment lines into a single description string.
+pub fn doc_description(lines: &[&str]) -> String {
+    lines
+        .iter()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
 
 #[derive(Clone)]
 struct CallableFunction {
     name: String,
     func: GenericCallableFn,
+    mutating: bool,
+    destructive: bool,
+    /// minimum time between two calls
+    min_interval: Option<Duration>,
 }
 
 impl CallableFunction {
-    fn call(&self, args: &str) -> Result<String, DispatchError> {
+    fn call(&self, args: &str) -> Result<Value, DispatchError> {
         (self.func)(args)
     }
 }
@@ -28,79 +93,480 @@ impl CallableFunction {
 pub struct CallableFunctionList {
     functions: Vec<CallableFunction>,
     function_definitions: Vec<ChatCompletionFunctionDefinition>,
+    aliases: HashMap<String, String>,
+    result_filters: Vec<ResultFilterFn>,
+    max_result_size: Option<usize>,
+    retry_policy: RetryPolicy,
+    dry_run: bool,
+    confirmation: Option<ConfirmationFn>,
+    /// which functions the model may see and call
+    access: Option<AccessFn>,
+    /// when each rate-limited function was last called
+    last_calls: Mutex<HashMap<String, Instant>>,
 }
 
 impl CallableFunctionList {
+    /// Enables the dry-run mode: mutating functions are not called, their calls are logged
+    /// and reported to the model as successful.
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    /// Sets the confirmation gate for destructive functions.
+    ///
+    /// The gate gets the function name and arguments and returns whether the call is allowed.
+    pub fn set_confirmation<F>(&mut self, confirmation: F)
+    where
+        F: Fn(&str, &str) -> bool + 'static,
+    {
+        self.confirmation = Some(Box::new(confirmation));
+    }
+
+    /// Restricts the functions offered to the model, e.g. to the ones of an agent role.
+    ///
+    /// The filter gets the function name and whether the function is mutating. Calls of the
+    /// other functions are rejected.
+    pub fn set_access<F>(&mut self, access: F)
+    where
+        F: Fn(&str, bool) -> bool + 'static,
+    {
+        self.access = Some(Box::new(access));
+    }
+
+    /// Sets how function calls failed with a [`TransientError`] are retried.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    /// Sets the maximum size of a function result in bytes.
+    ///
+    /// Larger results are truncated before they are sent to the model.
+    pub fn set_max_result_size(&mut self, size: Option<usize>) {
+        self.max_result_size = size;
+    }
+
     /// Adds a function to the list of callable functions.
-    pub fn add_function<F, A, R>(&mut self, name: &str, description: &str, function: F)
+    pub fn add_function<F, A, R>(
+        &mut self,
+        name: &str,
+        description: &str,
+        function: F,
+    ) -> FunctionOptions<'_>
     where
         F: Fn(A) -> R + 'static,
         A: DeserializeOwned + JsonSchema + 'static,
         R: Serialize + 'static,
+    {
+        self.add_retryable_function(name, description, move |args| Ok(function(args)))
+    }
+
+    /// Adds a function that may fail with a [`TransientError`] to the list of callable
+    /// functions. Such calls are retried according to the retry policy.
+    pub fn add_retryable_function<F, A, R>(
+        &mut self,
+        name: &str,
+        description: &str,
+        function: F,
+    ) -> FunctionOptions<'_>
+    where
+        F: Fn(A) -> Result<R, TransientError> + 'static,
+        A: DeserializeOwned + JsonSchema + 'static,
+        R: Serialize + 'static,
     {
         assert!(!self.function_definitions.iter().any(|e| e.name == name));
 
         let caller = move |args_str: &str| {
-            let args_str = if TypeId::of::<()>() == TypeId::of::<A>() {
-                "null"
-            } else {
-                args_str
-            };
-            let args: A = serde_json::from_str(args_str).map_err(DispatchError::Deserialize)?;
-            let result: R = function(args);
-            serde_json::to_string(&result).map_err(DispatchError::Serialize)
+            let args: A = parse_arguments(args_str).map_err(DispatchError::Deserialize)?;
+            let result: R = function(args).map_err(DispatchError::Transient)?;
+            serde_json::to_value(&result).map_err(DispatchError::Serialize)
         };
 
         self.functions.push(CallableFunction {
             name: name.to_string(),
             func: Arc::new(caller),
+            mutating: false,
+            destructive: false,
+            min_interval: None,
         });
 
-        let argument_schema = if TypeId::of::<()>() == TypeId::of::<A>() {
-            Value::Null
-        } else {
-            let mut schema = schema_for!(A);
-            schema.meta_schema = None;
-            schema.schema.metadata.as_mut().unwrap().title = None;
-            serde_json::to_value(&schema).unwrap()
-        };
-
         let definition = ChatCompletionFunctionDefinition {
             name: name.to_string(),
             description: Some(description.to_string()),
-            parameters: Some(argument_schema),
+            parameters: Some(argument_schema::<A>()),
         };
         log::debug!("Adding function: {definition:?}");
         self.function_definitions.push(definition);
+
+        FunctionOptions {
+            function: self.functions.last_mut().unwrap(),
+        }
+    }
+
+    /// Adds a filter applied to every function result before it's sent to the model.
+    pub fn add_result_filter<F>(&mut self, filter: F)
+    where
+        F: Fn(&str) -> String + 'static,
+    {
+        self.result_filters.push(Box::new(filter));
+    }
+
+    /// Registers an alias, so that calls to `alias` are routed to the function `name`.
+    ///
+    /// Aliases are not advertised to the model, they only keep old function names working.
+    pub fn add_alias(&mut self, alias: &str, name: &str) -> Result<(), String> {
+        if !self.functions.iter().any(|f| f.name == name) {
+            return Err(format!(
+                "Cannot add alias {alias}: function {name} not found"
+            ));
+        }
+        if self.functions.iter().any(|f| f.name == alias) || self.aliases.contains_key(alias) {
+            return Err(format!(
+                "Cannot add alias {alias}: the name is already used"
+            ));
+        }
+        self.aliases.insert(alias.to_string(), name.to_string());
+        Ok(())
     }
 
     /// Returns the function definitions.
     pub fn function_definitions(&self) -> Vec<ChatCompletionFunctionDefinition> {
-        self.function_definitions.clone()
+        self.function_definitions
+            .iter()
+            .zip(&self.functions)
+            .filter(|(_, function)| self.is_allowed(function))
+            .map(|(definition, _)| definition.clone())
+            .collect()
+    }
+
+    fn is_allowed(&self, function: &CallableFunction) -> bool {
+        self.access
+            .as_ref()
+            .is_none_or(|access| access(&function.name, function.mutating || function.destructive))
     }
 
     /// Dispatches the function call to the appropriate function.
+    ///
+    /// Returns both the message for the model and the function result value.
     pub fn dispatch(
         &self,
         call: &ChatCompletionFunctionCall,
-    ) -> Result<ChatCompletionMessage, DispatchError> {
+    ) -> Result<DispatchResult, DispatchError> {
+        let name = match self.aliases.get(&call.name) {
+            Some(name) => {
+                log::debug!("Routing call of {} to {name}", call.name);
+                name
+            }
+            None => &call.name,
+        };
         let function = self
             .functions
             .iter()
-            .find(|f| f.name == call.name)
+            .find(|f| &f.name == name)
             .ok_or(DispatchError::FunctionNotFound)?;
 
-        let output = function.call(&call.arguments)?;
+        let value = if !self.is_allowed(function) {
+            json!({ "error": format!("{name} is not available to you.") })
+        } else if self.dry_run && function.mutating {
+            log::info!("Dry run: {name}({})", call.arguments);
+            json!({ "error": null })
+        } else if function.destructive
+            && self
+                .confirmation
+                .as_ref()
+                .is_some_and(|confirm| !confirm(name, &call.arguments))
+        {
+            json!({ "error": "The operator declined the operation." })
+        } else if let Some(wait) = self.rate_limit(function) {
+            json!({
+                "error": format!(
+                    "{name} was called recently, make more changes before calling it again \
+                    (possible in {} seconds).",
+                    wait.as_secs().max(1)
+                )
+            })
+        } else {
+            self.call_with_retries(function, &call.arguments)?
+        };
+
+        let mut output = serde_json::to_string(&value).map_err(DispatchError::Serialize)?;
+        for filter in &self.result_filters {
+            output = filter(&output);
+        }
+        if let Some(limit) = self.max_result_size {
+            output = truncate_result(output, limit);
+        }
         let message = ChatCompletionMessage {
             role: ChatCompletionMessageRole::Function,
             content: Some(output),
             name: Some(call.name.clone()),
             ..Default::default()
         };
-        Ok(message)
+        Ok(DispatchResult { message, value })
+    }
+
+    /// Returns how long to wait if the function was called too recently, otherwise records
+    /// the call.
+    fn rate_limit(&self, function: &CallableFunction) -> Option<Duration> {
+        let min_interval = function.min_interval?;
+        let mut last_calls = self.last_calls.lock().unwrap();
+        let now = Instant::now();
+        if let Some(last_call) = last_calls.get(&function.name) {
+            let elapsed = now.duration_since(*last_call);
+            if elapsed < min_interval {
+                return Some(min_interval - elapsed);
+            }
+        }
+        last_calls.insert(function.name.clone(), now);
+        None
+    }
+
+    fn call_with_retries(
+        &self,
+        function: &CallableFunction,
+        args: &str,
+    ) -> Result<Value, DispatchError> {
+        let mut delay = self.retry_policy.delay;
+        let mut attempt = 1;
+        loop {
+            match function.call(args) {
+                Err(DispatchError::Transient(e)) if attempt < self.retry_policy.attempts => {
+                    log::warn!(
+                        "Function {} failed (attempt {attempt}): {e}, retrying",
+                        function.name
+                    );
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                    attempt += 1;
+                }
+                Err(DispatchError::Transient(e)) => return Ok(json!({ "error": e.to_string() })),
+                result => return result,
+            }
+        }
     }
 }
 
+/// Options of a function added to a [`CallableFunctionList`].
+pub struct FunctionOptions<'a> {
+    function: &'a mut CallableFunction,
+}
+
+impl FunctionOptions<'_> {
+    /// Marks the function as changing the destination project.
+    pub fn mutating(self) -> Self {
+        self.function.mutating = true;
+        self
+    }
+
+    /// Marks the function as irreversibly changing the destination project,
+    /// its calls have to pass the confirmation gate.
+    pub fn destructive(self) -> Self {
+        self.function.destructive = true;
+        self.mutating()
+    }
+
+    /// Rejects calls made less than `interval` after the previous one, for expensive functions
+    /// like builds.
+    pub fn rate_limited(self, interval: Duration) -> Self {
+        self.function.min_interval = Some(interval).filter(|interval| !interval.is_zero());
+        self
+    }
+}
+
+/// Failure of a function call that may succeed if the call is repeated,
+/// e.g. because a file is temporarily locked.
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct TransientError(pub String);
+
+/// Defines how function calls failed with a [`TransientError`] are retried.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one.
+    pub attempts: u32,
+    /// Delay before the first retry, doubled after every attempt.
+    pub delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 3,
+            delay: Duration::from_millis(100),
+        }
+    }
+}
+
+/// Result of a dispatched function call.
+pub struct DispatchResult {
+    /// Message with the function output to be sent to the model.
+    pub message: ChatCompletionMessage,
+    /// Function result, not affected by truncation.
+    pub value: Value,
+}
+
+/// Generates the JSON schema for function arguments.
+///
+/// Optional arguments are described by the schema of the inner type, and types
+/// without an object representation (e.g. `()`) get an empty object schema, since
+/// the API expects function parameters to always be an object.
+///
+/// The schema is compatible with strict function calling, see [`make_strict`].
+fn argument_schema<A: JsonSchema>() -> Value {
+    let settings = SchemaSettings::draft07().with(|s| {
+        s.option_add_null_type = false;
+        s.inline_subschemas = true;
+        s.meta_schema = None;
+    });
+    let mut schema = settings.into_generator().into_root_schema_for::<A>();
+    if let Some(metadata) = schema.schema.metadata.as_mut() {
+        metadata.title = None;
+    }
+    let mut schema = serde_json::to_value(&schema.schema).unwrap();
+    if schema.get("type") != Some(&json!("object")) {
+        schema = json!({ "type": "object", "properties": {} });
+    }
+    make_strict(&mut schema);
+    schema
+}
+
+/// Keywords that are not supported by strict function calling.
+const UNSUPPORTED_KEYWORDS: &[&str] = &[
+    "$schema",
+    "title",
+    "default",
+    "examples",
+    "format",
+    "minimum",
+    "maximum",
+    "exclusiveMinimum",
+    "exclusiveMaximum",
+    "multipleOf",
+    "minLength",
+    "maxLength",
+    "pattern",
+    "minItems",
+    "maxItems",
+    "uniqueItems",
+    "minProperties",
+    "maxProperties",
+];
+
+/// Converts the schema into the form required by strict function calling.
+///
+/// Every object gets `additionalProperties: false` and lists all its properties as
+/// required, optional properties are made nullable instead. Unsupported keywords are
+/// removed.
+fn make_strict(schema: &mut Value) {
+    let Some(object) = schema.as_object_mut() else {
+        return;
+    };
+    for keyword in UNSUPPORTED_KEYWORDS {
+        object.remove(*keyword);
+    }
+
+    let required = object.remove("required").unwrap_or_else(|| json!([]));
+    if let Some(Value::Object(properties)) = object.get_mut("properties") {
+        let required = required.as_array().unwrap();
+        for (name, property) in properties.iter_mut() {
+            if !required.contains(&json!(name)) {
+                make_nullable(property);
+            }
+        }
+        let names = properties.keys().map(|name| json!(name)).collect();
+        object.insert("required".to_string(), Value::Array(names));
+        object.insert("additionalProperties".to_string(), Value::Bool(false));
+    }
+
+    for (key, value) in object.iter_mut() {
+        match key.as_str() {
+            "properties" => value
+                .as_object_mut()
+                .unwrap()
+                .values_mut()
+                .for_each(make_strict),
+            "items" | "additionalProperties" => make_strict(value),
+            "anyOf" | "oneOf" | "allOf" => {
+                if let Some(variants) = value.as_array_mut() {
+                    variants.iter_mut().for_each(make_strict);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Allows `null` as a value of the schema.
+fn make_nullable(schema: &mut Value) {
+    match schema.get_mut("type") {
+        Some(Value::String(ty)) => {
+            let ty = std::mem::take(ty);
+            schema["type"] = json!([ty, "null"]);
+        }
+        Some(Value::Array(types)) => {
+            if !types.contains(&json!("null")) {
+                types.push(json!("null"));
+            }
+        }
+        _ => {
+            let inner = schema.take();
+            *schema = json!({ "anyOf": [inner, { "type": "null" }] });
+        }
+    }
+}
+
+/// Removes `null` fields from objects, so that nullable fields of strict schemas
+/// are treated the same way as missing ones.
+fn remove_null_fields(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|_, v| !v.is_null());
+            map.values_mut().for_each(remove_null_fields);
+        }
+        Value::Array(items) => items.iter_mut().for_each(remove_null_fields),
+        _ => {}
+    }
+}
+
+/// Parses function arguments.
+///
+/// Functions without arguments may be called with an empty string, `null` or `{}`,
+/// so if the arguments can't be parsed as is, the other empty form is tried as well.
+fn parse_arguments<A: DeserializeOwned>(args: &str) -> Result<A, serde_json::Error> {
+    let mut value = if args.trim().is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_str(args)?
+    };
+    remove_null_fields(&mut value);
+    A::deserialize(&value).or_else(|e| {
+        let fallback = match &value {
+            Value::Null => json!({}),
+            Value::Object(map) if map.is_empty() => Value::Null,
+            _ => return Err(e),
+        };
+        A::deserialize(&fallback).map_err(|_| e)
+    })
+}
+
+/// Truncates a function result that exceeds `limit` bytes, appending a marker that tells
+/// the model how to get the rest.
+fn truncate_result(mut output: String, limit: usize) -> String {
+    if output.len() <= limit {
+        return output;
+    }
+    let total = output.len();
+    let mut end = limit;
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    output.truncate(end);
+    output.push_str(&format!(
+        "\n[TRUNCATED: the result is {total} bytes long, only the first {end} bytes are shown. \
+        Request the rest in smaller parts, e.g. read a file by line ranges with *_read_file_range.]"
+    ));
+    output
+}
+
 #[derive(Debug, Error)]
 pub enum DispatchError {
     #[error("Function not found")]
@@ -109,11 +575,46 @@ pub enum DispatchError {
     Deserialize(#[source] serde_json::Error),
     #[error("Failed to serialize function result")]
     Serialize(#[source] serde_json::Error),
+    #[error("Function failed")]
+    Transient(#[source] TransientError),
+    #[error("The budget of {0} tokens is spent")]
+    BudgetExhausted(u64),
+    #[error("Gave up on `{0}`, it needs human attention")]
+    Flagged(String),
+}
+
+impl DispatchError {
+    /// Returns the error text including the underlying cause.
+    pub fn details(&self) -> String {
+        match std::error::Error::source(self) {
+            Some(source) => format!("{self}: {source}"),
+            None => self.to_string(),
+        }
+    }
+
+    /// Explains why the conversation stopped.
+    pub fn reason(&self) -> String {
+        match self {
+            DispatchError::BudgetExhausted(_) | DispatchError::Flagged(_) => self.to_string(),
+            _ => format!("Too many failed function calls: {}", self.details()),
+        }
+    }
+
+    /// Converts the error into a function message, so that the model can correct the call.
+    pub fn to_message(&self, name: &str) -> ChatCompletionMessage {
+        ChatCompletionMessage {
+            role: ChatCompletionMessageRole::Function,
+            content: Some(json!({ "error": self.details() }).to_string()),
+            name: Some(name.to_string()),
+            ..Default::default()
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde::Deserialize;
 
     #[test]
     fn unit_type_args() {
@@ -132,7 +633,373 @@ mod tests {
                 name: "unit_test".to_string(),
                 arguments: "{}".to_string(),
             })
-            .unwrap();
+            .unwrap()
+            .message;
         assert_eq!(message.content.unwrap(), r#"{"message":"Hello"}"#);
+        assert_eq!(
+            list.function_definitions()[0].parameters,
+            Some(json!({
+                "type": "object",
+                "properties": {},
+                "required": [],
+                "additionalProperties": false
+            }))
+        );
+    }
+
+    #[test]
+    fn optional_args() {
+        let mut list = CallableFunctionList::default();
+
+        #[derive(Deserialize, JsonSchema)]
+        struct Args {
+            path: String,
+        }
+        list.add_function(
+            "opt_test",
+            "optional test function",
+            |args: Option<Args>| args.map(|a| a.path),
+        );
+
+        let schema = list.function_definitions()[0].parameters.clone().unwrap();
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["required"], json!(["path"]));
+
+        for (arguments, expected) in [("", "null"), ("{}", "null"), (r#"{"path":"a"}"#, r#""a""#)] {
+            let message = list
+                .dispatch(&ChatCompletionFunctionCall {
+                    name: "opt_test".to_string(),
+                    arguments: arguments.to_string(),
+                })
+                .unwrap()
+                .message;
+            assert_eq!(message.content.unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn default_args() {
+        let mut list = CallableFunctionList::default();
+
+        #[derive(Default, Deserialize, JsonSchema)]
+        #[serde(default)]
+        struct Args {
+            depth: u32,
+        }
+        list.add_function("default_test", "default test function", |args: Args| {
+            args.depth
+        });
+
+        let schema = list.function_definitions()[0].parameters.clone().unwrap();
+        assert_eq!(
+            schema,
+            json!({
+                "type": "object",
+                "properties": { "depth": { "type": ["integer", "null"] } },
+                "required": ["depth"],
+                "additionalProperties": false
+            })
+        );
+
+        for (arguments, expected) in [
+            ("", "0"),
+            ("null", "0"),
+            (r#"{"depth":null}"#, "0"),
+            (r#"{"depth":3}"#, "3"),
+        ] {
+            let message = list
+                .dispatch(&ChatCompletionFunctionCall {
+                    name: "default_test".to_string(),
+                    arguments: arguments.to_string(),
+                })
+                .unwrap()
+                .message;
+            assert_eq!(message.content.unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn typed_result() {
+        let mut list = CallableFunctionList::default();
+
+        #[derive(Serialize)]
+        struct FuncResult {
+            error: Option<String>,
+        }
+        list.add_function("typed_test", "typed result function", |_: ()| FuncResult {
+            error: Some("failed".to_string()),
+        });
+
+        let result = list
+            .dispatch(&ChatCompletionFunctionCall {
+                name: "typed_test".to_string(),
+                arguments: String::new(),
+            })
+            .unwrap();
+        assert_eq!(result.value, json!({ "error": "failed" }));
+        assert_eq!(result.message.content.unwrap(), r#"{"error":"failed"}"#);
+    }
+
+    #[test]
+    fn result_truncation() {
+        let mut list = CallableFunctionList::default();
+        list.set_max_result_size(Some(10));
+        list.add_function("long_test", "long result function", |_: ()| "ф".repeat(10));
+
+        let message = list
+            .dispatch(&ChatCompletionFunctionCall {
+                name: "long_test".to_string(),
+                arguments: String::new(),
+            })
+            .unwrap()
+            .message;
+        let content = message.content.unwrap();
+        assert!(content.starts_with("\"фффф\n[TRUNCATED: the result is 22 bytes long"));
+    }
+
+    #[test]
+    fn result_filter() {
+        let mut list = CallableFunctionList::default();
+        list.add_result_filter(|output| output.replace("secret", "******"));
+        list.add_function("filter_test", "filtered function", |_: ()| "my secret");
+
+        let result = list
+            .dispatch(&ChatCompletionFunctionCall {
+                name: "filter_test".to_string(),
+                arguments: String::new(),
+            })
+            .unwrap();
+        assert_eq!(result.message.content.unwrap(), r#""my ******""#);
+        assert_eq!(result.value, json!("my secret"));
+    }
+
+    #[test]
+    fn transient_retry() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let mut list = CallableFunctionList::default();
+        list.set_retry_policy(RetryPolicy {
+            attempts: 3,
+            delay: Duration::ZERO,
+        });
+
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        add_function!(list,
+            /// Fails twice, then succeeds.
+            retry fn flaky(_: ()) => match counter.fetch_add(1, Ordering::Relaxed) {
+                0 | 1 => Err(TransientError("busy".to_string())),
+                _ => Ok("done"),
+            }
+        );
+        add_function!(list,
+            /// Always fails.
+            retry fn broken(_: ()) => Err::<(), _>(TransientError("busy".to_string()))
+        );
+
+        let call = |name: &str| {
+            list.dispatch(&ChatCompletionFunctionCall {
+                name: name.to_string(),
+                arguments: String::new(),
+            })
+            .unwrap()
+            .value
+        };
+        assert_eq!(call("flaky"), json!("done"));
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+        assert_eq!(call("broken"), json!({ "error": "busy" }));
+    }
+
+    #[test]
+    fn dry_run() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let mut list = CallableFunctionList::default();
+        list.set_dry_run(true);
+
+        let written = Arc::new(AtomicBool::new(false));
+        let flag = written.clone();
+        add_function!(list,
+            /// Writes something.
+            fn write(_: ()) => flag.store(true, Ordering::Relaxed)
+        )
+        .mutating();
+        add_function!(list,
+            /// Reads something.
+            fn read(_: ()) => "contents"
+        );
+
+        let call = |name: &str| {
+            list.dispatch(&ChatCompletionFunctionCall {
+                name: name.to_string(),
+                arguments: String::new(),
+            })
+            .unwrap()
+            .value
+        };
+        assert_eq!(call("write"), json!({ "error": null }));
+        assert!(!written.load(Ordering::Relaxed));
+        assert_eq!(call("read"), json!("contents"));
+    }
+
+    #[test]
+    fn error_message() {
+        let mut list = CallableFunctionList::default();
+        list.add_function("args_test", "function with arguments", |n: u32| n);
+
+        let error = list
+            .dispatch(&ChatCompletionFunctionCall {
+                name: "args_test".to_string(),
+                arguments: r#"{"n":"#.to_string(),
+            })
+            .err()
+            .unwrap();
+        let message = error.to_message("args_test");
+        assert_eq!(message.role, ChatCompletionMessageRole::Function);
+        assert!(
+            message
+                .content
+                .unwrap()
+                .starts_with(r#"{"error":"Failed to deserialize function argument: EOF"#)
+        );
+    }
+
+    #[test]
+    fn confirmation_gate() {
+        let mut list = CallableFunctionList::default();
+        list.set_confirmation(|name, _| name == "allowed");
+        add_function!(list,
+            /// Deletes something.
+            fn allowed(_: ()) => "deleted"
+        )
+        .destructive();
+        add_function!(list,
+            /// Deletes something else.
+            fn denied(_: ()) => "deleted"
+        )
+        .destructive();
+
+        let call = |name: &str| {
+            list.dispatch(&ChatCompletionFunctionCall {
+                name: name.to_string(),
+                arguments: String::new(),
+            })
+            .unwrap()
+            .value
+        };
+        assert_eq!(call("allowed"), json!("deleted"));
+        assert_eq!(
+            call("denied"),
+            json!({ "error": "The operator declined the operation." })
+        );
+    }
+
+    #[test]
+    fn rate_limit() {
+        let mut list = CallableFunctionList::default();
+        add_function!(list,
+            /// Builds something.
+            fn build(_: ()) => "built"
+        )
+        .rate_limited(Duration::from_secs(60));
+        add_function!(list,
+            /// Reads something.
+            fn read(_: ()) => "contents"
+        );
+
+        let call = |name: &str| {
+            list.dispatch(&ChatCompletionFunctionCall {
+                name: name.to_string(),
+                arguments: String::new(),
+            })
+            .unwrap()
+            .value
+        };
+        assert_eq!(call("build"), json!("built"));
+        assert!(
+            call("build")["error"]
+                .as_str()
+                .unwrap()
+                .starts_with("build was called recently")
+        );
+        assert_eq!(call("read"), json!("contents"));
+        assert_eq!(call("read"), json!("contents"));
+    }
+
+    #[test]
+    fn access() {
+        let mut list = CallableFunctionList::default();
+        add_function!(list,
+            /// Writes something.
+            fn write(_: ()) => "written"
+        )
+        .mutating();
+        add_function!(list,
+            /// Reads something.
+            fn read(_: ()) => "contents"
+        );
+        list.set_access(|_, mutating| !mutating);
+
+        let names: Vec<String> = list
+            .function_definitions()
+            .into_iter()
+            .map(|definition| definition.name)
+            .collect();
+        assert_eq!(names, ["read"]);
+        let call = |name: &str| {
+            list.dispatch(&ChatCompletionFunctionCall {
+                name: name.to_string(),
+                arguments: String::new(),
+            })
+            .unwrap()
+            .value
+        };
+        assert_eq!(call("read"), json!("contents"));
+        assert_eq!(
+            call("write"),
+            json!({ "error": "write is not available to you." })
+        );
+    }
+
+    #[test]
+    fn aliases() {
+        let mut list = CallableFunctionList::default();
+        list.add_function("new_name", "renamed function", |_: ()| 42);
+
+        assert!(list.add_alias("old_name", "new_name").is_ok());
+        assert!(list.add_alias("old_name", "new_name").is_err());
+        assert!(list.add_alias("new_name", "new_name").is_err());
+        assert!(list.add_alias("other_name", "missing").is_err());
+        assert_eq!(list.function_definitions().len(), 1);
+
+        let message = list
+            .dispatch(&ChatCompletionFunctionCall {
+                name: "old_name".to_string(),
+                arguments: String::new(),
+            })
+            .unwrap()
+            .message;
+        assert_eq!(message.name.unwrap(), "old_name");
+        assert_eq!(message.content.unwrap(), "42");
+    }
+
+    #[test]
+    fn function_macro() {
+        let mut list = CallableFunctionList::default();
+
+        let greeting = "Hello".to_string();
+        add_function!(list,
+            /// Returns a greeting.
+            ///
+            /// The greeting is always the same.
+            fn greet(_: ()) => greeting.clone()
+        );
+
+        let definition = &list.function_definitions()[0];
+        assert_eq!(definition.name, "greet");
+        assert_eq!(
+            definition.description.as_deref(),
+            Some("Returns a greeting. The greeting is always the same.")
+        );
     }
 }
@@ -0,0 +1,237 @@
+use crate::config::FuzzConfig;
+use crate::process::{CommandOutput, run_command};
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+/// Cargo example of the destination project calling the port.
+pub const PORTED_HARNESS: &str = "examples/fuzz_harness.rs";
+
+/// Script of the destination project building and running the driver of the original library.
+pub const ORIGINAL_HARNESS: &str = "fuzz/run-original.sh";
+
+/// The captured output of each harness is truncated to this many bytes.
+const MAX_CAPTURED_OUTPUT: usize = 16 * 1024 * 1024;
+
+/// How many discrepancies are reported to the model at once.
+const MAX_REPORTED_DISCREPANCIES: usize = 10;
+
+/// Asks the model to write both harnesses.
+pub const HARNESS_PROMPT: &str = "\
+    Now write a fuzzing harness that compares the port with the original library on random \
+    inputs. It consists of two programs that both read inputs from stdin, one per line, each \
+    line being a hex-encoded byte string, possibly empty. For every input a program must print \
+    exactly one line to stdout: the results of calling the library with arguments derived from \
+    the bytes, formatted deterministically, or the error it reported. Both programs must derive \
+    the arguments in exactly the same way and exercise as much of the public API as possible. \
+    The programs must not crash on any input.\n\
+    1. `examples/fuzz_harness.rs` in the destination project calls the Rust port.\n\
+    2. The driver of the original library lives in the `fuzz` directory of the destination \
+    project and is written in the language of the original. `fuzz/run-original.sh` builds and \
+    runs it: it is started with `sh` in a copy of the source project, the absolute path of the \
+    `fuzz` directory is in the `RIIR_FUZZ_DIR` environment variable, and the inputs are on its \
+    stdin. Build artifacts go to `$RIIR_FUZZ_DIR/build`.";
+
+/// An input for which the harnesses print different results.
+pub struct Discrepancy {
+    /// the input in hex
+    pub input: String,
+    pub original: String,
+    pub ported: String,
+}
+
+/// Generates random inputs, starting with a few edge cases. The same seed gives the same inputs.
+pub fn random_inputs(seed: u64, count: usize, max_size: usize) -> Vec<Vec<u8>> {
+    let mut inputs: Vec<Vec<u8>> = vec![
+        vec![],
+        vec![0],
+        vec![0xff],
+        b"0".to_vec(),
+        vec![0; max_size],
+        vec![0xff; max_size],
+    ];
+    let mut state = seed;
+    while inputs.len() < count {
+        let size = (next_random(&mut state) % (max_size as u64 + 1)) as usize;
+        inputs.push((0..size).map(|_| next_random(&mut state) as u8).collect());
+    }
+    inputs.truncate(count);
+    inputs
+}
+
+/// The splitmix64 generator.
+fn next_random(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Feeds the random inputs to both harnesses and returns the inputs whose results differ. The
+/// port's harness must already be built.
+pub fn run_fuzz(
+    config: &FuzzConfig,
+    source_copy: &Path,
+    destination: &Path,
+) -> Result<Vec<Discrepancy>, String> {
+    let inputs: Vec<String> = random_inputs(config.seed, config.iterations, config.max_input_size)
+        .iter()
+        .map(|input| input.iter().map(|byte| format!("{byte:02x}")).collect())
+        .collect();
+    let stdin = inputs.join("\n") + "\n";
+    let timeout = Some(Duration::from_secs(config.timeout)).filter(|timeout| !timeout.is_zero());
+    let run = |command: &mut Command, name: &str| {
+        let output = run_command(
+            command,
+            Some(stdin.as_bytes()),
+            timeout,
+            Some(MAX_CAPTURED_OUTPUT),
+        )?;
+        check_output(&output, name)?;
+        Ok::<_, String>(output.stdout)
+    };
+
+    let fuzz_dir = destination.join("fuzz");
+    let original = run(
+        Command::new("sh")
+            .arg(destination.join(ORIGINAL_HARNESS))
+            .env("RIIR_FUZZ_DIR", &fuzz_dir)
+            .current_dir(source_copy),
+        "The harness of the original library",
+    )?;
+    let ported = run(
+        Command::new(destination.join("target/debug/examples/fuzz_harness"))
+            .current_dir(destination),
+        "The harness of the port",
+    )?;
+
+    let original: Vec<&str> = original.lines().collect();
+    let ported: Vec<&str> = ported.lines().collect();
+    for (lines, name) in [(&original, "original"), (&ported, "port")] {
+        if lines.len() != inputs.len() {
+            return Err(format!(
+                "The harness of the {name} printed {} lines for {} inputs.",
+                lines.len(),
+                inputs.len()
+            ));
+        }
+    }
+    Ok(inputs
+        .into_iter()
+        .zip(original.into_iter().zip(ported))
+        .filter(|(_, (original, ported))| original != ported)
+        .map(|(input, (original, ported))| Discrepancy {
+            input,
+            original: original.to_string(),
+            ported: ported.to_string(),
+        })
+        .collect())
+}
+
+fn check_output(output: &CommandOutput, name: &str) -> Result<(), String> {
+    if output.timed_out {
+        return Err(format!("{name} was killed after the timeout."));
+    }
+    if !output.success {
+        let status = output
+            .exit_code
+            .map_or("killed by a signal".to_string(), |code| {
+                format!("exit code {code}")
+            });
+        return Err(format!(
+            "{name} failed ({status}):\n{}",
+            output.stderr.trim_end()
+        ));
+    }
+    if output.stdout_truncated {
+        return Err(format!("{name} printed too much."));
+    }
+    Ok(())
+}
+
+/// Describes the discrepancies for the model, the first few of them in full.
+pub fn format_discrepancies(discrepancies: &[Discrepancy], inputs: usize) -> String {
+    let mut report = format!(
+        "The port gives different results than the original library for {} of {inputs} random \
+        inputs:\n",
+        discrepancies.len()
+    );
+    for discrepancy in discrepancies.iter().take(MAX_REPORTED_DISCREPANCIES) {
+        report += &format!(
+            "\ninput: {:?}\noriginal: {}\nport: {}\n",
+            discrepancy.input, discrepancy.original, discrepancy.ported
+        );
+    }
+    if discrepancies.len() > MAX_REPORTED_DISCREPANCIES {
+        report += &format!(
+            "\n{} more inputs give different results.\n",
+            discrepancies.len() - MAX_REPORTED_DISCREPANCIES
+        );
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn inputs() {
+        let inputs = random_inputs(7, 100, 8);
+        assert_eq!(inputs.len(), 100);
+        assert!(inputs.iter().all(|input| input.len() <= 8));
+        assert_eq!(inputs[..2], [vec![], vec![0]]);
+        assert_eq!(inputs, random_inputs(7, 100, 8));
+        assert_ne!(inputs, random_inputs(8, 100, 8));
+        assert_eq!(random_inputs(7, 3, 8).len(), 3);
+    }
+
+    #[test]
+    fn fuzz() {
+        let dir = TempDir::new("fuzz");
+        let source = dir.join("source");
+        let destination = dir.join("destination");
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::create_dir_all(destination.join("fuzz")).unwrap();
+        std::fs::create_dir_all(destination.join("target/debug/examples")).unwrap();
+        // Both harnesses print the length of the hex string, but the port gets it wrong for
+        // the empty input.
+        std::fs::write(
+            destination.join(ORIGINAL_HARNESS),
+            "test -d \"$RIIR_FUZZ_DIR\" || exit 1\n\
+            while read -r line; do echo ${#line}; done\n",
+        )
+        .unwrap();
+        let ported = destination.join("target/debug/examples/fuzz_harness");
+        std::fs::write(
+            &ported,
+            "#!/bin/sh\nwhile read -r line; do echo $(( ${#line} > 0 ? ${#line} : 1 )); done\n",
+        )
+        .unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&ported, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let config = FuzzConfig {
+            iterations: 50,
+            ..Default::default()
+        };
+        let discrepancies = run_fuzz(&config, &source, &destination).unwrap();
+        assert!(!discrepancies.is_empty());
+        for discrepancy in &discrepancies {
+            assert_eq!(discrepancy.input, "");
+            assert_eq!(discrepancy.original, "0");
+            assert_eq!(discrepancy.ported, "1");
+        }
+        let report = format_discrepancies(&discrepancies, 50);
+        assert!(report.contains(" of 50 random inputs"));
+
+        std::fs::write(destination.join(ORIGINAL_HARNESS), "exit 3\n").unwrap();
+        let error = run_fuzz(&config, &source, &destination).err().unwrap();
+        assert!(error.contains("failed (exit code 3)"));
+    }
+}
@@ -0,0 +1,213 @@
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// The package clause of a Go file.
+static PACKAGE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?m)^package\s+(\w+)").unwrap());
+
+/// How Go concurrency and error handling map to Rust.
+const GUIDANCE: &str = "\
+    Translate goroutines to threads (`std::thread::spawn`, or `std::thread::scope` when they \
+    borrow local data) or to tokio tasks if the code is I/O bound and already async, and channels \
+    to `std::sync::mpsc` or `tokio::sync::mpsc`, with `sync_channel` or a bounded channel for \
+    buffered ones. `select` becomes `tokio::select!` or the `select!` of crossbeam-channel, \
+    `sync.WaitGroup` joining the handles, `sync.Mutex` a `Mutex` owning the data it guards, \
+    `context.Context` cancellation a cancellation token or a channel, and `defer` a `Drop` guard. \
+    Values returned with an `error` become a `Result`, interfaces become traits, and \
+    `panic`/`recover` become errors except for bugs.";
+
+/// Layout of a Go module from its `go.mod` and the package clauses of its files.
+#[derive(Debug, PartialEq)]
+pub struct GoModule {
+    /// import path of the module, e.g. `github.com/foo/bar`
+    pub path: String,
+    /// Go version the module requires
+    pub version: Option<String>,
+    /// packages by directory, `.` for the root
+    pub packages: BTreeMap<String, GoPackage>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct GoPackage {
+    /// name from the package clause, e.g. `main`
+    pub name: String,
+    pub files: Vec<String>,
+    /// `_test.go` files
+    pub tests: Vec<String>,
+}
+
+impl GoModule {
+    /// Reads the module in the root of the source project, if it's a Go module.
+    pub fn load(root: &Path, files: &[String]) -> Option<Self> {
+        let manifest = std::fs::read_to_string(root.join("go.mod")).ok()?;
+        let field = |keyword: &str| {
+            manifest.lines().find_map(|line| {
+                let value = line.trim().strip_prefix(keyword)?;
+                value.starts_with(char::is_whitespace).then(|| {
+                    value
+                        .split_whitespace()
+                        .next()
+                        .unwrap_or_default()
+                        .to_string()
+                })
+            })
+        };
+        let mut module = GoModule {
+            path: field("module")?,
+            version: field("go"),
+            packages: BTreeMap::new(),
+        };
+        for file in files.iter().filter(|file| file.ends_with(".go")) {
+            let dir = match Path::new(file).parent() {
+                Some(dir) if dir != Path::new("") => dir.to_string_lossy().to_string(),
+                _ => ".".to_string(),
+            };
+            // Vendored dependencies and test data aren't part of the module.
+            if dir
+                .split('/')
+                .any(|part| ["vendor", "testdata"].contains(&part))
+            {
+                continue;
+            }
+            let package = module.packages.entry(dir).or_default();
+            if file.ends_with("_test.go") {
+                package.tests.push(file.clone());
+                continue;
+            }
+            if package.name.is_empty()
+                && let Ok(source) = std::fs::read_to_string(root.join(file))
+                && let Some(captures) = PACKAGE.captures(&source)
+            {
+                package.name = captures[1].to_string();
+            }
+            package.files.push(file.clone());
+        }
+        Some(module)
+    }
+
+    /// Describes the module for the planning prompt: where each package goes in the crate, how
+    /// to translate the concurrency and which test files the plan must include.
+    pub fn planning_notes(&self) -> String {
+        let mut notes = format!("The source project is the Go module `{}`", self.path);
+        if let Some(version) = &self.version {
+            notes += &format!(" for Go {version}");
+        }
+        notes += ". Suggested places of its packages in the Rust crate:";
+        for (dir, package) in &self.packages {
+            if package.files.is_empty() {
+                continue;
+            }
+            notes += &format!(
+                "\n- `{dir}` (package {}, {} files) -> `{}`",
+                package.name,
+                package.files.len(),
+                destination(dir, &package.name)
+            );
+        }
+        notes += &format!("\n{GUIDANCE}");
+        let tests: Vec<String> = self
+            .packages
+            .values()
+            .flat_map(|package| &package.tests)
+            .map(|file| format!("`{file}`"))
+            .collect();
+        if !tests.is_empty() {
+            notes += &format!(
+                "\nInclude the test files in the plan, converted with the modules they test: \
+                in-package tests into a `#[cfg(test)] mod tests` of the module and `_test` \
+                packages into `tests/`. Table-driven tests become a loop over the cases, `t.Run` \
+                subtests separate #[test] functions or a loop, `t.Fatal` and `t.Error` \
+                assertions, and benchmarks go to `benches/`. The test files: {}.",
+                tests.join(", ")
+            );
+        }
+        notes
+    }
+}
+
+/// Suggests the Rust file of a package: commands become binaries, the other packages modules
+/// of the library, without the `internal` and `pkg` directories Go needs for the visibility.
+fn destination(dir: &str, name: &str) -> String {
+    if name == "main" {
+        return match dir {
+            "." => "src/main.rs".to_string(),
+            _ => format!("src/bin/{}.rs", dir.rsplit('/').next().unwrap_or(dir)),
+        };
+    }
+    let path: Vec<&str> = dir
+        .split('/')
+        .filter(|part| !["internal", "pkg", "."].contains(part))
+        .map(|part| part.trim_start_matches("go-"))
+        .collect();
+    if path.is_empty() {
+        return "src/lib.rs".to_string();
+    }
+    format!("src/{}.rs", path.join("/").replace('-', "_"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn module() {
+        let dir = TempDir::new("go");
+        for package in ["cmd/tool", "internal/parser", "vendor/x"] {
+            std::fs::create_dir_all(dir.join(package)).unwrap();
+        }
+        std::fs::write(
+            dir.join("go.mod"),
+            "module github.com/foo/bar\n\ngo 1.22\n\nrequire (\n\tgithub.com/spf13/cobra v1.8.0\n)\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("bar.go"), "// Package bar.\npackage bar\n").unwrap();
+        std::fs::write(dir.join("cmd/tool/main.go"), "package main\n").unwrap();
+        std::fs::write(dir.join("internal/parser/parser.go"), "package parser\n").unwrap();
+        std::fs::write(
+            dir.join("internal/parser/parser_test.go"),
+            "package parser\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("vendor/x/x.go"), "package x\n").unwrap();
+        let files: Vec<String> = [
+            "bar.go",
+            "cmd/tool/main.go",
+            "go.mod",
+            "internal/parser/parser.go",
+            "internal/parser/parser_test.go",
+            "vendor/x/x.go",
+        ]
+        .iter()
+        .map(|file| file.to_string())
+        .collect();
+
+        let module = GoModule::load(&dir, &files).unwrap();
+        assert_eq!(module.path, "github.com/foo/bar");
+        assert_eq!(module.version.as_deref(), Some("1.22"));
+        assert_eq!(
+            module.packages.keys().collect::<Vec<_>>(),
+            [".", "cmd/tool", "internal/parser"]
+        );
+        assert_eq!(
+            module.packages["internal/parser"],
+            GoPackage {
+                name: "parser".to_string(),
+                files: vec!["internal/parser/parser.go".to_string()],
+                tests: vec!["internal/parser/parser_test.go".to_string()],
+            }
+        );
+
+        let notes = module.planning_notes();
+        assert!(notes.starts_with(
+            "The source project is the Go module `github.com/foo/bar` for Go 1.22. Suggested \
+            places of its packages in the Rust crate:\n\
+            - `.` (package bar, 1 files) -> `src/lib.rs`\n\
+            - `cmd/tool` (package main, 1 files) -> `src/bin/tool.rs`\n\
+            - `internal/parser` (package parser, 1 files) -> `src/parser.rs`\n"
+        ));
+        assert!(notes.ends_with("The test files: `internal/parser/parser_test.go`."));
+        assert!(GoModule::load(&dir.join("cmd"), &files).is_none());
+    }
+}
@@ -0,0 +1,145 @@
+use crate::cargo::Diagnostic;
+
+/// Kinds of compiler errors that call for different fixing strategies.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorClass {
+    UnresolvedName,
+    TypeMismatch,
+    MissingTrait,
+    Borrow,
+    Lifetime,
+}
+
+/// Codes of the errors of each class.
+const CLASSES: &[(ErrorClass, &[&str])] = &[
+    (
+        ErrorClass::UnresolvedName,
+        &[
+            "E0405", "E0407", "E0412", "E0422", "E0423", "E0425", "E0432", "E0433", "E0583",
+        ],
+    ),
+    (
+        ErrorClass::TypeMismatch,
+        &[
+            "E0061", "E0063", "E0308", "E0560", "E0605", "E0606", "E0614",
+        ],
+    ),
+    (
+        ErrorClass::MissingTrait,
+        &["E0046", "E0277", "E0369", "E0599", "E0600"],
+    ),
+    (
+        ErrorClass::Borrow,
+        &[
+            "E0382", "E0384", "E0499", "E0502", "E0505", "E0506", "E0507", "E0594", "E0596",
+        ],
+    ),
+    (
+        ErrorClass::Lifetime,
+        &[
+            "E0106", "E0495", "E0515", "E0597", "E0621", "E0700", "E0716", "E0759",
+        ],
+    ),
+];
+
+impl ErrorClass {
+    /// Classifies an error by its code. Errors like "lifetime may not live long enough" have no
+    /// code and are recognized by the message.
+    pub fn of(diagnostic: &Diagnostic) -> Option<Self> {
+        if !diagnostic.is_error() {
+            return None;
+        }
+        let Some(code) = diagnostic.code.as_deref() else {
+            return diagnostic
+                .message
+                .contains("lifetime")
+                .then_some(ErrorClass::Lifetime);
+        };
+        CLASSES
+            .iter()
+            .find(|(_, codes)| codes.contains(&code))
+            .map(|(class, _)| *class)
+    }
+
+    /// A short strategy for fixing errors of the class.
+    pub fn hint(self) -> &'static str {
+        match self {
+            ErrorClass::UnresolvedName => {
+                "Unresolved names: check the `use` paths and the `mod` declarations, and that the \
+                item exists and is public. Fix these first, they cause many of the other errors."
+            }
+            ErrorClass::TypeMismatch => {
+                "Type mismatches: read the expected and the found type, then convert explicitly \
+                (`.into()`, `as`, `&`/`*`, `.as_str()`, `Some(..)`) or change the signature; \
+                don't add casts at random."
+            }
+            ErrorClass::MissingTrait => {
+                "Missing trait implementations or methods: derive the trait (`Clone`, `Debug`, \
+                `PartialEq`, ...), implement it, add a trait bound to the generic parameter, or \
+                import the trait that provides the method."
+            }
+            ErrorClass::Borrow => {
+                "Borrow checker errors: shorten borrows by moving them into a smaller scope, \
+                clone cheap values, take ownership instead of borrowing, or restructure the code \
+                to avoid holding a reference while mutating. Don't add `unsafe`."
+            }
+            ErrorClass::Lifetime => {
+                "Lifetime errors: prefer returning owned values (`String`, `Vec`) over references; \
+                if a reference is needed, tie it to the input with a named lifetime. Don't reach \
+                for `'static` or `Box::leak`."
+            }
+        }
+    }
+}
+
+/// Returns the hints for the classes of the errors, one per class in the order they first occur,
+/// or an empty string if none apply.
+pub fn error_hints(diagnostics: &[&Diagnostic]) -> String {
+    let mut classes: Vec<ErrorClass> = vec![];
+    for diagnostic in diagnostics {
+        if let Some(class) = ErrorClass::of(diagnostic)
+            && !classes.contains(&class)
+        {
+            classes.push(class);
+        }
+    }
+    if classes.is_empty() {
+        return String::new();
+    }
+    let mut hints = "Hints:\n".to_string();
+    for class in classes {
+        hints += &format!("- {}\n", class.hint());
+    }
+    hints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify() {
+        let error = |code: Option<&str>, message: &str| Diagnostic {
+            level: "error".to_string(),
+            code: code.map(str::to_string),
+            message: message.to_string(),
+            file: Some("src/lib.rs".to_string()),
+            line: Some(1),
+            column: Some(1),
+            rendered: String::new(),
+            member: None,
+        };
+        let borrow = error(Some("E0502"), "cannot borrow `v` as mutable");
+        let lifetime = error(None, "lifetime may not live long enough");
+        let mismatch = error(Some("E0308"), "mismatched types");
+        let syntax = error(None, "expected one of `;` or `}`");
+        assert_eq!(ErrorClass::of(&borrow), Some(ErrorClass::Borrow));
+        assert_eq!(ErrorClass::of(&lifetime), Some(ErrorClass::Lifetime));
+        assert_eq!(ErrorClass::of(&syntax), None);
+
+        let hints = error_hints(&[&borrow, &mismatch, &borrow, &syntax]);
+        assert!(hints.starts_with("Hints:\n- Borrow checker errors"));
+        assert_eq!(hints.lines().count(), 3);
+        assert_eq!(error_hints(&[&syntax]), "");
+    }
+}
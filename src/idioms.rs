@@ -0,0 +1,108 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// A loop over the indices of a collection, e.g. `for i in 0..items.len()`.
+static INDEX_LOOP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"\bfor\s+\w+\s+in\s+0\s*\.\.\s*([\w.]+)\.len\(\)|\bwhile\s+\w+\s*<\s*([\w.]+)\.len\(\)",
+    )
+    .unwrap()
+});
+/// An error that is only a message, e.g. `Result<Config, String>` or `Err(format!(...))`.
+static STRING_ERROR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"Result<.*,\s*String>|\bErr\((format!|"[^"]*"\.(to_string|to_owned|into)\(\)|String::from)"#)
+        .unwrap()
+});
+
+/// How many `.clone()` calls in a file hint at ownership copied from a garbage-collected
+/// language rather than designed.
+const MAX_CLONES: usize = 8;
+
+/// Finds the patterns of a Rust file that look transliterated from the original language: index
+/// loops, clones everywhere and errors that are only strings. Each finding is described for the
+/// model with the line it's on.
+pub fn transliterated_patterns(source: &str) -> Vec<String> {
+    let mut findings = vec![];
+    let mut string_errors = vec![];
+    let mut clones = 0;
+    for (number, line) in source.lines().enumerate() {
+        let code = line.trim_start();
+        if code.starts_with("//") {
+            continue;
+        }
+        if let Some(captures) = INDEX_LOOP.captures(code) {
+            let collection = captures.get(1).or(captures.get(2)).unwrap().as_str();
+            findings.push(format!(
+                "line {}: index loop over `{collection}`; iterate over it, with `enumerate` if \
+                the index is needed, or use `zip`, `windows` or `chunks`",
+                number + 1
+            ));
+        }
+        if STRING_ERROR.is_match(code) {
+            string_errors.push((number + 1).to_string());
+        }
+        clones += code.matches(".clone()").count();
+    }
+    if !string_errors.is_empty() {
+        findings.push(format!(
+            "lines {}: errors that are only strings; define an error enum with a variant per \
+            failure, implementing `std::error::Error`, and match on it instead of on messages",
+            string_errors.join(", ")
+        ));
+    }
+    if clones > MAX_CLONES {
+        findings.push(format!(
+            "{clones} calls of `.clone()`; borrow where the value isn't kept, move it where it's \
+            used last, and share it with `Rc` or `Arc` only where ownership is really shared"
+        ));
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns() {
+        let clones = "    let a = b.clone();\n".repeat(MAX_CLONES + 1);
+        let source = format!(
+            r#"fn parse(items: &Vec<String>) -> Result<Vec<u32>, String> {{
+    let mut numbers = vec![];
+    // for i in 0..items.len() is the original loop
+    for i in 0..items.len() {{
+        numbers.push(items[i].parse().map_err(|_| "not a number".to_string())?);
+    }}
+    if numbers.is_empty() {{
+        return Err(format!("no numbers in {{}} items", items.len()));
+    }}
+    let mut j = 0;
+    while j < self.values.len() {{
+        j += 1;
+    }}
+{clones}    Ok(numbers)
+}}
+"#
+        );
+        assert_eq!(
+            transliterated_patterns(&source),
+            [
+                "line 4: index loop over `items`; iterate over it, with `enumerate` if the index \
+                is needed, or use `zip`, `windows` or `chunks`",
+                "line 11: index loop over `self.values`; iterate over it, with `enumerate` if the \
+                index is needed, or use `zip`, `windows` or `chunks`",
+                "lines 1, 8: errors that are only strings; define an error enum with a variant \
+                per failure, implementing `std::error::Error`, and match on it instead of on \
+                messages",
+                "9 calls of `.clone()`; borrow where the value isn't kept, move it where it's \
+                used last, and share it with `Rc` or `Arc` only where ownership is really shared",
+            ]
+        );
+        assert!(
+            transliterated_patterns(
+                "fn sum(values: &[u32]) -> u32 {\n    values.iter().sum()\n}\n"
+            )
+            .is_empty()
+        );
+    }
+}
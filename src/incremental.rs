@@ -0,0 +1,94 @@
+use crate::hash::stable_hash;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Hashes of the source files at the time of the last successful conversion, used to convert
+/// only what changed on the next run against the same destination.
+pub struct SourceHashes {
+    path: PathBuf,
+    hashes: BTreeMap<String, u64>,
+}
+
+impl SourceHashes {
+    /// Loads the hashes from the file, starting with no files if it doesn't exist.
+    pub fn load(path: PathBuf) -> Self {
+        let hashes = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        SourceHashes { path, hashes }
+    }
+
+    /// Whether a conversion finished before.
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// Returns the files that were added, changed or deleted since the hashes were saved.
+    pub fn changed(&self, root: &Path, files: &[String]) -> Vec<String> {
+        let mut changed: Vec<String> = files
+            .iter()
+            .filter(|file| {
+                let current = std::fs::read(root.join(file))
+                    .ok()
+                    .map(|data| stable_hash(&data));
+                current != self.hashes.get(*file).copied()
+            })
+            .cloned()
+            .collect();
+        changed.extend(
+            self.hashes
+                .keys()
+                .filter(|file| !files.contains(file))
+                .cloned(),
+        );
+        changed
+    }
+
+    /// Replaces the hashes with the ones of the current contents of the files.
+    pub fn save(&mut self, root: &Path, files: &[String]) -> Result<(), String> {
+        self.hashes = files
+            .iter()
+            .filter_map(|file| {
+                let data = std::fs::read(root.join(file)).ok()?;
+                Some((file.clone(), stable_hash(&data)))
+            })
+            .collect();
+        let contents = serde_json::to_string_pretty(&self.hashes).map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, contents)
+            .map_err(|e| format!("Cannot write {}: {e}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn changed_files() {
+        let dir = TempDir::new("hashes");
+        std::fs::write(dir.join("main.c"), "int main() {}\n").unwrap();
+        std::fs::write(dir.join("util.c"), "int f() {}\n").unwrap();
+        let files =
+            |names: &[&str]| -> Vec<String> { names.iter().map(|n| n.to_string()).collect() };
+
+        let mut hashes = SourceHashes::load(dir.join("hashes.json"));
+        assert!(hashes.is_empty());
+        hashes.save(&dir, &files(&["main.c", "util.c"])).unwrap();
+        assert!(
+            hashes
+                .changed(&dir, &files(&["main.c", "util.c"]))
+                .is_empty()
+        );
+
+        std::fs::write(dir.join("util.c"), "int f() { return 1; }\n").unwrap();
+        std::fs::write(dir.join("extra.c"), "int g() {}\n").unwrap();
+        let hashes = SourceHashes::load(dir.join("hashes.json"));
+        assert!(!hashes.is_empty());
+        assert_eq!(
+            hashes.changed(&dir, &files(&["extra.c", "util.c"])),
+            ["extra.c", "util.c", "main.c"]
+        );
+    }
+}
@@ -0,0 +1,351 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use syn::spanned::Spanned;
+use syn::{Attribute, ImplItem, Item, TraitItem, Visibility};
+
+#[derive(Deserialize, JsonSchema)]
+pub struct RustItemsArgs {
+    /// a relative path to a Rust file in the project directory
+    pub path: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RustItemsResult {
+    pub error: Option<String>,
+    /// public items of the file, including the methods of impl blocks and traits
+    pub items: Vec<RustItem>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct RustItem {
+    /// "fn", "struct", "enum", "trait", "impl", "method", "mod", "macro", etc.
+    pub kind: String,
+    /// item name, methods are prefixed with the type or trait name, e.g. "Parser::parse"
+    pub name: String,
+    pub line: usize,
+    /// the declaration without the body; structs and enums include their fields and variants
+    pub signature: String,
+}
+
+/// Parses Rust source code and lists its public items.
+pub fn rust_items(source: &str) -> RustItemsResult {
+    parse_items(source, false)
+}
+
+/// Parses Rust source code and lists all its items, private ones included.
+pub fn all_rust_items(source: &str) -> RustItemsResult {
+    parse_items(source, true)
+}
+
+fn parse_items(source: &str, private: bool) -> RustItemsResult {
+    let result = match syn::parse_file(source) {
+        Ok(file) => {
+            let mut items = vec![];
+            collect_items(source, &file.items, "", private, &mut items);
+            RustItemsResult { error: None, items }
+        }
+        Err(e) => RustItemsResult {
+            error: Some(format!(
+                "Cannot parse the file at line {}: {e}",
+                e.span().start().line
+            )),
+            items: vec![],
+        },
+    };
+    // Spans of the parsed files are kept per thread until they are invalidated.
+    proc_macro2::extra::invalidate_current_thread_spans();
+    result
+}
+
+fn collect_items(
+    source: &str,
+    items: &[Item],
+    prefix: &str,
+    private: bool,
+    result: &mut Vec<RustItem>,
+) {
+    let visible = |vis: &Visibility| private || is_public(vis);
+    let mut add = |kind: &str, name: String, span: proc_macro2::Span, signature: String| {
+        result.push(RustItem {
+            kind: kind.to_string(),
+            name: format!("{prefix}{name}"),
+            line: span.start().line,
+            signature,
+        });
+    };
+    let mut modules = vec![];
+    for item in items {
+        match item {
+            Item::Fn(f) if visible(&f.vis) => {
+                let signature = text(source, f.vis.span(), f.sig.span());
+                add("fn", f.sig.ident.to_string(), f.sig.span(), signature);
+            }
+            Item::Struct(s) if visible(&s.vis) => {
+                add(
+                    "struct",
+                    s.ident.to_string(),
+                    s.ident.span(),
+                    item_text(source, item, &s.attrs),
+                );
+            }
+            Item::Enum(e) if visible(&e.vis) => {
+                add(
+                    "enum",
+                    e.ident.to_string(),
+                    e.ident.span(),
+                    item_text(source, item, &e.attrs),
+                );
+            }
+            Item::Union(u) if visible(&u.vis) => {
+                add(
+                    "union",
+                    u.ident.to_string(),
+                    u.ident.span(),
+                    item_text(source, item, &u.attrs),
+                );
+            }
+            Item::Type(t) if visible(&t.vis) => {
+                add(
+                    "type",
+                    t.ident.to_string(),
+                    t.ident.span(),
+                    item_text(source, item, &t.attrs),
+                );
+            }
+            Item::Const(c) if visible(&c.vis) => {
+                add(
+                    "const",
+                    c.ident.to_string(),
+                    c.ident.span(),
+                    item_text(source, item, &c.attrs),
+                );
+            }
+            Item::Static(s) if visible(&s.vis) => {
+                add(
+                    "static",
+                    s.ident.to_string(),
+                    s.ident.span(),
+                    item_text(source, item, &s.attrs),
+                );
+            }
+            Item::Use(u) if visible(&u.vis) => {
+                add(
+                    "use",
+                    String::new(),
+                    u.span(),
+                    item_text(source, item, &u.attrs),
+                );
+            }
+            Item::Macro(m) if m.attrs.iter().any(|a| a.path().is_ident("macro_export")) => {
+                if let Some(ident) = &m.ident {
+                    add(
+                        "macro",
+                        ident.to_string(),
+                        ident.span(),
+                        format!("macro_rules! {ident}"),
+                    );
+                }
+            }
+            Item::Mod(m) if visible(&m.vis) => {
+                let name = m.ident.to_string();
+                match &m.content {
+                    Some((brace, _)) => {
+                        let signature = text(source, m.vis.span(), brace.span.open());
+                        add("mod", name.clone(), m.ident.span(), strip_body(&signature));
+                        modules.push((name, m));
+                    }
+                    None => add(
+                        "mod",
+                        name,
+                        m.ident.span(),
+                        item_text(source, item, &m.attrs),
+                    ),
+                }
+            }
+            Item::Trait(t) if visible(&t.vis) => {
+                let name = t.ident.to_string();
+                let header = text(source, t.vis.span(), t.brace_token.span.open());
+                add("trait", name.clone(), t.ident.span(), strip_body(&header));
+                for trait_item in &t.items {
+                    if let TraitItem::Fn(f) = trait_item {
+                        let signature = text(source, f.sig.span(), f.sig.span());
+                        add(
+                            "method",
+                            format!("{name}::{}", f.sig.ident),
+                            f.sig.span(),
+                            signature,
+                        );
+                    }
+                }
+            }
+            Item::Impl(i) => {
+                let header =
+                    strip_body(&text(source, i.impl_token.span, i.brace_token.span.open()));
+                let self_type = text(source, i.self_ty.span(), i.self_ty.span());
+                // Methods are named after the type without its generic parameters.
+                let self_type = self_type
+                    .split('<')
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string();
+                if i.trait_.is_some() {
+                    add("impl", self_type.clone(), i.impl_token.span, header);
+                    // Methods of trait implementations are as public as the trait itself.
+                    if !private {
+                        continue;
+                    }
+                }
+                for impl_item in &i.items {
+                    if let ImplItem::Fn(f) = impl_item
+                        && visible(&f.vis)
+                    {
+                        let signature = text(source, f.vis.span(), f.sig.span());
+                        add(
+                            "method",
+                            format!("{self_type}::{}", f.sig.ident),
+                            f.sig.span(),
+                            signature,
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    for (name, module) in modules {
+        if let Some((_, items)) = &module.content {
+            collect_items(source, items, &format!("{prefix}{name}::"), private, result);
+        }
+    }
+}
+
+fn is_public(vis: &Visibility) -> bool {
+    !matches!(vis, Visibility::Inherited)
+}
+
+/// Source text from the start of one span to the end of another.
+fn text(source: &str, from: proc_macro2::Span, to: proc_macro2::Span) -> String {
+    let range = from.byte_range().start..to.byte_range().end;
+    source.get(range).unwrap_or_default().trim().to_string()
+}
+
+/// Source text of a whole item without its attributes and doc comments.
+fn item_text(source: &str, item: &Item, attrs: &[Attribute]) -> String {
+    let range = item.span().byte_range();
+    let start = attrs
+        .iter()
+        .map(|attr| attr.span().byte_range().end)
+        .max()
+        .unwrap_or(range.start)
+        .max(range.start);
+    source
+        .get(start..range.end)
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// Removes the opening brace at the end of a header.
+fn strip_body(header: &str) -> String {
+    header.trim_end_matches('{').trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn items() {
+        let source = r#"
+use std::fmt;
+pub use crate::error::Error;
+
+/// A parser.
+#[derive(Debug)]
+pub struct Parser<'a> {
+    pub input: &'a str,
+    pos: usize,
+}
+
+struct Private;
+
+impl<'a> Parser<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Parser { input, pos: 0 }
+    }
+
+    fn advance(&mut self) {}
+}
+
+impl fmt::Display for Parser<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Ok(())
+    }
+}
+
+pub trait Visit: Sized {
+    fn visit(&mut self, node: &Node) -> bool;
+}
+
+pub mod util {
+    pub(crate) fn helper<T: Clone>(value: T) -> T where T: Copy { value }
+}
+"#;
+        let result = rust_items(source);
+        assert!(result.error.is_none());
+        let summary: Vec<_> = result
+            .items
+            .iter()
+            .map(|i| {
+                (
+                    i.kind.as_str(),
+                    i.name.as_str(),
+                    i.line,
+                    i.signature.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("use", "", 3, "pub use crate::error::Error;"),
+                (
+                    "struct",
+                    "Parser",
+                    7,
+                    "pub struct Parser<'a> {\n    pub input: &'a str,\n    pos: usize,\n}"
+                ),
+                (
+                    "method",
+                    "Parser::new",
+                    15,
+                    "pub fn new(input: &'a str) -> Self"
+                ),
+                ("impl", "Parser", 22, "impl fmt::Display for Parser<'_>"),
+                ("trait", "Visit", 28, "pub trait Visit: Sized"),
+                (
+                    "method",
+                    "Visit::visit",
+                    29,
+                    "fn visit(&mut self, node: &Node) -> bool"
+                ),
+                ("mod", "util", 32, "pub mod util"),
+                (
+                    "fn",
+                    "util::helper",
+                    33,
+                    "pub(crate) fn helper<T: Clone>(value: T) -> T where T: Copy"
+                ),
+            ]
+        );
+
+        let result = rust_items("fn main() {\n    let x = ;\n}\n");
+        assert!(
+            result
+                .error
+                .unwrap()
+                .starts_with("Cannot parse the file at line 2")
+        );
+    }
+}
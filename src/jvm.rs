@@ -0,0 +1,252 @@
+use crate::mapping::{SourceDependency, source_dependencies};
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// A `<module>` of a multi-module `pom.xml`.
+static MAVEN_MODULE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<module>\s*([^<\s]+)\s*</module>").unwrap());
+/// An `include` of `settings.gradle(.kts)`, e.g. `include(":core", ":app")`.
+static GRADLE_INCLUDE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^\s*include\b(.*)$").unwrap());
+static GRADLE_PROJECT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"["']:?([\w\-.:/]+)["']"#).unwrap());
+/// The package declaration of a Java or Kotlin file.
+static PACKAGE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^\s*package\s+([\w.]+)").unwrap());
+
+/// How the JVM idioms map to Rust, for all the phases of the conversion.
+pub const GUIDANCE: &str = " The source project runs on the JVM. Translate checked and unchecked \
+    exceptions to `Result` with an error enum per module (`thiserror` if it's a dependency), \
+    `null` to `Option`, class inheritance to traits with default methods or to composition, \
+    abstract classes and interfaces to traits, static members to module-level items, \
+    overloaded methods to differently named functions or a trait, and getters and setters to \
+    public fields where they add nothing. Collections map to `Vec`, `HashMap` and `HashSet`, \
+    `synchronized` to a `Mutex` owning the data, and `try-with-resources` to `Drop`.";
+
+/// Layout of a Maven or Gradle project.
+#[derive(Debug, PartialEq)]
+pub struct JvmProject {
+    /// "Maven" or "Gradle"
+    pub build: &'static str,
+    /// directories of the modules of a multi-module build
+    pub modules: Vec<String>,
+    /// number of Java and Kotlin files of each package
+    pub packages: BTreeMap<String, usize>,
+}
+
+impl JvmProject {
+    /// Reads the build files in the root of the source project, if it's a Maven or Gradle
+    /// project.
+    pub fn load(root: &Path, files: &[String]) -> Option<Self> {
+        let read = |name: &str| std::fs::read_to_string(root.join(name)).ok();
+        let mut project = if let Some(pom) = read("pom.xml") {
+            JvmProject {
+                build: "Maven",
+                modules: MAVEN_MODULE
+                    .captures_iter(&pom)
+                    .map(|captures| captures[1].to_string())
+                    .collect(),
+                packages: BTreeMap::new(),
+            }
+        } else if [
+            "build.gradle",
+            "build.gradle.kts",
+            "settings.gradle",
+            "settings.gradle.kts",
+        ]
+        .iter()
+        .any(|name| root.join(name).is_file())
+        {
+            let settings = read("settings.gradle")
+                .or_else(|| read("settings.gradle.kts"))
+                .unwrap_or_default();
+            let mut modules = vec![];
+            for include in GRADLE_INCLUDE.captures_iter(&settings) {
+                for project in GRADLE_PROJECT.captures_iter(&include[1]) {
+                    modules.push(project[1].replace(':', "/"));
+                }
+            }
+            JvmProject {
+                build: "Gradle",
+                modules,
+                packages: BTreeMap::new(),
+            }
+        } else {
+            return None;
+        };
+        for file in files
+            .iter()
+            .filter(|file| [".java", ".kt"].iter().any(|ext| file.ends_with(ext)))
+        {
+            let Ok(source) = std::fs::read_to_string(root.join(file)) else {
+                continue;
+            };
+            let package = PACKAGE
+                .captures(&source)
+                .map(|captures| captures[1].to_string())
+                .unwrap_or_default();
+            *project.packages.entry(package).or_default() += 1;
+        }
+        Some(project)
+    }
+
+    /// Collects the dependencies declared in the build files of the modules, which the
+    /// manifests in the root don't list.
+    pub fn module_dependencies(&self, root: &Path) -> Vec<SourceDependency> {
+        let mut dependencies: Vec<SourceDependency> = vec![];
+        for module in &self.modules {
+            for dependency in source_dependencies(&root.join(module)) {
+                if !dependencies.iter().any(|d| d.name == dependency.name) {
+                    dependencies.push(dependency);
+                }
+            }
+        }
+        dependencies
+    }
+
+    /// Describes the project for the planning prompt: the modules of the build and the Rust
+    /// module of each package.
+    pub fn planning_notes(&self) -> String {
+        let files: usize = self.packages.values().sum();
+        let mut notes = format!(
+            "The source project is built with {} and has {files} Java and Kotlin files.",
+            self.build
+        );
+        if !self.modules.is_empty() {
+            let modules: Vec<String> = self
+                .modules
+                .iter()
+                .map(|module| format!("`{module}`"))
+                .collect();
+            notes += &format!(
+                " Its build modules are {}; make each one a crate of a Cargo workspace in the \
+                directory of the same name, with path dependencies between them.",
+                modules.join(", ")
+            );
+        }
+        let prefix = common_prefix(self.packages.keys().map(String::as_str));
+        if prefix.is_empty() {
+            notes += " Suggested Rust modules of its packages:";
+        } else {
+            notes += &format!(
+                " Suggested Rust modules of its packages, without the common prefix `{prefix}`:"
+            );
+        }
+        for (package, count) in &self.packages {
+            let path = package
+                .strip_prefix(&prefix)
+                .unwrap_or(package)
+                .trim_start_matches('.')
+                .replace('.', "::");
+            let module = if path.is_empty() {
+                "the crate root".to_string()
+            } else {
+                format!("`{path}`")
+            };
+            let package = if package.is_empty() {
+                "the default package"
+            } else {
+                package
+            };
+            notes += &format!("\n- `{package}` ({count} files) -> {module}");
+        }
+        notes
+            + "\nPut each class into the module of its package, in a file of its own if it's \
+            large."
+    }
+}
+
+/// The package prefix shared by all the packages, e.g. `com.example`.
+fn common_prefix<'a>(mut packages: impl Iterator<Item = &'a str>) -> String {
+    let Some(first) = packages.next() else {
+        return String::new();
+    };
+    let mut prefix: Vec<&str> = first.split('.').collect();
+    for package in packages {
+        let shared = prefix
+            .iter()
+            .zip(package.split('.'))
+            .take_while(|(a, b)| *a == b)
+            .count();
+        prefix.truncate(shared);
+    }
+    prefix.join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn maven_project() {
+        let dir = TempDir::new("jvm");
+        let core = dir.join("core/src/main/java/com/example/core");
+        std::fs::create_dir_all(core.join("util")).unwrap();
+        std::fs::create_dir_all(dir.join("app")).unwrap();
+        std::fs::write(
+            dir.join("pom.xml"),
+            "<project><modules>\n<module>core</module>\n<module>app</module>\n</modules></project>",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("core/pom.xml"),
+            "<project><dependencies><dependency><groupId>com.google.code.gson</groupId>\
+            <artifactId>gson</artifactId></dependency></dependencies></project>",
+        )
+        .unwrap();
+        std::fs::write(
+            core.join("Parser.java"),
+            "package com.example.core;\n\nclass Parser {}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            core.join("util/Strings.kt"),
+            "package com.example.core.util\n\nobject Strings\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("app/Main.java"), "package com.example.app;\n").unwrap();
+        let files: Vec<String> = [
+            "app/Main.java",
+            "core/pom.xml",
+            "core/src/main/java/com/example/core/Parser.java",
+            "core/src/main/java/com/example/core/util/Strings.kt",
+            "pom.xml",
+        ]
+        .iter()
+        .map(|file| file.to_string())
+        .collect();
+
+        let project = JvmProject::load(&dir, &files).unwrap();
+        assert_eq!(project.build, "Maven");
+        assert_eq!(project.modules, ["core", "app"]);
+        let dependencies = project.module_dependencies(&dir);
+        assert_eq!(dependencies.len(), 1);
+        assert_eq!(dependencies[0].name, "gson");
+        assert_eq!(
+            project.planning_notes(),
+            "The source project is built with Maven and has 3 Java and Kotlin files. Its build \
+            modules are `core`, `app`; make each one a crate of a Cargo workspace in the \
+            directory of the same name, with path dependencies between them. Suggested Rust \
+            modules of its packages, without the common prefix `com.example`:\n\
+            - `com.example.app` (1 files) -> `app`\n\
+            - `com.example.core` (1 files) -> `core`\n\
+            - `com.example.core.util` (1 files) -> `core::util`\n\
+            Put each class into the module of its package, in a file of its own if it's large."
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("settings.gradle.kts"),
+            "rootProject.name = \"demo\"\ninclude(\":core\", \":tools:cli\")\n",
+        )
+        .unwrap();
+        let project = JvmProject::load(&dir, &[]).unwrap();
+        assert_eq!(project.build, "Gradle");
+        assert_eq!(project.modules, ["core", "tools/cli"]);
+        assert!(JvmProject::load(&dir.join("core"), &[]).is_none());
+    }
+}
@@ -0,0 +1,118 @@
+use crate::text::is_binary;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Languages recognized by the file extension.
+const EXTENSIONS: &[(&str, &[&str])] = &[
+    ("C", &["c", "h"]),
+    ("C++", &["cc", "cpp", "cxx", "hh", "hpp", "hxx"]),
+    ("C#", &["cs"]),
+    ("Go", &["go"]),
+    ("Java", &["java"]),
+    ("Kotlin", &["kt", "kts"]),
+    ("JavaScript", &["js", "mjs", "cjs", "jsx"]),
+    ("TypeScript", &["ts", "tsx"]),
+    ("Python", &["py", "pyi"]),
+    ("Ruby", &["rb"]),
+    ("Rust", &["rs"]),
+    ("Swift", &["swift"]),
+    ("Shell", &["sh", "bash"]),
+    ("Assembly", &["s", "asm"]),
+    ("Makefile", &["mk"]),
+    ("CMake", &["cmake"]),
+    ("TOML", &["toml"]),
+    ("JSON", &["json"]),
+    ("YAML", &["yml", "yaml"]),
+    ("Markdown", &["md"]),
+];
+
+/// Guesses the language of a file from its name.
+pub fn detect_language(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?;
+    match name {
+        "Makefile" | "makefile" | "GNUmakefile" => return Some("Makefile"),
+        "CMakeLists.txt" => return Some("CMake"),
+        _ => {}
+    }
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    EXTENSIONS
+        .iter()
+        .find(|(_, extensions)| extensions.contains(&extension.as_str()))
+        .map(|(language, _)| *language)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LanguageStatsResult {
+    /// statistics per language, the largest first
+    pub languages: Vec<LanguageStats>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct LanguageStats {
+    /// language name, or "Other" for unrecognized text files
+    pub language: String,
+    pub files: usize,
+    pub lines: usize,
+    pub blank_lines: usize,
+}
+
+/// Counts files and lines per language, skipping binary files.
+///
+/// `files` are paths relative to `root`.
+pub fn language_stats(root: &Path, files: &[String]) -> LanguageStatsResult {
+    let mut stats: HashMap<&str, LanguageStats> = HashMap::new();
+    for file in files {
+        let Ok(data) = std::fs::read(root.join(file)) else {
+            continue;
+        };
+        if is_binary(&data) {
+            continue;
+        }
+        let language = detect_language(Path::new(file)).unwrap_or("Other");
+        let entry = stats.entry(language).or_insert_with(|| LanguageStats {
+            language: language.to_string(),
+            ..Default::default()
+        });
+        let text = String::from_utf8_lossy(&data);
+        entry.files += 1;
+        entry.lines += text.lines().count();
+        entry.blank_lines += text.lines().filter(|line| line.trim().is_empty()).count();
+    }
+
+    let mut languages: Vec<_> = stats.into_values().collect();
+    languages.sort_by(|a, b| b.lines.cmp(&a.lines).then(a.language.cmp(&b.language)));
+    LanguageStatsResult { languages }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn languages() {
+        assert_eq!(detect_language(Path::new("src/main.c")), Some("C"));
+        assert_eq!(detect_language(Path::new("lib/Foo.HPP")), Some("C++"));
+        assert_eq!(detect_language(Path::new("Makefile")), Some("Makefile"));
+        assert_eq!(detect_language(Path::new("README")), None);
+    }
+
+    #[test]
+    fn stats() {
+        let dir = TempDir::new("stats");
+        std::fs::write(dir.join("a.c"), "int a;\n\nint b;\n").unwrap();
+        std::fs::write(dir.join("b.h"), "int a;\n").unwrap();
+        std::fs::write(dir.join("run.py"), "import os\n").unwrap();
+        std::fs::write(dir.join("data.bin"), b"\0\0").unwrap();
+        let files = ["a.c", "b.h", "run.py", "data.bin"].map(String::from);
+
+        let result = language_stats(&dir, &files);
+        let summary: Vec<_> = result
+            .languages
+            .iter()
+            .map(|s| (s.language.as_str(), s.files, s.lines, s.blank_lines))
+            .collect();
+        assert_eq!(summary, [("C", 2, 4, 1), ("Python", 1, 1, 0)]);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::config::LayoutConfig;
+use crate::plan::PlanModuleArgs;
+use crate::search::build_globs;
+use globset::{Glob, GlobMatcher, GlobSet};
+use std::path::Path;
+
+/// Rust files that don't correspond to a particular source file.
+const ROOT_FILES: &[&str] = &["main.rs", "lib.rs", "mod.rs", "build.rs"];
+
+#[derive(Clone)]
+struct LayoutRule {
+    source: GlobMatcher,
+    /// literal directory the source pattern starts with, e.g. `src/` for `src/**/*.py`
+    prefix: String,
+    /// destination with the placeholders, e.g. `src/{path}.rs`
+    template: String,
+    /// files the template can produce, e.g. `src/**/*.rs`
+    destinations: GlobMatcher,
+}
+
+/// Rules from the configuration deciding where each ported file goes in the destination
+/// project. The plan and the writes of Rust files are checked against them.
+#[derive(Clone)]
+pub struct Layout {
+    rules: Vec<LayoutRule>,
+    /// Rust files allowed besides the destinations of the rules
+    allow: GlobSet,
+    description: String,
+}
+
+impl Layout {
+    pub fn new(config: &LayoutConfig) -> Result<Self, String> {
+        let matcher = |pattern: &str| {
+            Glob::new(pattern)
+                .map(|glob| glob.compile_matcher())
+                .map_err(|e| format!("Invalid glob {pattern}: {e}"))
+        };
+        let mut rules = vec![];
+        let mut description = vec![];
+        for rule in &config.rules {
+            let template = if rule.destination.ends_with('/') {
+                format!("{}{{path}}.rs", rule.destination)
+            } else {
+                rule.destination.clone()
+            };
+            let literal = rule
+                .source
+                .find(['*', '?', '[', '{'])
+                .map_or(rule.source.as_str(), |end| &rule.source[..end]);
+            let prefix = match literal.rfind('/') {
+                Some(end) => literal[..=end].to_string(),
+                None => String::new(),
+            };
+            rules.push(LayoutRule {
+                source: matcher(&rule.source)?,
+                prefix,
+                destinations: matcher(&template.replace("{path}", "**/*").replace("{name}", "*"))?,
+                template,
+            });
+            description.push(format!("`{}` -> `{}`", rule.source, rule.destination));
+        }
+        Ok(Layout {
+            rules,
+            allow: build_globs(&config.allow)?,
+            description: description.join(", "),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Explains the rules to the model.
+    pub fn describe(&self) -> String {
+        format!(
+            " Place the ported files according to these layout rules, where `{{path}}` is the \
+            path of the source file after the directory of the pattern and `{{name}}` its name, \
+            both without the extension and in snake case: {}.",
+            self.description
+        )
+    }
+
+    /// Returns where the rules put a source file, if any rule matches it.
+    pub fn destination(&self, source: &str) -> Option<String> {
+        let rule = self
+            .rules
+            .iter()
+            .find(|rule| rule.source.is_match(source))?;
+        let relative = source.strip_prefix(&rule.prefix).unwrap_or(source);
+        let relative = Path::new(relative).with_extension("");
+        let path: Vec<String> = relative
+            .iter()
+            .map(|part| snake_case(&part.to_string_lossy()))
+            .collect();
+        let name = path.last().cloned().unwrap_or_default();
+        Some(
+            rule.template
+                .replace("{path}", &path.join("/"))
+                .replace("{name}", &name),
+        )
+    }
+
+    /// Checks the destinations of the modules of a plan against the rules.
+    pub fn check_plan(&self, modules: &[PlanModuleArgs]) -> Option<String> {
+        let mut mismatches = vec![];
+        for module in modules {
+            for source in &module.source {
+                if let Some(expected) = self.destination(source)
+                    && expected != module.destination
+                {
+                    mismatches.push(format!(
+                        "`{source}` belongs in `{expected}`, not `{}`",
+                        module.destination
+                    ));
+                }
+            }
+        }
+        if mismatches.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "The plan doesn't follow the layout rules: {}. Fix the destinations and write the \
+            plan again.",
+            mismatches.join("; ")
+        ))
+    }
+
+    /// Checks that a Rust file written where the rules put ported files is the destination of a
+    /// source file, the crate root or an allowed file.
+    pub fn check_write(&self, path: &str, sources: &[String]) -> Option<String> {
+        let name = Path::new(path).file_name()?.to_str()?;
+        if !path.ends_with(".rs")
+            || ROOT_FILES.contains(&name)
+            || self.allow.is_match(path)
+            || !self
+                .rules
+                .iter()
+                .any(|rule| rule.destinations.is_match(path))
+            || sources
+                .iter()
+                .any(|source| self.destination(source).as_deref() == Some(path))
+        {
+            return None;
+        }
+        Some(format!(
+            "`{path}` isn't where the layout rules put any source file:{} Write the port of each \
+            source file to its destination.",
+            self.describe()
+        ))
+    }
+}
+
+/// Converts a file name to a Rust module name, e.g. `ParserUtils` or `parser-utils` to
+/// `parser_utils`.
+pub fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        if c.is_uppercase() && previous.is_some_and(|p| p.is_lowercase() || p.is_ascii_digit()) {
+            snake.push('_');
+        }
+        if c.is_alphanumeric() {
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push('_');
+        }
+        previous = Some(c);
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LayoutRule as LayoutRuleConfig;
+    use crate::plan::{Plan, PlanWriteArgs};
+    use crate::testing::TempDir;
+
+    #[test]
+    fn rules() {
+        let config = LayoutConfig {
+            rules: vec![
+                LayoutRuleConfig {
+                    source: "src/**/*.py".to_string(),
+                    destination: "src/{path}.rs".to_string(),
+                },
+                LayoutRuleConfig {
+                    source: "tests/**".to_string(),
+                    destination: "tests/".to_string(),
+                },
+                LayoutRuleConfig {
+                    source: "lib/*.java".to_string(),
+                    destination: "src/jvm/{name}.rs".to_string(),
+                },
+            ],
+            allow: vec!["src/error.rs".to_string()],
+        };
+        let layout = Layout::new(&config).unwrap();
+        assert!(!layout.is_empty());
+        assert_eq!(
+            layout.destination("src/shapes/circle-area.py").as_deref(),
+            Some("src/shapes/circle_area.rs")
+        );
+        assert_eq!(
+            layout.destination("tests/test_shapes.py").as_deref(),
+            Some("tests/test_shapes.rs")
+        );
+        assert_eq!(
+            layout.destination("lib/ParserUtils.java").as_deref(),
+            Some("src/jvm/parser_utils.rs")
+        );
+        assert_eq!(layout.destination("setup.py"), None);
+
+        let module = |source: &str, destination: &str| PlanModuleArgs {
+            source: vec![source.to_string()],
+            destination: destination.to_string(),
+            description: String::new(),
+        };
+        let dir = TempDir::new("layout");
+        let path = dir.join("layout.json");
+        let plan = Plan::load(path.clone()).with_layout(layout);
+        let result = plan.write(PlanWriteArgs {
+            modules: vec![
+                module("src/shapes/circle.py", "src/circle.rs"),
+                module("setup.py", "build.rs"),
+            ],
+            dependencies: vec![],
+        });
+        assert_eq!(
+            result.error.as_deref(),
+            Some(
+                "The plan doesn't follow the layout rules: `src/shapes/circle.py` belongs in \
+                `src/shapes/circle.rs`, not `src/circle.rs`. Fix the destinations and write the \
+                plan again."
+            )
+        );
+        assert!(plan.is_empty());
+
+        let layout = Layout::new(&config).unwrap();
+        let sources = vec!["src/shapes/circle.py".to_string()];
+        assert_eq!(layout.check_write("src/shapes/circle.rs", &sources), None);
+        assert_eq!(layout.check_write("src/lib.rs", &sources), None);
+        assert_eq!(layout.check_write("src/error.rs", &sources), None);
+        assert_eq!(layout.check_write("benches/area.rs", &sources), None);
+        assert_eq!(layout.check_write("Cargo.toml", &sources), None);
+        assert!(
+            layout
+                .check_write("src/circle.rs", &sources)
+                .unwrap()
+                .starts_with("`src/circle.rs` isn't where the layout rules put any source file")
+        );
+    }
+}
@@ -1,41 +1,362 @@
-use crate::function::CallableFunctionList;
-use crate::project::{Project, ReadFileArgs, WriteFileArgs};
+use crate::agents::{Agents, Reviewer, Role};
+use crate::api::{ApiManifest, format_items};
+use crate::audit::format_problems;
+use crate::benchmark::{format_table, run_benchmarks};
+use crate::cargo::{
+    CargoOptions, CargoOutput, CargoRunResult, Diagnostic, affects_build, format_diagnostics,
+    rustc_version,
+};
+use crate::checkpoint::{CHECKPOINT_FILE, Checkpoint, Phase, checkpoint_path};
+use crate::cli::{CliInterface, TEST_FILE, TEST_TARGET, original_env};
+use crate::commands::run_verify_commands;
+use crate::compare::CompareFilesArgs;
+use crate::config::{
+    AuditConfig, BenchmarkConfig, CONFIG_FILE, ClippyConfig, Config, EquivalenceConfig,
+    EscalationStep, FuzzConfig, VerifyConfig, WarningPolicy,
+};
+use crate::conversion::{CONVERSION_FILE, ConversionNotes};
+use crate::coverage::coverage;
+use crate::crates::{CratesSearchArgs, best_crate, crate_version, search_crates};
+use crate::docs::{DocsLookupArgs, ExplainErrorArgs, explain_error, lookup_docs};
+use crate::equivalence::{compare_programs, format_mismatches, run_original};
+use crate::escalation::{Escalation, diagnostics_fingerprint, fingerprint};
+use crate::ffi::{SysCrate, native_files};
+use crate::function::{
+    CallableFunctionList, DispatchError, RetryPolicy, TransientError, add_function,
+};
+use crate::fuzz::{
+    HARNESS_PROMPT, ORIGINAL_HARNESS, PORTED_HARNESS, format_discrepancies, run_fuzz,
+};
+use crate::golang::GoModule;
+use crate::hints::error_hints;
+use crate::idioms::transliterated_patterns;
+use crate::incremental::SourceHashes;
+use crate::items::RustItemsArgs;
+use crate::jvm::JvmProject;
+use crate::layout::Layout;
+use crate::manifest::{EditManifestArgs, ManifestEdit};
+use crate::mapping::{dependencies_section, propose_mappings, source_dependencies};
+use crate::metadata::propagate_metadata;
+use crate::notes::{NoteReadArgs, NoteWriteArgs, Notes};
+use crate::npm::NpmPackage;
+use crate::operator::{AskUserArgs, Operator};
+use crate::plan::{ModuleStatus, Plan, PlanUpdateArgs, PlanWriteArgs};
+use crate::project::{
+    AppendFileArgs, ApplyPatchArgs, CargoAddArgs, CopyAssetArgs, CreateDirArgs, DeleteFileArgs,
+    IgnoreList, Project, ReadFileArgs, ReadFileRangeArgs, ReadFileResult, ReadOnly, RenameFileArgs,
+    Review, StatArgs, WriteFileArgs, WriteFileResult,
+};
+use crate::python::{SHIM_DIR, package_name, python_api, write_shim_scaffolding};
+use crate::redact::Redactor;
+use crate::reference::{ReferenceResult, copy_source, run_reference};
+use crate::report::VerificationReport;
+use crate::search::GrepArgs;
+use crate::semantic::{SemanticIndex, SemanticSearchArgs};
+use crate::session::Session;
+use crate::skeleton::module_skeleton;
+use crate::snapshot::create_snapshot;
+use crate::source::prepare_source;
+use crate::symbols::{FindReferencesArgs, SymbolsArgs};
+use crate::test_suite::{TestSuiteProgress, find_tests};
+use crate::todo::{TodoAddArgs, TodoList, TodoUpdateArgs};
+use crate::tree::TreeArgs;
+use crate::watch::SourceWatcher;
+use crate::written::WrittenFiles;
 use argh::FromArgs;
 use dotenvy::dotenv;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use openai::Credentials;
 use openai::chat::{ChatCompletion, ChatCompletionMessage, ChatCompletionMessageRole};
+use std::collections::BTreeSet;
 use std::env;
-use std::path::PathBuf;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
+mod agents;
+mod api;
+mod audit;
+mod benchmark;
+mod cargo;
+mod checkpoint;
+mod cli;
+mod commands;
+mod compare;
+mod config;
+mod conversion;
+mod coverage;
+mod crates;
+mod deps;
+mod docs;
+mod equivalence;
+mod escalation;
+mod ffi;
 mod function;
+mod fuzz;
+mod golang;
+mod hash;
+mod hints;
+mod idioms;
+mod incremental;
+mod items;
+mod jvm;
+mod language;
+mod layout;
+mod manifest;
+mod mapping;
+mod metadata;
+mod notes;
+mod npm;
+mod operator;
+mod patch;
+mod plan;
+mod process;
 mod project;
+mod python;
+mod redact;
+mod reference;
+mod report;
+mod search;
+mod semantic;
+mod session;
+mod skeleton;
+mod snapshot;
+mod source;
+mod symbols;
+mod test_suite;
+#[cfg(test)]
+mod testing;
+mod text;
+mod todo;
+mod tree;
+mod watch;
+mod written;
+
+/// How many function calls in a row may fail before the conversation is aborted.
+const MAX_DISPATCH_FAILURES: usize = 3;
+
+/// How many times the model is reminded about unfinished tasks before giving up.
+const MAX_TODO_REMINDERS: usize = 3;
+
+/// How many times the model is asked to fix the discrepancies found by fuzzing.
+const MAX_FUZZ_ROUNDS: usize = 5;
+
+/// How many times the model is asked to fix the command line interface of the port.
+const MAX_CLI_ROUNDS: usize = 5;
+
+/// Asks the model for the conversion plan once it has analyzed the source project.
+const PLAN_PROMPT: &str = "\
+    Before writing anything, make a conversion plan and save it with plan_write. List the modules \
+    of the destination project in the order they should be converted, modules used by others \
+    first, each with the source files it's converted from and a short description. Map every \
+    dependency of the source project to the crate replacing it, or to none if the standard \
+    library or the converted code covers it. Don't create any files yet.";
+
+/// Embedding model used unless `EMBEDDING_MODEL` is set.
+const DEFAULT_EMBEDDING_MODEL: &str = "text-embedding-3-small";
 
 #[derive(FromArgs)]
 /// a command line interface for a large language model
+#[argh(
+    note = "Run `riir resume <session>` with the destination project directory or its `.riir` \
+directory to continue an interrupted conversion."
+)]
 struct Args {
-    /// path to the source project directory
+    /// path to the source project directory, a zip or tar archive of it, or a URL of its git repository
     #[argh(positional)]
     source: PathBuf,
 
     /// path to the destination project directory
     #[argh(positional)]
     destination: PathBuf,
+
+    /// path to another source project directory or git repository URL, e.g. a library used by the main one, can be repeated
+    #[argh(option)]
+    extra_source: Vec<PathBuf>,
+
+    /// branch, tag or commit checked out when the source is a git repository URL, the default branch by default
+    #[argh(option)]
+    rev: Option<String>,
+
+    /// path to the configuration file, `riir.toml` in the current directory by default
+    #[argh(option)]
+    config: Option<PathBuf>,
+
+    /// maximum size of a function result in bytes, larger results are truncated (0 disables the limit)
+    #[argh(option, default = "64 * 1024")]
+    max_result_size: usize,
+
+    /// rustup toolchain of the destination project, e.g. `1.78` or `nightly`; it's written to `rust-toolchain.toml`
+    #[argh(option)]
+    toolchain: Option<String>,
+
+    /// edition of the destination project, e.g. `2021`
+    #[argh(option)]
+    edition: Option<String>,
+
+    /// maximum time in seconds a cargo command may take before it's killed (0 disables the limit)
+    #[argh(option, default = "600")]
+    cargo_timeout: u64,
+
+    /// maximum size of the captured output of a cargo command in bytes, the rest is dropped (0 disables the limit)
+    #[argh(option, default = "4 * 1024 * 1024")]
+    max_cargo_output: usize,
+
+    /// maximum size of a file returned by the read_file functions in bytes, larger files are truncated (0 disables the limit)
+    #[argh(option, default = "32 * 1024")]
+    max_read_size: usize,
+
+    /// don't ask the operator anything, answer the model's questions from the answers file
+    #[argh(switch)]
+    headless: bool,
+
+    /// path to a JSON file with answers to the model's questions in headless mode
+    #[argh(option)]
+    answers: Option<PathBuf>,
+
+    /// don't change the destination project, only log what the model would do
+    #[argh(switch)]
+    dry_run: bool,
+
+    /// how many times a function call failed due to a transient condition is attempted
+    #[argh(option, default = "3")]
+    tool_attempts: u32,
+
+    /// don't remove secrets (API keys, passwords, tokens) from function results
+    #[argh(switch)]
+    no_redact: bool,
+
+    /// an alias for a function in the form `old_name=new_name`, can be repeated
+    #[argh(option)]
+    alias: Vec<String>,
+
+    /// index the source project with embeddings and let the model search it by meaning
+    #[argh(switch)]
+    semantic_search: bool,
+
+    /// after the conversion, keep watching the source project and port its changes
+    #[argh(switch)]
+    watch: bool,
+
+    /// let the model overwrite destination files that were changed by hand after riir wrote them
+    #[argh(switch)]
+    force: bool,
+
+    /// save a tarball of the destination project whenever it's checked, in `.riir/snapshots`
+    #[argh(switch)]
+    snapshots: bool,
+
+    /// don't format the Rust files the model writes with rustfmt
+    #[argh(switch)]
+    no_rustfmt: bool,
+
+    /// run `cargo test` once the destination project compiles and let the model fix failures
+    #[argh(switch)]
+    run_tests: bool,
+
+    /// after the conversion, have the model port the tests of the source project one file at a time and report how many pass
+    #[argh(switch)]
+    port_tests: bool,
+
+    /// after the conversion, have the model refactor the Rust files that still read like the original language, such as index loops, clones everywhere and string errors, one file at a time
+    #[argh(switch)]
+    refactor: bool,
+
+    /// run `cargo doc` and the doctests once the destination project compiles and let the model fix failures
+    #[argh(switch)]
+    check_docs: bool,
+
+    /// show every change of a destination file as a diff and wait for the operator to accept, reject or edit it
+    #[argh(switch)]
+    approve_writes: bool,
+
+    /// have the model re-read each Rust file it writes and compare it with the original before the project is checked
+    #[argh(switch)]
+    self_critique: bool,
+
+    /// for Python sources, also generate a PyO3 extension module exposing the API of the original package on top of the port; asked interactively if not given
+    #[argh(switch)]
+    pyo3_shim: bool,
+
+    /// for C and C++ sources, generate Rust module skeletons with the types and function signatures from the headers and make the crate compile with them before the modules are ported
+    #[argh(switch)]
+    skeletons: bool,
+
+    /// for libraries, extract the public functions, types and constants of the source project and check that the crate exposes each of them under the Rust name of the original
+    #[argh(switch)]
+    preserve_api: bool,
+
+    /// for command line tools, extract the flags and subcommands of the original from its argument parsing code and the `--help` of the equivalence program, require the port to accept the same ones, and check it with generated integration tests running both programs
+    #[argh(switch)]
+    cli_compat: bool,
+
+    /// for C and C++ sources, link the original code through a generated `-sys` crate first and retire it module by module as the Rust code replaces it
+    #[argh(switch)]
+    ffi_first: bool,
+
+    /// build the destination project with this cargo profile, e.g. `dev` or `release`, once it passes the check, can be repeated
+    #[argh(option)]
+    build_profile: Vec<String>,
+
+    /// minimum time in seconds between two calls of the same check, clippy or test function by the model (0 disables the limit)
+    #[argh(option, default = "30")]
+    verify_interval: u64,
+
+    /// after the run, write a JSON report on the build, clippy, the tests and each file to this path, for CI
+    #[argh(option)]
+    report: Option<PathBuf>,
+
+    /// split the work between a planner owning the plan, a coder writing the files and a reviewer checking each change before it's written
+    #[argh(switch)]
+    agents: bool,
+
+    /// stop once the model used this many tokens, the run can be resumed with a larger budget (0 disables the limit)
+    #[argh(option, default = "0")]
+    max_tokens: u64,
+}
+
+#[derive(FromArgs)]
+/// continue an interrupted conversion with the same arguments
+struct ResumeArgs {
+    /// destination project directory of the interrupted run or its `.riir` directory
+    #[argh(positional)]
+    session: PathBuf,
+
+    /// new token budget of the run, the one of the interrupted run by default (0 disables the limit)
+    #[argh(option)]
+    max_tokens: Option<u64>,
+}
+
+/// Parses a command line like `argh::from_env` does, exiting on `--help` and on errors.
+fn parse_command_line<T: FromArgs>(command: &str, args: &[String]) -> T {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    T::from_args(&[command], &args).unwrap_or_else(|early_exit| match early_exit.status {
+        Ok(()) => {
+            println!("{}", early_exit.output);
+            std::process::exit(0);
+        }
+        Err(()) => {
+            eprintln!(
+                "{}\nRun {command} --help for more information.",
+                early_exit.output
+            );
+            std::process::exit(1);
+        }
+    })
 }
 
 fn dump_message(message: &ChatCompletionMessage) {
     let role = message.role;
-    if let Some(text) = &message.content {
-        if [
+    if let Some(text) = &message.content
+        && [
             ChatCompletionMessageRole::System,
             ChatCompletionMessageRole::User,
             ChatCompletionMessageRole::Assistant,
         ]
         .contains(&role)
-        {
-            println!("==== {role:#?} ====\n{text}\n");
-        }
+    {
+        println!("==== {role:#?} ====\n{text}\n");
     }
     debug!("{role:#?}: {message:#?}");
 }
@@ -45,6 +366,10 @@ struct Chat {
     credentials: Credentials,
     messages: Vec<ChatCompletionMessage>,
     functions: CallableFunctionList,
+    /// tokens used by the completions so far
+    tokens: u64,
+    max_tokens: Option<u64>,
+    checkpoint: Option<Arc<Checkpoint>>,
 }
 
 impl Chat {
@@ -54,6 +379,9 @@ impl Chat {
             credentials,
             messages: vec![],
             functions: Default::default(),
+            tokens: 0,
+            max_tokens: None,
+            checkpoint: None,
         }
     }
 
@@ -63,7 +391,18 @@ impl Chat {
         Chat::new(model, credentials)
     }
 
-    async fn send_message(&mut self, message: &str) {
+    /// Drops the conversation, keeping only the system message.
+    fn reset(&mut self) {
+        self.messages.truncate(1);
+    }
+
+    fn save_checkpoint(&self) {
+        if let Some(checkpoint) = &self.checkpoint {
+            checkpoint.set_messages(&self.messages, self.tokens);
+        }
+    }
+
+    async fn send_message(&mut self, message: &str) -> Result<(), DispatchError> {
         let chat_message = ChatCompletionMessage {
             role: ChatCompletionMessageRole::User,
             content: Some(message.to_string()),
@@ -73,11 +412,17 @@ impl Chat {
         dump_message(&chat_message);
         self.messages.push(chat_message);
 
-        self.execute().await;
+        self.execute().await
     }
 
-    async fn execute(&mut self) {
+    async fn execute(&mut self) -> Result<(), DispatchError> {
+        let mut failures = 0;
         loop {
+            if let Some(max_tokens) = self.max_tokens
+                && self.tokens >= max_tokens
+            {
+                return Err(DispatchError::BudgetExhausted(max_tokens));
+            }
             let chat_completion = ChatCompletion::builder(&self.model, self.messages.clone())
                 .credentials(self.credentials.clone())
                 .functions(self.functions.function_definitions())
@@ -85,19 +430,41 @@ impl Chat {
                 .await
                 .unwrap();
 
+            if let Some(usage) = &chat_completion.usage {
+                self.tokens += u64::from(usage.total_tokens);
+            }
             let returned_message = chat_completion.choices.first().unwrap().message.clone();
             self.messages.push(returned_message.clone());
 
             dump_message(&returned_message);
 
             if let Some(call) = returned_message.function_call.as_ref() {
-                let message = self.functions.dispatch(call).unwrap();
+                let message = match self.functions.dispatch(call) {
+                    Ok(result) => {
+                        failures = 0;
+                        if let Some(error) = result.value.get("error").and_then(|e| e.as_str()) {
+                            warn!("Function {} failed: {error}", call.name);
+                        }
+                        result.message
+                    }
+                    Err(e) => {
+                        failures += 1;
+                        if failures >= MAX_DISPATCH_FAILURES {
+                            return Err(e);
+                        }
+                        warn!("Cannot call function {}: {}", call.name, e.details());
+                        e.to_message(&call.name)
+                    }
+                };
                 dump_message(&message);
                 self.messages.push(message);
+                self.save_checkpoint();
             } else {
+                self.save_checkpoint();
                 break;
             }
         }
+        Ok(())
     }
 }
 
@@ -105,65 +472,623 @@ impl Chat {
 async fn main() {
     env_logger::init();
 
-    let args: Args = argh::from_env();
+    let command_line: Vec<String> = env::args().skip(1).collect();
+    let (mut args, resumed) = if command_line.first().is_some_and(|arg| arg == "resume") {
+        let resume: ResumeArgs = parse_command_line("riir resume", &command_line[1..]);
+        let checkpoint = match Checkpoint::load(checkpoint_path(&resume.session)) {
+            Ok(checkpoint) => checkpoint,
+            Err(e) => {
+                error!("{e}");
+                return;
+            }
+        };
+        let state = checkpoint.state();
+        if state.phase == Phase::Done {
+            info!("The conversion of this session is finished, there is nothing to resume");
+            return;
+        }
+        if let Err(e) = env::set_current_dir(&state.dir) {
+            error!("Cannot change to {}: {e}", state.dir.display());
+            return;
+        }
+        let mut args: Args = parse_command_line("riir", &state.args);
+        if let Some(max_tokens) = resume.max_tokens {
+            args.max_tokens = max_tokens;
+        }
+        (args, Some(checkpoint))
+    } else {
+        (parse_command_line("riir", &command_line), None)
+    };
+
+    let rev = args.rev.as_deref();
+    let extract_dir = RunDir::new("sources", "the extracted sources");
+    let sources = std::iter::once(&args.source)
+        .chain(&args.extra_source)
+        .map(|source| prepare_source(source, rev, &extract_dir))
+        .collect::<Result<Vec<_>, _>>();
+    match sources {
+        Ok(mut sources) => {
+            args.extra_source = sources.split_off(1);
+            args.source = sources.remove(0);
+        }
+        Err(e) => {
+            error!("{e}");
+            return;
+        }
+    }
 
-    if !args.source.is_dir() {
-        error!("The source project directory does not exist.");
+    if let Some(source) = std::iter::once(&args.source)
+        .chain(&args.extra_source)
+        .find(|source| !source.is_dir())
+    {
+        error!(
+            "The source project directory {} does not exist.",
+            source.display()
+        );
         return;
     }
     if !args.destination.is_dir() {
         error!("The destination project directory does not exist.");
         return;
     }
-    let source_project = Arc::new(Project::new(args.source));
-    let destination_project = Arc::new(Project::new(args.destination));
+    let config = match &args.config {
+        Some(path) => Config::load(path),
+        None if Path::new(CONFIG_FILE).exists() => Config::load(Path::new(CONFIG_FILE)),
+        None => Ok(Config::default()),
+    };
+    let mut config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            error!("{e}");
+            return;
+        }
+    };
+    // cargo deny runs in the destination project.
+    if let Some(policy) = &mut config.audit.deny_policy {
+        match std::path::absolute(&*policy) {
+            Ok(path) => *policy = path,
+            Err(e) => {
+                error!("Cannot find {}: {e}", policy.display());
+                return;
+            }
+        }
+    }
+    let ignore_list = match IgnoreList::new(&config.ignore) {
+        Ok(ignore) => ignore,
+        Err(e) => {
+            error!("{e}");
+            return;
+        }
+    };
+    let layout = match Layout::new(&config.layout) {
+        Ok(layout) => Some(layout).filter(|layout| !layout.is_empty()),
+        Err(e) => {
+            error!("{e}");
+            return;
+        }
+    };
+
+    let operator = if args.headless {
+        match Operator::headless(args.answers.as_deref()) {
+            Ok(operator) => Arc::new(operator),
+            Err(e) => {
+                error!("{e}");
+                return;
+            }
+        }
+    } else {
+        Arc::new(Operator::interactive())
+    };
+    let scratch_dir = RunDir::new("scratch", "the scratch directory");
+    if let Err(e) = std::fs::create_dir_all(&scratch_dir) {
+        error!("Cannot create the scratch directory: {e}");
+        return;
+    }
+    // The dry run must not leave anything in the destination project, not even the session.
+    let session_root = if args.dry_run {
+        &scratch_dir
+    } else {
+        args.destination.as_path()
+    };
+    let session = match Session::open(session_root) {
+        Ok(session) => session,
+        Err(e) => {
+            error!("Cannot create the session directory: {e}");
+            return;
+        }
+    };
+    let checkpoint = Arc::new(match resumed {
+        Some(checkpoint) => checkpoint,
+        None => Checkpoint::new(
+            session.file(CHECKPOINT_FILE),
+            env::current_dir().unwrap_or_default(),
+            command_line,
+        ),
+    });
+    let resumed_state = checkpoint.state();
+    let session_notes = Arc::new(Notes::load(session.file("notes.json")));
+    let todo_list = Arc::new(TodoList::load(session.file("todo.json")));
+    let mut conversion_plan = Plan::load(session.file("plan.json"));
+    if let Some(layout) = &layout {
+        conversion_plan = conversion_plan.with_layout(layout.clone());
+    }
+    let conversion_plan = Arc::new(conversion_plan);
+    let max_read_size = Some(args.max_read_size).filter(|&size| size > 0);
+    let scratch_project = Arc::new(Project::new(scratch_dir.to_path_buf()));
+    let source_projects: Vec<Arc<Project<ReadOnly>>> = std::iter::once(args.source)
+        .chain(args.extra_source)
+        .map(|path| {
+            Arc::new(
+                Project::new(path)
+                    .with_ignore_list(ignore_list.clone())
+                    .with_max_read_size(max_read_size)
+                    .read_only(),
+            )
+        })
+        .collect();
+    let prefixes = source_prefixes(source_projects.len());
+    // License, metadata and the build of the original project come from the first source.
+    let source_project = source_projects[0].clone();
+    let mut destination_project = Project::new(args.destination)
+        .with_ignore_list(ignore_list)
+        .with_max_read_size(max_read_size)
+        .with_backup_dir(session.file("backups"))
+        .with_written_files(WrittenFiles::load(session.file("written.json")), args.force)
+        .with_rustfmt(!args.no_rustfmt)
+        .with_cargo_options(CargoOptions {
+            toolchain: args.toolchain.clone(),
+            timeout: Some(Duration::from_secs(args.cargo_timeout))
+                .filter(|timeout| !timeout.is_zero()),
+            max_output: Some(args.max_cargo_output).filter(|&size| size > 0),
+        });
+    // Files in the wrong place are rejected before anyone reviews them.
+    if let Some(layout) = layout.clone() {
+        let sources = source_project.list_contents().files;
+        destination_project = destination_project.with_review(Box::new(move |path, _, new| {
+            // Deleting a file can't put it in the wrong place.
+            new.and_then(|_| layout.check_write(path, &sources))
+                .map_or(Review::Approve, Review::Reject)
+        }));
+    }
+    if args.agents || config.review.enabled {
+        let reviewer = Reviewer::from_env(&config.review);
+        destination_project = destination_project.with_review(Box::new(move |path, old, new| {
+            // The reviewer judges code, deletions are left to the operator.
+            new.and_then(|new| reviewer.review(path, old, new))
+                .map_or(Review::Approve, Review::Reject)
+        }));
+    }
+    // The operator sees the changes last, after the reviewer.
+    if args.approve_writes {
+        let operator = operator.clone();
+        destination_project = destination_project.with_review(Box::new(move |path, old, new| {
+            operator.approve_write(path, old, new)
+        }));
+    }
+    let destination_project = Arc::new(destination_project);
+
+    if let Some(toolchain) = &args.toolchain
+        && !args.dry_run
+    {
+        let contents = format!("[toolchain]\nchannel = \"{toolchain}\"\n");
+        match destination_project.write_generated_file("rust-toolchain.toml", &contents) {
+            Ok(WriteFileResult { error: None, .. }) => {}
+            Ok(WriteFileResult { error: Some(e), .. }) | Err(TransientError(e)) => {
+                error!("Cannot write rust-toolchain.toml: {e}");
+                return;
+            }
+        }
+        // There is nothing to check yet.
+        destination_project.clear_dirty();
+    }
+
+    // A previous conversion into the same destination is only redone for the changed sources.
+    let mut source_hashes = SourceHashes::load(session.file("source_hashes.json"));
+    let mut unplanned: Vec<String> = vec![];
+    if !source_hashes.is_empty() && !conversion_plan.is_empty() {
+        let source_files = source_project.list_contents().files;
+        let changed = source_hashes.changed(source_project.path(), &source_files);
+        if changed.is_empty() && conversion_plan.next_pending(&[]).is_none() {
+            info!("The source didn't change since the last conversion, there is nothing to do");
+            return;
+        }
+        let reopened = conversion_plan.reopen(&changed);
+        unplanned = changed
+            .into_iter()
+            .filter(|file| source_files.contains(file) && !conversion_plan.covers(file))
+            .collect();
+        info!(
+            "Converting {} modules again because their sources changed, {} new files",
+            reopened.len(),
+            unplanned.len()
+        );
+    }
+
+    let reference_result = config.reference.enabled.then(|| {
+        let languages: Vec<String> = source_project
+            .language_stats()
+            .languages
+            .into_iter()
+            .map(|stats| stats.language)
+            .collect();
+        info!("Building and testing the source project for reference");
+        let result = run_reference(
+            &config.reference,
+            &languages,
+            source_project.path(),
+            &session.file("reference"),
+        );
+        match &result.error {
+            Some(e) => warn!("Cannot run the reference commands: {e}"),
+            None => {
+                for run in &result.runs {
+                    info!(
+                        "`{}` {}",
+                        run.command,
+                        if run.success { "succeeded" } else { "failed" }
+                    );
+                }
+            }
+        }
+        Arc::new(result)
+    });
+
+    if !config.equivalence.cases.is_empty()
+        && (config.equivalence.original.is_empty() || config.equivalence.ported.is_empty())
+    {
+        error!("The equivalence cases need both the original and the ported command.");
+        return;
+    }
+    // The programs are compared in a copy of the source project, which the reference stage has
+    // already made.
+    if (!config.equivalence.cases.is_empty()
+        || args.cli_compat && !config.equivalence.original.is_empty()
+        || config.fuzz.enabled
+        || !config.benchmark.cases.is_empty())
+        && !config.reference.enabled
+        && let Err(e) = copy_source(source_project.path(), &session.file("reference"))
+    {
+        error!("{e}");
+        return;
+    }
+
+    // The extension module is offered when the source is mostly Python.
+    let pyo3_shim = args.pyo3_shim
+        || !args.headless
+            && !args.dry_run
+            && source_project
+                .language_stats()
+                .languages
+                .first()
+                .is_some_and(|stats| stats.language == "Python")
+            && operator.confirm(
+                "The source project is written in Python. Also generate a PyO3 extension module \
+                exposing its API on top of the port, for the Python callers?",
+            );
 
     // Make sure you have a file named `.env` with the `OPENAI_KEY` environment variable defined!
     dotenv().unwrap();
 
     let mut chat = Chat::from_env();
+    chat.max_tokens = Some(args.max_tokens).filter(|&tokens| tokens > 0);
+    chat.checkpoint = Some(checkpoint.clone());
+    chat.functions
+        .set_max_result_size(Some(args.max_result_size).filter(|&size| size > 0));
+    chat.functions.set_dry_run(args.dry_run);
+    let gate_operator = operator.clone();
+    chat.functions.set_confirmation(move |name, args| {
+        gate_operator.confirm(&format!("The model wants to call {name} with {args}"))
+    });
+    chat.functions.set_retry_policy(RetryPolicy {
+        attempts: args.tool_attempts.max(1),
+        ..Default::default()
+    });
+    if !args.no_redact {
+        let redactor = Redactor::new();
+        chat.functions
+            .add_result_filter(move |output| redactor.redact(output));
+    }
 
-    let project = source_project.clone();
-    chat.functions.add_function(
-        "src_list_files",
-        "List all files in the source project directory.",
-        move |_: ()| project.list_contents(),
+    for (source_project, prefix) in source_projects.iter().zip(&prefixes) {
+        let embeddings_cache = if source_projects.len() == 1 {
+            session.file("embeddings.json")
+        } else {
+            session.file(&format!("embeddings-{prefix}.json"))
+        };
+        add_source_functions(
+            &mut chat,
+            source_project,
+            &destination_project,
+            prefix,
+            args.semantic_search,
+            embeddings_cache,
+        )
+        .await;
+    }
+
+    let project = destination_project.clone();
+    add_function!(chat.functions,
+        /// List all files in the destination project directory.
+        fn dst_list_files(_: ()) => project.list_contents()
     );
 
-    let project = source_project.clone();
-    chat.functions.add_function(
-        "src_read_file",
-        "Reads the contents of a file in the source project directory.",
-        move |args: ReadFileArgs| project.read_file(&args.path),
+    let project = destination_project.clone();
+    add_function!(chat.functions,
+        /// Shows the destination project directory as a tree with file sizes and line counts.
+        /// Prefer it to the flat file list to get an overview of a large project.
+        fn dst_tree(args: TreeArgs) => project.tree(&args)
     );
 
     let project = destination_project.clone();
-    chat.functions.add_function(
-        "dst_list_files",
-        "List all files in the destination project directory.",
-        move |_: ()| project.list_contents(),
+    add_function!(chat.functions,
+        /// Reads the contents of a file in the destination project directory.
+        retry fn dst_read_file(args: ReadFileArgs) => project.read_file(&args.path)
     );
 
     let project = destination_project.clone();
-    chat.functions.add_function(
-        "dst_read_file",
-        "Reads the contents of a file in the destination project directory.",
-        move |args: ReadFileArgs| project.read_file(&args.path),
+    add_function!(chat.functions,
+        /// Returns the size, line count, language and modification time of a file in the destination
+        /// project directory without reading it.
+        retry fn dst_stat(args: StatArgs) => project.stat(&args.path)
     );
 
     let project = destination_project.clone();
-    chat.functions.add_function(
-        "dst_write_file",
-        "Saves the contents to a file in the destination project directory.",
-        move |args: WriteFileArgs| project.write_file(&args.path, &args.contents),
+    add_function!(chat.functions,
+        /// Lists the public items of a Rust file in the destination project with their
+        /// signatures. Use it to check what is already defined without reading the whole file.
+        retry fn dst_symbols(args: RustItemsArgs) => project.rust_items(&args.path)
     );
 
+    let project = destination_project.clone();
+    add_function!(chat.functions,
+        /// Searches the destination project for lines matching a text or a regular expression.
+        /// Use it to find where something is defined instead of re-reading files.
+        fn dst_search(args: GrepArgs) => project.grep(&args)
+    );
+
+    let project = destination_project.clone();
+    add_function!(chat.functions,
+        /// Reads a range of lines of a file in the destination project directory.
+        /// Lines are prefixed with their numbers.
+        retry fn dst_read_file_range(args: ReadFileRangeArgs) => {
+            project.read_file_range(&args.path, args.start_line, args.end_line)
+        }
+    );
+
+    let project = destination_project.clone();
+    add_function!(chat.functions,
+        /// Saves the contents to a file in the destination project directory.
+        retry fn dst_write_file(args: WriteFileArgs) => project.write_file(&args.path, &args.contents)
+    )
+    .mutating();
+
+    let project = destination_project.clone();
+    add_function!(chat.functions,
+        /// Appends a chunk to a file in the destination project directory.
+        /// Use it to write a file that is too long for a single call: set `truncate` for the
+        /// first chunk and append the rest with subsequent calls.
+        retry fn dst_append_file(args: AppendFileArgs) => {
+            project.append_file(&args.path, &args.contents, args.truncate.unwrap_or(false))
+        }
+    )
+    .mutating();
+
+    let project = destination_project.clone();
+    add_function!(chat.functions,
+        /// Creates a directory in the destination project directory, including missing parents.
+        retry fn dst_create_dir(args: CreateDirArgs) => project.create_dir(&args.path)
+    )
+    .mutating();
+
+    let project = destination_project.clone();
+    add_function!(chat.functions,
+        /// Deletes a file in the destination project directory, e.g. an obsolete stub.
+        retry fn dst_delete_file(args: DeleteFileArgs) => project.delete_file(&args.path)
+    )
+    .destructive();
+
+    let project = destination_project.clone();
+    add_function!(chat.functions,
+        /// Renames or moves a file within the destination project directory.
+        retry fn dst_rename_file(args: RenameFileArgs) => project.rename_file(&args.from, &args.to)
+    )
+    .mutating();
+
+    let project = destination_project.clone();
+    add_function!(chat.functions,
+        /// Changes an existing file in the destination project directory without rewriting it
+        /// completely. Pass either search/replace edits or a unified diff.
+        retry fn dst_apply_patch(args: ApplyPatchArgs) => {
+            project.apply_patch(&args.path, args.edits.as_deref(), args.diff.as_deref())
+        }
+    )
+    .mutating();
+
+    let project = destination_project.clone();
+    add_function!(chat.functions,
+        /// Changes dependencies, features, edition and targets in the destination manifest
+        /// while keeping the rest of the file intact. Prefer it to rewriting Cargo.toml.
+        retry fn dst_edit_manifest(args: EditManifestArgs) => {
+            project.edit_manifest(args.path.as_deref().unwrap_or("Cargo.toml"), &args.edits)
+        }
+    )
+    .mutating();
+
+    let verify_interval = Duration::from_secs(args.verify_interval);
+    let project = destination_project.clone();
+    add_function!(chat.functions,
+        /// Runs `cargo check` in the destination project and returns the number of errors and
+        /// warnings with the most important errors. Use it to verify your changes before moving on.
+        fn dst_run_check(_: ()) => CargoRunResult::new(project.run_cargo_check_all(), CargoOutput::report)
+    )
+    .rate_limited(verify_interval);
+
+    let project = destination_project.clone();
+    let clippy = config.clippy.clone();
+    add_function!(chat.functions,
+        /// Runs `cargo clippy` in the destination project and returns the errors and lints.
+        fn dst_run_clippy(_: ()) => CargoRunResult::new(project.run_clippy(&clippy), |output| {
+            let lints = output.clippy_lints();
+            if lints.is_empty() {
+                output.report()
+            } else {
+                format_diagnostics(&lints)
+            }
+        })
+    )
+    .rate_limited(verify_interval);
+
+    let project = destination_project.clone();
+    add_function!(chat.functions,
+        /// Runs `cargo test` in the destination project and returns the failed tests with their
+        /// output, or the compiler errors if the tests don't build.
+        fn dst_run_tests(_: ()) => CargoRunResult::new(project.run_tests(), CargoOutput::test_report)
+    )
+    .rate_limited(verify_interval);
+
+    let project = destination_project.clone();
+    add_function!(chat.functions,
+        /// Adds a dependency to the destination project with `cargo add`. It fails if the crate
+        /// or the version doesn't exist, and reports the features of the crate.
+        retry fn dst_cargo_add(args: CargoAddArgs) => project.cargo_add(&args)
+    )
+    .mutating();
+
+    add_function!(chat.functions,
+        /// Searches crates.io for crates matching the query and returns their latest versions
+        /// and download counts. Use it to pick an existing crate and version.
+        retry fn crates_search(args: CratesSearchArgs) => search_crates(&args)
+    );
+
+    add_function!(chat.functions,
+        /// Returns the signature and documentation of an item of a crate or the standard
+        /// library. Use it to check the actual API instead of guessing it.
+        retry fn docs_lookup(args: DocsLookupArgs) => lookup_docs(&args)
+    );
+
+    add_function!(chat.functions,
+        /// Explains a compiler error code, e.g. E0502, with examples of the erroneous code and
+        /// its fixes. Use it when a compiler error is not clear.
+        fn explain_error(args: ExplainErrorArgs) => explain_error(&args)
+    );
+
+    let project = scratch_project.clone();
+    add_function!(chat.functions,
+        /// Saves a draft to a file in the scratch directory.
+        /// The scratch directory is not a part of the destination project and is not checked.
+        retry fn scratch_write(args: WriteFileArgs) => project.write_file(&args.path, &args.contents)
+    );
+
+    let project = scratch_project.clone();
+    add_function!(chat.functions,
+        /// Reads a draft from a file in the scratch directory.
+        retry fn scratch_read(args: ReadFileArgs) => project.read_file(&args.path)
+    );
+
+    add_function!(chat.functions,
+        /// Asks the operator a clarifying question and returns the answer.
+        /// Use it only for decisions that can't be made from the source project,
+        /// e.g. the choice between equally suitable crates.
+        fn ask_user(args: AskUserArgs) => operator.ask(&args.question)
+    );
+
+    let notes = session_notes.clone();
+    add_function!(chat.functions,
+        /// Saves a note about the conversion, e.g. chosen crates or naming conventions.
+        /// Notes are kept between sessions, so record every decision you want to remember.
+        fn note_write(args: NoteWriteArgs) => notes.write(&args.topic, &args.text)
+    );
+
+    let notes = session_notes.clone();
+    add_function!(chat.functions,
+        /// Reads the notes saved earlier with note_write.
+        fn note_read(args: NoteReadArgs) => notes.read(args.topic.as_deref())
+    );
+
+    let todo = todo_list.clone();
+    add_function!(chat.functions,
+        /// Adds a task to the todo list, e.g. a file to port or an issue to fix.
+        /// Returns the identifier of the task. If the destination is a cargo workspace, give
+        /// the member crate the task is about.
+        fn todo_add(args: TodoAddArgs) => todo.add(&args.title, args.member.as_deref())
+    );
+
+    let todo = todo_list.clone();
+    add_function!(chat.functions,
+        /// Updates the status or the description of a task in the todo list.
+        fn todo_update(args: TodoUpdateArgs) => todo.update(args)
+    );
+
+    let todo = todo_list.clone();
+    add_function!(chat.functions,
+        /// Lists all tasks in the todo list.
+        fn todo_list(_: ()) => todo.list()
+    );
+
+    let plan = conversion_plan.clone();
+    add_function!(chat.functions,
+        /// Replaces the conversion plan: the modules of the destination project in the order they
+        /// are converted, with the source files of each, and the Rust replacement of each
+        /// dependency of the source project. Modules already in the plan keep their status.
+        fn plan_write(args: PlanWriteArgs) => plan.write(args)
+    );
+
+    let plan = conversion_plan.clone();
+    add_function!(chat.functions,
+        /// Sets the status of a module of the conversion plan, e.g. to `done` once it's converted.
+        fn plan_update(args: PlanUpdateArgs) => plan.update(args)
+    );
+
+    let plan = conversion_plan.clone();
+    add_function!(chat.functions,
+        /// Returns the conversion plan.
+        fn plan_read(_: ()) => plan.read()
+    );
+
+    if let Some(result) = &reference_result {
+        let result = result.clone();
+        add_function!(chat.functions,
+            /// Returns the output of the build and test commands of the original project, run in
+            /// a copy of it. Use it as the ground truth about the intended behavior and what the
+            /// tests expect.
+            fn reference_results(_: ()) => ReferenceResult::clone(&result)
+        );
+    }
+
+    for alias in &args.alias {
+        let Some((alias, name)) = alias.split_once('=') else {
+            error!("Invalid alias: {alias}");
+            return;
+        };
+        if let Err(e) = chat.functions.add_alias(alias.trim(), name.trim()) {
+            error!("{e}");
+            return;
+        }
+    }
+
+    let jvm_project =
+        JvmProject::load(source_project.path(), &source_project.list_contents().files);
     let system_prompt = "\
         You are a large language model that is capable of converting project source code to Rust source code. \
         You have access to two project directories: the source project directory if read-only and contains the source files of the original project. \
         The destination project directory is initially empty and should be populated with project files in Rust language. \
         When you propose an action or a change to the source code, execute this action or change right away.\
-    ".to_string();
+    ".to_string()
+        + &source_layout(&source_projects, &prefixes)
+        + &toolchain_notes(args.toolchain.as_deref(), args.edition.as_deref())
+        + &layout.as_ref().map_or(String::new(), Layout::describe)
+        + if jvm_project.is_some() {
+            jvm::GUIDANCE
+        } else {
+            ""
+        }
+        + if reference_result.is_some() {
+            " The original project was built and tested for reference, reference_results returns \
+            the output."
+        } else {
+            ""
+        };
+    let mut agents = args.agents.then(|| Agents::new(system_prompt.clone()));
     let system_message = ChatCompletionMessage {
         role: ChatCompletionMessageRole::System,
         content: Some(system_prompt),
@@ -171,21 +1096,1895 @@ async fn main() {
     };
     dump_message(&system_message);
     chat.messages = vec![system_message];
+    // The conversation of the interrupted run goes on where it stopped.
+    if resumed_state.messages.len() > 1 {
+        info!(
+            "Resuming the conversation with {} messages, {} tokens spent",
+            resumed_state.messages.len(),
+            resumed_state.tokens
+        );
+        chat.messages = resumed_state.messages.clone();
+        chat.tokens = resumed_state.tokens;
+    }
+
+    if let Some(agents) = &mut agents
+        && resumed_state.phase < Phase::Conversion
+    {
+        agents.hand_over(&mut chat, Role::Planner);
+    }
+    if resumed_state.phase < Phase::Planning {
+        if let Err(e) = chat.send_message("Please analyze the project in the source directory and read all files you need to understand the implementation, but don't make any changes at this point.").await {
+            error!("{}", e.reason());
+            return;
+        }
+        checkpoint.set_phase(Phase::Planning);
+    }
 
-    chat.send_message("Please analyze the project in the source directory and read all files you need to understand the implementation, but don't make any changes at this point.").await;
+    if conversion_plan.is_empty() && resumed_state.phase < Phase::Conversion {
+        let mut prompt = PLAN_PROMPT.to_string();
+        let mut dependencies = source_dependencies(source_project.path());
+        if let Some(jvm_project) = &jvm_project {
+            for dependency in jvm_project.module_dependencies(source_project.path()) {
+                if !dependencies.iter().any(|d| d.name == dependency.name) {
+                    dependencies.push(dependency);
+                }
+            }
+        }
+        if !dependencies.is_empty() {
+            info!(
+                "Looking for replacements of {} dependencies",
+                dependencies.len()
+            );
+            prompt += " These dependencies were found in the manifests of the source project, \
+                with proposed replacements; check them and correct them in the plan:";
+            for mapping in propose_mappings(&dependencies, best_crate) {
+                prompt += &format!(
+                    "\n- {} -> {} ({})",
+                    mapping.source,
+                    mapping.rust.as_deref().unwrap_or("no crate"),
+                    mapping.notes.unwrap_or_default()
+                );
+            }
+        }
+        prompt += &language_notes(&source_project);
+        if let Err(e) = chat.send_message(&prompt).await {
+            error!("{}", e.reason());
+            return;
+        }
+        if conversion_plan.is_empty() {
+            warn!("The model didn't write a conversion plan");
+        }
+        let mappings = conversion_plan.read().dependencies;
+        if mappings.iter().any(|mapping| mapping.rust.is_some()) {
+            let section = dependencies_section(&mappings, crate_version);
+            println!("==== Proposed dependencies ====\n{section}");
+            if let Err(e) = std::fs::write(session.file("dependencies.toml"), section) {
+                warn!("Cannot save the proposed dependencies: {e}");
+            }
+        }
+    }
+    if !unplanned.is_empty() && !conversion_plan.is_empty() {
+        let message = format!(
+            "These source files were added or changed since the last conversion, but no module of \
+            the conversion plan is made from them: {}. Read them and add them to the plan with \
+            plan_write, keeping the existing modules.",
+            unplanned.join(", ")
+        );
+        if let Err(e) = chat.send_message(&message).await {
+            error!("{}", e.reason());
+            return;
+        }
+    }
+    println!("==== Plan ====\n{conversion_plan}");
+    checkpoint.set_phase(Phase::Conversion);
+    if let Some(agents) = &mut agents {
+        agents.hand_over(&mut chat, Role::Coder);
+    }
 
-    let mut message = "Now create Rust project in the destination project directory so that it matches the implementation in the source project directory.".to_string();
+    let mut message = "Now create Rust project in the destination project directory so that it matches the implementation in the source project directory. Use the todo list to track your progress.".to_string();
+    let sys_crate = if args.ffi_first && !args.dry_run {
+        let path = session.file("sys_crate.json");
+        SysCrate::load(path.clone(), destination_project.path()).or_else(|| {
+            let files = native_files(&source_project.list_contents().files);
+            if files.is_empty() {
+                warn!("The source project has no C or C++ files to link through FFI");
+                return None;
+            }
+            let name = format!(
+                "{}-sys",
+                destination_project
+                    .path()
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_lowercase())
+                    .unwrap_or_else(|| "original".to_string())
+            );
+            info!("Linking the original code through {name}");
+            SysCrate::create(
+                path,
+                destination_project.path(),
+                &name,
+                source_project.path(),
+                &files,
+            )
+            .inspect_err(|e| error!("{e}"))
+            .ok()
+        })
+    } else {
+        None
+    };
+    if let Some(sys_crate) = &sys_crate {
+        message = format!("{message} {}", sys_crate.instructions());
+    }
+    let api = if args.preserve_api && !args.dry_run {
+        let path = session.file("api.json");
+        let manifest = ApiManifest::load(&path).unwrap_or_else(|| {
+            let manifest =
+                ApiManifest::extract(source_project.path(), &source_project.list_contents().files);
+            if let Err(e) = manifest.save(&path) {
+                warn!("{e}");
+            }
+            manifest
+        });
+        if manifest.items.is_empty() {
+            warn!("The source project has no public API to preserve");
+            None
+        } else {
+            info!(
+                "Preserving {} items of the public API",
+                manifest.items.len()
+            );
+            Some(manifest)
+        }
+    } else {
+        None
+    };
+    let cli = if args.cli_compat && !args.dry_run {
+        let path = session.file("cli.json");
+        let interface = CliInterface::load(&path).unwrap_or_else(|| {
+            let mut interface = CliInterface::from_sources(
+                source_project.path(),
+                &source_project.list_contents().files,
+            );
+            if !config.equivalence.original.is_empty() {
+                let help = ["--help".to_string()];
+                match run_original(&config.equivalence, &session.file("reference"), &help) {
+                    Ok(output) => {
+                        interface.merge(CliInterface::from_help(&(output.stdout + &output.stderr)))
+                    }
+                    Err(e) => warn!("Cannot run the original program: {e}"),
+                }
+            }
+            if let Err(e) = interface.save(&path) {
+                warn!("{e}");
+            }
+            interface
+        });
+        if interface.is_empty() {
+            warn!("No flags or subcommands of the original program found");
+            None
+        } else {
+            info!(
+                "Keeping {} flags and {} subcommands of the original program",
+                interface.flags.len(),
+                interface.subcommands.len()
+            );
+            Some(interface)
+        }
+    } else {
+        None
+    };
+    if let Some(cli) = &cli {
+        message = format!("{message} {}", cli.instructions());
+    }
+    // With a plan, each module gets the items of its sources.
+    if let Some(api) = &api
+        && conversion_plan.is_empty()
+    {
+        message = format!(
+            "{message} The crate is a library that must keep the public API of the original: \
+            expose each of these items publicly under this name:\n{}",
+            format_items(&api.items.iter().collect::<Vec<_>>())
+        );
+    }
+    if resumed_state.phase == Phase::Conversion {
+        message = format!(
+            "The run was interrupted and is resumed now, the destination project contains the \
+            work done so far. {message}"
+        );
+    }
+    let options = WorkOptions {
+        edition: args.edition,
+        dry_run: args.dry_run,
+        snapshot_dir: args.snapshots.then(|| session.file("snapshots")),
+        clippy: config.clippy,
+        warnings: config.warnings.policy,
+        build_profiles: args.build_profile,
+        run_tests: args.run_tests,
+        check_docs: args.check_docs,
+        verify_commands: config.verify,
+        equivalence: config.equivalence,
+        fuzz: config.fuzz,
+        audit: config.audit,
+        escalation: Escalation::load(config.escalation, session.file("flagged.json")),
+        source_copy: session.file("reference"),
+        self_critique: args.self_critique,
+        plan: conversion_plan.clone(),
+        sys_crate,
+        skeletons: args.skeletons,
+        api,
+        cli,
+    };
+    let mut result = if conversion_plan.is_empty() {
+        work_until_done(
+            &mut chat,
+            message,
+            &source_project,
+            &destination_project,
+            &todo_list,
+            &options,
+        )
+        .await
+    } else {
+        convert_by_plan(
+            &mut chat,
+            &conversion_plan,
+            &source_project,
+            &destination_project,
+            &todo_list,
+            &options,
+        )
+        .await
+    };
+    if result.is_ok() && options.fuzz.enabled && !options.dry_run {
+        result = fuzz_until_equivalent(
+            &mut chat,
+            &source_project,
+            &destination_project,
+            &todo_list,
+            &options,
+        )
+        .await;
+    }
+    if result.is_ok() && options.cli.is_some() {
+        result = check_cli_compat(
+            &mut chat,
+            &source_project,
+            &destination_project,
+            &todo_list,
+            &options,
+        )
+        .await;
+    }
+    if result.is_ok() && args.port_tests && !options.dry_run {
+        result = port_test_suite(
+            &mut chat,
+            &source_project,
+            &destination_project,
+            &todo_list,
+            &options,
+        )
+        .await;
+    }
+    if result.is_ok() && args.refactor && !options.dry_run {
+        result = refactor_idioms(
+            &mut chat,
+            &source_project,
+            &destination_project,
+            &todo_list,
+            &options,
+        )
+        .await;
+    }
+    if result.is_ok() && pyo3_shim && !options.dry_run {
+        result = write_python_shim(
+            &mut chat,
+            &source_project,
+            &destination_project,
+            &todo_list,
+            &options,
+        )
+        .await;
+    }
+    if result.is_ok() && !config.benchmark.cases.is_empty() && !options.dry_run {
+        compare_performance(
+            &config.benchmark,
+            &destination_project,
+            &options.source_copy,
+        );
+    }
+    if result.is_ok()
+        && !options.dry_run
+        && let Err(e) =
+            source_hashes.save(source_project.path(), &source_project.list_contents().files)
+    {
+        warn!("Cannot save the hashes of the source files: {e}");
+    }
+    if let Err(e) = &result {
+        error!("{}", e.reason());
+        if !options.dry_run {
+            info!(
+                "Continue the conversion with `riir resume {}`",
+                destination_project.path().display()
+            );
+        }
+    } else {
+        checkpoint.set_phase(Phase::Done);
+    }
+    if result.is_ok() && args.watch {
+        watch_source(
+            &mut chat,
+            &source_projects,
+            &prefixes,
+            &destination_project,
+            &todo_list,
+            &options,
+        )
+        .await;
+    }
+
+    let mut gaps = vec![];
+    if !options.dry_run {
+        let destination_files = destination_project.list_contents().files;
+        let plan = conversion_plan.read();
+        for source_project in &source_projects {
+            let coverage = coverage(
+                &source_project.list_contents().files,
+                &source_project.read_files(),
+                &destination_files,
+                &plan,
+            );
+            println!(
+                "==== Coverage of {} ====\n{coverage}",
+                source_project.path().display()
+            );
+            let origin = if source_projects.len() > 1 {
+                format!(" of `{}`", source_project.path().display())
+            } else {
+                String::new()
+            };
+            gaps.extend(
+                coverage
+                    .unconverted
+                    .iter()
+                    .map(|file| format!("`{file}`{origin} isn't converted")),
+            );
+        }
+    }
+
+    let flagged = options.escalation.flagged();
+    let needs_attention = !flagged.is_empty();
+    if needs_attention {
+        println!("==== Needs human attention ====\n{}", flagged.join("\n"));
+    }
+
+    if !options.dry_run {
+        let mut notes = ConversionNotes::collect(
+            conversion_plan.read(),
+            destination_project.path(),
+            &destination_project.list_contents().files,
+        );
+        notes.decisions = session_notes.read(None).notes;
+        notes.gaps = gaps;
+        notes.gaps.extend(
+            flagged
+                .iter()
+                .map(|problem| format!("Needs human attention: {problem}")),
+        );
+        notes.todos = todo_list
+            .unfinished()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        match destination_project.write_generated_file(CONVERSION_FILE, &notes.to_string()) {
+            Ok(WriteFileResult { error: None, .. }) => {
+                info!("Saved the conversion notes to {CONVERSION_FILE}")
+            }
+            Ok(WriteFileResult { error: Some(e), .. }) | Err(TransientError(e)) => {
+                warn!("Cannot write {CONVERSION_FILE}: {e}")
+            }
+        }
+    }
+
+    if let Some(path) = &args.report
+        && !options.dry_run
+    {
+        info!("Verifying the destination project for the report");
+        let check = destination_project.run_cargo_check_all();
+        let builds = check.as_ref().is_ok_and(|output| output.success);
+        let report = VerificationReport::new(
+            check,
+            options
+                .clippy
+                .enabled
+                .then(|| destination_project.run_clippy(&options.clippy)),
+            builds.then(|| destination_project.run_tests()),
+            flagged,
+            options.warnings,
+        );
+        match report.save(path) {
+            Ok(()) => info!("Saved the verification report to {}", path.display()),
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    if needs_attention {
+        // Exiting skips the destructors.
+        drop(scratch_dir);
+        drop(extract_dir);
+        std::process::exit(1);
+    }
+}
+
+/// A directory of the run in the system temporary directory, removed when riir stops, whatever
+/// way it does.
+struct RunDir {
+    path: PathBuf,
+    /// what the directory holds, for the warning if it can't be removed
+    description: &'static str,
+}
+
+impl RunDir {
+    fn new(name: &str, description: &'static str) -> Self {
+        RunDir {
+            path: env::temp_dir().join(format!("riir-{name}-{}", std::process::id())),
+            description,
+        }
+    }
+}
+
+impl Deref for RunDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for RunDir {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for RunDir {
+    fn drop(&mut self) {
+        if self.path.exists()
+            && let Err(e) = std::fs::remove_dir_all(&self.path)
+        {
+            warn!("Cannot remove {}: {e}", self.description);
+        }
+    }
+}
+
+/// Tells the model which toolchain and edition the destination project uses, so that it only
+/// uses the available language features.
+fn toolchain_notes(toolchain: Option<&str>, edition: Option<&str>) -> String {
+    let mut notes = String::new();
+    if let Some(toolchain) = toolchain {
+        match rustc_version(Some(toolchain)) {
+            Ok(version) => {
+                notes += &format!(
+                    " The destination project is built with the `{toolchain}` toolchain ({version}), \
+                    set in `rust-toolchain.toml`."
+                );
+            }
+            Err(e) => warn!("Cannot get the version of the {toolchain} toolchain: {e}"),
+        }
+        if toolchain.starts_with("nightly") {
+            notes += " Unstable features may be enabled with `#![feature(...)]` if they help.";
+        } else {
+            notes += " Don't use language features or library APIs stabilized in later versions, \
+                and don't use unstable features.";
+        }
+    }
+    if let Some(edition) = edition {
+        notes += &format!(" Use Rust edition {edition} in `Cargo.toml`.");
+    }
+    notes
+}
+
+/// Guidance for the plan specific to the language and the build system of the source project.
+fn language_notes(source_project: &Project<ReadOnly>) -> String {
+    let files = source_project.list_contents().files;
+    let mut notes = String::new();
+    if let Some(module) = GoModule::load(source_project.path(), &files) {
+        notes += &format!("\n{}", module.planning_notes());
+    }
+    if let Some(project) = JvmProject::load(source_project.path(), &files) {
+        notes += &format!("\n{}", project.planning_notes());
+    }
+    if let Some(package) = NpmPackage::load(source_project.path()) {
+        notes += &format!("\n{}", package.planning_notes());
+    }
+    notes
+}
+
+/// Prefixes of the functions of each source project: `src` for a single project, `src1`,
+/// `src2` and so on for several ones.
+fn source_prefixes(count: usize) -> Vec<String> {
+    if count == 1 {
+        return vec!["src".to_string()];
+    }
+    (1..=count).map(|i| format!("src{i}")).collect()
+}
+
+/// Describes which functions access which source project, if there are several of them.
+fn source_layout(source_projects: &[Arc<Project<ReadOnly>>], prefixes: &[String]) -> String {
+    if source_projects.len() == 1 {
+        return String::new();
+    }
+    let mut layout = format!(
+        " The original project is split into {} source project directories, all of them are read-only \
+        and have their own functions:",
+        source_projects.len()
+    );
+    for (project, prefix) in source_projects.iter().zip(prefixes) {
+        let name = project
+            .path()
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        layout += &format!(" the `{prefix}_*` functions access `{name}`;");
+    }
+    layout.pop();
+    layout + ". Convert all of them into the single destination project."
+}
+
+/// Settings of the work loop.
+struct WorkOptions {
+    /// edition set in `Cargo.toml` once it's created
+    edition: Option<String>,
+    dry_run: bool,
+    /// where to save a snapshot of the destination project at each check, if anywhere
+    snapshot_dir: Option<PathBuf>,
+    clippy: ClippyConfig,
+    warnings: WarningPolicy,
+    /// cargo profiles the project is built with after the check
+    build_profiles: Vec<String>,
+    run_tests: bool,
+    check_docs: bool,
+    /// user-defined commands run after cargo
+    verify_commands: VerifyConfig,
+    /// cases comparing the ported program with the original, run if there are any
+    equivalence: EquivalenceConfig,
+    fuzz: FuzzConfig,
+    audit: AuditConfig,
+    escalation: Escalation,
+    /// copy of the source project the programs are compared in
+    source_copy: PathBuf,
+    /// have the model compare each new Rust file with the original before it's checked
+    self_critique: bool,
+    /// the conversion plan, which tells the source files of a destination file
+    plan: Arc<Plan>,
+    /// crate linking the original code that isn't ported yet, in the FFI-first conversion
+    sys_crate: Option<SysCrate>,
+    /// generate the skeletons of the modules from the C and C++ headers before porting them
+    skeletons: bool,
+    /// public API of the original library the crate must expose once all modules are converted
+    api: Option<ApiManifest>,
+    /// command line interface of the original program the port must accept
+    cli: Option<CliInterface>,
+}
+
+/// Sends the message and keeps the model working until the destination project builds and
+/// the todo list is done.
+async fn work_until_done(
+    chat: &mut Chat,
+    mut message: String,
+    source_project: &Project<ReadOnly>,
+    destination_project: &Project,
+    todo_list: &TodoList,
+    options: &WorkOptions,
+) -> Result<(), DispatchError> {
+    let mut todo_reminders = 0;
+    let mut metadata_propagated = false;
+    // Files changed since the last successful verification, including the ones of an interrupted
+    // run.
+    let mut unverified: BTreeSet<PathBuf> = chat
+        .checkpoint
+        .as_ref()
+        .map(|checkpoint| checkpoint.state().unverified.into_iter().collect())
+        .unwrap_or_default();
+    // Files the model was asked to compare with the original.
+    let mut critiqued: BTreeSet<PathBuf> = BTreeSet::new();
     loop {
-        chat.send_message(&message).await;
+        chat.send_message(&message).await?;
+        println!("==== Todo ====\n{todo_list}");
+        if options.self_critique && !options.dry_run {
+            let written: Vec<PathBuf> = destination_project
+                .dirty_files()
+                .into_keys()
+                .filter(|path| path.extension().is_some_and(|ext| ext == "rs"))
+                .filter(|path| path.is_relative() && !critiqued.contains(path))
+                .collect();
+            for file in written {
+                let file_name = file.display().to_string();
+                info!("Comparing {file_name} with the original");
+                let sources = options.plan.sources_of(&file_name);
+                chat.send_message(&critique_message(&file_name, &sources))
+                    .await?;
+                critiqued.insert(file);
+            }
+        }
+        if !metadata_propagated
+            && !options.dry_run
+            && destination_project.path().join("Cargo.toml").is_file()
+        {
+            metadata_propagated = true;
+            match propagate_metadata(source_project, destination_project) {
+                Ok(changed) if !changed.is_empty() => {
+                    info!("Copied the source metadata to {}", changed.join(", "));
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Cannot copy the source metadata: {e}"),
+            }
+            if let Some(edition) = &options.edition {
+                let edit = ManifestEdit::SetEdition {
+                    edition: edition.clone(),
+                };
+                match destination_project.edit_manifest("Cargo.toml", &[edit]) {
+                    Ok(WriteFileResult { error: None, .. }) => {}
+                    Ok(WriteFileResult { error: Some(e), .. }) | Err(TransientError(e)) => {
+                        warn!("Cannot set the edition: {e}")
+                    }
+                }
+            }
+        }
         if destination_project.is_dirty() {
+            let dirty_files = destination_project.dirty_files();
+            let changed_files = dirty_files
+                .keys()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
             destination_project.clear_dirty();
+            info!("Files changed since the last check: {changed_files}");
+            unverified.extend(dirty_files.into_keys());
+            if let Some(checkpoint) = &chat.checkpoint {
+                checkpoint.set_unverified(&unverified);
+            }
+            if let Some(snapshot_dir) = &options.snapshot_dir {
+                match create_snapshot(destination_project.path(), snapshot_dir) {
+                    Ok(path) => info!("Saved a snapshot to {}", path.display()),
+                    Err(e) => warn!("Cannot save a snapshot: {e}"),
+                }
+            }
+
+            let relevant: Vec<&Path> = unverified
+                .iter()
+                .map(PathBuf::as_path)
+                .filter(|path| affects_build(path))
+                .collect();
+            if relevant.is_empty() {
+                info!("Skipping the verification, the changed files don't affect the build");
+            } else if let Some(failure) =
+                verify(destination_project, options, &relevant, &changed_files)
+            {
+                message = failure.message;
+                match options
+                    .escalation
+                    .record_failure(&failure.subject, failure.fingerprint)
+                {
+                    None => {}
+                    Some(EscalationStep::StrongerModel) => {
+                        match options.escalation.stronger_model() {
+                            Some(model) => {
+                                warn!("Switching to {model}, `{}` keeps failing", failure.subject);
+                                chat.model = model.to_string();
+                            }
+                            None => warn!("No stronger model is configured"),
+                        }
+                    }
+                    Some(EscalationStep::Reset) => {
+                        warn!(
+                            "Starting a new conversation, `{}` keeps failing",
+                            failure.subject
+                        );
+                        chat.reset();
+                        message = format!(
+                            "The conversation so far was dropped because the same problem kept \
+                            coming back. Continue the conversion from the current state of the \
+                            destination project; use note_read to recall the decisions made so \
+                            far. The todo list:\n{todo_list}\n{message}"
+                        );
+                    }
+                    // The tree is broken, the callers must not carry on as if it passed.
+                    Some(EscalationStep::Flag) => {
+                        return Err(DispatchError::Flagged(failure.subject));
+                    }
+                }
+                continue;
+            }
+            unverified.clear();
+            if let Some(checkpoint) = &chat.checkpoint {
+                checkpoint.set_unverified(&unverified);
+            }
+            options.escalation.reset();
+        }
+
+        let unfinished = todo_list.unfinished();
+        if !unfinished.is_empty() && todo_reminders < MAX_TODO_REMINDERS {
+            todo_reminders += 1;
+            message = "The todo list still has unfinished tasks. Please continue working on them:\n"
+                .to_string()
+                + &unfinished
+                    .iter()
+                    .map(|item| item.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+            continue;
+        }
+        return Ok(());
+    }
+}
+
+/// A failed verification stage.
+struct VerifyFailure {
+    /// what the failure is blamed on: the file with the first errors, or the stage, e.g.
+    /// "cargo test"
+    subject: String,
+    /// asks the model to fix the failure
+    message: String,
+    /// tells whether the same failure comes back, see `Escalation::record_failure`
+    fingerprint: u64,
+}
+
+impl VerifyFailure {
+    /// A failure identified by its message, which only describes the failure itself.
+    fn new(subject: String, message: String) -> Self {
+        VerifyFailure {
+            subject,
+            fingerprint: fingerprint(&message),
+            message,
+        }
+    }
+}
+
+/// Runs the verification stages after the changes and returns the first failing one, if any.
+fn verify(
+    destination_project: &Project,
+    options: &WorkOptions,
+    changed: &[&Path],
+    changed_files: &str,
+) -> Option<VerifyFailure> {
+    match destination_project.run_cargo_check(changed) {
+        Ok(output) if !output.success => {
+            return Some(check_failure(destination_project, &output, changed_files));
+        }
+        Ok(output) => {
+            let warnings: Vec<&Diagnostic> = output
+                .diagnostics
+                .iter()
+                .filter(|d| !d.is_error())
+                .collect();
+            match options.warnings {
+                _ if warnings.is_empty() => {}
+                WarningPolicy::Allow => {}
+                WarningPolicy::Report => {
+                    warn!("The code compiles with {} warning(s)", warnings.len())
+                }
+                WarningPolicy::Deny => {
+                    return Some(VerifyFailure {
+                        subject: warnings[0]
+                            .file
+                            .clone()
+                            .unwrap_or_else(|| "warnings".to_string()),
+                        message: format!(
+                            "The code compiles, but there are warnings, and the code is only \
+                            accepted without any. Please fix them:\n{}",
+                            format_diagnostics(&warnings)
+                        ),
+                        fingerprint: diagnostics_fingerprint(&warnings),
+                    });
+                }
+            }
+        }
+        Err(e) => error!("{e}"),
+    }
+    if options.clippy.enabled {
+        match destination_project.run_clippy(&options.clippy) {
+            Ok(output) => {
+                let lints = output.clippy_lints();
+                if !lints.is_empty() {
+                    return Some(VerifyFailure {
+                        subject: "clippy".to_string(),
+                        message: format!(
+                            "The code compiles, but clippy reports some problems. \
+                            Please fix them to make the code idiomatic:\n{}",
+                            format_diagnostics(&lints)
+                        ),
+                        fingerprint: diagnostics_fingerprint(&lints),
+                    });
+                }
+                if !output.success {
+                    warn!("cargo clippy failed: {}", output.stderr.trim());
+                }
+            }
+            Err(e) => error!("{e}"),
+        }
+    }
+    let dependencies_changed = changed.iter().any(|path| {
+        path.file_name()
+            .is_some_and(|name| name == "Cargo.toml" || name == "Cargo.lock")
+    });
+    if dependencies_changed {
+        let mut problems = vec![];
+        if options.audit.enabled {
+            match destination_project.run_audit() {
+                Ok(found) => problems.extend(found),
+                Err(e) => warn!("{e}"),
+            }
+        }
+        if let Some(policy) = &options.audit.deny_policy {
+            match destination_project.run_deny(policy) {
+                Ok(found) => problems.extend(found),
+                Err(e) => warn!("{e}"),
+            }
+        }
+        if !problems.is_empty() {
+            return Some(VerifyFailure::new(
+                "dependencies".to_string(),
+                format!(
+                    "The code compiles, but some dependencies must not be used. Upgrade them or \
+                    replace them with other crates:\n{}",
+                    format_problems(&problems)
+                ),
+            ));
+        }
+    }
+    // Linking and build scripts are only checked by a full build.
+    for profile in &options.build_profiles {
+        match destination_project.run_build(profile) {
+            Ok(output) if !output.success => {
+                return Some(VerifyFailure::new(
+                    format!("cargo build --profile {profile}"),
+                    format!(
+                        "The code passes `cargo check`, but `cargo build --profile {profile}` \
+                        fails. Please fix it:\n{}",
+                        output.report()
+                    ),
+                ));
+            }
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+    if options.run_tests {
+        match destination_project.run_tests() {
+            Ok(output) if !output.success => {
+                return Some(VerifyFailure::new(
+                    "cargo test".to_string(),
+                    format!(
+                        "The code compiles, but `cargo test` fails. Please fix the code; \
+                        change the tests only if they are wrong themselves:\n{}",
+                        output.test_report()
+                    ),
+                ));
+            }
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+    if options.check_docs {
+        match destination_project.run_doc() {
+            Ok(output) if !output.success => {
+                return Some(VerifyFailure::new(
+                    "cargo doc".to_string(),
+                    format!(
+                        "The code compiles, but `cargo doc` fails. Please fix the doc \
+                        comments:\n{}",
+                        output.report()
+                    ),
+                ));
+            }
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+        match destination_project.run_doctests() {
+            // A binary crate has no doctests.
+            Ok(output) if output.stderr.contains("no library targets found") => {}
+            Ok(output) if !output.success => {
+                return Some(VerifyFailure::new(
+                    "cargo test --doc".to_string(),
+                    format!(
+                        "The code compiles, but the examples in the doc comments fail. Please fix \
+                        the examples, or the code if the examples show the intended behavior:\n{}",
+                        output.test_report()
+                    ),
+                ));
+            }
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+    match run_verify_commands(&options.verify_commands, destination_project.path()) {
+        Ok(Some(failure)) => {
+            return Some(VerifyFailure::new(
+                failure.command.clone(),
+                format!(
+                    "The code compiles, but the verification command `{}` fails ({}). Please \
+                    fix it:\n{}",
+                    failure.command, failure.status, failure.output
+                ),
+            ));
+        }
+        Ok(None) => {}
+        Err(e) => error!("{e}"),
+    }
+    // The modules of a plan that aren't converted yet can't expose their part of the API.
+    if let Some(api) = &options.api
+        && options.plan.next_pending(&[]).is_none()
+    {
+        let missing = api.missing(
+            destination_project.path(),
+            &destination_project.list_contents().files,
+        );
+        if !missing.is_empty() {
+            return Some(VerifyFailure::new(
+                "public API".to_string(),
+                format!(
+                    "The code compiles, but the crate doesn't expose these items of the public \
+                    API of the original library. Make them public under these names, adding or \
+                    renaming items as needed:\n{}",
+                    format_items(&missing)
+                ),
+            ));
+        }
+    }
+    if !options.equivalence.cases.is_empty() {
+        match destination_project.run_build("dev") {
+            Ok(output) if !output.success => {
+                return Some(VerifyFailure::new(
+                    "cargo build".to_string(),
+                    format!(
+                        "The code passes `cargo check`, but `cargo build` fails. Please fix it:\n{}",
+                        output.report()
+                    ),
+                ));
+            }
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+        match compare_programs(
+            &options.equivalence,
+            &options.source_copy,
+            destination_project.path(),
+        ) {
+            Ok(mismatches) if !mismatches.is_empty() => {
+                return Some(VerifyFailure::new(
+                    "equivalence cases".to_string(),
+                    format!(
+                        "The code builds, but the Rust program doesn't behave like the original. \
+                        These are bugs of the port; please fix the code so that its output and exit \
+                        code match the original's exactly:\n{}",
+                        format_mismatches(&mismatches, options.equivalence.cases.len())
+                    ),
+                ));
+            }
+            Ok(_) => {}
+            Err(e) => error!("Cannot compare the programs: {e}"),
+        }
+    }
+    None
+}
+
+/// Asks the model to compare a file it has written with the original and to fix what's missing.
+/// Dropped functions and branches often compile fine, so the check doesn't notice them.
+fn critique_message(file: &str, sources: &[String]) -> String {
+    let original = if sources.is_empty() {
+        "the source files it was converted from".to_string()
+    } else {
+        sources
+            .iter()
+            .map(|source| format!("`{source}`"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    format!(
+        "Before the project is checked, re-read `{file}` that you have just written and compare \
+        it with {original}, function by function. List everything that's missing or behaves \
+        differently: functions, methods, types, constants, branches, error handling and edge \
+        cases, then fix all of it right away. If nothing is missing, just say so."
+    )
+}
+
+/// Turns the errors of `cargo check` into a failure blamed on the file with the first errors.
+/// The fingerprint only covers the errors, not the rest of the message.
+fn check_failure(
+    destination_project: &Project,
+    output: &CargoOutput,
+    changed_files: &str,
+) -> VerifyFailure {
+    let subject = match output.files_with_errors().first() {
+        Some((file, _)) => file.to_string(),
+        None => "cargo check".to_string(),
+    };
+    let errors: Vec<&Diagnostic> = output.errors().collect();
+    VerifyFailure {
+        subject,
+        message: check_errors_message(destination_project, output, changed_files),
+        fingerprint: diagnostics_fingerprint(&errors),
+    }
+}
+
+/// Asks the model to fix the `cargo check` errors of one file, together with the file's contents.
+/// The file whose errors likely cause the others goes first; the next one is picked after the
+/// following check.
+fn check_errors_message(
+    destination_project: &Project,
+    output: &CargoOutput,
+    changed_files: &str,
+) -> String {
+    let files = output.files_with_errors();
+    let Some(&(file, _)) = files.first() else {
+        return format!(
+            "Apparently there are some problems with the code. Please correct them. \
+            Files changed since the last check: {changed_files}. \
+            Here is the `cargo check` output:\n{}",
+            output.report()
+        );
+    };
+
+    let errors: Vec<&Diagnostic> = output
+        .errors()
+        .filter(|error| error.file.as_deref().is_none_or(|f| f == file))
+        .collect();
+    let mut message = format!(
+        "Apparently there are some problems with the code. \
+        Files changed since the last check: {changed_files}. \
+        Please correct the `cargo check` errors in `{file}` first:\n{}",
+        format_diagnostics(&errors)
+    );
+    message += &error_hints(&errors);
+    if let Ok(ReadFileResult {
+        contents: Some(contents),
+        ..
+    }) = destination_project.read_file(file)
+    {
+        message += &format!("\nCurrent contents of `{file}`:\n```\n{contents}\n```\n");
+    }
+    if files.len() > 1 {
+        let others: Vec<String> = files[1..]
+            .iter()
+            .map(|(file, count)| format!("`{file}` ({count})"))
+            .collect();
+        message += &format!(
+            "\nThe errors in other files will be shown once these are fixed: {}.",
+            others.join(", ")
+        );
+    }
+    message
+}
+
+/// Has the model write the fuzzing harnesses, unless they exist, and fix the discrepancies
+/// between the port and the original found with them.
+async fn fuzz_until_equivalent(
+    chat: &mut Chat,
+    source_project: &Project<ReadOnly>,
+    destination_project: &Project,
+    todo_list: &TodoList,
+    options: &WorkOptions,
+) -> Result<(), DispatchError> {
+    let destination = destination_project.path();
+    if !destination.join(PORTED_HARNESS).is_file() || !destination.join(ORIGINAL_HARNESS).is_file()
+    {
+        let message = HARNESS_PROMPT.to_string();
+        work_until_done(
+            chat,
+            message,
+            source_project,
+            destination_project,
+            todo_list,
+            options,
+        )
+        .await?;
+    }
+    for round in 1..=MAX_FUZZ_ROUNDS {
+        // The harness of the port is an example, so it's built with the other targets.
+        let message = match destination_project.run_build("dev") {
+            Ok(output) if !output.success => format!(
+                "The fuzzing harness doesn't build. Please fix it:\n{}",
+                output.report()
+            ),
+            Ok(_) => match run_fuzz(&options.fuzz, &options.source_copy, destination) {
+                Ok(discrepancies) if discrepancies.is_empty() => {
+                    info!(
+                        "The port matches the original on {} random inputs",
+                        options.fuzz.iterations
+                    );
+                    return Ok(());
+                }
+                Ok(discrepancies) => {
+                    info!(
+                        "Fuzzing round {round}: {} discrepancies",
+                        discrepancies.len()
+                    );
+                    format_discrepancies(&discrepancies, options.fuzz.iterations)
+                        + "\nThese are bugs of the port, unless the harnesses call the libraries \
+                        differently. Please fix them."
+                }
+                Err(e) => format!("The fuzzing harness doesn't work: {e}\nPlease fix it."),
+            },
+            Err(e) => {
+                error!("{e}");
+                return Ok(());
+            }
+        };
+        work_until_done(
+            chat,
+            message,
+            source_project,
+            destination_project,
+            todo_list,
+            options,
+        )
+        .await?;
+    }
+    warn!("The port still differs from the original after {MAX_FUZZ_ROUNDS} rounds of fuzzing");
+    Ok(())
+}
+
+/// Generates the integration tests comparing the command line interface of the port with the
+/// original and has the model fix the port until they pass. The original program is only run if
+/// the equivalence cases configure it.
+async fn check_cli_compat(
+    chat: &mut Chat,
+    source_project: &Project<ReadOnly>,
+    destination_project: &Project,
+    todo_list: &TodoList,
+    options: &WorkOptions,
+) -> Result<(), DispatchError> {
+    let Some(cli) = &options.cli else {
+        return Ok(());
+    };
+    let Some(binary) = destination_project.package_name() else {
+        warn!("Cannot generate the command line tests without the package name in Cargo.toml");
+        return Ok(());
+    };
+    match destination_project.write_file(TEST_FILE, &cli.test_file(&binary)) {
+        Ok(WriteFileResult { error: None, .. }) => {}
+        Ok(WriteFileResult { error: Some(e), .. }) | Err(TransientError(e)) => {
+            error!("Cannot write {TEST_FILE}: {e}");
+            return Ok(());
+        }
+    }
+    let env = original_env(&options.equivalence.original, &options.source_copy);
+    for round in 1..=MAX_CLI_ROUNDS {
+        let message = match destination_project.run_test_target(TEST_TARGET, &env) {
+            Ok(output) if output.success => {
+                info!("The command line interface matches the original");
+                return Ok(());
+            }
+            Ok(output) => {
+                info!("Command line compatibility round {round}: the tests fail");
+                format!(
+                    "The generated integration tests in `{TEST_FILE}` check that the port accepts \
+                    the flags and subcommands of the original program{}. They fail; please fix the \
+                    argument parsing of the port rather than the tests, except for the name of \
+                    the binary in `CARGO_BIN_EXE_` if it's wrong:\n{}",
+                    if env.is_empty() {
+                        ""
+                    } else {
+                        " and succeeds or fails with the same arguments as the original"
+                    },
+                    output.test_report()
+                )
+            }
+            Err(e) => {
+                error!("{e}");
+                return Ok(());
+            }
+        };
+        work_until_done(
+            chat,
+            message,
+            source_project,
+            destination_project,
+            todo_list,
+            options,
+        )
+        .await?;
+    }
+    warn!(
+        "The command line interface still differs from the original after {MAX_CLI_ROUNDS} rounds"
+    );
+    Ok(())
+}
+
+/// Has the model port the tests of the source project, one test file at a time, measuring how
+/// many of them pass after each file and reporting the progress at the end.
+async fn port_test_suite(
+    chat: &mut Chat,
+    source_project: &Project<ReadOnly>,
+    destination_project: &Project,
+    todo_list: &TodoList,
+    options: &WorkOptions,
+) -> Result<(), DispatchError> {
+    let tests = find_tests(source_project.path(), &source_project.list_contents().files);
+    if tests.is_empty() {
+        info!("No tests found in the source project");
+        return Ok(());
+    }
+    let mut files: Vec<&str> = tests.iter().map(|test| test.file.as_str()).collect();
+    files.dedup();
+    info!("Found {} tests in {} files", tests.len(), files.len());
+
+    let mut history: Vec<(String, TestSuiteProgress)> = vec![];
+    for file in files {
+        let names: Vec<String> = tests
+            .iter()
+            .filter(|test| test.file == file)
+            .map(|test| format!("`{}`", test.name))
+            .collect();
+        let message = format!(
+            "Now port the tests of `{file}` in the source project to Rust `#[test]` functions: {}. \
+            Name each Rust test after the original one in snake case, e.g. `test_parse_empty` \
+            for `TestParseEmpty`, and put it where such tests belong in Rust: unit tests in a \
+            `tests` module of the tested file, tests of the public API in `tests/`. Keep what the \
+            tests check; if a test fails, fix the port rather than the test.",
+            names.join(", ")
+        );
+        work_until_done(
+            chat,
+            message,
+            source_project,
+            destination_project,
+            todo_list,
+            options,
+        )
+        .await?;
+        match destination_project.run_tests() {
+            Ok(output) => {
+                let progress = TestSuiteProgress::measure(&tests, &output);
+                info!("Tests after porting {file}: {progress}");
+                history.push((file.to_string(), progress));
+            }
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    println!("==== Test suite ====");
+    for (file, progress) in &history {
+        println!("after {file}: {progress}");
+    }
+    Ok(())
+}
+
+/// Has the model rewrite the transliterated patterns of the ported files into idiomatic Rust, one
+/// file at a time in a new conversation. Each refactor is verified like any other changes before
+/// the next file, so that the behavior stays the same.
+async fn refactor_idioms(
+    chat: &mut Chat,
+    source_project: &Project<ReadOnly>,
+    destination_project: &Project,
+    todo_list: &TodoList,
+    options: &WorkOptions,
+) -> Result<(), DispatchError> {
+    let files = destination_project.list_contents().files;
+    for file in files.iter().filter(|file| file.ends_with(".rs")) {
+        let Ok(ReadFileResult {
+            contents: Some(contents),
+            ..
+        }) = destination_project.read_file(file)
+        else {
+            continue;
+        };
+        let findings = transliterated_patterns(&contents);
+        if findings.is_empty() {
+            continue;
+        }
+        info!("Refactoring {file}: {} findings", findings.len());
+        chat.reset();
+        let message = format!(
+            "The destination project compiles, but `{file}` still reads like a transliteration \
+            of the original code. Refactor it into idiomatic Rust without changing what it does: \
+            the tests must keep passing and the public API may only change where the callers in \
+            the project are updated too. These patterns were found:\n- {}\n\
+            Fix other transliterated patterns of the file as well, but don't restructure the \
+            project.",
+            findings.join("\n- ")
+        );
+        work_until_done(
+            chat,
+            message,
+            source_project,
+            destination_project,
+            todo_list,
+            options,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Has the model write a PyO3 extension module exposing the API of the original Python package
+/// on top of the port, so that the Python callers keep working.
+async fn write_python_shim(
+    chat: &mut Chat,
+    source_project: &Project<ReadOnly>,
+    destination_project: &Project,
+    todo_list: &TodoList,
+    options: &WorkOptions,
+) -> Result<(), DispatchError> {
+    let files = source_project.list_contents().files;
+    let api = python_api(source_project.path(), &files);
+    if api.is_empty() {
+        info!("The Python source has no public API to expose");
+        return Ok(());
+    }
+    let Some(crate_name) = destination_project.package_name() else {
+        warn!("Cannot generate the Python extension module without the package name in Cargo.toml");
+        return Ok(());
+    };
+    let package = package_name(&files).unwrap_or_else(|| crate_name.replace('-', "_"));
+    if let Err(e) = write_shim_scaffolding(destination_project.path(), &crate_name, &package) {
+        error!("{e}");
+        return Ok(());
+    }
+    info!("Generating the {package} Python extension module");
+    chat.reset();
+    let message = format!(
+        "The Python callers of the original `{package}` package should keep working with the \
+        port. A PyO3 extension crate was created in `{SHIM_DIR}/`, built with maturin as the \
+        `{package}` module. Now write `{SHIM_DIR}/src/lib.rs` so that it exposes this API of the \
+        original package with the same names, arguments and defaults, calling into the Rust \
+        crate:\n{}\n\
+        Use #[pyfunction] and #[pyclass] wrappers, submodules for the modules of the package, and \
+        turn Rust errors into the Python exceptions the original raised. Make the library target \
+        of `{crate_name}` export what the bindings need, and add `{SHIM_DIR}` to the workspace \
+        members in `Cargo.toml`.",
+        api.iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+    work_until_done(
+        chat,
+        message,
+        source_project,
+        destination_project,
+        todo_list,
+        options,
+    )
+    .await
+}
+
+/// Converts the modules of the plan one at a time in its order, each in a new conversation, so
+/// that the context doesn't grow with the project. The modules are verified like any other
+/// changes before the next one starts.
+async fn convert_by_plan(
+    chat: &mut Chat,
+    plan: &Plan,
+    source_project: &Project<ReadOnly>,
+    destination_project: &Project,
+    todo_list: &TodoList,
+    options: &WorkOptions,
+) -> Result<(), DispatchError> {
+    if options.skeletons && !options.dry_run {
+        write_skeletons(
+            chat,
+            plan,
+            source_project,
+            destination_project,
+            todo_list,
+            options,
+        )
+        .await?;
+    }
+    let mut converted: Vec<String> = vec![];
+    while let Some(module) = plan.next_pending(&converted) {
+        info!("Converting {}", module.destination);
+        chat.reset();
+        let message = format!(
+            "The project is converted to Rust module by module, following this conversion plan \
+            (x marks the converted modules):\n{plan}\n\
+            Now convert `{}` from {}: {}\n\
+            The converted modules are in the destination project, read them as needed, and use \
+            note_read to recall the decisions made so far. Create `Cargo.toml` first if it doesn't \
+            exist yet. Record decisions the other modules need to know with note_write, and fix \
+            the plan with plan_write if it turns out to be wrong. Mark the module done with \
+            plan_update once it's converted.{}{}{}{}",
+            module.destination,
+            module
+                .source
+                .iter()
+                .map(|file| format!("`{file}`"))
+                .collect::<Vec<_>>()
+                .join(", "),
+            module.description,
+            if options.skeletons {
+                " If the module has a skeleton generated from the headers, keep its signatures \
+                unless they are wrong and replace every todo!() with the port of the \
+                implementation."
+            } else {
+                ""
+            },
+            options
+                .sys_crate
+                .as_ref()
+                .map(|sys_crate| format!(" {}", sys_crate.instructions()))
+                .unwrap_or_default(),
+            options
+                .api
+                .as_ref()
+                .map(|api| api.items_of(&module.source))
+                .filter(|items| !items.is_empty())
+                .map(|items| format!(
+                    " The module is part of a library that must keep the public API of the \
+                    original: expose each of these items publicly under this name:\n{}",
+                    format_items(&items)
+                ))
+                .unwrap_or_default(),
+            // The interface is defined where the arguments are parsed.
+            options
+                .cli
+                .as_ref()
+                .filter(|_| {
+                    module.destination.ends_with("main.rs") || module.destination.contains("/bin/")
+                })
+                .map(|cli| format!(" {}", cli.instructions()))
+                .unwrap_or_default()
+        );
+        work_until_done(
+            chat,
+            message,
+            source_project,
+            destination_project,
+            todo_list,
+            options,
+        )
+        .await?;
+        // The module passed the verification even if the model didn't mark it.
+        let update = PlanUpdateArgs {
+            destination: module.destination.clone(),
+            status: ModuleStatus::Done,
+        };
+        if let Some(e) = plan.update(update).error {
+            warn!("{e}");
+        }
+        if let Some(sys_crate) = &options.sys_crate
+            && !options.dry_run
+        {
+            retire_native_code(sys_crate, &module.source, destination_project, options);
+        }
+        converted.push(module.destination);
+        println!("==== Plan ====\n{plan}");
+    }
 
-            let errors = destination_project.run_cargo_check();
-            if let Some(errors) = errors {
-                message = "Apparently there are some problems with the code. Please correct them. Here is the `cargo check` output:\n".to_string() + &errors;
+    chat.reset();
+    let mut message = format!(
+        "All modules of the conversion plan are converted:\n{plan}\n\
+        Now make sure the destination project is complete and matches the implementation in the \
+        source project directory: the module declarations, the entry points, `Cargo.toml` and \
+        anything the plan missed."
+    );
+    if let Some(sys_crate) = &options.sys_crate {
+        let name = sys_crate.name();
+        if sys_crate.is_ported() {
+            let edit = ManifestEdit::RemoveDependency {
+                name: name.clone(),
+                dev: None,
+            };
+            let error = match destination_project.edit_manifest("Cargo.toml", &[edit]) {
+                Ok(result) => result.error,
+                Err(e) => Some(e.0),
+            };
+            if let Some(e) = error {
+                warn!("Cannot remove the dependency on {name}: {e}");
+            }
+            match sys_crate.remove() {
+                Ok(()) => {
+                    info!("All the original code is ported, removed {name}");
+                    message += &format!(
+                        " The `{name}` crate linking the original code is removed, nothing may \
+                        use it anymore."
+                    );
+                }
+                Err(e) => warn!("{e}"),
+            }
+        } else {
+            message += &format!(
+                " Some original code is still linked through the `{name}` crate, port it and \
+                switch its callers to Rust."
+            );
+        }
+    }
+    work_until_done(
+        chat,
+        message,
+        source_project,
+        destination_project,
+        todo_list,
+        options,
+    )
+    .await
+}
+
+/// Writes the skeletons of the pending modules generated from the C and C++ headers, and has the
+/// model make the crate compile with them, so that the modules are then ported into a crate that
+/// already builds.
+async fn write_skeletons(
+    chat: &mut Chat,
+    plan: &Plan,
+    source_project: &Project<ReadOnly>,
+    destination_project: &Project,
+    todo_list: &TodoList,
+    options: &WorkOptions,
+) -> Result<(), DispatchError> {
+    let mut written = vec![];
+    for module in plan.read().modules {
+        // A resumed or incremental run keeps what was written before.
+        if module.status != ModuleStatus::Pending
+            || destination_project
+                .path()
+                .join(&module.destination)
+                .exists()
+        {
+            continue;
+        }
+        let Some(skeleton) = module_skeleton(source_project.path(), &module.source) else {
+            continue;
+        };
+        match destination_project.write_file(&module.destination, &skeleton) {
+            Ok(WriteFileResult { error: None, .. }) => {
+                written.push(format!("`{}`", module.destination));
+            }
+            Ok(WriteFileResult { error: Some(e), .. }) | Err(TransientError(e)) => {
+                warn!("Cannot write the skeleton of {}: {e}", module.destination);
+            }
+        }
+    }
+    if written.is_empty() {
+        return Ok(());
+    }
+    info!(
+        "Generated {} module skeletons from the headers",
+        written.len()
+    );
+    chat.reset();
+    let message = format!(
+        "Skeletons of {} were generated from the C and C++ headers of the source project: the \
+        types, the constants and the functions with todo!() bodies. Create `Cargo.toml` if it \
+        doesn't exist yet and declare the modules so that the crate compiles with the skeletons, \
+        fixing the types the generator got wrong. Don't port the function bodies yet, the modules \
+        are converted one by one next.",
+        written.join(", ")
+    );
+    work_until_done(
+        chat,
+        message,
+        source_project,
+        destination_project,
+        todo_list,
+        options,
+    )
+    .await
+}
+
+/// Removes the original files of a ported module from the `-sys` crate, and puts them back if
+/// the project doesn't pass the verification without them, e.g. because the remaining C code
+/// still calls the module.
+fn retire_native_code(
+    sys_crate: &SysCrate,
+    module_source: &[String],
+    destination_project: &Project,
+    options: &WorkOptions,
+) {
+    let retired = match sys_crate.retire(module_source) {
+        Ok(retired) if retired.is_empty() => return,
+        Ok(retired) => retired,
+        Err(e) => {
+            warn!("{e}");
+            return;
+        }
+    };
+    let build_script = PathBuf::from(sys_crate.name()).join("build.rs");
+    let Some(failure) = verify(
+        destination_project,
+        options,
+        &[&build_script],
+        &retired.join(", "),
+    ) else {
+        info!("Retired {} from {}", retired.join(", "), sys_crate.name());
+        return;
+    };
+    warn!(
+        "Keeping {} in {}, the project doesn't pass without them: {}",
+        retired.join(", "),
+        sys_crate.name(),
+        failure.subject
+    );
+    if let Err(e) = sys_crate.restore(&retired) {
+        warn!("{e}");
+    }
+}
+
+/// Builds the destination project in release mode and prints how fast the benchmarks run with
+/// the port compared to the original.
+fn compare_performance(
+    config: &BenchmarkConfig,
+    destination_project: &Project,
+    source_copy: &Path,
+) {
+    match destination_project.run_build("release") {
+        Ok(output) if !output.success => {
+            error!("Cannot build the benchmarks:\n{}", output.report());
+            return;
+        }
+        Ok(_) => {}
+        Err(e) => {
+            error!("{e}");
+            return;
+        }
+    }
+    info!("Running {} benchmarks", config.cases.len());
+    let results = run_benchmarks(config, source_copy, destination_project.path());
+    println!("==== Benchmarks ====\n{}", format_table(&results));
+}
+
+/// Waits for changes in the source project and asks the model to port them, until the
+/// process is interrupted.
+async fn watch_source(
+    chat: &mut Chat,
+    source_projects: &[Arc<Project<ReadOnly>>],
+    prefixes: &[String],
+    destination_project: &Project,
+    todo_list: &TodoList,
+    options: &WorkOptions,
+) {
+    let roots: Vec<&Path> = source_projects
+        .iter()
+        .map(|project| project.path())
+        .collect();
+    let watcher = match SourceWatcher::new(&roots) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            error!("{e}");
+            return;
+        }
+    };
+    info!("Watching the source project for changes");
+    loop {
+        // The watcher blocks, and nothing else runs in the meantime anyway.
+        let paths = match tokio::task::block_in_place(|| watcher.wait_for_changes()) {
+            Ok(paths) => paths,
+            Err(e) => {
+                error!("{e}");
+                return;
+            }
+        };
+        let mut changed = vec![];
+        let mut deleted = vec![];
+        for path in &paths {
+            let Some((project, prefix, file)) = source_projects
+                .iter()
+                .zip(prefixes)
+                .find_map(|(project, prefix)| Some((project, prefix, project.visible_path(path)?)))
+            else {
                 continue;
+            };
+            let exists = project.path().join(&file).exists();
+            // With several source projects, the files are named after their function prefix.
+            let file = if source_projects.len() == 1 {
+                file
+            } else {
+                format!("{prefix}:{file}")
+            };
+            if exists {
+                changed.push(file);
+            } else {
+                deleted.push(file);
+            }
+        }
+        if changed.is_empty() && deleted.is_empty() {
+            continue;
+        }
+        info!(
+            "Source files changed: {}",
+            changed
+                .iter()
+                .chain(&deleted)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        let mut message = "The source project has changed since the conversion. \
+            Update only the parts of the Rust project that correspond to these files."
+            .to_string();
+        if !changed.is_empty() {
+            message += &format!("\nChanged or added files: {}.", changed.join(", "));
+        }
+        if !deleted.is_empty() {
+            message += &format!("\nDeleted files: {}.", deleted.join(", "));
+        }
+        let result = work_until_done(
+            chat,
+            message,
+            &source_projects[0],
+            destination_project,
+            todo_list,
+            options,
+        )
+        .await;
+        if let Err(e) = result {
+            error!("{}", e.reason());
+        }
+    }
+}
+
+/// Registers the functions reading a source project. Their names start with `prefix`, e.g.
+/// `src_read_file`.
+async fn add_source_functions(
+    chat: &mut Chat,
+    source_project: &Arc<Project<ReadOnly>>,
+    destination_project: &Arc<Project>,
+    prefix: &str,
+    semantic_search: bool,
+    embeddings_cache: PathBuf,
+) {
+    let project = source_project.clone();
+    add_function!(chat.functions,
+        /// List all files in the source project directory.
+        fn [format!("{prefix}_list_files")](_: ()) => project.list_contents()
+    );
+
+    let project = source_project.clone();
+    add_function!(chat.functions,
+        /// Shows the source project directory as a tree with file sizes and line counts.
+        /// Prefer it to the flat file list to get an overview of a large project.
+        fn [format!("{prefix}_tree")](args: TreeArgs) => project.tree(&args)
+    );
+
+    let project = source_project.clone();
+    add_function!(chat.functions,
+        /// Counts files, lines and blank lines per language in the source project.
+        /// Use it to estimate the size of the conversion and plan it.
+        fn [format!("{prefix}_language_stats")](_: ()) => project.language_stats()
+    );
+
+    let project = source_project.clone();
+    add_function!(chat.functions,
+        /// Lists functions, classes, types and globals defined in the source files with their
+        /// line numbers. Supports C, C++, Python, Go, Java and JavaScript.
+        /// Use it to plan the module structure without reading every file.
+        fn [format!("{prefix}_symbols")](args: SymbolsArgs) => project.symbols(&args)
+    );
+
+    let project = source_project.clone();
+    add_function!(chat.functions,
+        /// Finds where an identifier is defined and used in the source project.
+        /// Use it to see how a function or type is used before porting it.
+        fn [format!("{prefix}_find_references")](args: FindReferencesArgs) => {
+            project.find_references(&args)
+        }
+    );
+
+    if semantic_search {
+        let model = env::var("EMBEDDING_MODEL").unwrap_or(DEFAULT_EMBEDDING_MODEL.to_string());
+        let index = SemanticIndex::build(
+            model,
+            chat.credentials.clone(),
+            source_project.path(),
+            &source_project.list_contents().files,
+            embeddings_cache,
+        )
+        .await;
+        match index {
+            Ok(index) => {
+                add_function!(chat.functions,
+                    /// Finds the parts of the source files most related to a description.
+                    /// Use it to find the code relevant to the module you are porting.
+                    fn [format!("{prefix}_semantic_search")](args: SemanticSearchArgs) => index.search(&args)
+                );
             }
+            Err(e) => warn!("Semantic search is disabled: {e}"),
         }
-        break;
+    }
+
+    let project = source_project.clone();
+    add_function!(chat.functions,
+        /// Finds which source files include or import which, and orders the files so that
+        /// dependencies go first. Port the files in this order, starting from the leaves.
+        fn [format!("{prefix}_dependencies")](_: ()) => project.dependency_graph()
+    );
+
+    let project = source_project.clone();
+    add_function!(chat.functions,
+        /// Returns the license, license files, authors, repository, version and description of
+        /// the source project. They are copied to the destination once it has a Cargo.toml.
+        fn [format!("{prefix}_metadata")](_: ()) => project.metadata()
+    );
+
+    let project = source_project.clone();
+    add_function!(chat.functions,
+        /// Reads the contents of a file in the source project directory.
+        retry fn [format!("{prefix}_read_file")](args: ReadFileArgs) => project.read_file(&args.path)
+    );
+
+    let project = source_project.clone();
+    add_function!(chat.functions,
+        /// Returns the size, line count, language and modification time of a file in the source
+        /// project directory without reading it.
+        retry fn [format!("{prefix}_stat")](args: StatArgs) => project.stat(&args.path)
+    );
+
+    let project = source_project.clone();
+    add_function!(chat.functions,
+        /// Reads a range of lines of a file in the source project directory.
+        /// Lines are prefixed with their numbers. Use it to explore large files.
+        retry fn [format!("{prefix}_read_file_range")](args: ReadFileRangeArgs) => {
+            project.read_file_range(&args.path, args.start_line, args.end_line)
+        }
+    );
+
+    let project = source_project.clone();
+    add_function!(chat.functions,
+        /// Searches the source project for lines matching a regular expression.
+        /// Use it to find where a symbol is defined or used without reading every file.
+        fn [format!("{prefix}_grep")](args: GrepArgs) => project.grep(&args)
+    );
+
+    let copy_asset_name = if prefix == "src" {
+        "copy_asset".to_string()
+    } else {
+        format!("{prefix}_copy_asset")
+    };
+    let project = destination_project.clone();
+    let source = source_project.clone();
+    add_function!(chat.functions,
+        /// Copies a file from the source project directory to the destination project directory
+        /// verbatim. Use it for binary files and assets that don't need conversion.
+        retry fn [copy_asset_name](args: CopyAssetArgs) => {
+            project.copy_file_from(&source, &args.source_path, &args.destination_path)
+        }
+    )
+    .mutating();
+
+    let compare_files_name = if prefix == "src" {
+        "compare_files".to_string()
+    } else {
+        format!("{prefix}_compare_files")
+    };
+    let project = destination_project.clone();
+    let source = source_project.clone();
+    add_function!(chat.functions,
+        /// Compares a source file with its Rust port: lists the functions, types and globals of
+        /// the source file that have no Rust item of a similar name, and optionally returns a
+        /// unified diff. Use it to check that nothing was dropped during the port.
+        retry fn [compare_files_name](args: CompareFilesArgs) => project.compare_file(&source, &args)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cargo::parse_diagnostics;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn check_failure_fingerprint() {
+        let dir = TempDir::new("check-failure");
+        let project = Project::new(dir.to_path_buf());
+        project
+            .write_file("src/a.rs", "fn a() -> u32 {}\n")
+            .unwrap();
+        let output = |line: usize| CargoOutput {
+            success: false,
+            diagnostics: parse_diagnostics(&format!(
+                r#"{{"reason":"compiler-message","message":{{"rendered":"","message":"mismatched types","code":{{"code":"E0308","explanation":null}},"level":"error","spans":[{{"file_name":"src/a.rs","line_start":{line},"column_start":11,"is_primary":true}}],"children":[]}}}}"#
+            )),
+            test_failures: vec![],
+            passed_tests: vec![],
+            truncated: false,
+            stderr: String::new(),
+        };
+
+        // The prompts differ in the changed files and the lines, the failure is the same.
+        let a = check_failure(&project, &output(1), "src/a.rs");
+        let b = check_failure(&project, &output(2), "src/a.rs, src/b.rs");
+        assert_ne!(a.message, b.message);
+        assert_eq!(a.subject, "src/a.rs");
+        assert_eq!(a.fingerprint, b.fingerprint);
     }
 }
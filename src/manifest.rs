@@ -0,0 +1,259 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use toml_edit::{Array, ArrayOfTables, DocumentMut, InlineTable, Item, Table, value};
+
+const EDITIONS: &[&str] = &["2015", "2018", "2021", "2024"];
+
+#[derive(Deserialize, JsonSchema)]
+pub struct EditManifestArgs {
+    /// a relative path to the manifest in the project directory, `Cargo.toml` by default
+    pub path: Option<String>,
+    /// changes applied in order
+    pub edits: Vec<ManifestEdit>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ManifestEdit {
+    /// adds a dependency or replaces an existing one with the same name
+    AddDependency {
+        name: String,
+        /// version requirement, e.g. "1.0"
+        version: Option<String>,
+        /// features of the dependency to enable
+        features: Option<Vec<String>>,
+        /// true if the dependency is only enabled by a feature
+        optional: Option<bool>,
+        /// a relative path to a local crate instead of a version
+        path: Option<String>,
+        /// add to `[dev-dependencies]` instead of `[dependencies]`
+        dev: Option<bool>,
+    },
+    RemoveDependency {
+        name: String,
+        /// remove from `[dev-dependencies]` instead of `[dependencies]`
+        dev: Option<bool>,
+    },
+    /// defines a feature of the crate in the `[features]` table
+    SetFeature {
+        name: String,
+        /// features and optional dependencies enabled by this feature, e.g. "dep:serde"
+        enables: Vec<String>,
+    },
+    SetEdition {
+        /// one of 2015, 2018, 2021, 2024
+        edition: String,
+    },
+    /// sets the given fields of the `[package]` table
+    SetPackageMetadata {
+        /// SPDX license expression, e.g. "MIT OR Apache-2.0"
+        license: Option<String>,
+        authors: Option<Vec<String>>,
+        repository: Option<String>,
+        version: Option<String>,
+        description: Option<String>,
+    },
+    /// adds a binary target or replaces an existing one with the same name
+    AddBin {
+        name: String,
+        /// a relative path to the main file, e.g. "src/bin/tool.rs"
+        path: String,
+    },
+    /// configures the library target
+    SetLib {
+        name: Option<String>,
+        /// a relative path to the library root, e.g. "src/lib.rs"
+        path: Option<String>,
+        /// e.g. ["cdylib", "rlib"]
+        crate_type: Option<Vec<String>>,
+    },
+}
+
+/// Applies the edits to the manifest, keeping its formatting and comments.
+pub fn edit_manifest(manifest: &str, edits: &[ManifestEdit]) -> Result<String, String> {
+    let mut doc: DocumentMut = manifest
+        .parse()
+        .map_err(|e| format!("Cannot parse the manifest: {e}"))?;
+    for edit in edits {
+        apply_edit(&mut doc, edit)?;
+    }
+    Ok(doc.to_string())
+}
+
+fn apply_edit(doc: &mut DocumentMut, edit: &ManifestEdit) -> Result<(), String> {
+    match edit {
+        ManifestEdit::AddDependency {
+            name,
+            version,
+            features,
+            optional,
+            path,
+            dev,
+        } => {
+            let dependency = match (version, features, optional, path) {
+                (Some(version), None, None | Some(false), None) => value(version),
+                (None, _, _, None) => {
+                    return Err(format!("Specify a version or a path of {name}."));
+                }
+                _ => {
+                    let mut table = InlineTable::new();
+                    if let Some(version) = version {
+                        table.insert("version", version.into());
+                    }
+                    if let Some(path) = path {
+                        table.insert("path", path.into());
+                    }
+                    if let Some(features) = features {
+                        table.insert("features", string_array(features).into());
+                    }
+                    if *optional == Some(true) {
+                        table.insert("optional", true.into());
+                    }
+                    value(table)
+                }
+            };
+            table_mut(doc, dependencies_table(*dev))?.insert(name, dependency);
+        }
+        ManifestEdit::RemoveDependency { name, dev } => {
+            let table_name = dependencies_table(*dev);
+            let removed = table_mut(doc, table_name)?.remove(name);
+            if removed.is_none() {
+                return Err(format!("{name} is not in [{table_name}]."));
+            }
+        }
+        ManifestEdit::SetFeature { name, enables } => {
+            table_mut(doc, "features")?.insert(name, value(string_array(enables)));
+        }
+        ManifestEdit::SetEdition { edition } => {
+            if !EDITIONS.contains(&edition.as_str()) {
+                return Err(format!("Unknown edition {edition}."));
+            }
+            table_mut(doc, "package")?.insert("edition", value(edition));
+        }
+        ManifestEdit::SetPackageMetadata {
+            license,
+            authors,
+            repository,
+            version,
+            description,
+        } => {
+            let package = table_mut(doc, "package")?;
+            for (key, field) in [
+                ("version", version),
+                ("description", description),
+                ("license", license),
+                ("repository", repository),
+            ] {
+                if let Some(field) = field {
+                    package.insert(key, value(field));
+                }
+            }
+            if let Some(authors) = authors {
+                package.insert("authors", value(string_array(authors)));
+            }
+        }
+        ManifestEdit::AddBin { name, path } => {
+            let bins = doc
+                .entry("bin")
+                .or_insert(Item::ArrayOfTables(ArrayOfTables::new()))
+                .as_array_of_tables_mut()
+                .ok_or("bin is not an array of tables.")?;
+            bins.retain(|bin| bin.get("name").and_then(Item::as_str) != Some(name));
+            let mut bin = Table::new();
+            bin.insert("name", value(name));
+            bin.insert("path", value(path));
+            bins.push(bin);
+        }
+        ManifestEdit::SetLib {
+            name,
+            path,
+            crate_type,
+        } => {
+            let lib = table_mut(doc, "lib")?;
+            if let Some(name) = name {
+                lib.insert("name", value(name));
+            }
+            if let Some(path) = path {
+                lib.insert("path", value(path));
+            }
+            if let Some(crate_type) = crate_type {
+                lib.insert("crate-type", value(string_array(crate_type)));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn dependencies_table(dev: Option<bool>) -> &'static str {
+    if dev == Some(true) {
+        "dev-dependencies"
+    } else {
+        "dependencies"
+    }
+}
+
+/// Returns a top-level table, creating it if needed.
+fn table_mut<'a>(doc: &'a mut DocumentMut, name: &str) -> Result<&'a mut Table, String> {
+    doc.entry(name)
+        .or_insert(Item::Table(Table::new()))
+        .as_table_mut()
+        .ok_or_else(|| format!("{name} is not a table."))
+}
+
+fn string_array(strings: &[String]) -> Array {
+    strings.iter().map(String::as_str).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit() {
+        let manifest = "[package]\nname = \"app\" # the name\nedition = \"2021\"\n\n\
+            [dependencies]\nlibc = \"0.2\"\n";
+        let edits: Vec<ManifestEdit> = serde_json::from_str(
+            r#"[
+                {"action": "set_edition", "edition": "2024"},
+                {"action": "remove_dependency", "name": "libc"},
+                {"action": "add_dependency", "name": "anyhow", "version": "1"},
+                {"action": "add_dependency", "name": "serde", "version": "1", "features": ["derive"], "optional": true},
+                {"action": "add_dependency", "name": "tempfile", "version": "3", "dev": true},
+                {"action": "set_feature", "name": "serialize", "enables": ["dep:serde"]},
+                {"action": "add_bin", "name": "tool", "path": "src/bin/tool.rs"},
+                {"action": "set_lib", "crate_type": ["cdylib", "rlib"]}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            edit_manifest(manifest, &edits).unwrap(),
+            "[package]\nname = \"app\" # the name\nedition = \"2024\"\n\n\
+            [dependencies]\nanyhow = \"1\"\n\
+            serde = { version = \"1\", features = [\"derive\"], optional = true }\n\n\
+            [dev-dependencies]\ntempfile = \"3\"\n\n\
+            [features]\nserialize = [\"dep:serde\"]\n\n\
+            [[bin]]\nname = \"tool\"\npath = \"src/bin/tool.rs\"\n\n\
+            [lib]\ncrate-type = [\"cdylib\", \"rlib\"]\n"
+        );
+
+        let edits = [ManifestEdit::RemoveDependency {
+            name: "rand".to_string(),
+            dev: None,
+        }];
+        assert!(edit_manifest(manifest, &edits).is_err());
+        assert!(edit_manifest("[package", &[]).is_err());
+
+        let edits = [ManifestEdit::SetPackageMetadata {
+            license: Some("MIT".to_string()),
+            authors: Some(vec!["Ann".to_string()]),
+            repository: None,
+            version: None,
+            description: None,
+        }];
+        assert_eq!(
+            edit_manifest("[package]\nname = \"app\"\n", &edits).unwrap(),
+            "[package]\nname = \"app\"\nlicense = \"MIT\"\nauthors = [\"Ann\"]\n"
+        );
+    }
+}
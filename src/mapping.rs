@@ -0,0 +1,379 @@
+use crate::plan::DependencyMapping;
+use regex::Regex;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// `require github.com/a/b v1.2.3` in `go.mod`, inside or outside a block.
+static GO_REQUIRE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^(?:require\s+|\s+)([\w.\-/]+\.[\w.\-/]+)\s+v[\w.\-+]+(.*)$").unwrap()
+});
+/// A `<dependency>` of `pom.xml`.
+static MAVEN_DEPENDENCY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<dependency>(.*?)</dependency>").unwrap());
+static MAVEN_ARTIFACT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<artifactId>\s*([^<\s]+)\s*</artifactId>").unwrap());
+/// `implementation("group:artifact:1.0")` and the like in `build.gradle(.kts)`.
+static GRADLE_DEPENDENCY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?m)^\s*(?:implementation|api|compile)\s*\(?\s*["']([\w.\-]+):([\w.\-]+)"#)
+        .unwrap()
+});
+/// `find_package(ZLIB REQUIRED)` and `pkg_check_modules(CURL REQUIRED libcurl)` in CMake.
+static CMAKE_PACKAGE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(?:find_package\s*\(\s*(\w+)|pkg_check_modules\s*\(\s*\w+(?:\s+(?:REQUIRED|QUIET|IMPORTED_TARGET))*\s+([\w\-+]+))").unwrap()
+});
+
+/// Rust replacements of common dependencies of other languages; `None` means that the standard
+/// library covers it. Names are compared in lower case.
+const BUILTIN_MAPPINGS: &[(&str, Option<&str>)] = &[
+    // Python
+    ("requests", Some("reqwest")),
+    ("httpx", Some("reqwest")),
+    ("numpy", Some("ndarray")),
+    ("pyyaml", Some("serde_yaml")),
+    ("toml", Some("toml")),
+    ("click", Some("clap")),
+    ("typer", Some("clap")),
+    ("flask", Some("axum")),
+    ("fastapi", Some("axum")),
+    ("pydantic", Some("serde")),
+    ("python-dateutil", Some("chrono")),
+    ("pytz", Some("chrono-tz")),
+    ("regex", Some("regex")),
+    ("tqdm", Some("indicatif")),
+    ("colorama", None),
+    ("six", None),
+    // JavaScript
+    ("axios", Some("reqwest")),
+    ("node-fetch", Some("reqwest")),
+    ("express", Some("axum")),
+    ("commander", Some("clap")),
+    ("yargs", Some("clap")),
+    ("lodash", None),
+    ("uuid", Some("uuid")),
+    ("dotenv", Some("dotenvy")),
+    ("chalk", Some("colored")),
+    ("js-yaml", Some("serde_yaml")),
+    ("moment", Some("chrono")),
+    ("dayjs", Some("chrono")),
+    ("ws", Some("tokio-tungstenite")),
+    ("koa", Some("axum")),
+    ("fastify", Some("axum")),
+    ("got", Some("reqwest")),
+    ("minimist", Some("clap")),
+    ("meow", Some("clap")),
+    ("inquirer", Some("dialoguer")),
+    ("prompts", Some("dialoguer")),
+    ("ora", Some("indicatif")),
+    ("cli-progress", Some("indicatif")),
+    ("glob", Some("glob")),
+    ("fast-glob", Some("glob")),
+    ("debug", Some("log")),
+    ("winston", Some("tracing")),
+    ("pino", Some("tracing")),
+    ("semver", Some("semver")),
+    ("yaml", Some("serde_yaml")),
+    ("marked", Some("pulldown-cmark")),
+    ("cheerio", Some("scraper")),
+    ("ajv", Some("jsonschema")),
+    ("fs-extra", None),
+    ("rimraf", None),
+    ("mkdirp", None),
+    ("bluebird", None),
+    ("tslib", None),
+    // Go
+    ("github.com/spf13/cobra", Some("clap")),
+    ("github.com/urfave/cli", Some("clap")),
+    ("gopkg.in/yaml.v3", Some("serde_yaml")),
+    ("gopkg.in/yaml.v2", Some("serde_yaml")),
+    ("github.com/google/uuid", Some("uuid")),
+    ("github.com/pkg/errors", Some("anyhow")),
+    ("github.com/sirupsen/logrus", Some("log")),
+    ("go.uber.org/zap", Some("tracing")),
+    ("github.com/gorilla/mux", Some("axum")),
+    ("github.com/gin-gonic/gin", Some("axum")),
+    ("golang.org/x/sync", None),
+    // Java and Kotlin
+    ("gson", Some("serde_json")),
+    ("jackson-databind", Some("serde_json")),
+    ("slf4j-api", Some("log")),
+    ("logback-classic", Some("env_logger")),
+    ("guava", None),
+    ("commons-lang3", None),
+    ("picocli", Some("clap")),
+    ("okhttp", Some("reqwest")),
+    ("kotlinx-coroutines-core", Some("tokio")),
+    // C and C++ libraries found by CMake
+    ("zlib", Some("flate2")),
+    ("openssl", Some("openssl")),
+    ("curl", Some("reqwest")),
+    ("libcurl", Some("reqwest")),
+    ("sqlite3", Some("rusqlite")),
+    ("png", Some("png")),
+    ("jpeg", Some("image")),
+    ("threads", None),
+    ("boost", None),
+    ("fmt", None),
+    ("nlohmann_json", Some("serde_json")),
+    ("spdlog", Some("log")),
+];
+
+/// A third-party dependency declared in a manifest of the source project.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceDependency {
+    /// e.g. "requests", "github.com/spf13/cobra" or the artifact id of a Maven dependency
+    pub name: String,
+    /// the manifest declaring it, e.g. "requirements.txt"
+    pub manifest: &'static str,
+}
+
+/// Collects the runtime dependencies from the manifests in the root of the source project:
+/// `requirements.txt`, `pyproject.toml`, `package.json`, `go.mod`, `pom.xml`, `build.gradle` and
+/// `CMakeLists.txt`. Test and development dependencies are left out.
+pub fn source_dependencies(root: &Path) -> Vec<SourceDependency> {
+    let read = |name: &str| std::fs::read_to_string(root.join(name)).ok();
+    let mut dependencies = vec![];
+    let mut add = |name: &str, manifest: &'static str| {
+        let name = name.trim();
+        if !name.is_empty()
+            && !dependencies
+                .iter()
+                .any(|d: &SourceDependency| d.name == name)
+        {
+            dependencies.push(SourceDependency {
+                name: name.to_string(),
+                manifest,
+            });
+        }
+    };
+
+    if let Some(text) = read("requirements.txt") {
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if !line.starts_with('-') {
+                add(python_requirement(line), "requirements.txt");
+            }
+        }
+    }
+    if let Some(text) = read("pyproject.toml")
+        && let Ok(toml) = toml::from_str::<toml::Table>(&text)
+    {
+        let project = toml.get("project").and_then(|p| p.get("dependencies"));
+        for requirement in project
+            .and_then(toml::Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            if let Some(requirement) = requirement.as_str() {
+                add(python_requirement(requirement), "pyproject.toml");
+            }
+        }
+        let poetry = toml
+            .get("tool")
+            .and_then(|tool| tool.get("poetry")?.get("dependencies")?.as_table());
+        for name in poetry.into_iter().flat_map(|table| table.keys()) {
+            if name != "python" {
+                add(name, "pyproject.toml");
+            }
+        }
+    }
+    if let Some(text) = read("package.json")
+        && let Ok(json) = serde_json::from_str::<serde_json::Value>(&text)
+        && let Some(packages) = json["dependencies"].as_object()
+    {
+        // Type declarations have no runtime counterpart.
+        for name in packages.keys().filter(|name| !name.starts_with("@types/")) {
+            add(name, "package.json");
+        }
+    }
+    if let Some(text) = read("go.mod") {
+        for captures in GO_REQUIRE.captures_iter(&text) {
+            if !captures[2].contains("// indirect") {
+                add(&captures[1], "go.mod");
+            }
+        }
+    }
+    if let Some(text) = read("pom.xml") {
+        for dependency in MAVEN_DEPENDENCY.captures_iter(&text) {
+            if dependency[1].contains("<scope>test</scope>") {
+                continue;
+            }
+            if let Some(artifact) = MAVEN_ARTIFACT.captures(&dependency[1]) {
+                add(&artifact[1], "pom.xml");
+            }
+        }
+    }
+    for manifest in ["build.gradle", "build.gradle.kts"] {
+        if let Some(text) = read(manifest) {
+            for captures in GRADLE_DEPENDENCY.captures_iter(&text) {
+                add(&captures[2], "build.gradle");
+            }
+        }
+    }
+    if let Some(text) = read("CMakeLists.txt") {
+        for captures in CMAKE_PACKAGE.captures_iter(&text) {
+            if let Some(name) = captures.get(1).or(captures.get(2)) {
+                add(name.as_str(), "CMakeLists.txt");
+            }
+        }
+    }
+    dependencies
+}
+
+/// Strips the version, the extras and the markers from a requirement like
+/// `requests[socks]>=2.0; python_version > "3.8"`.
+fn python_requirement(requirement: &str) -> &str {
+    let end = requirement
+        .find(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_' || c == '.'))
+        .unwrap_or(requirement.len());
+    &requirement[..end]
+}
+
+/// Proposes a crate for each dependency: from the built-in table if it's known, otherwise the
+/// best match `search` finds on crates.io, if any.
+pub fn propose_mappings<F>(dependencies: &[SourceDependency], search: F) -> Vec<DependencyMapping>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    dependencies
+        .iter()
+        .map(|dependency| {
+            let name = dependency.name.to_lowercase();
+            if let Some((_, rust)) = BUILTIN_MAPPINGS.iter().find(|(source, _)| *source == name) {
+                return DependencyMapping {
+                    source: dependency.name.clone(),
+                    rust: rust.map(str::to_string),
+                    notes: Some(match rust {
+                        Some(_) => format!("from {}, the usual replacement", dependency.manifest),
+                        None => format!(
+                            "from {}, the standard library or the converted code covers it",
+                            dependency.manifest
+                        ),
+                    }),
+                };
+            }
+            // Go modules and Maven artifacts are searched by their last part.
+            let query = name.rsplit('/').next().unwrap_or(&name);
+            let rust = search(query);
+            DependencyMapping {
+                source: dependency.name.clone(),
+                notes: Some(match &rust {
+                    Some(_) => format!(
+                        "from {}, the best crates.io match for the name, check it",
+                        dependency.manifest
+                    ),
+                    None => format!("from {}, no replacement found", dependency.manifest),
+                }),
+                rust,
+            }
+        })
+        .collect()
+}
+
+/// Formats the crates of the mappings as a `[dependencies]` section of `Cargo.toml`, with the
+/// versions `version` finds, for the operator to review.
+pub fn dependencies_section<F>(mappings: &[DependencyMapping], version: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut crates: Vec<&str> = mappings
+        .iter()
+        .filter_map(|mapping| mapping.rust.as_deref())
+        .collect();
+    crates.sort();
+    crates.dedup();
+    let mut section = "[dependencies]\n".to_string();
+    for name in crates {
+        let sources: Vec<&str> = mappings
+            .iter()
+            .filter(|mapping| mapping.rust.as_deref() == Some(name))
+            .map(|mapping| mapping.source.as_str())
+            .collect();
+        let version = version(name).unwrap_or_else(|| "*".to_string());
+        section += &format!("{name} = \"{version}\" # {}\n", sources.join(", "));
+    }
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn parse_manifests() {
+        let dir = TempDir::new("mapping");
+        std::fs::write(
+            dir.join("requirements.txt"),
+            "# runtime\nrequests[socks]>=2.0\n-r dev.txt\nnumpy==1.26 ; python_version > \"3.8\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("package.json"),
+            r#"{"dependencies": {"express": "^4.0.0", "@types/node": "^20"}, "devDependencies": {"jest": "^29"}}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("go.mod"),
+            "module example.com/tool\n\nrequire (\n\tgithub.com/spf13/cobra v1.8.0\n\tgolang.org/x/sys v0.1.0 // indirect\n)\nrequire github.com/acme/widgets v0.2.0\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("pom.xml"),
+            "<dependencies><dependency><groupId>com.google.code.gson</groupId><artifactId>gson</artifactId></dependency>\
+            <dependency><artifactId>junit</artifactId><scope>test</scope></dependency></dependencies>",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("CMakeLists.txt"),
+            "find_package(ZLIB REQUIRED)\npkg_check_modules(CURL REQUIRED libcurl)\n",
+        )
+        .unwrap();
+
+        let names: Vec<String> = source_dependencies(&dir)
+            .into_iter()
+            .map(|dependency| dependency.name)
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "requests",
+                "numpy",
+                "express",
+                "github.com/spf13/cobra",
+                "github.com/acme/widgets",
+                "gson",
+                "ZLIB",
+                "libcurl"
+            ]
+        );
+    }
+
+    #[test]
+    fn propose() {
+        let dependency = |name: &str| SourceDependency {
+            name: name.to_string(),
+            manifest: "go.mod",
+        };
+        let dependencies = [
+            dependency("github.com/spf13/cobra"),
+            dependency("github.com/acme/widgets"),
+            dependency("golang.org/x/sync"),
+            dependency("github.com/acme/unknown"),
+        ];
+        let mappings = propose_mappings(&dependencies, |query| {
+            (query == "widgets").then(|| "widgets-rs".to_string())
+        });
+        let crates: Vec<Option<&str>> = mappings.iter().map(|m| m.rust.as_deref()).collect();
+        assert_eq!(crates, [Some("clap"), Some("widgets-rs"), None, None]);
+        assert!(mappings[1].notes.as_deref().unwrap().contains("check it"));
+
+        let section = dependencies_section(&mappings, |name| {
+            (name == "clap").then(|| "4.5.4".to_string())
+        });
+        assert_eq!(
+            section,
+            "[dependencies]\nclap = \"4.5.4\" # github.com/spf13/cobra\n\
+            widgets-rs = \"*\" # github.com/acme/widgets\n"
+        );
+    }
+}
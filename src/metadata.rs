@@ -0,0 +1,354 @@
+use crate::function::TransientError;
+use crate::manifest::ManifestEdit;
+use crate::project::Project;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// Prefixes of license files at the project root, compared case-insensitively.
+const LICENSE_FILES: &[&str] = &["license", "licence", "copying", "notice", "unlicense"];
+
+static CMAKE_PROJECT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)\bproject\s*\(([^)]*)\)").unwrap());
+static CMAKE_VERSION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\bVERSION\s+([\d.]+)").unwrap());
+static CMAKE_DESCRIPTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)\bDESCRIPTION\s+"([^"]*)""#).unwrap());
+static GO_MODULE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?m)^module\s+(\S+)").unwrap());
+static GIT_ORIGIN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?m)^\[remote "origin"\]\s*(?:^\s*\w+\s*=.*\n)*?^\s*url\s*=\s*(\S+)"#).unwrap()
+});
+static SCP_URL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^git@([\w.-]+):(.+?)(?:\.git)?$").unwrap());
+
+/// License, authorship and version of the source project, found in its license files and
+/// package manifests.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct SourceMetadata {
+    /// license files at the project root
+    pub license_files: Vec<String>,
+    /// SPDX license expression, e.g. "MIT"
+    pub license: Option<String>,
+    pub authors: Vec<String>,
+    pub repository: Option<String>,
+    /// version in the semver format
+    pub version: Option<String>,
+    pub description: Option<String>,
+}
+
+impl SourceMetadata {
+    /// Fills the missing fields with the ones from another source.
+    fn merge(&mut self, other: SourceMetadata) {
+        self.license = self.license.take().or(other.license);
+        if self.authors.is_empty() {
+            self.authors = other.authors;
+        }
+        self.repository = self.repository.take().or(other.repository);
+        self.version = self.version.take().or(other.version);
+        self.description = self.description.take().or(other.description);
+    }
+
+    fn manifest_edit(&self) -> ManifestEdit {
+        ManifestEdit::SetPackageMetadata {
+            license: self.license.clone(),
+            authors: Some(self.authors.clone()).filter(|authors| !authors.is_empty()),
+            repository: self.repository.clone(),
+            version: self.version.clone(),
+            description: self.description.clone(),
+        }
+    }
+}
+
+/// Collects the metadata of the project in `root`.
+///
+/// The files are read directly, since license files and `.git` are hidden from the model.
+pub fn source_metadata(root: &Path) -> SourceMetadata {
+    let read = |name: &str| std::fs::read_to_string(root.join(name)).ok();
+
+    let mut metadata = SourceMetadata::default();
+    if let Ok(entries) = std::fs::read_dir(root) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let lower = name.to_lowercase();
+            if entry.path().is_file() && LICENSE_FILES.iter().any(|p| lower.starts_with(p)) {
+                metadata.license_files.push(name);
+            }
+        }
+    }
+    metadata.license_files.sort();
+    metadata.license = metadata
+        .license_files
+        .iter()
+        .find_map(|name| read(name).and_then(|text| detect_license(&text)));
+
+    if let Some(text) = read("package.json") {
+        metadata.merge(package_json(&text));
+    }
+    if let Some(text) = read("pyproject.toml") {
+        metadata.merge(pyproject(&text));
+    }
+    if let Some(text) = read("CMakeLists.txt")
+        && let Some(project) = CMAKE_PROJECT.captures(&text)
+    {
+        metadata.merge(SourceMetadata {
+            version: CMAKE_VERSION
+                .captures(&project[1])
+                .map(|c| c[1].to_string()),
+            description: CMAKE_DESCRIPTION
+                .captures(&project[1])
+                .map(|c| c[1].to_string()),
+            ..Default::default()
+        });
+    }
+    if let Some(text) = read("go.mod")
+        && let Some(module) = GO_MODULE.captures(&text)
+        && ["github.com/", "gitlab.com/", "bitbucket.org/"]
+            .iter()
+            .any(|host| module[1].starts_with(host))
+    {
+        metadata.merge(SourceMetadata {
+            repository: Some(format!("https://{}", &module[1])),
+            ..Default::default()
+        });
+    }
+    if let Some(text) = read(".git/config")
+        && let Some(origin) = GIT_ORIGIN.captures(&text)
+    {
+        metadata.merge(SourceMetadata {
+            repository: Some(web_url(&origin[1])),
+            ..Default::default()
+        });
+    }
+
+    metadata.version = metadata.version.and_then(|version| semver(&version));
+    metadata
+}
+
+/// Guesses the SPDX identifier of a license from its text.
+fn detect_license(text: &str) -> Option<String> {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let has = |phrase: &str| text.contains(phrase);
+    let license = if has("Apache License") && has("Version 2.0") {
+        "Apache-2.0"
+    } else if has("GNU LESSER GENERAL PUBLIC LICENSE") && has("Version 3") {
+        "LGPL-3.0-only"
+    } else if has("GNU LESSER GENERAL PUBLIC LICENSE") || has("GNU Lesser General Public") {
+        "LGPL-2.1-only"
+    } else if has("GNU AFFERO GENERAL PUBLIC LICENSE") {
+        "AGPL-3.0-only"
+    } else if has("GNU GENERAL PUBLIC LICENSE") && has("Version 3") {
+        "GPL-3.0-only"
+    } else if has("GNU GENERAL PUBLIC LICENSE") && has("Version 2") {
+        "GPL-2.0-only"
+    } else if has("Mozilla Public License Version 2.0") {
+        "MPL-2.0"
+    } else if has("Permission is hereby granted, free of charge") {
+        "MIT"
+    } else if has("Permission to use, copy, modify, and/or distribute this software") {
+        "ISC"
+    } else if has("Redistribution and use in source and binary forms") {
+        if has("Neither the name") {
+            "BSD-3-Clause"
+        } else {
+            "BSD-2-Clause"
+        }
+    } else if has("This is free and unencumbered software released into the public domain") {
+        "Unlicense"
+    } else if has("This software is provided 'as-is', without any express or implied") {
+        "Zlib"
+    } else {
+        return None;
+    };
+    Some(license.to_string())
+}
+
+fn package_json(text: &str) -> SourceMetadata {
+    let Ok(json) = serde_json::from_str::<serde_json::Value>(text) else {
+        return SourceMetadata::default();
+    };
+    let string = |value: &serde_json::Value, key: &str| {
+        value.as_str().or(value[key].as_str()).map(String::from)
+    };
+    SourceMetadata {
+        license: string(&json["license"], "type"),
+        authors: string(&json["author"], "name").into_iter().collect(),
+        repository: string(&json["repository"], "url").map(|url| web_url(&url)),
+        version: json["version"].as_str().map(String::from),
+        description: json["description"].as_str().map(String::from),
+        ..Default::default()
+    }
+}
+
+fn pyproject(text: &str) -> SourceMetadata {
+    let Ok(toml) = toml::from_str::<toml::Table>(text) else {
+        return SourceMetadata::default();
+    };
+    let string =
+        |table: Option<&toml::Value>, key: &str| table?.get(key)?.as_str().map(String::from);
+    let project = toml.get("project");
+    let poetry = toml.get("tool").and_then(|tool| tool.get("poetry"));
+
+    let authors = |table: Option<&toml::Value>| -> Vec<String> {
+        let Some(authors) = table.and_then(|t| t.get("authors")?.as_array()) else {
+            return vec![];
+        };
+        authors
+            .iter()
+            .filter_map(|author| match author {
+                toml::Value::String(author) => Some(author.clone()),
+                toml::Value::Table(author) => {
+                    let name = author.get("name")?.as_str()?;
+                    Some(match author.get("email").and_then(toml::Value::as_str) {
+                        Some(email) => format!("{name} <{email}>"),
+                        None => name.to_string(),
+                    })
+                }
+                _ => None,
+            })
+            .collect()
+    };
+    let urls = project.and_then(|project| project.get("urls"));
+
+    let mut metadata = SourceMetadata {
+        license: project
+            .and_then(|project| project.get("license"))
+            .and_then(|license| {
+                license
+                    .as_str()
+                    .map(String::from)
+                    .or(string(Some(license), "text"))
+            }),
+        authors: authors(project),
+        repository: string(urls, "Repository").or(string(urls, "Source")),
+        version: string(project, "version"),
+        description: string(project, "description"),
+        ..Default::default()
+    };
+    metadata.merge(SourceMetadata {
+        license: string(poetry, "license"),
+        authors: authors(poetry),
+        repository: string(poetry, "repository"),
+        version: string(poetry, "version"),
+        description: string(poetry, "description"),
+        ..Default::default()
+    });
+    metadata
+}
+
+/// Turns a git remote URL into a web one, e.g. `git@github.com:a/b.git` into
+/// `https://github.com/a/b`.
+fn web_url(url: &str) -> String {
+    let url = url.trim_start_matches("git+");
+    if let Some(scp) = SCP_URL.captures(url) {
+        return format!("https://{}/{}", &scp[1], &scp[2]);
+    }
+    url.trim_end_matches(".git").to_string()
+}
+
+/// Converts a version to the semver format required by Cargo, e.g. `1.2` to `1.2.0`.
+fn semver(version: &str) -> Option<String> {
+    let version = version.trim().trim_start_matches('v');
+    let (core, rest) = match version.find(['-', '+']) {
+        Some(index) => version.split_at(index),
+        None => (version, ""),
+    };
+    let mut parts: Vec<&str> = core.split('.').collect();
+    if parts.len() > 3 || parts.iter().any(|p| p.parse::<u64>().is_err()) {
+        return None;
+    }
+    parts.resize(3, "0");
+    Some(format!("{}{rest}", parts.join(".")))
+}
+
+/// Copies the license files of the source project and sets the package metadata in the
+/// destination manifest. Returns the changed files.
+pub fn propagate_metadata<M>(
+    source: &Project<M>,
+    destination: &Project,
+) -> Result<Vec<String>, String> {
+    let metadata = source_metadata(source.path());
+    let mut changed = vec![];
+    for file in &metadata.license_files {
+        let result = destination
+            .copy_file_from(source, file, file)
+            .map_err(|TransientError(e)| e)?;
+        if let Some(error) = result.error {
+            return Err(format!("Cannot copy {file}: {error}"));
+        }
+        if !result.unchanged {
+            changed.push(file.clone());
+        }
+    }
+
+    let result = destination
+        .edit_manifest("Cargo.toml", &[metadata.manifest_edit()])
+        .map_err(|TransientError(e)| e)?;
+    if let Some(error) = result.error {
+        return Err(format!("Cannot update Cargo.toml: {error}"));
+    }
+    if !result.unchanged {
+        changed.push("Cargo.toml".to_string());
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn collect_metadata() {
+        let dir = TempDir::new("metadata");
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        std::fs::write(
+            dir.join("LICENSE"),
+            "MIT License\n\nPermission is hereby granted, free of charge,\nto any person",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("package.json"),
+            r#"{"name": "tool", "version": "1.2", "author": {"name": "Ann"}, "description": "A tool"}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join(".git/config"),
+            "[core]\n\tbare = false\n[remote \"origin\"]\n\tfetch = +refs/heads/*\n\
+            \turl = git@github.com:ann/tool.git\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            source_metadata(&dir),
+            SourceMetadata {
+                license_files: vec!["LICENSE".to_string()],
+                license: Some("MIT".to_string()),
+                authors: vec!["Ann".to_string()],
+                repository: Some("https://github.com/ann/tool".to_string()),
+                version: Some("1.2.0".to_string()),
+                description: Some("A tool".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn python_metadata() {
+        let metadata = pyproject(
+            r#"
+            [project]
+            version = "0.3.1"
+            authors = [{ name = "Bob", email = "bob@example.com" }]
+            license = { text = "BSD-3-Clause" }
+
+            [tool.poetry]
+            description = "Poetry description"
+            "#,
+        );
+        assert_eq!(metadata.authors, ["Bob <bob@example.com>"]);
+        assert_eq!(metadata.license.as_deref(), Some("BSD-3-Clause"));
+        assert_eq!(metadata.description.as_deref(), Some("Poetry description"));
+        assert_eq!(semver("v2.0-beta.1").as_deref(), Some("2.0.0-beta.1"));
+        assert_eq!(semver("unknown"), None);
+    }
+}
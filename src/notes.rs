@@ -0,0 +1,113 @@
+use crate::project::write_atomic;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Notes the model keeps about the conversion, persisted in the session directory.
+pub struct Notes {
+    path: PathBuf,
+    notes: Mutex<BTreeMap<String, String>>,
+}
+
+impl Notes {
+    /// Loads the notes from the file, starting with no notes if it doesn't exist.
+    pub fn load(path: PathBuf) -> Self {
+        let notes = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        Notes {
+            path,
+            notes: Mutex::new(notes),
+        }
+    }
+
+    pub fn write(&self, topic: &str, text: &str) -> NoteWriteResult {
+        let mut notes = self.notes.lock().unwrap();
+        if text.is_empty() {
+            notes.remove(topic);
+        } else {
+            notes.insert(topic.to_string(), text.to_string());
+        }
+
+        // A crash in the middle of the write leaves the old notes intact.
+        let result = serde_json::to_string_pretty(&*notes)
+            .map_err(std::io::Error::from)
+            .and_then(|contents| write_atomic(&self.path, contents.as_bytes()));
+        match result {
+            Ok(_) => NoteWriteResult { error: None },
+            Err(e) => NoteWriteResult {
+                error: Some(format!("Cannot save notes: {e}")),
+            },
+        }
+    }
+
+    pub fn read(&self, topic: Option<&str>) -> NoteReadResult {
+        let notes = self.notes.lock().unwrap();
+        NoteReadResult {
+            notes: notes
+                .iter()
+                .filter(|(t, _)| topic.is_none_or(|topic| *t == topic))
+                .map(|(topic, text)| Note {
+                    topic: topic.clone(),
+                    text: text.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct NoteWriteArgs {
+    /// a short topic of the note, e.g. "dependencies"; a note with the same topic is replaced
+    pub topic: String,
+    /// text of the note; an empty text deletes the note
+    pub text: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct NoteReadArgs {
+    /// topic of the note to read; all notes are returned if omitted
+    pub topic: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NoteWriteResult {
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NoteReadResult {
+    pub notes: Vec<Note>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Note {
+    pub topic: String,
+    pub text: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn notes_persist() {
+        let dir = TempDir::new("notes");
+        let path = dir.join("notes.json");
+        let notes = Notes::load(path.clone());
+        assert!(notes.write("crates", "Use clap for CLI.").error.is_none());
+        assert!(notes.write("naming", "snake_case modules").error.is_none());
+        assert!(notes.write("naming", "").error.is_none());
+
+        let notes = Notes::load(path.clone());
+        let all = notes.read(None).notes;
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].topic, "crates");
+        assert_eq!(all[0].text, "Use clap for CLI.");
+        assert!(notes.read(Some("naming")).notes.is_empty());
+    }
+}
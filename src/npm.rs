@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Test runners, linters, formatters and bundlers of npm scripts with the cargo command doing
+/// the same job.
+const TOOLS: &[(&[&str], &str)] = &[
+    (
+        &["jest", "mocha", "vitest", "ava", "tap", "node --test"],
+        "`cargo test`",
+    ),
+    (&["eslint", "tslint", "biome lint", "xo"], "`cargo clippy`"),
+    (&["prettier", "biome format"], "`cargo fmt`"),
+    (
+        &[
+            "tsc",
+            "webpack",
+            "rollup",
+            "esbuild",
+            "vite build",
+            "babel",
+            "parcel build",
+        ],
+        "`cargo build --release`",
+    ),
+];
+
+/// Commands running a script file, e.g. `node src/server.js`.
+const RUNNERS: &[&str] = &["node", "ts-node", "tsx", "bun", "deno run"];
+
+/// How TypeScript types map to Rust.
+const TYPESCRIPT_GUIDANCE: &str = "The source is TypeScript: interfaces and type aliases of \
+    objects become structs, or traits when classes implement them, union types become enums, \
+    optional properties `Option`, `any` and `unknown` an enum or `serde_json::Value`, and \
+    promises async functions.";
+
+/// The executables and scripts of `package.json`.
+#[derive(Debug, Default, PartialEq)]
+pub struct NpmPackage {
+    pub name: String,
+    /// executables by name with their script
+    pub bins: BTreeMap<String, String>,
+    /// scripts by name with their command
+    pub scripts: BTreeMap<String, String>,
+    pub typescript: bool,
+}
+
+impl NpmPackage {
+    /// Reads `package.json` in the root of the source project, if there is one.
+    pub fn load(root: &Path) -> Option<Self> {
+        let manifest = std::fs::read_to_string(root.join("package.json")).ok()?;
+        let json: serde_json::Value = serde_json::from_str(&manifest).ok()?;
+        let name = json["name"].as_str().unwrap_or_default();
+        let mut package = NpmPackage {
+            // Scoped packages install their executables without the scope.
+            name: name.rsplit('/').next().unwrap_or(name).to_string(),
+            typescript: root.join("tsconfig.json").is_file()
+                || json["devDependencies"]["typescript"].is_string()
+                || json["dependencies"]["typescript"].is_string(),
+            ..Default::default()
+        };
+        match &json["bin"] {
+            serde_json::Value::String(script) => {
+                package.bins.insert(package.name.clone(), script.clone());
+            }
+            serde_json::Value::Object(bins) => {
+                for (name, script) in bins {
+                    if let Some(script) = script.as_str() {
+                        package.bins.insert(name.clone(), script.to_string());
+                    }
+                }
+            }
+            _ => {}
+        }
+        for (name, command) in json["scripts"].as_object().into_iter().flatten() {
+            if let Some(command) = command.as_str() {
+                package.scripts.insert(name.clone(), command.to_string());
+            }
+        }
+        Some(package)
+    }
+
+    /// Describes the package for the planning prompt: the binaries its executables become and
+    /// the cargo equivalent of each script.
+    pub fn planning_notes(&self) -> String {
+        let mut notes = vec![format!(
+            "The source project is the npm package `{}`.",
+            self.name
+        )];
+        if !self.bins.is_empty() {
+            let mut section = "Its executables become binaries of the crate:".to_string();
+            for (name, script) in &self.bins {
+                let binary = if self.bins.len() == 1 {
+                    "src/main.rs".to_string()
+                } else {
+                    format!("src/bin/{name}.rs")
+                };
+                section += &format!("\n- `{name}` (`{script}`) -> `{binary}`");
+            }
+            notes.push(section);
+        }
+        if !self.scripts.is_empty() {
+            let mut section =
+                "Its scripts and their cargo equivalents; plan the binaries and examples they \
+                need:"
+                    .to_string();
+            for (name, command) in &self.scripts {
+                section += &format!(
+                    "\n- `{name}`: `{command}` -> {}",
+                    self.cargo_equivalent(name, command)
+                );
+            }
+            notes.push(section);
+        }
+        if self.typescript {
+            notes.push(TYPESCRIPT_GUIDANCE.to_string());
+        }
+        notes.join("\n")
+    }
+
+    /// Suggests the cargo command or target replacing an npm script.
+    fn cargo_equivalent(&self, name: &str, command: &str) -> String {
+        // `npm run lint && npm test` is judged by its first command.
+        let command = command
+            .split("&&")
+            .next()
+            .unwrap_or(command)
+            .trim()
+            .trim_start_matches("npx ");
+        for (tools, cargo) in TOOLS {
+            if tools
+                .iter()
+                .any(|tool| command == *tool || command.starts_with(&format!("{tool} ")))
+            {
+                return cargo.to_string();
+            }
+        }
+        let script = RUNNERS.iter().find_map(|runner| {
+            let rest = command.strip_prefix(runner)?.strip_prefix(' ')?;
+            rest.split_whitespace().find(|arg| !arg.starts_with('-'))
+        });
+        if let Some(script) = script {
+            let stem = Path::new(script)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().replace('-', "_"))
+                .unwrap_or_default();
+            let script = script.trim_start_matches("./");
+            if script.starts_with("examples/") {
+                return format!("the example `examples/{stem}.rs`, `cargo run --example {stem}`");
+            }
+            if let Some((bin, _)) = self
+                .bins
+                .iter()
+                .find(|(_, bin_script)| bin_script.trim_start_matches("./") == script)
+            {
+                return format!("`cargo run --bin {bin}`");
+            }
+            if ["start", "dev", "serve"].contains(&name) {
+                return "`cargo run`".to_string();
+            }
+            return format!("the binary `src/bin/{stem}.rs`, `cargo run --bin {stem}`");
+        }
+        match name {
+            "test" => "`cargo test`".to_string(),
+            "build" => "`cargo build --release`".to_string(),
+            "bench" => "`cargo bench` with the benchmarks in `benches/`".to_string(),
+            _ => "no direct equivalent, an alias in `.cargo/config.toml` or an xtask if it's \
+                still needed"
+                .to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn scripts() {
+        let dir = TempDir::new("npm");
+        std::fs::write(
+            dir.join("package.json"),
+            r#"{
+                "name": "@acme/shapes",
+                "bin": {"shapes": "./bin/shapes.js", "shapes-server": "bin/server.js"},
+                "scripts": {
+                    "start": "node bin/server.js --port 8080",
+                    "demo": "ts-node examples/area-demo.ts",
+                    "migrate": "node scripts/migrate.js",
+                    "test": "npx jest --coverage",
+                    "lint": "eslint src && prettier --check src",
+                    "build": "tsc -p .",
+                    "release": "np"
+                },
+                "devDependencies": {"typescript": "^5.4.0"}
+            }"#,
+        )
+        .unwrap();
+
+        let package = NpmPackage::load(&dir).unwrap();
+        assert_eq!(package.name, "shapes");
+        assert!(package.typescript);
+        assert_eq!(
+            package.planning_notes(),
+            "The source project is the npm package `shapes`.\n\
+            Its executables become binaries of the crate:\n\
+            - `shapes` (`./bin/shapes.js`) -> `src/bin/shapes.rs`\n\
+            - `shapes-server` (`bin/server.js`) -> `src/bin/shapes-server.rs`\n\
+            Its scripts and their cargo equivalents; plan the binaries and examples they need:\n\
+            - `build`: `tsc -p .` -> `cargo build --release`\n\
+            - `demo`: `ts-node examples/area-demo.ts` -> the example `examples/area_demo.rs`, \
+            `cargo run --example area_demo`\n\
+            - `lint`: `eslint src && prettier --check src` -> `cargo clippy`\n\
+            - `migrate`: `node scripts/migrate.js` -> the binary `src/bin/migrate.rs`, \
+            `cargo run --bin migrate`\n\
+            - `release`: `np` -> no direct equivalent, an alias in `.cargo/config.toml` or an \
+            xtask if it's still needed\n\
+            - `start`: `node bin/server.js --port 8080` -> `cargo run --bin shapes-server`\n\
+            - `test`: `npx jest --coverage` -> `cargo test`\n\
+            The source is TypeScript: interfaces and type aliases of objects become structs, or \
+            traits when classes implement them, union types become enums, optional properties \
+            `Option`, `any` and `unknown` an enum or `serde_json::Value`, and promises async \
+            functions."
+        );
+        assert!(NpmPackage::load(&dir.join("missing")).is_none());
+    }
+}
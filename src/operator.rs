@@ -0,0 +1,222 @@
+use crate::project::Review;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::process::Command;
+
+const DEFAULT_ANSWER: &str =
+    "The operator is not available. Make the decision yourself and mention it in the summary.";
+
+/// Reason given to the model when the operator doesn't answer whether to apply a change.
+const NO_APPROVAL: &str = "The operator didn't approve the change.";
+
+/// The person running the tool, who can answer questions from the model.
+pub struct Operator {
+    interactive: bool,
+    answers: AnswersFile,
+}
+
+impl Operator {
+    /// Creates an operator that answers the questions through the terminal.
+    pub fn interactive() -> Self {
+        Operator {
+            interactive: true,
+            answers: AnswersFile::default(),
+        }
+    }
+
+    /// Creates an operator that answers the questions from the answers file, if any.
+    pub fn headless(answers_path: Option<&Path>) -> Result<Self, String> {
+        let answers = match answers_path {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path)
+                    .map_err(|e| format!("Cannot read answers file: {e}"))?;
+                serde_json::from_str(&contents)
+                    .map_err(|e| format!("Cannot parse answers file: {e}"))?
+            }
+            None => AnswersFile::default(),
+        };
+        Ok(Operator {
+            interactive: false,
+            answers,
+        })
+    }
+
+    pub fn ask(&self, question: &str) -> AskUserResult {
+        let answer = if self.interactive {
+            read_answer(question)
+        } else {
+            self.answers.find(question)
+        };
+        AskUserResult {
+            answer: answer.unwrap_or_else(|| {
+                self.answers
+                    .default
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_ANSWER.to_string())
+            }),
+        }
+    }
+
+    /// Asks the operator to allow an action. In headless mode all actions are allowed.
+    pub fn confirm(&self, action: &str) -> bool {
+        if !self.interactive {
+            return true;
+        }
+        read_answer(&format!("{action}\nAllow? [y/N]"))
+            .is_some_and(|answer| answer.eq_ignore_ascii_case("y"))
+    }
+
+    /// Shows the change of a destination file as a colored diff and lets the operator accept,
+    /// reject or edit it. A deleted file, without new contents, can't be edited, and no answer
+    /// rejects the change. In headless mode all changes are accepted.
+    pub fn approve_write(&self, path: &str, old: &str, new: Option<&str>) -> Review {
+        if !self.interactive {
+            return Review::Approve;
+        }
+        let patch = diffy::create_patch(old, new.unwrap_or_default());
+        let diff = diffy::PatchFormatter::new()
+            .with_color()
+            .fmt_patch(&patch)
+            .to_string();
+        let question = match new {
+            Some(_) => {
+                format!("The model wants to write {path}:\n{diff}\nWrite it? [y]es, [N]o, [e]dit")
+            }
+            None => format!("The model wants to delete {path}:\n{diff}\nDelete it? [y]es, [N]o"),
+        };
+        loop {
+            // Without an answer, e.g. with stdin closed, the question would repeat forever.
+            let Some(answer) = read_answer(&question) else {
+                return Review::Reject(NO_APPROVAL.to_string());
+            };
+            match (answer.to_lowercase().as_str(), new) {
+                ("y" | "yes", _) => return Review::Approve,
+                ("n" | "no", _) => {
+                    let reason = read_answer("Why? The answer goes to the model.")
+                        .unwrap_or_else(|| "The operator rejected the change.".to_string());
+                    return Review::Reject(reason);
+                }
+                ("e" | "edit", Some(new)) => match edit_contents(path, new) {
+                    Ok(edited) if edited == new => return Review::Approve,
+                    Ok(edited) => return Review::Replace(edited),
+                    Err(e) => println!("{e}"),
+                },
+                ("e" | "edit", None) => println!("Deletions can't be edited."),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Opens the contents in the operator's editor, `$VISUAL` or `$EDITOR`, and returns the result.
+fn edit_contents(path: &str, contents: &str) -> Result<String, String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let name = Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("file");
+    let file = std::env::temp_dir().join(format!("riir-edit-{}-{name}", std::process::id()));
+    std::fs::write(&file, contents).map_err(|e| format!("Cannot write {}: {e}", file.display()))?;
+    // The editor command may have arguments, e.g. `code --wait`.
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{editor} \"$1\""))
+        .arg("sh")
+        .arg(&file)
+        .status()
+        .map_err(|e| format!("Cannot run {editor}: {e}"))?;
+    let edited = std::fs::read_to_string(&file);
+    let _ = std::fs::remove_file(&file);
+    if !status.success() {
+        return Err(format!("{editor} failed: {status}"));
+    }
+    edited.map_err(|e| format!("Cannot read the edited file: {e}"))
+}
+
+fn read_answer(question: &str) -> Option<String> {
+    println!("==== Question ====\n{question}\n");
+    print!("> ");
+    std::io::stdout().flush().ok()?;
+
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer).ok()?;
+    let answer = answer.trim();
+    (!answer.is_empty()).then(|| answer.to_string())
+}
+
+/// Predefined answers for headless mode.
+#[derive(Default, Deserialize)]
+struct AnswersFile {
+    /// answers to questions containing the given text
+    #[serde(default)]
+    answers: Vec<PredefinedAnswer>,
+    /// answer to questions that don't match any predefined answer
+    default: Option<String>,
+}
+
+impl AnswersFile {
+    fn find(&self, question: &str) -> Option<String> {
+        let question = question.to_lowercase();
+        self.answers
+            .iter()
+            .find(|a| question.contains(&a.question.to_lowercase()))
+            .map(|a| a.answer.clone())
+    }
+}
+
+#[derive(Deserialize)]
+struct PredefinedAnswer {
+    question: String,
+    answer: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct AskUserArgs {
+    /// a question to the operator
+    pub question: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AskUserResult {
+    pub answer: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headless_answers() {
+        let answers = r#"{
+            "answers": [{ "question": "async runtime", "answer": "tokio" }],
+            "default": "Use your best judgement."
+        }"#;
+        let operator = Operator {
+            interactive: false,
+            answers: serde_json::from_str(answers).unwrap(),
+        };
+
+        assert_eq!(
+            operator.ask("Which Async Runtime do you prefer?").answer,
+            "tokio"
+        );
+        assert_eq!(
+            operator.ask("Should I keep the CLI flags?").answer,
+            "Use your best judgement."
+        );
+    }
+
+    #[test]
+    fn headless_default_policy() {
+        let operator = Operator::headless(None).unwrap();
+        assert_eq!(operator.ask("Anything?").answer, DEFAULT_ANSWER);
+        assert_eq!(
+            operator.approve_write("src/lib.rs", "", Some("fn f() {}\n")),
+            Review::Approve
+        );
+    }
+}
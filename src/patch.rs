@@ -0,0 +1,79 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// Replacement of a unique fragment of a file.
+#[derive(Deserialize, JsonSchema)]
+pub struct SearchReplace {
+    /// exact text to find, it must occur in the file exactly once
+    pub search: String,
+    /// text to put instead of the found one
+    pub replace: String,
+}
+
+/// Applies search/replace edits one after another.
+pub fn apply_edits(contents: &str, edits: &[SearchReplace]) -> Result<String, String> {
+    let mut contents = contents.to_string();
+    for (i, edit) in edits.iter().enumerate() {
+        let number = i + 1;
+        if edit.search.is_empty() {
+            return Err(format!("Edit #{number}: the search text is empty."));
+        }
+        match contents.matches(&edit.search).count() {
+            0 => return Err(format!("Edit #{number}: the search text is not found.")),
+            1 => contents = contents.replacen(&edit.search, &edit.replace, 1),
+            count => {
+                return Err(format!(
+                    "Edit #{number}: the search text occurs {count} times, make it unique."
+                ));
+            }
+        }
+    }
+    Ok(contents)
+}
+
+/// Applies a unified diff.
+pub fn apply_diff(contents: &str, diff: &str) -> Result<String, String> {
+    let patch = diffy::Patch::from_str(diff).map_err(|e| format!("Cannot parse the diff: {e}."))?;
+    diffy::apply(contents, &patch).map_err(|e| format!("Cannot apply the diff: {e}."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(search: &str, replace: &str) -> SearchReplace {
+        SearchReplace {
+            search: search.to_string(),
+            replace: replace.to_string(),
+        }
+    }
+
+    #[test]
+    fn search_replace() {
+        let contents = "fn a() {}\nfn b() {}\n";
+        assert_eq!(
+            apply_edits(contents, &[edit("fn a", "pub fn a"), edit("b()", "c()")]).unwrap(),
+            "pub fn a() {}\nfn c() {}\n"
+        );
+        assert_eq!(
+            apply_edits(contents, &[edit("fn d", "")]).unwrap_err(),
+            "Edit #1: the search text is not found."
+        );
+        assert_eq!(
+            apply_edits(contents, &[edit("b", "c"), edit("fn", "")]).unwrap_err(),
+            "Edit #2: the search text occurs 2 times, make it unique."
+        );
+    }
+
+    #[test]
+    fn unified_diff() {
+        let contents = "fn a() {}\nfn b() {}\n";
+        let diff =
+            "--- a/lib.rs\n+++ b/lib.rs\n@@ -1,2 +1,2 @@\n fn a() {}\n-fn b() {}\n+fn c() {}\n";
+        assert_eq!(
+            apply_diff(contents, diff).unwrap(),
+            "fn a() {}\nfn c() {}\n"
+        );
+        assert!(apply_diff("fn x() {}\n", diff).is_err());
+    }
+}
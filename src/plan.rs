@@ -0,0 +1,324 @@
+use crate::layout::Layout;
+use log::warn;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// The conversion plan the model writes before changing anything, persisted in the session
+/// directory and followed by the later phases.
+pub struct Plan {
+    path: PathBuf,
+    contents: Mutex<PlanContents>,
+    /// rules the destinations of the modules must follow
+    layout: Option<Layout>,
+}
+
+impl Plan {
+    /// Loads the plan from the file, starting with an empty plan if it doesn't exist.
+    pub fn load(path: PathBuf) -> Self {
+        let contents = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        Plan {
+            path,
+            contents: Mutex::new(contents),
+            layout: None,
+        }
+    }
+
+    /// Rejects plans putting source files elsewhere than the layout rules say.
+    pub fn with_layout(mut self, layout: Layout) -> Self {
+        self.layout = Some(layout);
+        self
+    }
+
+    /// Replaces the plan. Modules that were already in the plan, by their destination, keep their
+    /// status.
+    pub fn write(&self, args: PlanWriteArgs) -> PlanResult {
+        if let Some(error) = self
+            .layout
+            .as_ref()
+            .and_then(|layout| layout.check_plan(&args.modules))
+        {
+            return PlanResult { error: Some(error) };
+        }
+        let mut contents = self.contents.lock().unwrap();
+        let modules = args
+            .modules
+            .into_iter()
+            .map(|module| {
+                let status = contents
+                    .modules
+                    .iter()
+                    .find(|old| old.destination == module.destination)
+                    .map_or(ModuleStatus::Pending, |old| old.status);
+                PlanModule {
+                    source: module.source,
+                    destination: module.destination,
+                    description: module.description,
+                    status,
+                }
+            })
+            .collect();
+        *contents = PlanContents {
+            modules,
+            dependencies: args.dependencies,
+        };
+        PlanResult {
+            error: self.save(&contents),
+        }
+    }
+
+    /// Changes the status of a module of the plan.
+    pub fn update(&self, args: PlanUpdateArgs) -> PlanResult {
+        let mut contents = self.contents.lock().unwrap();
+        let Some(module) = contents
+            .modules
+            .iter_mut()
+            .find(|module| module.destination == args.destination)
+        else {
+            return PlanResult {
+                error: Some(format!(
+                    "The plan has no module {}, add it with plan_write.",
+                    args.destination
+                )),
+            };
+        };
+        module.status = args.status;
+        PlanResult {
+            error: self.save(&contents),
+        }
+    }
+
+    /// Marks the converted modules made from any of the changed source files as pending again,
+    /// returning their destinations.
+    pub fn reopen(&self, changed: &[String]) -> Vec<String> {
+        let mut contents = self.contents.lock().unwrap();
+        let mut reopened = vec![];
+        for module in &mut contents.modules {
+            if module.status == ModuleStatus::Done
+                && module.source.iter().any(|file| changed.contains(file))
+            {
+                module.status = ModuleStatus::Pending;
+                reopened.push(module.destination.clone());
+            }
+        }
+        if !reopened.is_empty()
+            && let Some(e) = self.save(&contents)
+        {
+            warn!("{e}");
+        }
+        reopened
+    }
+
+    /// Returns the source files of the module with the destination file.
+    pub fn sources_of(&self, destination: &str) -> Vec<String> {
+        let contents = self.contents.lock().unwrap();
+        contents
+            .modules
+            .iter()
+            .find(|module| module.destination == destination)
+            .map(|module| module.source.clone())
+            .unwrap_or_default()
+    }
+
+    /// Returns whether any of the modules is made from the file.
+    pub fn covers(&self, file: &str) -> bool {
+        let contents = self.contents.lock().unwrap();
+        contents
+            .modules
+            .iter()
+            .any(|module| module.source.iter().any(|source| source == file))
+    }
+
+    /// Returns the first module that is still pending and not in `skip`.
+    pub fn next_pending(&self, skip: &[String]) -> Option<PlanModule> {
+        let contents = self.contents.lock().unwrap();
+        contents
+            .modules
+            .iter()
+            .find(|module| {
+                module.status == ModuleStatus::Pending && !skip.contains(&module.destination)
+            })
+            .cloned()
+    }
+
+    pub fn read(&self) -> PlanContents {
+        self.contents.lock().unwrap().clone()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contents.lock().unwrap().modules.is_empty()
+    }
+
+    fn save(&self, contents: &PlanContents) -> Option<String> {
+        let contents = serde_json::to_string_pretty(contents).unwrap();
+        std::fs::write(&self.path, contents)
+            .err()
+            .map(|_| "Cannot save the plan.".to_string())
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let contents = self.contents.lock().unwrap();
+        for module in &contents.modules {
+            let mark = match module.status {
+                ModuleStatus::Pending => " ",
+                ModuleStatus::Done => "x",
+                ModuleStatus::Skipped => "-",
+            };
+            writeln!(
+                f,
+                "[{mark}] {} <- {}",
+                module.destination,
+                module.source.join(", ")
+            )?;
+        }
+        for dependency in &contents.dependencies {
+            writeln!(
+                f,
+                "{} -> {}",
+                dependency.source,
+                dependency.rust.as_deref().unwrap_or("(no crate)")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct PlanContents {
+    /// modules of the destination project in the order they are converted
+    pub modules: Vec<PlanModule>,
+    pub dependencies: Vec<DependencyMapping>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlanModule {
+    pub source: Vec<String>,
+    pub destination: String,
+    pub description: String,
+    pub status: ModuleStatus,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ModuleStatus {
+    Pending,
+    Done,
+    /// not converted, e.g. because Rust or a crate provides it
+    Skipped,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct PlanModuleArgs {
+    /// source files the module is converted from, e.g. ["src/parser.c", "include/parser.h"]
+    pub source: Vec<String>,
+    /// file of the destination project, e.g. "src/parser.rs"
+    pub destination: String,
+    /// what the module contains and how it's converted
+    pub description: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DependencyMapping {
+    /// dependency of the source project, e.g. "requests" or "libcurl"
+    pub source: String,
+    /// the crate replacing it, e.g. "reqwest", or none if the standard library or the converted
+    /// code covers it
+    pub rust: Option<String>,
+    /// why, e.g. which features are needed
+    pub notes: Option<String>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct PlanWriteArgs {
+    /// modules of the destination project, in the order they should be converted: dependencies
+    /// first
+    pub modules: Vec<PlanModuleArgs>,
+    /// what each dependency of the source project is replaced with
+    pub dependencies: Vec<DependencyMapping>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct PlanUpdateArgs {
+    /// destination file of the module, as in the plan
+    pub destination: String,
+    /// new status of the module
+    pub status: ModuleStatus,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PlanResult {
+    pub error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn plan() {
+        let dir = TempDir::new("plan");
+        let path = dir.join("plan.json");
+        let plan = Plan::load(path.clone());
+        assert!(plan.is_empty());
+        let module = |source: &str, destination: &str| PlanModuleArgs {
+            source: vec![source.to_string()],
+            destination: destination.to_string(),
+            description: String::new(),
+        };
+        let args = || PlanWriteArgs {
+            modules: vec![
+                module("util.c", "src/util.rs"),
+                module("main.c", "src/main.rs"),
+            ],
+            dependencies: vec![DependencyMapping {
+                source: "zlib".to_string(),
+                rust: Some("flate2".to_string()),
+                notes: None,
+            }],
+        };
+        assert!(plan.write(args()).error.is_none());
+        let done = PlanUpdateArgs {
+            destination: "src/util.rs".to_string(),
+            status: ModuleStatus::Done,
+        };
+        assert!(plan.update(done).error.is_none());
+        let missing = PlanUpdateArgs {
+            destination: "src/lib.rs".to_string(),
+            status: ModuleStatus::Done,
+        };
+        assert!(plan.update(missing).error.is_some());
+        let next = |skip: &[String]| plan.next_pending(skip).map(|module| module.destination);
+        assert_eq!(next(&[]).as_deref(), Some("src/main.rs"));
+        assert_eq!(next(&["src/main.rs".to_string()]), None);
+
+        // Rewriting the plan keeps the progress.
+        let plan = Plan::load(path.clone());
+        assert!(plan.write(args()).error.is_none());
+        assert_eq!(
+            plan.to_string(),
+            "[x] src/util.rs <- util.c\n[ ] src/main.rs <- main.c\nzlib -> flate2\n"
+        );
+
+        // Changing a source file converts its module again.
+        assert_eq!(plan.reopen(&["main.c".to_string()]), Vec::<String>::new());
+        assert_eq!(plan.reopen(&["util.c".to_string()]), ["src/util.rs"]);
+        assert_eq!(
+            plan.next_pending(&[])
+                .map(|module| module.destination)
+                .as_deref(),
+            Some("src/util.rs")
+        );
+        assert!(plan.covers("main.c"));
+        assert_eq!(plan.sources_of("src/util.rs"), ["util.c"]);
+        assert!(plan.sources_of("src/lib.rs").is_empty());
+        assert!(!plan.covers("extra.c"));
+    }
+}
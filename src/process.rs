@@ -0,0 +1,166 @@
+use std::io::{Read, Write};
+use std::process::{Child, Command, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// How long the output of a killed process is waited for. Processes it started may still hold
+/// the pipes open.
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Output of a command run with [`run_command`].
+pub struct CommandOutput {
+    /// exit code, missing if the process was killed
+    pub exit_code: Option<i32>,
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+    pub stdout_truncated: bool,
+    pub stderr_truncated: bool,
+    /// whether the process was killed after the timeout
+    pub timed_out: bool,
+}
+
+/// Runs a command with piped stdout and stderr, killing it with all its children after the
+/// timeout and keeping at most `max_output` bytes of each stream. The input, if any, is written
+/// to stdin.
+pub fn run_command(
+    command: &mut Command,
+    input: Option<&[u8]>,
+    timeout: Option<Duration>,
+    max_output: Option<usize>,
+) -> Result<CommandOutput, String> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    command
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // A process group lets the timeout kill the processes the command started too.
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(command, 0);
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("Cannot run {program}: {e}"))?;
+
+    if let (Some(mut stdin), Some(input)) = (child.stdin.take(), input) {
+        let input = input.to_vec();
+        // The command may exit without reading everything, so write errors are ignored.
+        std::thread::spawn(move || stdin.write_all(&input));
+    }
+    let stdout = capture(child.stdout.take().unwrap(), max_output);
+    let stderr = capture(child.stderr.take().unwrap(), max_output);
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if deadline.is_some_and(|deadline| Instant::now() >= deadline) => {
+                kill(&mut child);
+                break None;
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(50)),
+            Err(e) => {
+                kill(&mut child);
+                return Err(format!("Cannot wait for {program}: {e}"));
+            }
+        }
+    };
+
+    let grace_deadline = status.is_none().then(|| Instant::now() + KILL_GRACE_PERIOD);
+    let (stdout, stdout_truncated) = finish_capture(stdout, grace_deadline);
+    let (stderr, stderr_truncated) = finish_capture(stderr, grace_deadline);
+    Ok(CommandOutput {
+        exit_code: status.and_then(|status| status.code()),
+        success: status.is_some_and(|status| status.success()),
+        stdout,
+        stderr,
+        stdout_truncated,
+        stderr_truncated,
+        timed_out: status.is_none(),
+    })
+}
+
+/// Reads a pipe to the end in a thread, keeping at most `max_size` bytes.
+fn capture(
+    mut pipe: impl Read + Send + 'static,
+    max_size: Option<usize>,
+) -> JoinHandle<(Vec<u8>, bool)> {
+    std::thread::spawn(move || {
+        let mut data = vec![];
+        let mut truncated = false;
+        let mut buffer = [0; 8192];
+        // Reading goes on after the limit, so that the process doesn't block on a full pipe.
+        while let Ok(n) = pipe.read(&mut buffer) {
+            if n == 0 {
+                break;
+            }
+            let keep = max_size.map_or(n, |max_size| n.min(max_size - data.len()));
+            data.extend_from_slice(&buffer[..keep]);
+            truncated |= keep < n;
+        }
+        (data, truncated)
+    })
+}
+
+/// Returns the captured text and whether it was truncated. If a deadline is given, waits only
+/// until then.
+fn finish_capture(
+    capture: JoinHandle<(Vec<u8>, bool)>,
+    deadline: Option<Instant>,
+) -> (String, bool) {
+    while deadline.is_some_and(|deadline| Instant::now() < deadline) && !capture.is_finished() {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    if deadline.is_some() && !capture.is_finished() {
+        return (String::new(), true);
+    }
+    match capture.join() {
+        Ok((data, truncated)) => (String::from_utf8_lossy(&data).into_owned(), truncated),
+        Err(_) => (String::new(), true),
+    }
+}
+
+fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    let _ = Command::new("kill")
+        .args(["-KILL", "--", &format!("-{}", child.id())])
+        .status();
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run() {
+        let output = run_command(
+            Command::new("sh").args(["-c", "cat; echo err >&2; exit 3"]),
+            Some(b"out\n"),
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(output.exit_code, Some(3));
+        assert!(!output.success);
+        assert_eq!(output.stdout, "out\n");
+        assert_eq!(output.stderr, "err\n");
+
+        let output = run_command(
+            Command::new("sh").args(["-c", "echo 0123456789; sleep 600"]),
+            None,
+            Some(Duration::from_secs(1)),
+            Some(4),
+        )
+        .unwrap();
+        assert!(output.timed_out);
+        assert_eq!(output.exit_code, None);
+        assert_eq!(output.stdout, "0123");
+        assert!(output.stdout_truncated);
+
+        assert!(run_command(&mut Command::new("riir-no-such-program"), None, None, None).is_err());
+    }
+}
@@ -1,106 +1,1136 @@
+use crate::audit::{DependencyProblem, run_audit, run_deny};
+use crate::cargo::{
+    CargoOptions, CargoOutput, affected_members, check_workspace, clippy_args, run_cargo,
+    run_cargo_with_env, rustfmt, workspace_members,
+};
+use crate::compare::{CompareFilesArgs, CompareFilesResult, compare_files};
+use crate::config::{ClippyConfig, IgnoreConfig};
+use crate::deps::{DependencyGraphResult, dependency_graph};
+use crate::function::TransientError;
+use crate::items::{RustItemsResult, rust_items};
+use crate::language::{LanguageStatsResult, detect_language, language_stats};
+use crate::manifest::{ManifestEdit, edit_manifest};
+use crate::metadata::{SourceMetadata, source_metadata};
+use crate::patch::{SearchReplace, apply_diff, apply_edits};
+use crate::process::run_command;
+use crate::search::{GrepArgs, GrepResult, grep};
+use crate::session::SESSION_DIR;
+use crate::symbols::{
+    FindReferencesArgs, FindReferencesResult, SymbolsArgs, SymbolsResult, find_references, symbols,
+};
+use crate::text::{DecodedText, count_lines, decode_text, is_binary};
+use crate::tree::{TreeArgs, TreeResult, tree};
+use crate::written::WrittenFiles;
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
 
-pub struct Project {
+/// Patterns of paths that are hidden from the model unless configured otherwise.
+const DEFAULT_IGNORE_PATTERNS: &[&str] = &[
+    ".git",
+    "target",
+    ".gitignore",
+    ".env",
+    "Cargo.lock",
+    "LICENSE",
+    "LICENSE.txt",
+];
+
+/// Suffix of temporary files used to replace project files atomically.
+const TEMP_SUFFIX: &str = ".riir-tmp";
+
+/// Note for the model when a review changed the contents it asked to write.
+const REVIEW_EDIT_NOTE: &str = "The contents were edited in the review before they were \
+    written, read the file to see the changes.";
+
+/// Set of glob patterns of paths hidden from the model.
+#[derive(Clone)]
+pub struct IgnoreList(GlobSet);
+
+impl IgnoreList {
+    pub fn new(config: &IgnoreConfig) -> Result<Self, String> {
+        let defaults = if config.use_defaults {
+            DEFAULT_IGNORE_PATTERNS
+        } else {
+            &[]
+        };
+        // The session directory and leftovers of interrupted writes are never a part of the project.
+        let temp_files = format!("*{TEMP_SUFFIX}");
+        let patterns = defaults
+            .iter()
+            .copied()
+            .chain([SESSION_DIR, temp_files.as_str()])
+            .chain(config.patterns.iter().map(String::as_str));
+
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob =
+                Glob::new(pattern).map_err(|e| format!("Invalid ignore pattern {pattern}: {e}"))?;
+            builder.add(glob);
+        }
+        builder.build().map(IgnoreList).map_err(|e| e.to_string())
+    }
+
+    fn is_ignored(&self, relpath: &Path) -> bool {
+        self.0.is_match(relpath)
+    }
+}
+
+impl Default for IgnoreList {
+    fn default() -> Self {
+        IgnoreList::new(&IgnoreConfig::default()).unwrap()
+    }
+}
+
+/// Reviews a change of a file given its relative path, the old and the new contents. The new
+/// contents are `None` if the file is deleted.
+pub type ReviewFn = Box<dyn Fn(&str, &str, Option<&str>) -> Review + Send + Sync>;
+
+/// Outcome of the review of a change.
+#[derive(Debug, PartialEq)]
+pub enum Review {
+    Approve,
+    /// the change isn't written, the comments go back to the model
+    Reject(String),
+    /// these contents are written instead
+    Replace(String),
+}
+
+/// Access mode of a project that only allows reading the files.
+pub struct ReadOnly;
+
+/// Access mode of a project that allows both reading and changing the files, with the state
+/// only the changes need.
+#[derive(Default)]
+pub struct ReadWrite {
+    /// files changed since the last check
+    dirty: Mutex<BTreeMap<PathBuf, SystemTime>>,
+    backup_dir: Option<PathBuf>,
+    written: Option<WrittenFiles>,
+    /// overwrite files changed outside of riir
+    force: bool,
+    /// format the written Rust files with rustfmt
+    format: bool,
+    /// gates for the changes the model makes to the files, in order
+    reviews: Vec<ReviewFn>,
+    cargo_options: CargoOptions,
+}
+
+/// A project directory. The access mode `M` decides which methods are available, so that
+/// e.g. a function writing to the source project can't be registered by mistake.
+pub struct Project<M = ReadWrite> {
     path: PathBuf,
-    dirty: AtomicBool,
+    /// files the model has read
+    read: Mutex<BTreeSet<String>>,
+    ignore: IgnoreList,
+    max_read_size: Option<usize>,
+    mode: M,
 }
 
-impl Project {
-    pub fn new(path: PathBuf) -> Self {
-        Project {
-            path,
-            dirty: AtomicBool::new(false),
-        }
+impl<M> Project<M> {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Replaces the list of paths hidden from the model.
+    pub fn with_ignore_list(mut self, ignore: IgnoreList) -> Self {
+        self.ignore = ignore;
+        self
+    }
+
+    /// Limits the size of a file returned by `read_file`, longer files are truncated.
+    pub fn with_max_read_size(mut self, max_read_size: Option<usize>) -> Self {
+        self.max_read_size = max_read_size;
+        self
     }
 
     pub fn list_contents(&self) -> ProjectDirectoryContents {
         let mut files = vec![];
-        list_project_files(&mut files, &self.path, &PathBuf::new());
+        list_project_files(&mut files, &self.path, &PathBuf::new(), &self.ignore);
 
+        let files: Vec<String> = files
+            .into_iter()
+            .filter_map(|path| path.to_str().map(Into::into))
+            .collect();
+        let binary_files = files
+            .iter()
+            .filter(|file| is_binary_file(&self.path.join(file)))
+            .cloned()
+            .collect();
         ProjectDirectoryContents {
-            files: files
-                .into_iter()
-                .filter_map(|path| path.to_str().map(Into::into))
-                .collect(),
+            files,
+            binary_files,
         }
     }
 
-    pub fn read_file(&self, path: &str) -> ReadFileResult {
-        if path.starts_with('/') || path.starts_with('.') || path.contains("..") {
-            return ReadFileResult {
-                error: Some("Invalid path.".to_string()),
-                contents: None,
-            };
+    /// Converts a path returned by `resolve` back to a path relative to the project directory.
+    fn relative_path(&self, path: &Path) -> Option<PathBuf> {
+        let root = self.path.canonicalize().ok()?;
+        path.strip_prefix(root).ok().map(Path::to_path_buf)
+    }
+
+    /// Converts an absolute path inside the project directory to a relative one, unless the
+    /// path is hidden from the model.
+    pub fn visible_path(&self, path: &Path) -> Option<String> {
+        let relpath = self.relative_path(path)?;
+        let hidden = relpath
+            .ancestors()
+            .any(|ancestor| !ancestor.as_os_str().is_empty() && self.ignore.is_ignored(ancestor));
+        if hidden {
+            return None;
         }
+        relpath.to_str().map(String::from)
+    }
 
-        let path = self.path.join(path);
-        match std::fs::read_to_string(path) {
-            Ok(contents) => ReadFileResult {
-                error: None,
-                contents: Some(contents),
-            },
-            Err(_) => ReadFileResult {
-                error: Some("Cannot read file.".to_string()),
+    /// Resolves a path given by the model, making sure it stays inside the project directory.
+    fn resolve(&self, path: &str) -> Result<PathBuf, PathError> {
+        resolve_path(&self.path, path)
+    }
+
+    pub fn grep(&self, args: &GrepArgs) -> GrepResult {
+        grep(&self.path, &self.list_contents().files, args)
+    }
+
+    pub fn tree(&self, args: &TreeArgs) -> TreeResult {
+        tree(&self.path, &self.list_contents().files, args)
+    }
+
+    pub fn language_stats(&self) -> LanguageStatsResult {
+        language_stats(&self.path, &self.list_contents().files)
+    }
+
+    pub fn symbols(&self, args: &SymbolsArgs) -> SymbolsResult {
+        symbols(&self.path, &self.list_contents().files, args)
+    }
+
+    pub fn find_references(&self, args: &FindReferencesArgs) -> FindReferencesResult {
+        find_references(&self.path, &self.list_contents().files, args)
+    }
+
+    pub fn dependency_graph(&self) -> DependencyGraphResult {
+        dependency_graph(&self.path, &self.list_contents().files)
+    }
+
+    /// Collects the license and package metadata, including hidden license files.
+    pub fn metadata(&self) -> SourceMetadata {
+        source_metadata(&self.path)
+    }
+
+    pub fn read_file(&self, path: &str) -> Result<ReadFileResult, TransientError> {
+        match self.read_text(path)? {
+            Ok(text) => {
+                let total_size = text.contents.len();
+                let contents = match self.max_read_size {
+                    Some(limit) => truncate_contents(text.contents, limit),
+                    None => text.contents,
+                };
+                Ok(ReadFileResult {
+                    error: None,
+                    contents: Some(contents),
+                    note: text.note,
+                    total_size: Some(total_size),
+                })
+            }
+            Err(error) => Ok(ReadFileResult {
+                error: Some(error),
                 contents: None,
-            },
+                note: None,
+                total_size: None,
+            }),
         }
     }
 
-    pub fn write_file(&self, path: &str, contents: &str) -> WriteFileResult {
-        if path.starts_with('/') || path.starts_with('.') || path.contains("..") {
-            WriteFileResult {
-                error: Some("Invalid path.".to_string()),
+    /// Reads the whole file as text, returning an error message for the model on failure.
+    fn read_text(&self, path: &str) -> Result<Result<DecodedText, String>, TransientError> {
+        let resolved = match self.resolve(path) {
+            Ok(resolved) => resolved,
+            Err(e) => return Ok(Err(e.to_string())),
+        };
+
+        let data = match std::fs::read(resolved) {
+            Ok(data) => data,
+            Err(e) if is_transient(&e) => {
+                return Err(TransientError(format!("Cannot read file: {e}")));
             }
-        } else {
-            let path = self.path.join(path);
+            Err(_) => return Ok(Err("Cannot read file.".to_string())),
+        };
+        if is_binary(&data) {
+            return Ok(Err(
+                "The file is binary, use copy_asset to copy it to the destination project."
+                    .to_string(),
+            ));
+        }
+        self.read
+            .lock()
+            .unwrap()
+            .insert(path.trim_start_matches("./").to_string());
+        Ok(Ok(decode_text(data)))
+    }
 
-            let Some(parent) = path.parent() else {
-                return WriteFileResult {
-                    error: Some("Invalid path.".to_string()),
-                };
-            };
-            if !parent.is_dir() && !parent.exists() {
-                std::fs::create_dir_all(parent).unwrap();
+    /// Returns the files the model has read so far.
+    pub fn read_files(&self) -> BTreeSet<String> {
+        self.read.lock().unwrap().clone()
+    }
+
+    /// Lists the public items of a Rust file with their signatures.
+    pub fn rust_items(&self, path: &str) -> Result<RustItemsResult, TransientError> {
+        Ok(match self.read_text(path)? {
+            Ok(text) => rust_items(&text.contents),
+            Err(error) => RustItemsResult {
+                error: Some(error),
+                items: vec![],
+            },
+        })
+    }
+
+    /// Compares a file of another project, e.g. the source one, with its port in this project.
+    pub fn compare_file<N>(
+        &self,
+        source: &Project<N>,
+        args: &CompareFilesArgs,
+    ) -> Result<CompareFilesResult, TransientError> {
+        let (source_text, destination_text) = match (
+            source.read_text(&args.source_path)?,
+            self.read_text(&args.destination_path)?,
+        ) {
+            (Ok(source_text), Ok(destination_text)) => (source_text, destination_text),
+            (Err(e), _) | (_, Err(e)) => return Ok(CompareFilesResult::error(e)),
+        };
+        Ok(compare_files(
+            &args.source_path,
+            &source_text.contents,
+            &destination_text.contents,
+            args.diff == Some(true),
+        ))
+    }
+
+    pub fn stat(&self, path: &str) -> Result<StatResult, TransientError> {
+        let error = |error: &str| StatResult {
+            error: Some(error.to_string()),
+            size: None,
+            lines: None,
+            language: None,
+            modified: None,
+        };
+        let full_path = match self.resolve(path) {
+            Ok(path) => path,
+            Err(e) => return Ok(error(&e.to_string())),
+        };
+        let metadata = match std::fs::metadata(&full_path) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            Ok(_) => return Ok(error("Not a file.")),
+            Err(e) if is_transient(&e) => {
+                return Err(TransientError(format!("Cannot read file metadata: {e}")));
+            }
+            Err(_) => return Ok(error("File not found.")),
+        };
+        let data = match std::fs::read(&full_path) {
+            Ok(data) => data,
+            Err(e) if is_transient(&e) => {
+                return Err(TransientError(format!("Cannot read file: {e}")));
             }
+            Err(_) => return Ok(error("Cannot read file.")),
+        };
 
-            self.dirty.store(true, Ordering::Release);
-            match std::fs::write(path, contents) {
-                Ok(_) => WriteFileResult { error: None },
-                Err(_) => WriteFileResult {
-                    error: Some("Cannot write file.".to_string()),
-                },
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs());
+        Ok(StatResult {
+            error: None,
+            size: Some(metadata.len()),
+            lines: (!is_binary(&data)).then(|| count_lines(&data)),
+            language: detect_language(Path::new(path)).map(Into::into),
+            modified,
+        })
+    }
+
+    pub fn read_file_range(
+        &self,
+        path: &str,
+        start_line: usize,
+        end_line: usize,
+    ) -> Result<ReadFileRangeResult, TransientError> {
+        let DecodedText { contents, note } = match self.read_text(path)? {
+            Ok(text) => text,
+            Err(error) => {
+                return Ok(ReadFileRangeResult {
+                    error: Some(error),
+                    lines: None,
+                    total_lines: None,
+                    note: None,
+                });
             }
+        };
+
+        let total_lines = contents.lines().count();
+        if start_line == 0 || start_line > end_line {
+            return Ok(ReadFileRangeResult {
+                error: Some("Invalid line range.".to_string()),
+                lines: None,
+                total_lines: Some(total_lines),
+                note,
+            });
         }
+
+        let width = end_line.min(total_lines).to_string().len();
+        let lines = contents
+            .lines()
+            .enumerate()
+            .skip(start_line - 1)
+            .take(end_line - start_line + 1)
+            .map(|(i, line)| format!("{:>width$}: {line}\n", i + 1))
+            .collect();
+        Ok(ReadFileRangeResult {
+            error: None,
+            lines: Some(lines),
+            total_lines: Some(total_lines),
+            note,
+        })
+    }
+}
+
+/// Methods changing the files or running cargo in the project directory are only available for
+/// read-write projects.
+impl Project {
+    pub fn new(path: PathBuf) -> Self {
+        Project {
+            path,
+            read: Mutex::default(),
+            ignore: IgnoreList::default(),
+            max_read_size: None,
+            mode: ReadWrite::default(),
+        }
+    }
+
+    /// Turns the project into a read-only one, which has no methods changing the files or
+    /// running cargo in the project directory.
+    pub fn read_only(self) -> Project<ReadOnly> {
+        Project {
+            path: self.path,
+            read: self.read,
+            ignore: self.ignore,
+            max_read_size: self.max_read_size,
+            mode: ReadOnly,
+        }
+    }
+
+    /// Limits the time and the output of the cargo commands run in the project.
+    pub fn with_cargo_options(mut self, cargo_options: CargoOptions) -> Self {
+        self.mode.cargo_options = cargo_options;
+        self
+    }
+
+    /// Keeps the previous versions of overwritten and deleted files in the given directory.
+    pub fn with_backup_dir(mut self, backup_dir: PathBuf) -> Self {
+        self.mode.backup_dir = Some(backup_dir);
+        self
+    }
+
+    /// Refuses to overwrite or delete files that were changed by someone else since riir wrote
+    /// them, unless `force` is set. The hashes of the written files are kept in `written`.
+    pub fn with_written_files(mut self, written: WrittenFiles, force: bool) -> Self {
+        self.mode.written = Some(written);
+        self.mode.force = force;
+        self
+    }
+
+    /// Formats the Rust files written with `write_file` and `apply_patch` with rustfmt.
+    pub fn with_rustfmt(mut self, format: bool) -> Self {
+        self.mode.format = format;
+        self
+    }
+
+    /// Has the changes the model makes to the files reviewed before they are written. Each
+    /// review gets the contents approved by the previous ones.
+    pub fn with_review(mut self, review: ReviewFn) -> Self {
+        self.mode.reviews.push(review);
+        self
+    }
+
+    /// Returns the edition from `Cargo.toml`, which is needed to parse the code correctly.
+    fn edition(&self) -> String {
+        let manifest = std::fs::read_to_string(self.path.join("Cargo.toml")).unwrap_or_default();
+        manifest
+            .parse::<toml::Table>()
+            .ok()
+            .and_then(|manifest| {
+                let edition = manifest.get("package")?.get("edition")?.as_str()?;
+                Some(edition.to_string())
+            })
+            // Cargo's default.
+            .unwrap_or_else(|| "2015".to_string())
+    }
+
+    /// Returns the name of the package from `Cargo.toml`, if it's created.
+    pub fn package_name(&self) -> Option<String> {
+        let manifest = std::fs::read_to_string(self.path.join("Cargo.toml")).ok()?;
+        let manifest = manifest.parse::<toml::Table>().ok()?;
+        let name = manifest.get("package")?.get("name")?.as_str()?;
+        Some(name.to_string())
+    }
+
+    /// Returns a conflict message for the model if the file was changed outside of riir since
+    /// riir wrote it.
+    fn check_conflict(&self, path: &Path) -> Option<String> {
+        let written = self.mode.written.as_ref().filter(|_| !self.mode.force)?;
+        let relpath = self.relative_path(path)?;
+        written.is_changed(&relpath, path).then(|| {
+            "The file was changed outside of riir after it was written, probably fixed by hand. \
+            It is not replaced to keep these changes: read it again and change it with \
+            dst_apply_patch instead."
+                .to_string()
+        })
+    }
+
+    /// Remembers the contents of a file riir has just changed.
+    fn record_written(&self, path: &Path) {
+        if let (Some(written), Some(relpath)) = (&self.mode.written, self.relative_path(path)) {
+            written.record(&relpath, path);
+        }
+    }
+
+    /// Copies the current contents of a file to the backup directory, if it's set and the file
+    /// exists.
+    ///
+    /// Backups of `src/lib.rs` are named `src/lib.rs.1`, `src/lib.rs.2` and so on, the latest
+    /// having the largest number. Returns an error message for the model if the backup fails.
+    fn backup(&self, path: &Path) -> Result<Option<String>, TransientError> {
+        let Some(backup_dir) = &self.mode.backup_dir else {
+            return Ok(None);
+        };
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let Some(relpath) = self.relative_path(path) else {
+            return Ok(Some("Cannot back up the file.".to_string()));
+        };
+
+        let backup_path = backup_dir.join(&relpath);
+        let version = next_backup_version(&backup_path);
+        let mut backup_name = backup_path.file_name().unwrap_or_default().to_owned();
+        backup_name.push(format!(".{version}"));
+        let backup_path = backup_path.with_file_name(backup_name);
+
+        if let Some(error) = create_parent_dir(&backup_path)? {
+            return Ok(Some(error));
+        }
+        match std::fs::copy(path, &backup_path) {
+            Ok(_) => Ok(None),
+            Err(e) if is_transient(&e) => Err(TransientError(format!("Cannot back up file: {e}"))),
+            Err(_) => Ok(Some("Cannot back up the file.".to_string())),
+        }
+    }
+
+    /// Remembers that a file has just been changed.
+    fn mark_dirty(&self, path: &Path) {
+        let relpath = self
+            .relative_path(path)
+            .unwrap_or_else(|| path.to_path_buf());
+        self.mode
+            .dirty
+            .lock()
+            .unwrap()
+            .insert(relpath, SystemTime::now());
     }
 
-    pub fn run_cargo_check(&self) -> Option<String> {
-        let output = std::process::Command::new("./run_cargo_check")
-            .arg(&self.path)
-            .output()
-            .unwrap();
-        let output = String::from_utf8(output.stdout).unwrap();
-        if output.is_empty() {
+    /// Runs the reviews of a change of a file from `old` to `new`. Returns the contents to write
+    /// instead if a review replaced them, or the message for the model if the change was
+    /// rejected.
+    fn review(&self, path: &Path, old: &str, new: Option<&str>) -> Result<Option<String>, String> {
+        // The reviews get the path of the resolved file, whatever way the model spelled it.
+        let root = self
+            .path
+            .canonicalize()
+            .or_else(|_| std::path::absolute(&self.path))
+            .unwrap_or_else(|_| self.path.clone());
+        let relpath = path
+            .strip_prefix(&root)
+            .or_else(|_| path.strip_prefix(&self.path))
+            .unwrap_or(path)
+            .to_string_lossy();
+        let mut replaced: Option<String> = None;
+        for review in &self.mode.reviews {
+            match review(&relpath, old, replaced.as_deref().or(new)) {
+                Review::Approve => {}
+                Review::Reject(comments) => {
+                    return Err(format!(
+                        "The change was reviewed and not written, address the comments and try \
+                        again:\n{comments}"
+                    ));
+                }
+                Review::Replace(contents) => replaced = Some(contents),
+            }
+        }
+        Ok(replaced)
+    }
+
+    pub fn write_file(
+        &self,
+        path: &str,
+        contents: &str,
+    ) -> Result<WriteFileResult, TransientError> {
+        self.write_contents(path, contents, true, true)
+    }
+
+    /// Writes a file riir generates itself, e.g. `rust-toolchain.toml`, without the reviews of
+    /// the changes the model makes.
+    pub fn write_generated_file(
+        &self,
+        path: &str,
+        contents: &str,
+    ) -> Result<WriteFileResult, TransientError> {
+        self.write_contents(path, contents, true, false)
+    }
+
+    /// Writes a file, checking for changes made outside of riir if `check_conflict` is set.
+    /// Edits of the current contents don't need the check, since they keep those changes.
+    /// The change is reviewed first if `review` is set.
+    fn write_contents(
+        &self,
+        path: &str,
+        contents: &str,
+        check_conflict: bool,
+        review: bool,
+    ) -> Result<WriteFileResult, TransientError> {
+        let path = match self.resolve(path) {
+            Ok(path) => path,
+            Err(e) => {
+                return Ok(WriteFileResult::error(e.to_string()));
+            }
+        };
+        // An unformatted file is still written, so that the model can fix the syntax error.
+        let mut format_error = None;
+        let formatted;
+        let contents = if self.mode.format && path.extension().is_some_and(|ext| ext == "rs") {
+            match rustfmt(contents, &self.edition()) {
+                Ok(code) => {
+                    formatted = code;
+                    &formatted
+                }
+                Err(error) => {
+                    format_error = Some(error);
+                    contents
+                }
+            }
+        } else {
+            contents
+        };
+        // Rewriting a file with the same contents would trigger a pointless check.
+        if has_contents(&path, contents.as_bytes()) {
+            return Ok(WriteFileResult::unchanged());
+        }
+        // A conflict is reported before the reviews, which may be expensive.
+        if let Some(error) = self.check_conflict(&path).filter(|_| check_conflict) {
+            return Ok(WriteFileResult::error(error));
+        }
+        let replaced = if review {
+            let old = std::fs::read_to_string(&path).unwrap_or_default();
+            match self.review(&path, &old, Some(contents)) {
+                Ok(replaced) => replaced,
+                Err(error) => return Ok(WriteFileResult::error(error)),
+            }
+        } else {
             None
+        };
+        let note = replaced.is_some().then(|| REVIEW_EDIT_NOTE.to_string());
+        let contents = replaced.as_deref().unwrap_or(contents);
+        if let Some(error) = create_parent_dir(&path)? {
+            return Ok(WriteFileResult::error(error));
+        }
+        if let Some(error) = self.backup(&path)? {
+            return Ok(WriteFileResult::error(error));
+        }
+
+        match write_atomic(&path, contents.as_bytes()) {
+            Ok(_) => {
+                self.mark_dirty(&path);
+                self.record_written(&path);
+                Ok(WriteFileResult {
+                    format_error,
+                    note,
+                    ..WriteFileResult::ok()
+                })
+            }
+            Err(e) if is_transient(&e) => Err(TransientError(format!("Cannot write file: {e}"))),
+            Err(_) => Ok(WriteFileResult::error("Cannot write file.".to_string())),
+        }
+    }
+
+    pub fn append_file(
+        &self,
+        path: &str,
+        contents: &str,
+        truncate: bool,
+    ) -> Result<WriteFileResult, TransientError> {
+        let path = match self.resolve(path) {
+            Ok(path) => path,
+            Err(e) => {
+                return Ok(WriteFileResult::error(e.to_string()));
+            }
+        };
+        // Appending keeps the changes made outside of riir, truncating doesn't.
+        if let Some(error) = self.check_conflict(&path).filter(|_| truncate) {
+            return Ok(WriteFileResult::error(error));
+        }
+        let old = match std::fs::read_to_string(&path) {
+            Ok(old) => old,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) if is_transient(&e) => {
+                return Err(TransientError(format!("Cannot read file: {e}")));
+            }
+            Err(_) => return Ok(WriteFileResult::error("Cannot read file.".to_string())),
+        };
+        // The reviews see the whole file, as it is after the chunk is added.
+        let new = if truncate {
+            contents.to_string()
         } else {
-            Some(output)
+            old.clone() + contents
+        };
+        let replaced = match self.review(&path, &old, Some(&new)) {
+            Ok(replaced) => replaced,
+            Err(error) => return Ok(WriteFileResult::error(error)),
+        };
+        let note = replaced.is_some().then(|| REVIEW_EDIT_NOTE.to_string());
+        let new = replaced.unwrap_or(new);
+        if let Some(error) = create_parent_dir(&path)? {
+            return Ok(WriteFileResult::error(error));
+        }
+        // Truncating replaces the file like `write_file` does.
+        if truncate && let Some(error) = self.backup(&path)? {
+            return Ok(WriteFileResult::error(error));
+        }
+
+        match write_atomic(&path, new.as_bytes()) {
+            Ok(_) => {
+                self.mark_dirty(&path);
+                self.record_written(&path);
+                Ok(WriteFileResult {
+                    note,
+                    ..WriteFileResult::ok()
+                })
+            }
+            Err(e) if is_transient(&e) => Err(TransientError(format!("Cannot write file: {e}"))),
+            Err(_) => Ok(WriteFileResult::error("Cannot write file.".to_string())),
+        }
+    }
+
+    /// Copies a file from another project verbatim, unless a review replaces the contents.
+    pub fn copy_file_from<M>(
+        &self,
+        other: &Project<M>,
+        from: &str,
+        to: &str,
+    ) -> Result<WriteFileResult, TransientError> {
+        let (from, to) = match (other.resolve(from), self.resolve(to)) {
+            (Ok(from), Ok(to)) => (from, to),
+            (Err(e), _) | (_, Err(e)) => {
+                return Ok(WriteFileResult::error(e.to_string()));
+            }
+        };
+
+        let contents = match std::fs::read(from) {
+            Ok(contents) => contents,
+            Err(e) if is_transient(&e) => {
+                return Err(TransientError(format!("Cannot read file: {e}")));
+            }
+            Err(_) => {
+                return Ok(WriteFileResult::error("Cannot read file.".to_string()));
+            }
+        };
+
+        if has_contents(&to, &contents) {
+            return Ok(WriteFileResult::unchanged());
+        }
+        if let Some(error) = self.check_conflict(&to) {
+            return Ok(WriteFileResult::error(error));
+        }
+        let old = std::fs::read_to_string(&to).unwrap_or_default();
+        let replaced = match self.review(&to, &old, Some(&review_text(&contents))) {
+            Ok(replaced) => replaced,
+            Err(error) => return Ok(WriteFileResult::error(error)),
+        };
+        let note = replaced.is_some().then(|| REVIEW_EDIT_NOTE.to_string());
+        let contents = replaced.map_or(contents, String::into_bytes);
+        if let Some(error) = create_parent_dir(&to)? {
+            return Ok(WriteFileResult::error(error));
+        }
+        if let Some(error) = self.backup(&to)? {
+            return Ok(WriteFileResult::error(error));
+        }
+
+        match write_atomic(&to, &contents) {
+            Ok(_) => {
+                self.mark_dirty(&to);
+                self.record_written(&to);
+                Ok(WriteFileResult {
+                    note,
+                    ..WriteFileResult::ok()
+                })
+            }
+            Err(e) if is_transient(&e) => Err(TransientError(format!("Cannot write file: {e}"))),
+            Err(_) => Ok(WriteFileResult::error("Cannot write file.".to_string())),
+        }
+    }
+
+    pub fn create_dir(&self, path: &str) -> Result<WriteFileResult, TransientError> {
+        let path = match self.resolve(path) {
+            Ok(path) => path,
+            Err(e) => {
+                return Ok(WriteFileResult::error(e.to_string()));
+            }
+        };
+
+        match std::fs::create_dir_all(path) {
+            Ok(_) => Ok(WriteFileResult::ok()),
+            Err(e) if is_transient(&e) => {
+                Err(TransientError(format!("Cannot create directory: {e}")))
+            }
+            Err(_) => Ok(WriteFileResult::error(
+                "Cannot create directory.".to_string(),
+            )),
+        }
+    }
+
+    pub fn delete_file(&self, path: &str) -> Result<WriteFileResult, TransientError> {
+        let path = match self.resolve(path) {
+            Ok(path) => path,
+            Err(e) => {
+                return Ok(WriteFileResult::error(e.to_string()));
+            }
+        };
+        if !path.is_file() {
+            return Ok(WriteFileResult::error("File not found.".to_string()));
+        }
+        if let Some(error) = self.check_conflict(&path) {
+            return Ok(WriteFileResult::error(error));
+        }
+        let old = std::fs::read(&path).unwrap_or_default();
+        let replaced = match self.review(&path, &review_text(&old), None) {
+            Ok(replaced) => replaced,
+            Err(error) => return Ok(WriteFileResult::error(error)),
+        };
+        if let Some(error) = self.backup(&path)? {
+            return Ok(WriteFileResult::error(error));
         }
+
+        // A review that replaced the contents keeps the file with them.
+        let result = match &replaced {
+            None => std::fs::remove_file(&path),
+            Some(contents) => write_atomic(&path, contents.as_bytes()),
+        };
+        let note = replaced.is_some().then(|| REVIEW_EDIT_NOTE.to_string());
+        match result {
+            Ok(_) => {
+                self.mark_dirty(&path);
+                self.record_written(&path);
+                Ok(WriteFileResult {
+                    note,
+                    ..WriteFileResult::ok()
+                })
+            }
+            Err(e) if is_transient(&e) => Err(TransientError(format!("Cannot delete file: {e}"))),
+            Err(_) => Ok(WriteFileResult::error("Cannot delete file.".to_string())),
+        }
+    }
+
+    /// Moves a file within the project. The reviews see it as a new file at the destination.
+    pub fn rename_file(&self, from: &str, to: &str) -> Result<WriteFileResult, TransientError> {
+        let (from, to) = match (self.resolve(from), self.resolve(to)) {
+            (Ok(from), Ok(to)) => (from, to),
+            (Err(e), _) | (_, Err(e)) => {
+                return Ok(WriteFileResult::error(e.to_string()));
+            }
+        };
+        if !from.is_file() {
+            return Ok(WriteFileResult::error("File not found.".to_string()));
+        }
+        if to.exists() {
+            return Ok(WriteFileResult::error(
+                "The destination file already exists.".to_string(),
+            ));
+        }
+        if let Some(error) = self.check_conflict(&from) {
+            return Ok(WriteFileResult::error(error));
+        }
+        let contents = match std::fs::read(&from) {
+            Ok(contents) => contents,
+            Err(e) if is_transient(&e) => {
+                return Err(TransientError(format!("Cannot read file: {e}")));
+            }
+            Err(_) => return Ok(WriteFileResult::error("Cannot read file.".to_string())),
+        };
+        let replaced = match self.review(&to, "", Some(&review_text(&contents))) {
+            Ok(replaced) => replaced,
+            Err(error) => return Ok(WriteFileResult::error(error)),
+        };
+        let note = replaced.is_some().then(|| REVIEW_EDIT_NOTE.to_string());
+        if let Some(error) = create_parent_dir(&to)? {
+            return Ok(WriteFileResult::error(error));
+        }
+        // The original contents are gone once the edited ones are written instead.
+        if replaced.is_some()
+            && let Some(error) = self.backup(&from)?
+        {
+            return Ok(WriteFileResult::error(error));
+        }
+
+        let result = match &replaced {
+            None => std::fs::rename(&from, &to),
+            // The edited contents take the place of the moved file.
+            Some(contents) => {
+                write_atomic(&to, contents.as_bytes()).and_then(|_| std::fs::remove_file(&from))
+            }
+        };
+        match result {
+            Ok(_) => {
+                self.mark_dirty(&from);
+                self.mark_dirty(&to);
+                self.record_written(&from);
+                self.record_written(&to);
+                Ok(WriteFileResult {
+                    note,
+                    ..WriteFileResult::ok()
+                })
+            }
+            Err(e) if is_transient(&e) => Err(TransientError(format!("Cannot rename file: {e}"))),
+            Err(_) => Ok(WriteFileResult::error("Cannot rename file.".to_string())),
+        }
+    }
+
+    pub fn apply_patch(
+        &self,
+        path: &str,
+        edits: Option<&[SearchReplace]>,
+        diff: Option<&str>,
+    ) -> Result<WriteFileResult, TransientError> {
+        let contents = match self.read_text(path)? {
+            Ok(text) => text.contents,
+            Err(error) => return Ok(WriteFileResult::error(error)),
+        };
+
+        let patched = match (edits, diff) {
+            (Some(edits), None) => apply_edits(&contents, edits),
+            (None, Some(diff)) => apply_diff(&contents, diff),
+            _ => Err("Specify either edits or diff.".to_string()),
+        };
+        match patched {
+            Ok(patched) => self.write_contents(path, &patched, false, true),
+            Err(error) => Ok(WriteFileResult::error(error)),
+        }
+    }
+
+    /// Edits `Cargo.toml` or another manifest in the project directory.
+    pub fn edit_manifest(
+        &self,
+        path: &str,
+        edits: &[ManifestEdit],
+    ) -> Result<WriteFileResult, TransientError> {
+        let contents = match self.read_text(path)? {
+            Ok(text) => text.contents,
+            Err(error) => return Ok(WriteFileResult::error(error)),
+        };
+        match edit_manifest(&contents, edits) {
+            Ok(edited) => self.write_contents(path, &edited, false, true),
+            Err(error) => Ok(WriteFileResult::error(error)),
+        }
+    }
+
+    /// Adds a dependency to the manifest with `cargo add`, which checks it against the registry.
+    pub fn cargo_add(&self, args: &CargoAddArgs) -> Result<CargoAddResult, TransientError> {
+        let valid =
+            |s: &str| !s.is_empty() && !s.starts_with('-') && !s.contains(char::is_whitespace);
+        if !valid(&args.name) || !args.version.as_deref().is_none_or(valid) {
+            return Ok(CargoAddResult::error(
+                "Invalid crate name or version.".to_string(),
+            ));
+        }
+        let manifest = self.path.join("Cargo.toml");
+        if !manifest.is_file() {
+            return Ok(CargoAddResult::error("Cargo.toml not found.".to_string()));
+        }
+        if let Some(error) = self.check_conflict(&manifest) {
+            return Ok(CargoAddResult::error(error));
+        }
+        let old = match std::fs::read_to_string(&manifest) {
+            Ok(old) => old,
+            Err(e) => return Err(TransientError(format!("Cannot read Cargo.toml: {e}"))),
+        };
+        if let Some(error) = self.backup(&manifest)? {
+            return Ok(CargoAddResult::error(error));
+        }
+
+        let options = &self.mode.cargo_options;
+        let mut command = std::process::Command::new("cargo");
+        if let Some(toolchain) = &options.toolchain {
+            command.arg(format!("+{toolchain}"));
+        }
+        command.arg("add").current_dir(&self.path);
+        if args.dev == Some(true) {
+            command.arg("--dev");
+        }
+        if let Some(features) = &args.features {
+            command.arg("--features").arg(features.join(","));
+        }
+        match &args.version {
+            Some(version) => command.arg(format!("{}@{version}", args.name)),
+            None => command.arg(&args.name),
+        };
+        // A registry that doesn't respond would block the conversion.
+        let output = match run_command(&mut command, None, options.timeout, options.max_output) {
+            Ok(output) => output,
+            Err(e) => return Ok(CargoAddResult::error(e)),
+        };
+
+        // cargo reports both progress and errors to stderr.
+        let mut log = output.stderr.trim().to_string();
+        if output.timed_out {
+            log += &format!(
+                "\n[cargo add was killed after running for {} seconds]",
+                options.timeout.unwrap_or_default().as_secs_f64()
+            );
+        }
+        if !output.success {
+            // A killed cargo may have left the manifest half written.
+            if output.timed_out {
+                let _ = write_atomic(&manifest, old.as_bytes());
+            }
+            return Ok(CargoAddResult::error(log));
+        }
+
+        // cargo has already changed the manifest, a rejected change is undone.
+        let new = std::fs::read_to_string(&manifest).unwrap_or_default();
+        let restore = |contents: &str| {
+            write_atomic(&manifest, contents.as_bytes())
+                .map_err(|e| TransientError(format!("Cannot write Cargo.toml: {e}")))
+        };
+        match self.review(&manifest, &old, Some(&new)) {
+            Ok(None) => {}
+            Ok(Some(replaced)) => restore(&replaced)?,
+            Err(error) => {
+                restore(&old)?;
+                return Ok(CargoAddResult::error(error));
+            }
+        }
+        self.mark_dirty(&manifest);
+        self.record_written(&manifest);
+        Ok(CargoAddResult {
+            error: None,
+            output: log,
+        })
+    }
+
+    /// Runs `cargo check` in the project directory. In a workspace, each member affected by the
+    /// changed files is checked separately.
+    pub fn run_cargo_check(&self, changed: &[&Path]) -> Result<CargoOutput, String> {
+        self.check(Some(changed))
+    }
+
+    /// Runs `cargo check` in the project directory. In a workspace, every member is checked
+    /// separately.
+    pub fn run_cargo_check_all(&self) -> Result<CargoOutput, String> {
+        self.check(None)
+    }
+
+    /// Checks the members affected by the changed files, or all of them without the list.
+    fn check(&self, changed: Option<&[&Path]>) -> Result<CargoOutput, String> {
+        // An invalid manifest is reported by the check itself.
+        match workspace_members(&self.path, &self.mode.cargo_options) {
+            Ok(members) if members.len() > 1 => {
+                let checked = match changed {
+                    Some(changed) => affected_members(&members, changed),
+                    None => members.iter().collect(),
+                };
+                check_workspace(&self.path, &members, &checked, &self.mode.cargo_options)
+            }
+            _ => run_cargo(&self.path, &["check"], &self.mode.cargo_options),
+        }
+    }
+
+    /// Runs `cargo build` in the project directory with a profile like "dev" or "release".
+    pub fn run_build(&self, profile: &str) -> Result<CargoOutput, String> {
+        run_cargo(
+            &self.path,
+            &["build", "--all-targets", "--profile", profile],
+            &self.mode.cargo_options,
+        )
+    }
+
+    /// Runs `cargo test` in the project directory, without stopping at the first failed target.
+    pub fn run_tests(&self) -> Result<CargoOutput, String> {
+        run_cargo(
+            &self.path,
+            &["test", "--no-fail-fast"],
+            &self.mode.cargo_options,
+        )
+    }
+
+    /// Runs the tests of one integration test target with additional environment variables.
+    pub fn run_test_target(
+        &self,
+        target: &str,
+        env: &[(&str, String)],
+    ) -> Result<CargoOutput, String> {
+        run_cargo_with_env(
+            &self.path,
+            &["test", "--no-fail-fast", "--test", target],
+            env,
+            &self.mode.cargo_options,
+        )
+    }
+
+    /// Runs `cargo doc` in the project directory, without documenting the dependencies.
+    pub fn run_doc(&self) -> Result<CargoOutput, String> {
+        run_cargo(&self.path, &["doc", "--no-deps"], &self.mode.cargo_options)
+    }
+
+    /// Runs the examples in the doc comments with `cargo test --doc`.
+    pub fn run_doctests(&self) -> Result<CargoOutput, String> {
+        run_cargo(&self.path, &["test", "--doc"], &self.mode.cargo_options)
+    }
+
+    /// Runs `cargo clippy` in the project directory with the configured lints.
+    pub fn run_clippy(&self, config: &ClippyConfig) -> Result<CargoOutput, String> {
+        run_cargo(&self.path, &clippy_args(config), &self.mode.cargo_options)
+    }
+
+    /// Runs `cargo audit` in the project directory.
+    pub fn run_audit(&self) -> Result<Vec<DependencyProblem>, String> {
+        run_audit(&self.path, &self.mode.cargo_options)
+    }
+
+    /// Runs `cargo deny` in the project directory with the policy in the given file.
+    pub fn run_deny(&self, policy: &Path) -> Result<Vec<DependencyProblem>, String> {
+        run_deny(&self.path, policy, &self.mode.cargo_options)
     }
 
     pub fn is_dirty(&self) -> bool {
-        self.dirty.load(Ordering::Acquire)
+        !self.mode.dirty.lock().unwrap().is_empty()
+    }
+
+    /// Returns the files changed since the last `clear_dirty` with the time of the last change.
+    pub fn dirty_files(&self) -> BTreeMap<PathBuf, SystemTime> {
+        self.mode.dirty.lock().unwrap().clone()
     }
 
     pub fn clear_dirty(&self) {
-        self.dirty.store(false, Ordering::Release);
+        self.mode.dirty.lock().unwrap().clear();
     }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct ProjectDirectoryContents {
     pub files: Vec<String>,
+    /// files from the list above that have binary contents and can't be read as text
+    pub binary_files: Vec<String>,
 }
 
 #[derive(Deserialize, JsonSchema)]
@@ -109,6 +1139,22 @@ pub struct ReadFileArgs {
     pub path: String,
 }
 
+#[derive(Deserialize, JsonSchema)]
+pub struct ReadFileRangeArgs {
+    /// a relative path to the file in the project directory
+    pub path: String,
+    /// number of the first line to read, starting from 1
+    pub start_line: usize,
+    /// number of the last line to read, inclusive
+    pub end_line: usize,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct StatArgs {
+    /// a relative path to the file in the project directory
+    pub path: String,
+}
+
 #[derive(Deserialize, JsonSchema)]
 pub struct WriteFileArgs {
     /// a relative path to the file in the project directory
@@ -117,30 +1163,169 @@ pub struct WriteFileArgs {
     pub contents: String,
 }
 
+#[derive(Deserialize, JsonSchema)]
+pub struct AppendFileArgs {
+    /// a relative path to the file in the project directory
+    pub path: String,
+    /// a chunk of contents to add to the end of the file
+    pub contents: String,
+    /// empty the file before writing, use it for the first chunk
+    pub truncate: Option<bool>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct CopyAssetArgs {
+    /// a relative path to the file in the source project directory
+    pub source_path: String,
+    /// a relative path to the file in the destination project directory
+    pub destination_path: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct CreateDirArgs {
+    /// a relative path to the directory in the project directory
+    pub path: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct DeleteFileArgs {
+    /// a relative path to the file in the project directory
+    pub path: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct RenameFileArgs {
+    /// a relative path to the existing file in the project directory
+    pub from: String,
+    /// a new relative path of the file, which must not exist yet
+    pub to: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ApplyPatchArgs {
+    /// a relative path to the file in the project directory
+    pub path: String,
+    /// search/replace edits applied in order; mutually exclusive with `diff`
+    pub edits: Option<Vec<SearchReplace>>,
+    /// a unified diff of the file; mutually exclusive with `edits`
+    pub diff: Option<String>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct CargoAddArgs {
+    /// name of the crate on crates.io
+    #[serde(rename = "crate")]
+    pub name: String,
+    /// version requirement, the latest version by default
+    pub version: Option<String>,
+    /// features of the crate to enable
+    pub features: Option<Vec<String>>,
+    /// add to `[dev-dependencies]` instead of `[dependencies]`
+    pub dev: Option<bool>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct ReadFileResult {
     pub error: Option<String>,
     pub contents: Option<String>,
+    /// set if the file had to be converted from a legacy encoding
+    pub note: Option<String>,
+    /// size of the whole file in bytes
+    pub total_size: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ReadFileRangeResult {
+    pub error: Option<String>,
+    /// requested lines prefixed with their numbers
+    pub lines: Option<String>,
+    pub total_lines: Option<usize>,
+    /// set if the file had to be converted from a legacy encoding
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CargoAddResult {
+    pub error: Option<String>,
+    /// what cargo reported, e.g. the available features of the crate
+    pub output: String,
+}
+
+impl CargoAddResult {
+    fn error(error: String) -> Self {
+        CargoAddResult {
+            error: Some(error),
+            output: String::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StatResult {
+    pub error: Option<String>,
+    /// size of the file in bytes
+    pub size: Option<u64>,
+    /// number of lines, absent for binary files
+    pub lines: Option<usize>,
+    /// programming language guessed from the file name
+    pub language: Option<String>,
+    /// time of the last modification in seconds since the Unix epoch
+    pub modified: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct WriteFileResult {
     pub error: Option<String>,
+    /// true if the file already had the same contents and wasn't touched
+    pub unchanged: bool,
+    /// set if the written Rust file can't be formatted, usually because of a syntax error
+    pub format_error: Option<String>,
+    /// remark on how the file was written, e.g. that the contents were changed in the review
+    pub note: Option<String>,
+}
+
+impl WriteFileResult {
+    fn ok() -> Self {
+        WriteFileResult {
+            error: None,
+            unchanged: false,
+            format_error: None,
+            note: None,
+        }
+    }
+
+    fn unchanged() -> Self {
+        WriteFileResult {
+            error: None,
+            unchanged: true,
+            format_error: None,
+            note: None,
+        }
+    }
+
+    fn error(error: String) -> Self {
+        WriteFileResult {
+            error: Some(error),
+            unchanged: false,
+            format_error: None,
+            note: None,
+        }
+    }
 }
 
-fn list_project_files(files: &mut Vec<PathBuf>, path: &Path, relpath: &Path) {
+fn list_project_files(files: &mut Vec<PathBuf>, path: &Path, relpath: &Path, ignore: &IgnoreList) {
     if let Ok(entries) = std::fs::read_dir(path) {
         for entry in entries.flatten() {
             let file_name = entry.file_name();
             let path2 = path.join(&file_name);
             let relpath2 = relpath.join(&file_name);
 
-            if is_not_important_path(&path2, &relpath2) {
+            if ignore.is_ignored(&relpath2) {
                 continue;
             }
 
             if path2.is_dir() {
-                list_project_files(files, &path2, &relpath2);
+                list_project_files(files, &path2, &relpath2, ignore);
             } else if path2.is_file() {
                 files.push(relpath2);
             }
@@ -148,18 +1333,523 @@ fn list_project_files(files: &mut Vec<PathBuf>, path: &Path, relpath: &Path) {
     }
 }
 
-fn is_not_important_path(path: &Path, relpath: &Path) -> bool {
-    if path.is_dir() {
-        relpath == Path::new(".git") || relpath == Path::new("target")
-    } else if path.is_file() {
-        relpath == Path::new(".gitignore")
-            || relpath == Path::new(".env")
-            || relpath == Path::new("Cargo.lock")
-            || relpath == Path::new("LICENSE")
-            || relpath == Path::new("LICENSE.txt")
+/// Cuts the file contents at a line boundary so that they fit into `limit` bytes.
+fn truncate_contents(mut contents: String, limit: usize) -> String {
+    if contents.len() <= limit {
+        return contents;
+    }
+    let total = contents.len();
+    let total_lines = contents.lines().count();
+    let mut end = limit;
+    while !contents.is_char_boundary(end) {
+        end -= 1;
+    }
+    // Keep whole lines unless the first line alone is too long.
+    if let Some(newline) = contents[..end].rfind('\n') {
+        end = newline + 1;
+    }
+    contents.truncate(end);
+    let shown_lines = contents.lines().count();
+    contents.push_str(&format!(
+        "\n[TRUNCATED: the file is {total} bytes and {total_lines} lines long, only the first \
+        {shown_lines} lines are shown. Read the rest with *_read_file_range starting from line {}.]",
+        shown_lines + 1
+    ));
+    contents
+}
+
+/// Checks whether the file has binary contents by looking at its beginning.
+fn is_binary_file(path: &Path) -> bool {
+    let Ok(file) = std::fs::File::open(path) else {
+        return false;
+    };
+    let mut head = Vec::with_capacity(8000);
+    if file.take(8000).read_to_end(&mut head).is_err() {
+        return false;
+    }
+    is_binary(&head)
+}
+
+/// Checks whether the file exists and has exactly the given contents.
+fn has_contents(path: &Path, contents: &[u8]) -> bool {
+    std::fs::read(path).is_ok_and(|existing| existing == contents)
+}
+
+/// Returns the file contents as the reviews see them: binary contents are only described.
+fn review_text(data: &[u8]) -> Cow<'_, str> {
+    if is_binary(data) {
+        Cow::Owned(format!("[binary file, {} bytes]", data.len()))
     } else {
-        true
+        String::from_utf8_lossy(data)
+    }
+}
+
+/// Finds the number of the next backup of a file, given the path of the backup without a number.
+fn next_backup_version(backup_path: &Path) -> u64 {
+    let (Some(dir), Some(name)) = (
+        backup_path.parent(),
+        backup_path.file_name().and_then(|name| name.to_str()),
+    ) else {
+        return 1;
+    };
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 1;
+    };
+    let prefix = format!("{name}.");
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let entry_name = entry.file_name();
+            entry_name
+                .to_str()?
+                .strip_prefix(&prefix)?
+                .parse::<u64>()
+                .ok()
+        })
+        .max()
+        .map_or(1, |version| version + 1)
+}
+
+/// Replaces the file contents so that the file is either old or new, never half-written.
+///
+/// The contents are written to a temporary file next to the target, which is then renamed over it.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let Some(file_name) = path.file_name() else {
+        return Err(ErrorKind::InvalidInput.into());
+    };
+    let mut temp_name = file_name.to_owned();
+    temp_name.push(TEMP_SUFFIX);
+    let temp_path = path.with_file_name(temp_name);
+
+    let result = std::fs::File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|_| std::fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Creates the parent directory of a file if it doesn't exist.
+///
+/// Returns an error message for the model if the directory can't be created.
+fn create_parent_dir(path: &Path) -> Result<Option<String>, TransientError> {
+    let Some(parent) = path.parent() else {
+        return Ok(Some("Invalid path.".to_string()));
+    };
+    if parent.is_dir() {
+        return Ok(None);
+    }
+    match std::fs::create_dir_all(parent) {
+        Ok(_) => Ok(None),
+        Err(e) if is_transient(&e) => Err(TransientError(format!(
+            "Cannot create parent directory: {e}"
+        ))),
+        Err(_) => Ok(Some("Cannot create parent directory.".to_string())),
+    }
+}
+
+/// Reasons to reject a path given by the model.
+#[derive(Debug, Error)]
+enum PathError {
+    #[error("Invalid path.")]
+    Invalid,
+    #[error("The path must be relative to the project directory.")]
+    Absolute,
+    #[error("The path leads outside of the project directory.")]
+    OutsideProject,
+    #[error("The path is reserved for the tool itself.")]
+    Reserved,
+}
+
+/// Top-level directories the model must not touch even though they are inside the project.
+const RESERVED_DIRS: &[&str] = &[SESSION_DIR, ".git"];
+
+/// Resolves a path relative to `root`, following symbolic links.
+///
+/// Components that don't exist yet are appended as is, so that paths of new files can be
+/// checked too. Fails if the path leaves `root` at any point.
+fn resolve_path(root: &Path, path: &str) -> Result<PathBuf, PathError> {
+    if path.is_empty() || path.contains(['\\', '\0']) {
+        return Err(PathError::Invalid);
+    }
+    let is_drive = path.len() >= 2 && path.as_bytes()[1] == b':';
+    if is_drive || Path::new(path).has_root() {
+        return Err(PathError::Absolute);
+    }
+
+    let root = root
+        .canonicalize()
+        .or_else(|_| std::path::absolute(root))
+        .map_err(|_| PathError::Invalid)?;
+    let mut resolved = root.clone();
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(name) => {
+                resolved.push(name);
+                if resolved.symlink_metadata().is_ok() {
+                    // Dangling symbolic links can point anywhere.
+                    resolved = resolved.canonicalize().map_err(|_| PathError::Invalid)?;
+                }
+            }
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            Component::Prefix(_) | Component::RootDir => return Err(PathError::Absolute),
+        }
+        if !resolved.starts_with(&root) {
+            return Err(PathError::OutsideProject);
+        }
     }
+
+    let relpath = resolved.strip_prefix(&root).unwrap();
+    if let Some(Component::Normal(first)) = relpath.components().next()
+        && RESERVED_DIRS.iter().any(|dir| first == *dir)
+    {
+        return Err(PathError::Reserved);
+    }
+    Ok(resolved)
+}
+
+/// Checks whether the I/O error may go away if the operation is repeated.
+fn is_transient(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::TimedOut
+            | ErrorKind::ResourceBusy
+    )
+}
+
+#[cfg(test)]
+use crate::testing::TempDir;
+
+#[test]
+fn test_read_file_range() {
+    let dir = TempDir::new("range");
+    let project = Project::new(dir.to_path_buf());
+    let contents = (1..=12).map(|i| format!("line {i}\n")).collect::<String>();
+    project.write_file("a.txt", &contents).unwrap();
+
+    let result = project.read_file_range("a.txt", 9, 20).unwrap();
+    assert_eq!(
+        result.lines.unwrap(),
+        " 9: line 9\n10: line 10\n11: line 11\n12: line 12\n"
+    );
+    assert_eq!(result.total_lines, Some(12));
+    assert!(
+        project
+            .read_file_range("a.txt", 0, 1)
+            .unwrap()
+            .error
+            .is_some()
+    );
+    assert!(
+        project
+            .read_file_range("b.txt", 1, 1)
+            .unwrap()
+            .error
+            .is_some()
+    );
+}
+
+#[test]
+fn test_rename_file() {
+    let dir = TempDir::new("rename");
+    let project = Project::new(dir.to_path_buf());
+    project.write_file("a.rs", "a").unwrap();
+    project.write_file("b.rs", "b").unwrap();
+    project.clear_dirty();
+
+    assert!(project.rename_file("a.rs", "b.rs").unwrap().error.is_some());
+    assert!(project.rename_file("c.rs", "d.rs").unwrap().error.is_some());
+    assert!(
+        project
+            .rename_file("a.rs", "../a.rs")
+            .unwrap()
+            .error
+            .is_some()
+    );
+    assert!(!project.is_dirty());
+
+    assert!(
+        project
+            .rename_file("a.rs", "src/a.rs")
+            .unwrap()
+            .error
+            .is_none()
+    );
+    assert!(project.is_dirty());
+    let dirty: Vec<_> = project.dirty_files().into_keys().collect();
+    assert_eq!(dirty, [PathBuf::from("a.rs"), PathBuf::from("src/a.rs")]);
+    assert!(!dir.join("a.rs").exists());
+    assert_eq!(std::fs::read_to_string(dir.join("src/a.rs")).unwrap(), "a");
+}
+
+#[test]
+fn test_parent_dir_errors() {
+    let dir = TempDir::new("mkdir");
+    let project = Project::new(dir.to_path_buf());
+
+    assert!(project.create_dir("src/bin").unwrap().error.is_none());
+    assert!(dir.join("src/bin").is_dir());
+
+    // A file in place of the parent directory.
+    project.write_file("file", "").unwrap();
+    let result = project.write_file("file/a.rs", "").unwrap();
+    assert_eq!(result.error.unwrap(), "Cannot create parent directory.");
+    assert!(project.create_dir("file/sub").unwrap().error.is_some());
+}
+
+#[test]
+fn test_append_file() {
+    let dir = TempDir::new("append");
+    let project = Project::new(dir.to_path_buf());
+
+    for (chunk, truncate) in [("old", false), ("a", true), ("b", false), ("c", false)] {
+        let result = project.append_file("src/lib.rs", chunk, truncate).unwrap();
+        assert!(result.error.is_none());
+    }
+    assert_eq!(
+        std::fs::read_to_string(dir.join("src/lib.rs")).unwrap(),
+        "abc"
+    );
+}
+
+#[test]
+fn test_ignore_list() {
+    let dir = TempDir::new("ignore");
+    for file in [
+        "src/main.c",
+        "data/big.csv",
+        "target/debug/app",
+        "Cargo.lock",
+    ] {
+        std::fs::create_dir_all(dir.join(file).parent().unwrap()).unwrap();
+        std::fs::write(dir.join(file), "").unwrap();
+    }
+
+    let ignore = IgnoreList::new(&IgnoreConfig {
+        use_defaults: true,
+        patterns: vec!["data".to_string()],
+    })
+    .unwrap();
+    let project = Project::new(dir.to_path_buf()).with_ignore_list(ignore);
+    assert_eq!(project.list_contents().files, ["src/main.c"]);
+
+    let ignore = IgnoreList::new(&IgnoreConfig {
+        use_defaults: false,
+        patterns: vec!["**/*.csv".to_string(), "target".to_string()],
+    })
+    .unwrap();
+    let project = Project::new(dir.to_path_buf()).with_ignore_list(ignore);
+    let mut files = project.list_contents().files;
+    files.sort();
+    assert_eq!(files, ["Cargo.lock", "src/main.c"]);
+}
+
+#[test]
+fn test_binary_files() {
+    let dir = TempDir::new("binary");
+    let source = Project::new(dir.join("src")).read_only();
+    let destination = Project::new(dir.join("dst"));
+    std::fs::create_dir_all(dir.join("src/assets")).unwrap();
+    std::fs::write(dir.join("src/assets/icon.png"), b"\x89PNG\r\n\x1a\n\0\0").unwrap();
+    std::fs::write(dir.join("src/main.py"), "print()").unwrap();
+
+    let contents = source.list_contents();
+    assert_eq!(contents.files.len(), 2);
+    assert_eq!(contents.binary_files, ["assets/icon.png"]);
+    assert!(source.read_file("assets/icon.png").unwrap().error.is_some());
+
+    let result = destination
+        .copy_file_from(&source, "assets/icon.png", "assets/icon.png")
+        .unwrap();
+    assert!(result.error.is_none());
+    assert_eq!(
+        std::fs::read(dir.join("dst/assets/icon.png")).unwrap(),
+        b"\x89PNG\r\n\x1a\n\0\0"
+    );
+}
+
+#[test]
+fn test_legacy_encoding() {
+    let dir = TempDir::new("encoding");
+    std::fs::write(dir.join("main.c"), b"// Gr\xfc\xdfe\nint main;\n").unwrap();
+    let project = Project::new(dir.to_path_buf());
+
+    let result = project.read_file("main.c").unwrap();
+    assert_eq!(result.contents.unwrap(), "// Grüße\nint main;\n");
+    assert!(result.note.is_some());
+    let result = project.read_file_range("main.c", 2, 2).unwrap();
+    assert_eq!(result.lines.unwrap(), "2: int main;\n");
+    assert!(result.note.is_some());
+}
+
+#[test]
+fn test_read_size_limit() {
+    let dir = TempDir::new("limit");
+    let project = Project::new(dir.to_path_buf()).with_max_read_size(Some(16));
+    let contents = (1..=10).map(|i| format!("line {i}\n")).collect::<String>();
+    project.write_file("a.txt", &contents).unwrap();
+    project.write_file("b.txt", "short\n").unwrap();
+
+    let result = project.read_file("a.txt").unwrap();
+    let contents = result.contents.unwrap();
+    assert!(contents.starts_with("line 1\nline 2\n\n[TRUNCATED"));
+    assert!(contents.contains("starting from line 3"));
+    assert_eq!(result.total_size, Some(71));
+    assert_eq!(
+        project.read_file("b.txt").unwrap().contents.unwrap(),
+        "short\n"
+    );
+}
+
+#[test]
+fn test_stat() {
+    let dir = TempDir::new("stat");
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::write(dir.join("src/main.c"), "int x;\nint y;\n").unwrap();
+    let project = Project::new(dir.to_path_buf());
+
+    let result = project.stat("src/main.c").unwrap();
+    assert!(result.error.is_none());
+    assert_eq!(result.size, Some(14));
+    assert_eq!(result.lines, Some(2));
+    assert_eq!(result.language.as_deref(), Some("C"));
+    assert!(result.modified.is_some());
+    assert!(project.stat("src").unwrap().error.is_some());
+    assert!(project.stat("missing.c").unwrap().error.is_some());
+}
+
+#[test]
+fn test_path_sandbox() {
+    let dir = TempDir::new("sandbox");
+    std::fs::create_dir_all(dir.join("project/src")).unwrap();
+    std::fs::write(dir.join("secret.txt"), "secret").unwrap();
+    let root = dir.join("project");
+
+    assert!(resolve_path(&root, "src/main.rs").is_ok());
+    assert!(resolve_path(&root, "new/dir/../lib.rs").is_ok());
+    assert!(resolve_path(&root, "./.gitignore").is_ok());
+    assert!(matches!(
+        resolve_path(&root, "/etc/passwd"),
+        Err(PathError::Absolute)
+    ));
+    assert!(matches!(
+        resolve_path(&root, "C:/Windows"),
+        Err(PathError::Absolute)
+    ));
+    assert!(matches!(
+        resolve_path(&root, "src\\..\\..\\secret.txt"),
+        Err(PathError::Invalid)
+    ));
+    assert!(matches!(
+        resolve_path(&root, "src/../../secret.txt"),
+        Err(PathError::OutsideProject)
+    ));
+    assert!(matches!(
+        resolve_path(&root, ".riir/notes.json"),
+        Err(PathError::Reserved)
+    ));
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(&dir, root.join("src/escape")).unwrap();
+        let project = Project::new(root.clone());
+        let result = project.read_file("src/escape/secret.txt").unwrap();
+        assert_eq!(
+            result.error.unwrap(),
+            "The path leads outside of the project directory."
+        );
+        assert!(
+            project
+                .write_file("src/escape/new.txt", "")
+                .unwrap()
+                .error
+                .is_some()
+        );
+        assert!(!dir.join("new.txt").exists());
+    }
+}
+
+#[test]
+fn test_atomic_write() {
+    let dir = TempDir::new("atomic");
+    let project = Project::new(dir.to_path_buf());
+
+    project.write_file("src/lib.rs", "old").unwrap();
+    project.write_file("src/lib.rs", "new").unwrap();
+    assert_eq!(
+        std::fs::read_to_string(dir.join("src/lib.rs")).unwrap(),
+        "new"
+    );
+    assert!(!dir.join("src/lib.rs.riir-tmp").exists());
+
+    // A leftover of an interrupted write is not shown to the model.
+    std::fs::write(dir.join("src/main.rs.riir-tmp"), "").unwrap();
+    assert_eq!(project.list_contents().files, ["src/lib.rs"]);
+}
+
+#[test]
+fn test_backups() {
+    let dir = TempDir::new("backup");
+    std::fs::create_dir_all(dir.join("dst")).unwrap();
+    let backups = dir.join("dst/.riir/backups");
+    let project = Project::new(dir.join("dst")).with_backup_dir(backups.clone());
+
+    for contents in ["v1", "v2"] {
+        project.write_file("src/lib.rs", contents).unwrap();
+    }
+    // Truncating replaces the file, appending only extends it.
+    project.append_file("src/lib.rs", "v", true).unwrap();
+    project.append_file("src/lib.rs", "3", false).unwrap();
+    project.delete_file("src/lib.rs").unwrap();
+    for (version, contents) in [(1, "v1"), (2, "v2"), (3, "v3")] {
+        let backup = backups.join(format!("src/lib.rs.{version}"));
+        assert_eq!(std::fs::read_to_string(backup).unwrap(), contents);
+    }
+    assert!(!backups.join("src/lib.rs.4").exists());
+}
+
+#[test]
+fn test_unchanged_write() {
+    let dir = TempDir::new("unchanged");
+    let project = Project::new(dir.to_path_buf()).with_backup_dir(dir.join("backups"));
+
+    assert!(!project.write_file("a.rs", "fn a() {}").unwrap().unchanged);
+    project.clear_dirty();
+    assert!(project.write_file("a.rs", "fn a() {}").unwrap().unchanged);
+    assert!(!project.is_dirty());
+    assert!(!dir.join("backups").exists());
+    assert!(!project.write_file("a.rs", "fn b() {}").unwrap().unchanged);
+    assert!(project.is_dirty());
+}
+
+#[test]
+fn test_cargo_add_args() {
+    let dir = TempDir::new("cargo-add");
+    let project = Project::new(dir.to_path_buf());
+    let args = |name: &str, version: Option<&str>| CargoAddArgs {
+        name: name.to_string(),
+        version: version.map(String::from),
+        features: None,
+        dev: None,
+    };
+
+    let result = project.cargo_add(&args("--offline", None)).unwrap();
+    assert_eq!(result.error.unwrap(), "Invalid crate name or version.");
+    let result = project
+        .cargo_add(&args("serde", Some("1 --dry-run")))
+        .unwrap();
+    assert_eq!(result.error.unwrap(), "Invalid crate name or version.");
+    let result = project.cargo_add(&args("serde", None)).unwrap();
+    assert_eq!(result.error.unwrap(), "Cargo.toml not found.");
 }
 
 #[test]
@@ -168,3 +1858,317 @@ fn test_list_files() {
     let contents = project.list_contents();
     assert_eq!(contents.files.len(), 1);
 }
+
+#[test]
+fn test_write_conflicts() {
+    let dir = TempDir::new("conflict");
+    let written = || WrittenFiles::load(dir.join("written.json"));
+    let project = Project::new(dir.join("dst")).with_written_files(written(), false);
+    project.write_file("lib.rs", "fn a() {}\n").unwrap();
+    project.write_file("main.rs", "fn main() {}\n").unwrap();
+    assert!(
+        project
+            .write_file("lib.rs", "fn b() {}\n")
+            .unwrap()
+            .error
+            .is_none()
+    );
+
+    std::fs::write(dir.join("dst/lib.rs"), "fn fixed() {}\n").unwrap();
+    assert!(
+        project
+            .write_file("lib.rs", "fn c() {}\n")
+            .unwrap()
+            .error
+            .is_some()
+    );
+    assert!(project.delete_file("lib.rs").unwrap().error.is_some());
+    assert!(
+        project
+            .append_file("lib.rs", "", true)
+            .unwrap()
+            .error
+            .is_some()
+    );
+    assert!(
+        project
+            .rename_file("lib.rs", "moved.rs")
+            .unwrap()
+            .error
+            .is_some()
+    );
+    assert!(
+        project
+            .write_file("main.rs", "fn main() {}\n// c\n")
+            .unwrap()
+            .error
+            .is_none()
+    );
+    let edits = [SearchReplace {
+        search: "fixed".to_string(),
+        replace: "d".to_string(),
+    }];
+    assert!(
+        project
+            .apply_patch("lib.rs", Some(&edits), None)
+            .unwrap()
+            .error
+            .is_none()
+    );
+    assert!(
+        project
+            .write_file("lib.rs", "fn e() {}\n")
+            .unwrap()
+            .error
+            .is_none()
+    );
+
+    std::fs::write(dir.join("dst/lib.rs"), "fn fixed() {}\n").unwrap();
+    let project = Project::new(dir.join("dst")).with_written_files(written(), true);
+    assert!(
+        project
+            .write_file("lib.rs", "fn f() {}\n")
+            .unwrap()
+            .error
+            .is_none()
+    );
+}
+
+#[test]
+fn test_cargo_check() {
+    let dir = TempDir::new("check");
+    let project = Project::new(dir.to_path_buf());
+    project
+        .write_file(
+            "Cargo.toml",
+            "[package]\nname = \"check\"\nversion = \"0.1.0\"\nedition = \"2024\"\n",
+        )
+        .unwrap();
+    project.write_file("src/lib.rs", "pub fn f() {}\n").unwrap();
+    assert!(project.run_cargo_check(&[]).unwrap().success);
+
+    project
+        .write_file("src/lib.rs", "pub fn f() -> u32 { \"\" }\n")
+        .unwrap();
+    let output = project.run_cargo_check_all().unwrap();
+    assert!(!output.success);
+    let errors: Vec<_> = output.errors().collect();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].code.as_deref(), Some("E0308"));
+    assert_eq!(errors[0].file.as_deref(), Some("src/lib.rs"));
+}
+
+#[test]
+fn test_doctests() {
+    let dir = TempDir::new("doctests");
+    let project = Project::new(dir.to_path_buf());
+    project
+        .write_file(
+            "Cargo.toml",
+            "[package]\nname = \"doctests\"\nversion = \"0.1.0\"\nedition = \"2024\"\n",
+        )
+        .unwrap();
+    project
+        .write_file(
+            "src/lib.rs",
+            "/// ```\n/// assert_eq!(doctests::two(), 3);\n/// ```\npub fn two() -> u32 { 2 }\n",
+        )
+        .unwrap();
+    assert!(project.run_doc().unwrap().success);
+    let output = project.run_doctests().unwrap();
+    assert!(!output.success);
+    assert_eq!(output.test_failures.len(), 1);
+    assert!(
+        output.test_failures[0]
+            .name
+            .starts_with("src/lib.rs - two (line 1)")
+    );
+}
+
+#[test]
+fn test_rustfmt() {
+    let dir = TempDir::new("rustfmt");
+    let project = Project::new(dir.to_path_buf()).with_rustfmt(true);
+    project
+        .write_file("Cargo.toml", "[package]\nedition = \"2024\"\n")
+        .unwrap();
+    let result = project
+        .write_file("src/lib.rs", "mod a;\nasync fn f( ){}")
+        .unwrap();
+    assert!(result.error.is_none() && result.format_error.is_none());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("src/lib.rs")).unwrap(),
+        "mod a;\nasync fn f() {}\n"
+    );
+    assert!(
+        project
+            .write_file("src/lib.rs", "mod a;\nasync fn f() {}\n")
+            .unwrap()
+            .unchanged
+    );
+
+    let result = project.write_file("src/lib.rs", "fn f( {}").unwrap();
+    assert!(result.error.is_none() && result.format_error.is_some());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("src/lib.rs")).unwrap(),
+        "fn f( {}"
+    );
+}
+
+#[test]
+fn test_review() {
+    let dir = TempDir::new("review");
+    let project = Project::new(dir.to_path_buf())
+        .with_review(Box::new(|path, old, new| {
+            if path == "src/lib.rs"
+                && new.is_some_and(|new| new.contains("unsafe"))
+                && !old.is_empty()
+            {
+                Review::Reject("Avoid unsafe.".to_string())
+            } else {
+                Review::Approve
+            }
+        }))
+        .with_review(Box::new(|_, _, new| {
+            new.map_or(Review::Approve, |new| {
+                Review::Replace(new.replace("fn g()", "fn h()"))
+            })
+        }));
+    let result = project.write_file("src/lib.rs", "fn f() {}\n").unwrap();
+    assert!(result.error.is_none());
+    let result = project
+        .apply_patch(
+            "src/lib.rs",
+            Some(&[SearchReplace {
+                search: "fn f() {}".to_string(),
+                replace: "unsafe fn f() {}".to_string(),
+            }]),
+            None,
+        )
+        .unwrap();
+    assert!(result.error.unwrap().ends_with("\nAvoid unsafe."));
+    assert_eq!(
+        std::fs::read_to_string(dir.join("src/lib.rs")).unwrap(),
+        "fn f() {}\n"
+    );
+    let result = project.write_file("src/lib.rs", "fn g() {}\n").unwrap();
+    assert!(result.error.is_none() && result.note.is_some());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("src/lib.rs")).unwrap(),
+        "fn h() {}\n"
+    );
+
+    // Files riir generates itself aren't reviewed.
+    let result = project
+        .write_generated_file("NOTES.md", "fn g() {}\n")
+        .unwrap();
+    assert!(result.error.is_none() && result.note.is_none());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("NOTES.md")).unwrap(),
+        "fn g() {}\n"
+    );
+}
+
+#[test]
+fn test_review_all_writes() {
+    let dir = TempDir::new("review-all");
+    let reviewed = std::sync::Arc::new(Mutex::new(Vec::new()));
+    let log = reviewed.clone();
+    let src = Project::new(dir.join("src"));
+    let project = Project::new(dir.join("dst"))
+        .with_written_files(WrittenFiles::load(dir.join("written.json")), false)
+        .with_backup_dir(dir.join("backups"))
+        .with_review(Box::new(move |path, _, new| {
+            log.lock().unwrap().push(path.to_string());
+            match new {
+                None => Review::Reject("Keep it.".to_string()),
+                Some(new) if new.contains("unsafe") => Review::Reject("Avoid unsafe.".to_string()),
+                Some(new) => Review::Replace(new.replace("fn g()", "fn h()")),
+            }
+        }));
+    let reviewed = || std::mem::take(&mut *reviewed.lock().unwrap());
+
+    let result = project.append_file("a.rs", "fn f() {}\n", false).unwrap();
+    assert!(result.error.is_none());
+    let result = project
+        .append_file("a.rs", "unsafe fn g() {}\n", false)
+        .unwrap();
+    assert!(result.error.unwrap().ends_with("\nAvoid unsafe."));
+    let result = project.append_file("a.rs", "fn g() {}\n", false).unwrap();
+    assert!(result.note.is_some());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("dst/a.rs")).unwrap(),
+        "fn f() {}\nfn h() {}\n"
+    );
+    assert_eq!(reviewed(), ["a.rs", "a.rs", "a.rs"]);
+
+    let result = project.rename_file("a.rs", "b.rs").unwrap();
+    assert!(result.error.is_none());
+    assert!(!dir.join("dst/a.rs").exists());
+    // The review replaced the contents, the moved ones are backed up.
+    assert!(dir.join("backups/a.rs.1").is_file());
+    assert_eq!(reviewed(), ["b.rs"]);
+
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::write(dir.join("src/c.rs"), "fn g() {}\n").unwrap();
+    let result = project.copy_file_from(&src, "c.rs", "c.rs").unwrap();
+    assert!(result.note.is_some());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("dst/c.rs")).unwrap(),
+        "fn h() {}\n"
+    );
+    assert_eq!(reviewed(), ["c.rs"]);
+
+    let result = project.delete_file("b.rs").unwrap();
+    assert!(result.error.unwrap().ends_with("\nKeep it."));
+    assert!(dir.join("dst/b.rs").exists());
+    assert_eq!(reviewed(), ["b.rs"]);
+
+    // A conflict is reported without asking for a review.
+    std::fs::write(dir.join("dst/c.rs"), "fn fixed() {}\n").unwrap();
+    assert!(
+        project
+            .write_file("c.rs", "fn e() {}\n")
+            .unwrap()
+            .error
+            .is_some()
+    );
+    assert!(reviewed().is_empty());
+}
+
+#[test]
+fn test_review_path() {
+    let dir = TempDir::new("review-path");
+    let reviewed = std::sync::Arc::new(Mutex::new(Vec::new()));
+    let log = reviewed.clone();
+    let project = Project::new(dir.to_path_buf()).with_review(Box::new(move |path, _, _| {
+        log.lock().unwrap().push(path.to_string());
+        Review::Approve
+    }));
+
+    // The reviews see where the file goes, not how the model spelled the path.
+    project.write_file("./src/a.rs", "fn a() {}\n").unwrap();
+    project
+        .write_file("foo/../src/b.rs", "fn b() {}\n")
+        .unwrap();
+    project
+        .append_file("src//a.rs", "fn c() {}\n", false)
+        .unwrap();
+    project.rename_file("src/b.rs", "src/./c.rs").unwrap();
+    assert_eq!(
+        *reviewed.lock().unwrap(),
+        ["src/a.rs", "src/b.rs", "src/a.rs", "src/c.rs"]
+    );
+}
+
+#[test]
+fn test_failed_write_not_dirty() {
+    let dir = TempDir::new("failed-write");
+    let project = Project::new(dir.to_path_buf());
+    std::fs::create_dir_all(dir.join("d/sub")).unwrap();
+
+    assert!(project.write_file("d", "").unwrap().error.is_some());
+    assert!(project.append_file("d", "", true).unwrap().error.is_some());
+    assert!(project.delete_file("d").unwrap().error.is_some());
+    assert!(!project.is_dirty());
+}
@@ -0,0 +1,277 @@
+use std::fmt;
+use std::path::Path;
+use tree_sitter::{Node, Parser};
+
+/// Directory of the PyO3 extension crate in the destination project.
+pub const SHIM_DIR: &str = "python";
+
+/// Public functions and classes of a module of a Python package.
+#[derive(Debug, PartialEq)]
+pub struct ModuleApi {
+    /// dotted name of the module, e.g. `foo.parser`
+    pub module: String,
+    /// signatures, e.g. `def parse(text, strict=False)`, `class Parser(Base)` or
+    /// `Parser.feed(self, data)`
+    pub items: Vec<String>,
+}
+
+impl fmt::Display for ModuleApi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let items: Vec<String> = self.items.iter().map(|item| format!("`{item}`")).collect();
+        write!(f, "- `{}`: {}", self.module, items.join(", "))
+    }
+}
+
+/// Returns the name of the top-level package, e.g. `foo` for `src/foo/__init__.py`.
+pub fn package_name(files: &[String]) -> Option<String> {
+    files
+        .iter()
+        .filter_map(|file| {
+            let package = file.strip_suffix("/__init__.py")?;
+            let package = package.strip_prefix("src/").unwrap_or(package);
+            (!package.contains('/') && !is_private(package)).then(|| package.to_string())
+        })
+        .min()
+}
+
+/// Extracts the public API of the Python files: the module-level functions and the classes
+/// with their methods, skipping tests, scripts and private names.
+pub fn python_api(root: &Path, files: &[String]) -> Vec<ModuleApi> {
+    let mut api = vec![];
+    for file in files {
+        let Some(module) = module_name(file) else {
+            continue;
+        };
+        let Ok(source) = std::fs::read_to_string(root.join(file)) else {
+            continue;
+        };
+        let mut parser = Parser::new();
+        if parser
+            .set_language(&tree_sitter_python::LANGUAGE.into())
+            .is_err()
+        {
+            continue;
+        }
+        let Some(tree) = parser.parse(&source, None) else {
+            continue;
+        };
+        let mut items = vec![];
+        add_definitions(tree.root_node(), &source, None, &mut items);
+        if !items.is_empty() {
+            api.push(ModuleApi { module, items });
+        }
+    }
+    api
+}
+
+/// Returns the dotted name of a module that is part of the public API, if it is.
+fn module_name(file: &str) -> Option<String> {
+    let path = file.strip_suffix(".py")?;
+    let path = path.strip_prefix("src/").unwrap_or(path);
+    let parts: Vec<&str> = path.split('/').collect();
+    let name = *parts.last()?;
+    if ["setup", "conftest", "__main__"].contains(&name)
+        || name.starts_with("test_")
+        || name.ends_with("_test")
+        || parts
+            .iter()
+            .any(|part| is_private(part) || ["test", "tests", "examples", "docs"].contains(part))
+    {
+        return None;
+    }
+    let parts = match parts.split_last() {
+        Some((&"__init__", package)) if !package.is_empty() => package,
+        _ => &parts[..],
+    };
+    Some(parts.join("."))
+}
+
+fn is_private(name: &str) -> bool {
+    name.starts_with('_') && name != "__init__"
+}
+
+fn add_definitions(node: Node, source: &str, class: Option<&str>, items: &mut Vec<String>) {
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        let definition = match child.kind() {
+            "decorated_definition" => match child.child_by_field_name("definition") {
+                Some(definition) => definition,
+                None => continue,
+            },
+            _ => child,
+        };
+        let text = |field: &str| {
+            definition
+                .child_by_field_name(field)
+                .and_then(|node| node.utf8_text(source.as_bytes()).ok())
+                .unwrap_or_default()
+        };
+        let name = text("name");
+        // Constructors are part of the API even though they look private.
+        if is_private(name) && name != "__init__" {
+            continue;
+        }
+        match (definition.kind(), class) {
+            ("function_definition", None) => {
+                items.push(format!("def {name}{}", text("parameters")));
+            }
+            ("function_definition", Some(class)) => {
+                items.push(format!("{class}.{name}{}", text("parameters")));
+            }
+            ("class_definition", None) => {
+                items.push(format!("class {name}{}", text("superclasses")));
+                if let Some(body) = definition.child_by_field_name("body") {
+                    add_definitions(body, source, Some(name), items);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Writes the scaffolding of the PyO3 extension crate exposing the port under the name of the
+/// original package, so that its Python callers keep working. Existing files are kept.
+pub fn write_shim_scaffolding(root: &Path, crate_name: &str, package: &str) -> Result<(), String> {
+    let dir = root.join(SHIM_DIR);
+    let manifest = format!(
+        "[package]\n\
+        name = \"{package}-py\"\n\
+        version = \"0.1.0\"\n\
+        edition = \"2021\"\n\
+        publish = false\n\
+        \n\
+        [lib]\n\
+        name = \"{package}\"\n\
+        crate-type = [\"cdylib\"]\n\
+        \n\
+        [dependencies]\n\
+        pyo3 = \"0.23\"\n\
+        {crate_name} = {{ path = \"..\" }}\n"
+    );
+    // The extension-module feature is only enabled by maturin, `cargo test` can't link with it.
+    let pyproject = format!(
+        "[build-system]\n\
+        requires = [\"maturin>=1.0,<2.0\"]\n\
+        build-backend = \"maturin\"\n\
+        \n\
+        [project]\n\
+        name = \"{package}\"\n\
+        requires-python = \">=3.8\"\n\
+        dynamic = [\"version\"]\n\
+        \n\
+        [tool.maturin]\n\
+        features = [\"pyo3/extension-module\"]\n"
+    );
+    let lib = format!(
+        "//! Python bindings of the Rust port, keeping the API of the original `{package}` \
+        package.\n\
+        \n\
+        use pyo3::prelude::*;\n\
+        \n\
+        #[pymodule]\n\
+        fn {package}(module: &Bound<'_, PyModule>) -> PyResult<()> {{\n\
+        \x20   let _ = module;\n\
+        \x20   Ok(())\n\
+        }}\n"
+    );
+    for (file, contents) in [
+        ("Cargo.toml", manifest),
+        ("pyproject.toml", pyproject),
+        ("src/lib.rs", lib),
+    ] {
+        let path = dir.join(file);
+        if path.exists() {
+            continue;
+        }
+        std::fs::create_dir_all(path.parent().unwrap())
+            .and_then(|_| std::fs::write(&path, contents))
+            .map_err(|e| format!("Cannot write {}: {e}", path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn api() {
+        let dir = TempDir::new("python");
+        std::fs::create_dir_all(dir.join("src/shapes/tests")).unwrap();
+        std::fs::write(
+            dir.join("src/shapes/__init__.py"),
+            "from .core import area\n\nVERSION = '1.0'\n\ndef version():\n    return VERSION\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("src/shapes/core.py"),
+            r#"import math
+
+def area(shape, unit="m"):
+    return shape.area()
+
+def _helper(x):
+    return x
+
+class Circle(Shape):
+    def __init__(self, radius):
+        self.radius = radius
+
+    @property
+    def diameter(self):
+        return 2 * self.radius
+
+    def _cache(self):
+        pass
+"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("src/shapes/_util.py"), "def clamp(x):\n    pass\n").unwrap();
+        std::fs::write(
+            dir.join("src/shapes/tests/test_core.py"),
+            "def test_area():\n    pass\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("setup.py"), "def run():\n    pass\n").unwrap();
+        let files: Vec<String> = [
+            "setup.py",
+            "src/shapes/__init__.py",
+            "src/shapes/_util.py",
+            "src/shapes/core.py",
+            "src/shapes/tests/test_core.py",
+        ]
+        .iter()
+        .map(|file| file.to_string())
+        .collect();
+
+        assert_eq!(package_name(&files).as_deref(), Some("shapes"));
+        let api = python_api(&dir, &files);
+        assert_eq!(
+            api,
+            [
+                ModuleApi {
+                    module: "shapes".to_string(),
+                    items: vec!["def version()".to_string()],
+                },
+                ModuleApi {
+                    module: "shapes.core".to_string(),
+                    items: vec![
+                        "def area(shape, unit=\"m\")".to_string(),
+                        "class Circle(Shape)".to_string(),
+                        "Circle.__init__(self, radius)".to_string(),
+                        "Circle.diameter(self)".to_string(),
+                    ],
+                },
+            ]
+        );
+        assert_eq!(api[0].to_string(), "- `shapes`: `def version()`");
+
+        write_shim_scaffolding(&dir, "shapes-rs", "shapes").unwrap();
+        let manifest = std::fs::read_to_string(dir.join("python/Cargo.toml")).unwrap();
+        assert!(manifest.contains("name = \"shapes\"\ncrate-type = [\"cdylib\"]"));
+        assert!(manifest.contains("shapes-rs = { path = \"..\" }"));
+        assert!(dir.join("python/pyproject.toml").is_file());
+        assert!(dir.join("python/src/lib.rs").is_file());
+    }
+}
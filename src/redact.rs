@@ -0,0 +1,130 @@
+use regex::{Captures, Regex};
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Patterns of well-known secrets that are replaced entirely.
+const SECRET_PATTERNS: &[&str] = &[
+    // OpenAI and similar API keys
+    r"sk-[A-Za-z0-9_-]{20,}",
+    // AWS access key ID
+    r"AKIA[0-9A-Z]{16}",
+    // GitHub tokens
+    r"gh[pousr]_[A-Za-z0-9]{36,}",
+    // Slack tokens
+    r"xox[abprs]-[A-Za-z0-9-]{10,}",
+    // Private keys
+    r"-----BEGIN [A-Z ]*PRIVATE KEY-----(?s:.*?)-----END [A-Z ]*PRIVATE KEY-----",
+];
+
+/// Assignments of string literals to secret-looking names, only the value is replaced.
+///
+/// The value must be quoted, so that code like `password: String` is left intact.
+const ASSIGNMENT_PATTERN: &str = r#"(?i)((?:password|passwd|secret|api_?key|access_?key|auth_?token|token)(?:\\?["'])?\s*[:=]\s*\\?["'])([^\s"'\\]{4,})"#;
+
+/// Candidates for the entropy check: long runs of token-like characters.
+const TOKEN_PATTERN: &str = r"[A-Za-z0-9+/_=-]{32,}";
+
+/// Minimum Shannon entropy (bits per character) of a token considered a secret.
+const MIN_TOKEN_ENTROPY: f64 = 4.3;
+
+/// Removes secrets from function results before they are sent to the model.
+pub struct Redactor {
+    secrets: Vec<Regex>,
+    assignment: Regex,
+    token: Regex,
+}
+
+impl Redactor {
+    pub fn new() -> Self {
+        Redactor {
+            secrets: SECRET_PATTERNS
+                .iter()
+                .map(|pattern| Regex::new(pattern).unwrap())
+                .collect(),
+            assignment: Regex::new(ASSIGNMENT_PATTERN).unwrap(),
+            token: Regex::new(TOKEN_PATTERN).unwrap(),
+        }
+    }
+
+    pub fn redact(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for secret in &self.secrets {
+            text = secret.replace_all(&text, REDACTED).into_owned();
+        }
+        text = self
+            .assignment
+            .replace_all(&text, |caps: &Captures| {
+                if &caps[2] == REDACTED {
+                    caps[0].to_string()
+                } else {
+                    format!("{}{REDACTED}", &caps[1])
+                }
+            })
+            .into_owned();
+        self.token
+            .replace_all(&text, |caps: &Captures| {
+                if looks_random(&caps[0]) {
+                    REDACTED.to_string()
+                } else {
+                    caps[0].to_string()
+                }
+            })
+            .into_owned()
+    }
+}
+
+/// Checks whether the token looks like a randomly generated secret.
+fn looks_random(token: &str) -> bool {
+    let has_digits = token.chars().any(|c| c.is_ascii_digit());
+    let has_letters = token.chars().any(|c| c.is_ascii_alphabetic());
+    has_digits && has_letters && entropy(token) >= MIN_TOKEN_ENTROPY
+}
+
+fn entropy(s: &str) -> f64 {
+    let mut counts = [0usize; 256];
+    for b in s.bytes() {
+        counts[b as usize] += 1;
+    }
+    let len = s.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_secrets() {
+        let redactor = Redactor::new();
+        assert_eq!(
+            redactor.redact("OPENAI_KEY=sk-proj-abcdefghijklmnopqrstuvwxyz"),
+            "OPENAI_KEY=[REDACTED]"
+        );
+        assert_eq!(
+            redactor.redact(r#"{"contents":"password = \"hunter2\"\n"}"#),
+            r#"{"contents":"password = \"[REDACTED]\"\n"}"#
+        );
+        assert_eq!(
+            redactor.redact("key: Zx8fQ2mL9vR4tW7yB1nC6kH3jD5sG0aP"),
+            "key: [REDACTED]"
+        );
+    }
+
+    #[test]
+    fn keep_regular_text() {
+        let redactor = Redactor::new();
+        let text = "fn read_password_from_terminal_without_echo() -> String\n\
+            struct Login { password: String, token: Token }\n\
+            let secret = read_secret();\n\
+            checksum = \"3f29a8c01d5b6e7f3f29a8c01d5b6e7f3f29a8c01d5b6e7f\"\n\
+            token_count: usize";
+        assert_eq!(redactor.redact(text), text);
+    }
+}
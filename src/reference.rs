@@ -0,0 +1,239 @@
+use crate::config::ReferenceConfig;
+use crate::process::run_command;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+/// Commands building and testing a project of each language, used unless the configuration
+/// has its own.
+const DEFAULT_COMMANDS: &[(&str, &[&str])] = &[
+    ("C", &["make", "make test"]),
+    ("C++", &["make", "make test"]),
+    ("Go", &["go build ./...", "go test ./..."]),
+    ("Python", &["python3 -m pytest"]),
+    ("JavaScript", &["npm install", "npm test"]),
+    ("TypeScript", &["npm install", "npm test"]),
+    ("Java", &["mvn -B test"]),
+    ("Kotlin", &["gradle test"]),
+    ("C#", &["dotnet test"]),
+];
+
+/// The captured stdout and stderr of a command are each truncated to this many bytes.
+const MAX_CAPTURED_OUTPUT: usize = 4 * 1024 * 1024;
+
+/// How much of the end of each output is kept in the results; build errors and test summaries
+/// are printed last.
+const MAX_OUTPUT_TAIL: usize = 16 * 1024;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ReferenceRun {
+    pub command: String,
+    /// exit code, missing if the command was killed
+    pub exit_code: Option<i32>,
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ReferenceResult {
+    pub error: Option<String>,
+    /// language of the source project the commands were picked for
+    pub language: Option<String>,
+    /// the commands in the order they were run; the ones after a failed command are not run
+    pub runs: Vec<ReferenceRun>,
+}
+
+/// Picks the commands for the first language, in the order of `languages`, that has any.
+pub fn reference_commands(
+    config: &ReferenceConfig,
+    languages: &[String],
+) -> Option<(String, Vec<String>)> {
+    languages.iter().find_map(|language| {
+        let commands = config.commands.get(language).cloned().or_else(|| {
+            DEFAULT_COMMANDS
+                .iter()
+                .find(|(name, _)| name == language)
+                .map(|(_, commands)| commands.iter().map(|command| command.to_string()).collect())
+        })?;
+        Some((language.clone(), commands))
+    })
+}
+
+/// Copies the source project to `work_dir` and runs the build and test commands there with
+/// `sh -c`, so that the original project is left intact. `languages` are the languages of the
+/// source project, the main one first.
+///
+/// The copy is kept after the run, together with everything the commands built.
+pub fn run_reference(
+    config: &ReferenceConfig,
+    languages: &[String],
+    source_dir: &Path,
+    work_dir: &Path,
+) -> ReferenceResult {
+    let Some((language, commands)) = reference_commands(config, languages) else {
+        return ReferenceResult {
+            error: Some(
+                "No build or test commands are known for the languages of the source project, \
+                set them in the [reference] section of the configuration."
+                    .to_string(),
+            ),
+            ..Default::default()
+        };
+    };
+    let mut result = ReferenceResult {
+        language: Some(language),
+        ..Default::default()
+    };
+
+    if let Err(e) = copy_source(source_dir, work_dir) {
+        result.error = Some(e);
+        return result;
+    }
+
+    let timeout = Some(Duration::from_secs(config.timeout)).filter(|timeout| !timeout.is_zero());
+    for command in commands {
+        let output = match run_command(
+            Command::new("sh")
+                .args(["-c", &command])
+                .current_dir(work_dir),
+            None,
+            timeout,
+            Some(MAX_CAPTURED_OUTPUT),
+        ) {
+            Ok(output) => output,
+            Err(e) => {
+                result.error = Some(e);
+                return result;
+            }
+        };
+        let mut stderr = tail(&output.stderr);
+        if output.timed_out {
+            stderr += &format!(
+                "\n[the command was killed after running for {} seconds]\n",
+                config.timeout
+            );
+        }
+        result.runs.push(ReferenceRun {
+            command,
+            exit_code: output.exit_code,
+            success: output.success,
+            stdout: tail(&output.stdout),
+            stderr,
+        });
+        if !output.success {
+            break;
+        }
+    }
+    result
+}
+
+/// Replaces `work_dir` with a fresh copy of the source project, where its programs can be built
+/// and run without changing the original.
+pub fn copy_source(source_dir: &Path, work_dir: &Path) -> Result<(), String> {
+    if work_dir.exists() {
+        std::fs::remove_dir_all(work_dir)
+            .map_err(|e| format!("Cannot remove {}: {e}", work_dir.display()))?;
+    }
+    copy_dir(source_dir, work_dir).map_err(|e| format!("Cannot copy the source project: {e}"))
+}
+
+/// Copies a directory recursively, leaving out `.git`.
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        let target = to.join(entry.file_name());
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else if file_type.is_symlink() {
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(std::fs::read_link(entry.path())?, &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Keeps the end of the output, noting how much was dropped.
+pub fn tail(output: &str) -> String {
+    if output.len() <= MAX_OUTPUT_TAIL {
+        return output.to_string();
+    }
+    let mut start = output.len() - MAX_OUTPUT_TAIL;
+    while !output.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("[{start} bytes omitted]\n{}", &output[start..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn commands() {
+        let config = ReferenceConfig {
+            commands: BTreeMap::from([("Python".to_string(), vec!["pytest -q".to_string()])]),
+            ..Default::default()
+        };
+        let languages = ["Markdown", "Python", "C"].map(String::from);
+        assert_eq!(
+            reference_commands(&config, &languages),
+            Some(("Python".to_string(), vec!["pytest -q".to_string()]))
+        );
+        let languages = ["C", "Python"].map(String::from);
+        assert_eq!(
+            reference_commands(&config, &languages),
+            Some((
+                "C".to_string(),
+                vec!["make".to_string(), "make test".to_string()]
+            ))
+        );
+        assert_eq!(reference_commands(&config, &["Markdown".to_string()]), None);
+    }
+
+    #[test]
+    fn run() {
+        let dir = TempDir::new("reference");
+        let source = dir.join("source");
+        std::fs::create_dir_all(source.join(".git")).unwrap();
+        std::fs::write(source.join("input.txt"), "hello\n").unwrap();
+        let config = ReferenceConfig {
+            commands: BTreeMap::from([(
+                "Shell".to_string(),
+                ["cat input.txt", "ls -a; exit 2", "echo never"]
+                    .map(String::from)
+                    .to_vec(),
+            )]),
+            ..Default::default()
+        };
+
+        let work_dir = dir.join("work");
+        let result = run_reference(&config, &["Shell".to_string()], &source, &work_dir);
+        assert!(result.error.is_none());
+        assert_eq!(result.language.as_deref(), Some("Shell"));
+        assert_eq!(result.runs.len(), 2);
+        assert!(result.runs[0].success);
+        assert_eq!(result.runs[0].stdout, "hello\n");
+        assert_eq!(result.runs[1].exit_code, Some(2));
+        assert_eq!(result.runs[1].stdout, ".\n..\ninput.txt\n");
+    }
+
+    #[test]
+    fn output_tail() {
+        assert_eq!(tail("short"), "short");
+        let long = "é".repeat(MAX_OUTPUT_TAIL);
+        let kept = tail(&long);
+        assert!(kept.starts_with(&format!("[{} bytes omitted]\n", MAX_OUTPUT_TAIL)));
+        assert!(kept.ends_with("é"));
+    }
+}
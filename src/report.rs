@@ -0,0 +1,234 @@
+use crate::cargo::{CargoOutput, Diagnostic};
+use crate::config::WarningPolicy;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// State of the destination project after the conversion, written as JSON for CI.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct VerificationReport {
+    /// whether the result is acceptable: it compiles, the tests pass, nothing was left to the
+    /// operator and, with the `deny` warning policy, there are no warnings
+    pub accepted: bool,
+    pub build: BuildSummary,
+    /// absent if clippy isn't enabled
+    pub clippy: Option<ClippySummary>,
+    /// absent if the tests couldn't be run because the project doesn't compile
+    pub tests: Option<TestSummary>,
+    /// errors, warnings and lints per file of the destination project
+    pub files: BTreeMap<String, FileStatus>,
+    /// problems the model gave up on
+    pub flagged: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct BuildSummary {
+    /// why cargo couldn't be run at all
+    pub error: Option<String>,
+    pub success: bool,
+    pub errors: usize,
+    pub warnings: usize,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct ClippySummary {
+    pub error: Option<String>,
+    /// number of clippy lints reported
+    pub lints: usize,
+    /// how many times each lint was reported, e.g. `clippy::needless_return`
+    pub by_lint: BTreeMap<String, usize>,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct TestSummary {
+    pub error: Option<String>,
+    pub success: bool,
+    pub passed: usize,
+    /// names of the failed tests
+    pub failed: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct FileStatus {
+    pub errors: usize,
+    pub warnings: usize,
+    pub lints: usize,
+}
+
+impl VerificationReport {
+    /// Summarizes the outputs of the final `cargo check`, `cargo clippy` and `cargo test`.
+    pub fn new(
+        check: Result<CargoOutput, String>,
+        clippy: Option<Result<CargoOutput, String>>,
+        tests: Option<Result<CargoOutput, String>>,
+        flagged: Vec<String>,
+        policy: WarningPolicy,
+    ) -> Self {
+        let mut files: BTreeMap<String, FileStatus> = BTreeMap::new();
+
+        let build = match check {
+            Ok(output) => {
+                let mut build = BuildSummary {
+                    success: output.success,
+                    ..Default::default()
+                };
+                for diagnostic in &output.diagnostics {
+                    if diagnostic.is_error() {
+                        build.errors += 1;
+                        file_status(&mut files, diagnostic).errors += 1;
+                    } else {
+                        build.warnings += 1;
+                        file_status(&mut files, diagnostic).warnings += 1;
+                    }
+                }
+                build
+            }
+            Err(e) => BuildSummary {
+                error: Some(e),
+                ..Default::default()
+            },
+        };
+
+        let clippy = clippy.map(|output| match output {
+            Ok(output) => {
+                let mut clippy = ClippySummary::default();
+                for lint in &output.diagnostics {
+                    let Some(code) = lint.code.as_deref().filter(|c| c.starts_with("clippy::"))
+                    else {
+                        continue;
+                    };
+                    clippy.lints += 1;
+                    *clippy.by_lint.entry(code.to_string()).or_default() += 1;
+                    file_status(&mut files, lint).lints += 1;
+                }
+                clippy
+            }
+            Err(e) => ClippySummary {
+                error: Some(e),
+                ..Default::default()
+            },
+        });
+
+        let tests = tests.map(|output| match output {
+            Ok(output) => TestSummary {
+                error: None,
+                success: output.success,
+                passed: output.passed_tests.len(),
+                failed: output
+                    .test_failures
+                    .iter()
+                    .map(|failure| failure.name.clone())
+                    .collect(),
+            },
+            Err(e) => TestSummary {
+                error: Some(e),
+                ..Default::default()
+            },
+        });
+
+        let accepted = build.success
+            && tests.as_ref().is_some_and(|tests| tests.success)
+            && flagged.is_empty()
+            && (policy != WarningPolicy::Deny || build.warnings == 0);
+        VerificationReport {
+            accepted,
+            build,
+            clippy,
+            tests,
+            files,
+            flagged,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, contents + "\n")
+            .map_err(|e| format!("Cannot write {}: {e}", path.display()))
+    }
+}
+
+/// Returns the status of the file of the diagnostic, or a dummy one for diagnostics without a
+/// file, like errors in the manifest.
+fn file_status<'a>(
+    files: &'a mut BTreeMap<String, FileStatus>,
+    diagnostic: &Diagnostic,
+) -> &'a mut FileStatus {
+    let name = diagnostic
+        .file
+        .clone()
+        .unwrap_or_else(|| "(no file)".to_string());
+    files.entry(name).or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cargo::{TestFailure, parse_diagnostics};
+
+    #[test]
+    fn report() {
+        let output = |success: bool, messages: &str| CargoOutput {
+            success,
+            diagnostics: parse_diagnostics(messages),
+            test_failures: vec![],
+            passed_tests: vec![],
+            truncated: false,
+            stderr: String::new(),
+        };
+        let check = output(
+            true,
+            r#"{"reason":"compiler-message","message":{"rendered":"","message":"unused variable: `x`","code":{"code":"unused_variables","explanation":null},"level":"warning","spans":[{"file_name":"src/lib.rs","line_start":2,"column_start":9,"is_primary":true}],"children":[]}}"#,
+        );
+        let clippy = output(
+            true,
+            r#"{"reason":"compiler-message","message":{"rendered":"","message":"unneeded `return` statement","code":{"code":"clippy::needless_return","explanation":null},"level":"warning","spans":[{"file_name":"src/main.rs","line_start":5,"column_start":5,"is_primary":true}],"children":[]}}"#,
+        );
+        let mut tests = output(false, "");
+        tests.passed_tests = vec!["tests::parse".to_string()];
+        tests.test_failures = vec![TestFailure {
+            name: "tests::empty".to_string(),
+            panic_message: None,
+            output: String::new(),
+        }];
+
+        let report = VerificationReport::new(
+            Ok(check),
+            Some(Ok(clippy)),
+            Some(Ok(tests)),
+            vec![],
+            WarningPolicy::Allow,
+        );
+        assert!(!report.accepted);
+        assert_eq!(
+            report.build,
+            BuildSummary {
+                error: None,
+                success: true,
+                errors: 0,
+                warnings: 1
+            }
+        );
+        assert_eq!(report.clippy.unwrap().by_lint["clippy::needless_return"], 1);
+        assert_eq!(report.tests.unwrap().failed, ["tests::empty"]);
+        assert_eq!(report.files["src/lib.rs"].warnings, 1);
+        assert_eq!(report.files["src/main.rs"].lints, 1);
+
+        let report = VerificationReport::new(
+            Ok(output(true, "")),
+            None,
+            Some(Ok(output(true, ""))),
+            vec![],
+            WarningPolicy::Deny,
+        );
+        assert!(report.accepted);
+        let report = VerificationReport::new(
+            Err("cargo not found".to_string()),
+            None,
+            None,
+            vec![],
+            WarningPolicy::Allow,
+        );
+        assert!(!report.accepted);
+        assert_eq!(report.build.error.as_deref(), Some("cargo not found"));
+    }
+}
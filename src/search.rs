@@ -0,0 +1,178 @@
+use crate::text::{decode_text, is_binary};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const DEFAULT_MAX_MATCHES: usize = 100;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct GrepArgs {
+    /// a regular expression to search for (Rust regex syntax)
+    pub pattern: String,
+    /// treat the pattern as a plain text instead of a regular expression
+    pub literal: Option<bool>,
+    /// glob patterns of files to search in, e.g. "src/**/*.c"; all files if omitted
+    pub include: Option<Vec<String>>,
+    /// maximum number of matches to return, 100 by default
+    pub max_matches: Option<usize>,
+    /// number of lines to show before and after each match, 0 by default
+    pub context_lines: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GrepResult {
+    pub error: Option<String>,
+    pub matches: Vec<GrepMatch>,
+    /// true if there are more matches than returned
+    pub truncated: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GrepMatch {
+    pub path: String,
+    pub line: usize,
+    /// the matching line with context lines, prefixed with line numbers
+    pub text: String,
+}
+
+impl GrepResult {
+    fn error(error: String) -> Self {
+        GrepResult {
+            error: Some(error),
+            matches: vec![],
+            truncated: false,
+        }
+    }
+}
+
+/// Searches the files of a project for lines matching a regular expression.
+///
+/// `files` are paths relative to `root`.
+pub fn grep(root: &Path, files: &[String], args: &GrepArgs) -> GrepResult {
+    let pattern = if args.literal.unwrap_or(false) {
+        regex::escape(&args.pattern)
+    } else {
+        args.pattern.clone()
+    };
+    let regex = match Regex::new(&pattern) {
+        Ok(regex) => regex,
+        Err(e) => return GrepResult::error(format!("Invalid pattern: {e}")),
+    };
+    let include = match args.include.as_deref().map(build_globs).transpose() {
+        Ok(include) => include,
+        Err(e) => return GrepResult::error(e),
+    };
+    let max_matches = args.max_matches.unwrap_or(DEFAULT_MAX_MATCHES);
+    let context = args.context_lines.unwrap_or(0);
+
+    let mut result = GrepResult {
+        error: None,
+        matches: vec![],
+        truncated: false,
+    };
+    for file in files {
+        if include.as_ref().is_some_and(|globs| !globs.is_match(file)) {
+            continue;
+        }
+        let Ok(data) = std::fs::read(root.join(file)) else {
+            continue;
+        };
+        if is_binary(&data) {
+            continue;
+        }
+        let contents = decode_text(data).contents;
+        let lines: Vec<&str> = contents.lines().collect();
+        for (i, line) in lines.iter().enumerate() {
+            if !regex.is_match(line) {
+                continue;
+            }
+            if result.matches.len() == max_matches {
+                result.truncated = true;
+                return result;
+            }
+            let first = i.saturating_sub(context);
+            let last = (i + context).min(lines.len() - 1);
+            let text = (first..=last)
+                .map(|j| {
+                    let marker = if j == i { '>' } else { ' ' };
+                    format!("{marker}{}: {}\n", j + 1, lines[j])
+                })
+                .collect();
+            result.matches.push(GrepMatch {
+                path: file.clone(),
+                line: i + 1,
+                text,
+            });
+        }
+    }
+    result
+}
+
+/// Builds a matcher of any of the glob patterns.
+pub fn build_globs(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern).map_err(|e| format!("Invalid glob {pattern}: {e}"))?;
+        builder.add(glob);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn grep_files() {
+        let dir = TempDir::new("grep");
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/a.c"), "int x;\nint foo(void);\nint y;\n").unwrap();
+        std::fs::write(dir.join("b.h"), "int foo(void);\n").unwrap();
+        let files = vec!["src/a.c".to_string(), "b.h".to_string()];
+
+        let args = GrepArgs {
+            pattern: r"foo\(".to_string(),
+            literal: None,
+            include: Some(vec!["**/*.c".to_string()]),
+            max_matches: None,
+            context_lines: Some(1),
+        };
+        let result = grep(&dir, &files, &args);
+        assert_eq!(result.matches.len(), 1);
+        assert_eq!(result.matches[0].path, "src/a.c");
+        assert_eq!(result.matches[0].line, 2);
+        assert_eq!(
+            result.matches[0].text,
+            " 1: int x;\n>2: int foo(void);\n 3: int y;\n"
+        );
+
+        let args = GrepArgs {
+            pattern: "int".to_string(),
+            literal: None,
+            include: None,
+            max_matches: Some(2),
+            context_lines: None,
+        };
+        let result = grep(&dir, &files, &args);
+        assert_eq!(result.matches.len(), 2);
+        assert!(result.truncated);
+
+        let args = GrepArgs {
+            pattern: "foo(void)".to_string(),
+            literal: Some(true),
+            include: None,
+            max_matches: None,
+            context_lines: None,
+        };
+        let result = grep(&dir, &files, &args);
+        let found: Vec<_> = result
+            .matches
+            .iter()
+            .map(|m| format!("{}:{}", m.path, m.line))
+            .collect();
+        assert_eq!(found, ["src/a.c:2", "b.h:1"]);
+    }
+}
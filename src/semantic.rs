@@ -0,0 +1,252 @@
+use crate::hash::stable_hash;
+use crate::text::{decode_text, is_binary};
+use log::{info, warn};
+use openai::Credentials;
+use openai::embeddings::Embeddings;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Number of lines in a chunk of a file that gets its own embedding.
+const CHUNK_LINES: usize = 60;
+/// Number of chunks embedded in one request.
+const BATCH_SIZE: usize = 64;
+/// Longer chunks are cut to stay within the input limit of the embedding model.
+const MAX_CHUNK_BYTES: usize = 8000;
+const DEFAULT_MAX_RESULTS: usize = 10;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct SemanticSearchArgs {
+    /// description of the code you are looking for, e.g. "parsing of the config file"
+    pub query: String,
+    /// maximum number of chunks to return, 10 by default
+    pub max_results: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SemanticSearchResult {
+    pub error: Option<String>,
+    /// the most relevant chunks of the source files, the best match first
+    pub matches: Vec<SemanticMatch>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SemanticMatch {
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    /// cosine similarity to the query, higher is better
+    pub score: f64,
+}
+
+/// An embedded part of a file.
+#[derive(Serialize, Deserialize)]
+struct Chunk {
+    path: String,
+    start_line: usize,
+    end_line: usize,
+    /// hash of the chunk text, to reuse the embedding while the text doesn't change
+    hash: u64,
+    vec: Vec<f64>,
+}
+
+/// Vector store of the source project chunks for semantic search.
+///
+/// The embeddings are cached in a file, so only the changed chunks are embedded again.
+pub struct SemanticIndex {
+    model: String,
+    credentials: Credentials,
+    chunks: Vec<Chunk>,
+}
+
+impl SemanticIndex {
+    /// Embeds the chunks of the text files, reusing the embeddings from `cache_path`.
+    ///
+    /// `files` are paths relative to `root`.
+    pub async fn build(
+        model: String,
+        credentials: Credentials,
+        root: &Path,
+        files: &[String],
+        cache_path: PathBuf,
+    ) -> Result<Self, String> {
+        let mut cached: HashMap<u64, Vec<f64>> = std::fs::read_to_string(&cache_path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Vec<Chunk>>(&contents).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|chunk| (chunk.hash, chunk.vec))
+            .collect();
+
+        let mut chunks = vec![];
+        let mut pending = vec![];
+        for file in files {
+            let Ok(data) = std::fs::read(root.join(file)) else {
+                continue;
+            };
+            if is_binary(&data) {
+                continue;
+            }
+            let contents = decode_text(data).contents;
+            for (start_line, end_line, text) in split_chunks(&contents) {
+                let text = format!("{file}:{start_line}-{end_line}\n{text}");
+                let hash = stable_hash(text.as_bytes());
+                let vec = cached.remove(&hash).unwrap_or_default();
+                if vec.is_empty() {
+                    pending.push((chunks.len(), text));
+                }
+                chunks.push(Chunk {
+                    path: file.clone(),
+                    start_line,
+                    end_line,
+                    hash,
+                    vec,
+                });
+            }
+        }
+
+        info!(
+            "Embedding {} of {} source chunks",
+            pending.len(),
+            chunks.len()
+        );
+        for batch in pending.chunks(BATCH_SIZE) {
+            let input = batch.iter().map(|(_, text)| text.as_str()).collect();
+            let embeddings = Embeddings::create(&model, input, "", credentials.clone())
+                .await
+                .map_err(|e| format!("Cannot embed the source files: {}", e.message))?;
+            for ((index, _), embedding) in batch.iter().zip(embeddings.data) {
+                chunks[*index].vec = embedding.vec;
+            }
+        }
+
+        let index = SemanticIndex {
+            model,
+            credentials,
+            chunks,
+        };
+        if let Ok(contents) = serde_json::to_string(&index.chunks)
+            && let Err(e) = std::fs::write(&cache_path, contents)
+        {
+            warn!("Cannot save the embeddings: {e}");
+        }
+        Ok(index)
+    }
+
+    /// Finds the chunks closest to the query. Must be called within a multi-threaded runtime.
+    pub fn search(&self, args: &SemanticSearchArgs) -> SemanticSearchResult {
+        let request = Embeddings::create(
+            &self.model,
+            vec![args.query.as_str()],
+            "",
+            self.credentials.clone(),
+        );
+        // Functions are synchronous, but they are called from the chat loop running in tokio.
+        let response =
+            tokio::task::block_in_place(|| tokio::runtime::Handle::current().block_on(request));
+        let query = match response {
+            Ok(embeddings) if !embeddings.data.is_empty() => embeddings.data[0].vec.clone(),
+            Ok(_) => Vec::new(),
+            Err(e) => {
+                return SemanticSearchResult {
+                    error: Some(format!("Cannot embed the query: {}", e.message)),
+                    matches: vec![],
+                };
+            }
+        };
+        SemanticSearchResult {
+            error: None,
+            matches: best_matches(
+                &self.chunks,
+                &query,
+                args.max_results.unwrap_or(DEFAULT_MAX_RESULTS),
+            ),
+        }
+    }
+}
+
+/// Splits the text into chunks of `CHUNK_LINES` lines, returning their first and last line
+/// numbers starting from 1.
+fn split_chunks(text: &str) -> Vec<(usize, usize, String)> {
+    let lines: Vec<&str> = text.lines().collect();
+    lines
+        .chunks(CHUNK_LINES)
+        .enumerate()
+        .filter(|(_, chunk)| chunk.iter().any(|line| !line.trim().is_empty()))
+        .map(|(i, chunk)| {
+            let start_line = i * CHUNK_LINES + 1;
+            let mut text = chunk.join("\n");
+            if text.len() > MAX_CHUNK_BYTES {
+                let mut end = MAX_CHUNK_BYTES;
+                while !text.is_char_boundary(end) {
+                    end -= 1;
+                }
+                text.truncate(end);
+            }
+            (start_line, start_line + chunk.len() - 1, text)
+        })
+        .collect()
+}
+
+fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+fn best_matches(chunks: &[Chunk], query: &[f64], max_results: usize) -> Vec<SemanticMatch> {
+    let mut matches: Vec<SemanticMatch> = chunks
+        .iter()
+        .map(|chunk| SemanticMatch {
+            path: chunk.path.clone(),
+            start_line: chunk.start_line,
+            end_line: chunk.end_line,
+            score: cosine_similarity(&chunk.vec, query),
+        })
+        .collect();
+    matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+    matches.truncate(max_results);
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks() {
+        let text = (1..=130).map(|i| format!("line {i}\n")).collect::<String>();
+        let ranges: Vec<_> = split_chunks(&text)
+            .into_iter()
+            .map(|(start, end, _)| (start, end))
+            .collect();
+        assert_eq!(ranges, [(1, 60), (61, 120), (121, 130)]);
+        assert!(split_chunks("\n\n").is_empty());
+    }
+
+    #[test]
+    fn ranking() {
+        let chunk = |path: &str, vec: Vec<f64>| Chunk {
+            path: path.to_string(),
+            start_line: 1,
+            end_line: 1,
+            hash: 0,
+            vec,
+        };
+        let chunks = [
+            chunk("far.c", vec![0.0, 1.0]),
+            chunk("near.c", vec![1.0, 0.1]),
+            chunk("exact.c", vec![2.0, 0.0]),
+        ];
+        let matches = best_matches(&chunks, &[1.0, 0.0], 2);
+        let paths: Vec<_> = matches.iter().map(|m| m.path.as_str()).collect();
+        assert_eq!(paths, ["exact.c", "near.c"]);
+        assert!((matches[0].score - 1.0).abs() < 1e-9);
+    }
+}
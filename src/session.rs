@@ -0,0 +1,23 @@
+use std::path::{Path, PathBuf};
+
+/// Name of the session directory inside the destination project.
+pub const SESSION_DIR: &str = ".riir";
+
+/// Directory with the state of the conversion that is kept between runs.
+pub struct Session {
+    dir: PathBuf,
+}
+
+impl Session {
+    /// Opens the session directory of the destination project, creating it if needed.
+    pub fn open(destination: &Path) -> std::io::Result<Self> {
+        let dir = destination.join(SESSION_DIR);
+        std::fs::create_dir_all(&dir)?;
+        Ok(Session { dir })
+    }
+
+    /// Returns the path of a file in the session directory.
+    pub fn file(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+}
@@ -0,0 +1,576 @@
+use crate::language::detect_language;
+use std::collections::BTreeSet;
+use std::path::Path;
+use tree_sitter::{Node, Parser};
+
+/// Words that can't be used as identifiers in Rust without the `r#` prefix.
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "do", "dyn", "else", "enum",
+    "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "macro",
+    "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "static", "struct",
+    "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual", "where",
+    "while", "yield",
+];
+
+/// Where a C type is used, which decides how its pointers are translated.
+#[derive(Clone, Copy, PartialEq)]
+enum Place {
+    Parameter,
+    Field,
+    Return,
+}
+
+/// Generates the skeleton of a Rust module from the C and C++ headers among the source files of
+/// the module: the types, the constants and the functions with `todo!()` bodies. Returns `None`
+/// if there are no headers or nothing could be extracted from them.
+pub fn module_skeleton(root: &Path, files: &[String]) -> Option<String> {
+    let mut skeleton = Skeleton::default();
+    let mut headers = vec![];
+    for file in files {
+        let is_header = Path::new(file)
+            .extension()
+            .is_some_and(|ext| ["h", "hh", "hpp", "hxx"].contains(&ext.to_str().unwrap_or("")));
+        if !is_header || !matches!(detect_language(Path::new(file)), Some("C" | "C++")) {
+            continue;
+        }
+        let Ok(source) = std::fs::read_to_string(root.join(file)) else {
+            continue;
+        };
+        skeleton.add_header(&source);
+        headers.push(format!("`{file}`"));
+    }
+    let items = skeleton.finish();
+    if items.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "// Skeleton generated from {}, replace the todo!() bodies with the port of the \
+        implementation.\n\n{items}",
+        headers.join(", ")
+    ))
+}
+
+/// Rust items collected from the headers.
+#[derive(Default)]
+struct Skeleton {
+    items: Vec<String>,
+    /// names of the items, so that a type declared twice is generated once
+    names: BTreeSet<String>,
+    /// structs used without a definition, generated empty at the end
+    opaque: BTreeSet<String>,
+}
+
+impl Skeleton {
+    fn add_header(&mut self, source: &str) {
+        let mut parser = Parser::new();
+        // The C grammar parses the C subset of C++ headers, which is what the skeleton needs.
+        if parser
+            .set_language(&tree_sitter_c::LANGUAGE.into())
+            .is_err()
+        {
+            return;
+        }
+        let Some(tree) = parser.parse(source, None) else {
+            return;
+        };
+        self.add_items(tree.root_node(), source);
+    }
+
+    fn add_items(&mut self, node: Node, source: &str) {
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            match child.kind() {
+                // Include guards, conditional declarations and `extern "C"` blocks.
+                "preproc_ifdef"
+                | "preproc_if"
+                | "preproc_else"
+                | "preproc_elif"
+                | "linkage_specification"
+                | "declaration_list" => self.add_items(child, source),
+                "preproc_def" => self.add_constant(child, source),
+                "struct_specifier" | "union_specifier" | "enum_specifier" => {
+                    self.add_type(child, None, source);
+                }
+                "declaration" | "function_definition" => self.add_declaration(child, source),
+                "type_definition" => self.add_type_definition(child, source),
+                _ => {}
+            }
+        }
+    }
+
+    fn add_item(&mut self, name: String, item: String) {
+        if self.names.insert(name) {
+            self.items.push(item);
+        }
+    }
+
+    /// Translates `#define NAME <literal>`; macros with parameters or expressions are left to
+    /// the port.
+    fn add_constant(&mut self, node: Node, source: &str) {
+        let name = text(node.child_by_field_name("name"), source);
+        let value = text(node.child_by_field_name("value"), source);
+        let value = value.trim();
+        let number = value.trim_end_matches(['u', 'U', 'l', 'L']);
+        let (rust_type, value) = if number.parse::<i64>().is_ok()
+            || number.starts_with("0x") && i64::from_str_radix(&number[2..], 16).is_ok()
+        {
+            ("i64", number)
+        } else if number.trim_end_matches(['f', 'F']).parse::<f64>().is_ok() && number.contains('.')
+        {
+            ("f64", number.trim_end_matches(['f', 'F']))
+        } else if value.starts_with('"') && value.ends_with('"') && value.len() > 1 {
+            ("&str", value)
+        } else {
+            return;
+        };
+        let item = format!("pub const {name}: {rust_type} = {value};\n");
+        self.add_item(name, item);
+    }
+
+    /// Generates a struct or an enum with a body, under the given name if it's a typedef.
+    fn add_type(&mut self, node: Node, name: Option<&str>, source: &str) -> Option<String> {
+        let name = type_name(name.unwrap_or(&text(node.child_by_field_name("name"), source)));
+        let body = node.child_by_field_name("body")?;
+        if name.is_empty() {
+            return None;
+        }
+        let mut item = String::new();
+        let mut cursor = body.walk();
+        if node.kind() == "enum_specifier" {
+            let variants: Vec<(String, Option<String>)> = body
+                .named_children(&mut cursor)
+                .filter(|child| child.kind() == "enumerator")
+                .map(|child| {
+                    let value = child
+                        .child_by_field_name("value")
+                        .map(|value| text(Some(value), source));
+                    (text(child.child_by_field_name("name"), source), value)
+                })
+                .collect();
+            let prefix = common_prefix(variants.iter().map(|(name, _)| name.as_str()));
+            item += &format!("pub enum {name} {{\n");
+            for (variant, value) in &variants {
+                let variant = type_name(&variant[prefix..]);
+                match value {
+                    Some(value) if value.parse::<i64>().is_ok() => {
+                        item += &format!("    {variant} = {value},\n");
+                    }
+                    _ => item += &format!("    {variant},\n"),
+                }
+            }
+        } else {
+            item += &format!("pub struct {name} {{\n");
+            for field in body.named_children(&mut cursor) {
+                if field.kind() != "field_declaration" {
+                    continue;
+                }
+                let base = base_type(field, source);
+                let is_const = is_const(field, source);
+                let mut field_cursor = field.walk();
+                for declarator in field.children_by_field_name("declarator", &mut field_cursor) {
+                    let declarator = Declarator::new(declarator, source);
+                    let Some(field_name) = &declarator.name else {
+                        continue;
+                    };
+                    let rust_type =
+                        declarator.rust_type(&base, is_const, Place::Field, self, source);
+                    item += &format!("    pub {}: {rust_type},\n", identifier(field_name));
+                }
+            }
+        }
+        item += "}\n";
+        self.add_item(name.clone(), item);
+        Some(name)
+    }
+
+    fn add_type_definition(&mut self, node: Node, source: &str) {
+        let Some(type_node) = node.child_by_field_name("type") else {
+            return;
+        };
+        let base = base_type(node, source);
+        let is_const = is_const(node, source);
+        let mut cursor = node.walk();
+        for declarator in node.children_by_field_name("declarator", &mut cursor) {
+            let declarator = Declarator::new(declarator, source);
+            let Some(name) = &declarator.name else {
+                continue;
+            };
+            if self
+                .add_type(type_node, Some(name.as_str()), source)
+                .is_some()
+            {
+                continue;
+            }
+            let name = type_name(name);
+            let rust_type = declarator.rust_type(&base, is_const, Place::Field, self, source);
+            if rust_type != name {
+                self.add_item(name.clone(), format!("pub type {name} = {rust_type};\n"));
+            }
+        }
+    }
+
+    fn add_declaration(&mut self, node: Node, source: &str) {
+        if let Some(type_node) = node.child_by_field_name("type") {
+            self.add_type(type_node, None, source);
+        }
+        let base = base_type(node, source);
+        let is_const = is_const(node, source);
+        let mut cursor = node.walk();
+        for declarator in node.children_by_field_name("declarator", &mut cursor) {
+            let declarator = Declarator::new(declarator, source);
+            let (Some(name), Some(parameters)) = (&declarator.name, declarator.parameters) else {
+                // Global variables are left to the port.
+                continue;
+            };
+            if declarator.function_pointer {
+                continue;
+            }
+            let return_type = rust_type(&base, declarator.pointers, is_const, Place::Return, self);
+            let parameters = self.parameters(parameters, source).join(", ");
+            let mut item = format!("pub fn {}({parameters})", identifier(name));
+            if return_type != "()" {
+                item += &format!(" -> {return_type}");
+            }
+            item += " {\n    todo!()\n}\n";
+            self.add_item(name.clone(), item);
+        }
+    }
+
+    /// Translates a parameter list into `name: Type` pairs.
+    fn parameters(&mut self, list: Node, source: &str) -> Vec<String> {
+        let mut parameters = vec![];
+        let mut cursor = list.walk();
+        for (index, parameter) in list.named_children(&mut cursor).enumerate() {
+            if parameter.kind() != "parameter_declaration" {
+                // Variadic parameters have no Rust counterpart in a skeleton.
+                continue;
+            }
+            let base = base_type(parameter, source);
+            let declarator = parameter
+                .child_by_field_name("declarator")
+                .map(|declarator| Declarator::new(declarator, source))
+                .unwrap_or_default();
+            if base == "void" && declarator.pointers == 0 {
+                continue;
+            }
+            let rust_type = declarator.rust_type(
+                &base,
+                is_const(parameter, source),
+                Place::Parameter,
+                self,
+                source,
+            );
+            let name = match &declarator.name {
+                Some(name) => identifier(name),
+                None => format!("arg{index}"),
+            };
+            parameters.push(format!("{name}: {rust_type}"));
+        }
+        parameters
+    }
+
+    fn finish(mut self) -> String {
+        for name in std::mem::take(&mut self.opaque) {
+            let item = format!("pub struct {name} {{}}\n");
+            self.add_item(name, item);
+        }
+        self.items.join("\n")
+    }
+}
+
+/// The parts of a C declarator the skeleton needs.
+#[derive(Default)]
+struct Declarator<'tree> {
+    name: Option<String>,
+    /// pointers applied to the declared type, e.g. the return type of a function
+    pointers: usize,
+    /// the size of an array
+    array: Option<String>,
+    parameters: Option<Node<'tree>>,
+    function_pointer: bool,
+}
+
+impl<'tree> Declarator<'tree> {
+    fn new(node: Node<'tree>, source: &str) -> Self {
+        let mut declarator = Declarator::default();
+        let mut node = Some(node);
+        while let Some(current) = node {
+            node = match current.kind() {
+                "pointer_declarator" | "abstract_pointer_declarator" => {
+                    if declarator.parameters.is_some() {
+                        declarator.function_pointer = true;
+                    } else {
+                        declarator.pointers += 1;
+                    }
+                    current.child_by_field_name("declarator")
+                }
+                "array_declarator" | "abstract_array_declarator" => {
+                    declarator.array = Some(text(current.child_by_field_name("size"), source));
+                    current.child_by_field_name("declarator")
+                }
+                "function_declarator" | "abstract_function_declarator" => {
+                    declarator.parameters = current.child_by_field_name("parameters");
+                    current.child_by_field_name("declarator")
+                }
+                "parenthesized_declarator" | "abstract_parenthesized_declarator" => {
+                    current.named_child(0)
+                }
+                "identifier" | "field_identifier" | "type_identifier" => {
+                    declarator.name = Some(text(Some(current), source));
+                    None
+                }
+                _ => None,
+            };
+        }
+        declarator
+    }
+
+    fn rust_type(
+        &self,
+        base: &str,
+        is_const: bool,
+        place: Place,
+        skeleton: &mut Skeleton,
+        source: &str,
+    ) -> String {
+        if self.function_pointer {
+            let return_type = rust_type(base, self.pointers, is_const, Place::Return, skeleton);
+            let parameters: Vec<String> = match self.parameters {
+                Some(parameters) => skeleton.parameters(parameters, source),
+                None => vec![],
+            }
+            .into_iter()
+            .map(|parameter| parameter.split_once(": ").unwrap().1.to_string())
+            .collect();
+            let mut function = format!("fn({})", parameters.join(", "));
+            if return_type != "()" {
+                function += &format!(" -> {return_type}");
+            }
+            return function;
+        }
+        let element = rust_type(base, self.pointers, is_const, place, skeleton);
+        match &self.array {
+            Some(size) if size.parse::<usize>().is_ok() => format!("[{element}; {size}]"),
+            Some(_) if place == Place::Parameter => format!("&[{element}]"),
+            Some(_) => format!("Vec<{element}>"),
+            None => element,
+        }
+    }
+}
+
+/// Translates a C type, guessing the ownership of the pointers from where they are used.
+fn rust_type(
+    base: &str,
+    pointers: usize,
+    is_const: bool,
+    place: Place,
+    skeleton: &mut Skeleton,
+) -> String {
+    let element = match primitive(base) {
+        Some(primitive) => primitive.to_string(),
+        None => {
+            let name = type_name(base);
+            if !skeleton.names.contains(&name) {
+                skeleton.opaque.insert(name.clone());
+            }
+            name
+        }
+    };
+    match (pointers, place) {
+        (0, _) => element,
+        (1, _) if base == "void" => {
+            let mutability = if is_const { "const" } else { "mut" };
+            format!("*{mutability} std::ffi::c_void")
+        }
+        (1, Place::Parameter) if base == "char" => "&str".to_string(),
+        (1, _) if base == "char" => "String".to_string(),
+        (1, Place::Parameter) if is_const => format!("&{element}"),
+        (1, Place::Parameter) => format!("&mut {element}"),
+        (1, _) => format!("Option<Box<{element}>>"),
+        (_, Place::Parameter) => format!(
+            "&[{}]",
+            rust_type(base, pointers - 1, is_const, Place::Field, skeleton)
+        ),
+        (_, _) => format!(
+            "Vec<{}>",
+            rust_type(base, pointers - 1, is_const, Place::Field, skeleton)
+        ),
+    }
+}
+
+fn primitive(c_type: &str) -> Option<&'static str> {
+    let rust_type = match c_type {
+        "void" => "()",
+        "_Bool" | "bool" => "bool",
+        "char" | "signed char" | "int8_t" => "i8",
+        "unsigned char" | "uint8_t" => "u8",
+        "short" | "short int" | "signed short" | "int16_t" => "i16",
+        "unsigned short" | "unsigned short int" | "uint16_t" => "u16",
+        "int" | "signed" | "signed int" | "int32_t" => "i32",
+        "unsigned" | "unsigned int" | "uint32_t" => "u32",
+        "long" | "long int" | "long long" | "long long int" | "int64_t" => "i64",
+        "unsigned long" | "unsigned long int" | "unsigned long long" | "uint64_t" => "u64",
+        "size_t" | "uintptr_t" => "usize",
+        "ssize_t" | "ptrdiff_t" | "intptr_t" => "isize",
+        "float" => "f32",
+        "double" | "long double" => "f64",
+        _ => return None,
+    };
+    Some(rust_type)
+}
+
+/// The type of a declaration without the declarators, e.g. `unsigned int` or `foo_t`.
+fn base_type(node: Node, source: &str) -> String {
+    let Some(type_node) = node.child_by_field_name("type") else {
+        return String::new();
+    };
+    match type_node.kind() {
+        "struct_specifier" | "union_specifier" | "enum_specifier" => {
+            text(type_node.child_by_field_name("name"), source)
+        }
+        _ => text(Some(type_node), source)
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+fn is_const(node: Node, source: &str) -> bool {
+    let mut cursor = node.walk();
+    node.children(&mut cursor)
+        .any(|child| child.kind() == "type_qualifier" && text(Some(child), source) == "const")
+}
+
+fn text(node: Option<Node>, source: &str) -> String {
+    node.and_then(|node| node.utf8_text(source.as_bytes()).ok())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Turns a C type name into a Rust one, e.g. `parser_state_t` into `ParserState`.
+pub fn type_name(name: &str) -> String {
+    let name = name.strip_suffix("_t").unwrap_or(name);
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            let rest = chars.as_str();
+            if rest.chars().all(|c| !c.is_ascii_lowercase()) {
+                format!("{first}{}", rest.to_ascii_lowercase())
+            } else {
+                format!("{first}{rest}")
+            }
+        })
+        .collect()
+}
+
+/// Length of the prefix shared by the variants of an enum up to an underscore, e.g. `COLOR_`.
+fn common_prefix<'a>(names: impl Iterator<Item = &'a str>) -> usize {
+    let names: Vec<&str> = names.collect();
+    let Some(first) = names.first() else {
+        return 0;
+    };
+    let mut prefix = first
+        .char_indices()
+        .filter(|(_, c)| *c == '_')
+        .map(|(index, _)| index + 1)
+        .take_while(|&end| names.iter().all(|name| name.starts_with(&first[..end])))
+        .last()
+        .unwrap_or(0);
+    // A variant can't be the prefix itself.
+    if names.iter().any(|name| name.len() == prefix) {
+        prefix = 0;
+    }
+    prefix
+}
+
+fn identifier(name: &str) -> String {
+    match name {
+        "self" | "super" | "crate" | "Self" => format!("{name}_"),
+        _ if KEYWORDS.contains(&name) => format!("r#{name}"),
+        _ => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn header() {
+        let dir = TempDir::new("skeleton");
+        std::fs::write(
+            dir.join("shape.h"),
+            r#"#ifndef SHAPE_H
+#define SHAPE_H
+#include <stddef.h>
+
+#define MAX_POINTS 64
+#define VERSION "1.2"
+#define SQUARE(x) ((x) * (x))
+
+typedef enum { SHAPE_CIRCLE = 1, SHAPE_POLYGON } shape_kind;
+
+struct point {
+    double x, y;
+};
+
+typedef struct shape {
+    shape_kind kind;
+    struct point points[MAX_POINTS];
+    size_t count;
+    char *name;
+    struct cache *cache;
+} shape_t;
+
+typedef int (*visit_fn)(const struct point *point, void *data);
+
+shape_t *shape_new(const char *name, shape_kind kind);
+void shape_free(shape_t *shape);
+double shape_area(const shape_t *shape);
+int shape_visit(shape_t *shape, visit_fn visit, void *data);
+size_t shape_count(void);
+
+#endif
+"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("shape.c"), "#include \"shape.h\"\n").unwrap();
+
+        let files = vec!["shape.c".to_string(), "shape.h".to_string()];
+        assert_eq!(
+            module_skeleton(&dir, &files).unwrap(),
+            "// Skeleton generated from `shape.h`, replace the todo!() bodies with the port of the \
+            implementation.\n\
+            \n\
+            pub const MAX_POINTS: i64 = 64;\n\
+            \n\
+            pub const VERSION: &str = \"1.2\";\n\
+            \n\
+            pub enum ShapeKind {\n    Circle = 1,\n    Polygon,\n}\n\
+            \n\
+            pub struct Point {\n    pub x: f64,\n    pub y: f64,\n}\n\
+            \n\
+            pub struct Shape {\n    pub kind: ShapeKind,\n    pub points: Vec<Point>,\n    \
+            pub count: usize,\n    pub name: String,\n    pub cache: Option<Box<Cache>>,\n}\n\
+            \n\
+            pub type VisitFn = fn(&Point, *mut std::ffi::c_void) -> i32;\n\
+            \n\
+            pub fn shape_new(name: &str, kind: ShapeKind) -> Option<Box<Shape>> {\n    todo!()\n}\n\
+            \n\
+            pub fn shape_free(shape: &mut Shape) {\n    todo!()\n}\n\
+            \n\
+            pub fn shape_area(shape: &Shape) -> f64 {\n    todo!()\n}\n\
+            \n\
+            pub fn shape_visit(shape: &mut Shape, visit: VisitFn, data: *mut std::ffi::c_void) \
+            -> i32 {\n    todo!()\n}\n\
+            \n\
+            pub fn shape_count() -> usize {\n    todo!()\n}\n\
+            \n\
+            pub struct Cache {}\n"
+        );
+        assert_eq!(module_skeleton(&dir, &files[..1]), None);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::session::SESSION_DIR;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// Top-level directories of the destination project left out of snapshots.
+const EXCLUDED_DIRS: &[&str] = &["target", SESSION_DIR];
+
+/// Packs the project directory into the next `snapshot-N.tar.gz` of `snapshot_dir`, leaving
+/// out build artifacts and the session directory. Returns the path of the tarball.
+pub fn create_snapshot(project_dir: &Path, snapshot_dir: &Path) -> Result<PathBuf, String> {
+    std::fs::create_dir_all(snapshot_dir)
+        .map_err(|e| format!("Cannot create {}: {e}", snapshot_dir.display()))?;
+    let path = snapshot_dir.join(format!("snapshot-{}.tar.gz", next_number(snapshot_dir)));
+    let error = |e: std::io::Error| format!("Cannot write {}: {e}", path.display());
+
+    let file = File::create(&path).map_err(error)?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    builder.follow_symlinks(false);
+    let result = append_dir(&mut builder, project_dir, Path::new(""))
+        .and_then(|_| builder.into_inner())
+        .and_then(|encoder| encoder.finish())
+        .map(|_| ());
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&path);
+        return Err(error(e));
+    }
+    Ok(path)
+}
+
+fn append_dir<W: std::io::Write>(
+    builder: &mut tar::Builder<W>,
+    dir: &Path,
+    relpath: &Path,
+) -> std::io::Result<()> {
+    let mut entries: Vec<_> = std::fs::read_dir(dir)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name();
+        if relpath.as_os_str().is_empty() && EXCLUDED_DIRS.iter().any(|excluded| name == *excluded)
+        {
+            continue;
+        }
+        let entry_relpath = relpath.join(&name);
+        if entry.file_type()?.is_dir() {
+            builder.append_dir(&entry_relpath, entry.path())?;
+            append_dir(builder, &entry.path(), &entry_relpath)?;
+        } else {
+            builder.append_path_with_name(entry.path(), &entry_relpath)?;
+        }
+    }
+    Ok(())
+}
+
+/// Finds the number following the largest one of the existing snapshots.
+fn next_number(snapshot_dir: &Path) -> u64 {
+    std::fs::read_dir(snapshot_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            name.strip_prefix("snapshot-")?
+                .strip_suffix(".tar.gz")?
+                .parse::<u64>()
+                .ok()
+        })
+        .max()
+        .unwrap_or(0)
+        + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use flate2::read::GzDecoder;
+
+    #[test]
+    fn snapshots() {
+        let dir = TempDir::new("snapshot");
+        let project = dir.join("project");
+        for subdir in ["src/bin", "target/debug", ".riir"] {
+            std::fs::create_dir_all(project.join(subdir)).unwrap();
+        }
+        std::fs::write(project.join("Cargo.toml"), "[package]\n").unwrap();
+        std::fs::write(project.join("src/bin/main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(project.join("target/debug/main"), "").unwrap();
+        std::fs::write(project.join(".riir/todo.json"), "[]").unwrap();
+
+        let snapshots = dir.join("snapshots");
+        let first = create_snapshot(&project, &snapshots).unwrap();
+        assert_eq!(first, snapshots.join("snapshot-1.tar.gz"));
+        let second = create_snapshot(&project, &snapshots).unwrap();
+        assert_eq!(second, snapshots.join("snapshot-2.tar.gz"));
+
+        let mut archive = tar::Archive::new(GzDecoder::new(File::open(second).unwrap()));
+        let paths: Vec<PathBuf> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().into_owned())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                Path::new("Cargo.toml"),
+                Path::new("src"),
+                Path::new("src/bin"),
+                Path::new("src/bin/main.rs")
+            ]
+        );
+    }
+}
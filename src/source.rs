@@ -0,0 +1,236 @@
+use crate::hash::stable_hash;
+use flate2::read::GzDecoder;
+use log::info;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const GIT_URL_PREFIXES: &[&str] = &["https://", "http://", "ssh://", "git://", "git@", "file://"];
+
+const ARCHIVE_EXTENSIONS: &[&str] = &[".zip", ".tar", ".tar.gz", ".tgz"];
+
+/// Turns a source argument into a local directory: git URLs are fetched into the cache,
+/// archives are extracted into `extract_dir`, and directories are returned as is.
+pub fn prepare_source(
+    source: &Path,
+    rev: Option<&str>,
+    extract_dir: &Path,
+) -> Result<PathBuf, String> {
+    if let Some(url) = source.to_str().filter(|source| is_git_url(source)) {
+        return fetch_git(url, rev, &cache_dir().join("git"));
+    }
+    if source.is_file() && is_archive(source) {
+        return extract_archive(source, extract_dir);
+    }
+    Ok(source.to_path_buf())
+}
+
+fn is_git_url(source: &str) -> bool {
+    GIT_URL_PREFIXES
+        .iter()
+        .any(|prefix| source.starts_with(prefix))
+}
+
+fn is_archive(path: &Path) -> bool {
+    let name = path.to_string_lossy().to_lowercase();
+    ARCHIVE_EXTENSIONS
+        .iter()
+        .any(|extension| name.ends_with(extension))
+}
+
+/// Extracts a zip or tar archive into a new subdirectory of `extract_dir` and returns the
+/// project directory in it.
+fn extract_archive(archive: &Path, extract_dir: &Path) -> Result<PathBuf, String> {
+    let name = archive.file_name().unwrap_or_default().to_string_lossy();
+    let lower = name.to_lowercase();
+    let mut dir = extract_dir.join(name.as_ref());
+    for i in 2.. {
+        if !dir.exists() {
+            break;
+        }
+        dir = extract_dir.join(format!("{name}-{i}"));
+    }
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Cannot create {}: {e}", dir.display()))?;
+
+    info!("Extracting {}", archive.display());
+    let error = |e: &dyn std::fmt::Display| format!("Cannot extract {}: {e}", archive.display());
+    let file = File::open(archive).map_err(|e| error(&e))?;
+    // Both extractors refuse to write outside of the target directory.
+    if lower.ends_with(".zip") {
+        zip::ZipArchive::new(file)
+            .and_then(|mut zip| zip.extract(&dir))
+            .map_err(|e| error(&e))?;
+    } else if lower.ends_with(".tar") {
+        tar::Archive::new(file)
+            .unpack(&dir)
+            .map_err(|e| error(&e))?;
+    } else {
+        tar::Archive::new(GzDecoder::new(file))
+            .unpack(&dir)
+            .map_err(|e| error(&e))?;
+    }
+
+    // Archives usually wrap the project in a single directory like `project-1.0`.
+    let entries: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .map_err(|e| error(&e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    match entries.as_slice() {
+        [single] if single.is_dir() => Ok(single.clone()),
+        _ => Ok(dir),
+    }
+}
+
+/// Directory for downloaded sources, kept between runs.
+fn cache_dir() -> PathBuf {
+    let cache = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(std::env::temp_dir);
+    cache.join("riir")
+}
+
+/// Checks out a revision of a repository, the default branch if `rev` is `None`.
+///
+/// Only the requested commit is fetched, without history. The checkout is reused by later runs
+/// with the same URL.
+fn fetch_git(url: &str, rev: Option<&str>, cache: &Path) -> Result<PathBuf, String> {
+    let name = url
+        .trim_end_matches('/')
+        .rsplit(['/', ':'])
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches(".git");
+    let dir = cache.join(format!("{name}-{:016x}", stable_hash(url.as_bytes())));
+
+    if !dir.join(".git").is_dir() {
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Cannot create {}: {e}", dir.display()))?;
+        git(&dir, &["init", "--quiet"])?;
+        git(&dir, &["remote", "add", "origin", url])?;
+    }
+    info!("Fetching {url}");
+    git(
+        &dir,
+        &[
+            "fetch",
+            "--quiet",
+            "--depth",
+            "1",
+            "origin",
+            rev.unwrap_or("HEAD"),
+        ],
+    )?;
+    git(
+        &dir,
+        &["checkout", "--quiet", "--force", "--detach", "FETCH_HEAD"],
+    )?;
+    git(&dir, &["clean", "--quiet", "-d", "--force"])?;
+    Ok(dir)
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<(), String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| format!("Cannot run git: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn fetch_repository() {
+        let dir = TempDir::new("git");
+        let origin = dir.join("origin");
+        std::fs::create_dir_all(&origin).unwrap();
+        let commit = |message: &str| {
+            git(
+                &origin,
+                &[
+                    "-c",
+                    "user.name=riir",
+                    "-c",
+                    "user.email=riir@example.com",
+                    "commit",
+                    "--quiet",
+                    "--allow-empty",
+                    "-am",
+                    message,
+                ],
+            )
+        };
+        git(&origin, &["init", "--quiet"]).unwrap();
+        std::fs::write(origin.join("main.c"), "int main;\n").unwrap();
+        git(&origin, &["add", "main.c"]).unwrap();
+        commit("first").unwrap();
+        git(&origin, &["tag", "v1"]).unwrap();
+        std::fs::write(origin.join("main.c"), "int main(void);\n").unwrap();
+        commit("second").unwrap();
+
+        let url = format!("file://{}", origin.display());
+        let cache = dir.join("cache");
+        let checkout = fetch_git(&url, None, &cache).unwrap();
+        assert!(checkout.starts_with(&cache));
+        assert_eq!(
+            std::fs::read_to_string(checkout.join("main.c")).unwrap(),
+            "int main(void);\n"
+        );
+        let checkout = fetch_git(&url, Some("v1"), &cache).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(checkout.join("main.c")).unwrap(),
+            "int main;\n"
+        );
+        assert!(fetch_git(&url, Some("v2"), &cache).is_err());
+
+        assert_eq!(
+            prepare_source(Path::new("../project"), None, &dir).unwrap(),
+            Path::new("../project")
+        );
+    }
+
+    #[test]
+    fn extract_archives() {
+        let dir = TempDir::new("archive");
+
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            File::create(dir.join("project-1.0.tar.gz")).unwrap(),
+            flate2::Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(10);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "project-1.0/main.c", &b"int main;\n"[..])
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let mut zip = zip::ZipWriter::new(File::create(dir.join("project.zip")).unwrap());
+        for file in ["a.c", "b.c"] {
+            zip.start_file(file, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            std::io::Write::write_all(&mut zip, b"int x;\n").unwrap();
+        }
+        zip.finish().unwrap();
+
+        let extract_dir = dir.join("extracted");
+        let project = prepare_source(&dir.join("project-1.0.tar.gz"), None, &extract_dir).unwrap();
+        assert_eq!(project, extract_dir.join("project-1.0.tar.gz/project-1.0"));
+        assert!(project.join("main.c").is_file());
+        let project = prepare_source(&dir.join("project.zip"), None, &extract_dir).unwrap();
+        assert_eq!(project, extract_dir.join("project.zip"));
+        assert!(project.join("b.c").is_file());
+    }
+}
@@ -0,0 +1,386 @@
+use crate::language::detect_language;
+use crate::search::build_globs;
+use crate::text::{decode_text, is_binary};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tree_sitter::{Language, Parser, Query, QueryCursor, StreamingIterator};
+
+const DEFAULT_MAX_REFERENCES: usize = 100;
+
+/// Symbol queries for the supported languages. Capture names are the kinds of the symbols.
+const C_QUERY: &str = r#"
+(function_definition declarator: (function_declarator declarator: (identifier) @function))
+(function_definition
+  declarator: (pointer_declarator declarator: (function_declarator declarator: (identifier) @function)))
+(struct_specifier name: (type_identifier) @struct body: (_))
+(union_specifier name: (type_identifier) @struct body: (_))
+(enum_specifier name: (type_identifier) @enum body: (_))
+(type_definition declarator: (type_identifier) @type)
+(translation_unit (declaration declarator: (identifier) @global))
+(translation_unit (declaration declarator: (init_declarator declarator: (identifier) @global)))
+(preproc_def name: (identifier) @macro)
+(preproc_function_def name: (identifier) @macro)
+"#;
+
+const CPP_QUERY: &str = r#"
+(function_definition declarator: (function_declarator declarator: (identifier) @function))
+(function_definition
+  declarator: (function_declarator declarator: (qualified_identifier) @function))
+(function_definition declarator: (function_declarator declarator: (field_identifier) @method))
+(class_specifier name: (type_identifier) @class body: (_))
+(struct_specifier name: (type_identifier) @struct body: (_))
+(enum_specifier name: (type_identifier) @enum body: (_))
+(type_definition declarator: (type_identifier) @type)
+(namespace_definition name: (namespace_identifier) @namespace)
+(translation_unit (declaration declarator: (identifier) @global))
+(translation_unit (declaration declarator: (init_declarator declarator: (identifier) @global)))
+(preproc_def name: (identifier) @macro)
+(preproc_function_def name: (identifier) @macro)
+"#;
+
+const PYTHON_QUERY: &str = r#"
+(function_definition name: (identifier) @function)
+(class_definition name: (identifier) @class)
+(module (expression_statement (assignment left: (identifier) @global)))
+"#;
+
+const GO_QUERY: &str = r#"
+(function_declaration name: (identifier) @function)
+(method_declaration name: (field_identifier) @method)
+(type_spec name: (type_identifier) @type)
+(source_file (var_declaration (var_spec name: (identifier) @global)))
+(source_file (const_declaration (const_spec name: (identifier) @global)))
+"#;
+
+const JAVA_QUERY: &str = r#"
+(class_declaration name: (identifier) @class)
+(interface_declaration name: (identifier) @interface)
+(enum_declaration name: (identifier) @enum)
+(method_declaration name: (identifier) @method)
+(field_declaration declarator: (variable_declarator name: (identifier) @field))
+"#;
+
+const JAVASCRIPT_QUERY: &str = r#"
+(function_declaration name: (identifier) @function)
+(generator_function_declaration name: (identifier) @function)
+(class_declaration name: (identifier) @class)
+(method_definition name: (property_identifier) @method)
+(program (lexical_declaration (variable_declarator name: (identifier) @global)))
+(program (variable_declaration (variable_declarator name: (identifier) @global)))
+(program (export_statement declaration: (lexical_declaration (variable_declarator name: (identifier) @global))))
+"#;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct SymbolsArgs {
+    /// glob patterns of files to index, e.g. "src/**/*.c"; all files if omitted
+    pub include: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SymbolsResult {
+    pub error: Option<String>,
+    pub files: Vec<FileSymbols>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FileSymbols {
+    pub path: String,
+    pub symbols: Vec<Symbol>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct Symbol {
+    /// function, method, class, struct, enum, type, global, macro etc.
+    pub kind: String,
+    pub name: String,
+    pub line: usize,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct FindReferencesArgs {
+    /// name of a function, type, variable etc.
+    pub symbol: String,
+    /// glob patterns of files to search in, e.g. "src/**/*.c"; all files if omitted
+    pub include: Option<Vec<String>>,
+    /// maximum number of definitions and references to return, 100 by default
+    pub max_matches: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FindReferencesResult {
+    pub error: Option<String>,
+    pub definitions: Vec<Occurrence>,
+    pub references: Vec<Occurrence>,
+    /// true if there are more occurrences than returned
+    pub truncated: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Occurrence {
+    pub path: String,
+    pub line: usize,
+    /// the line with the symbol
+    pub text: String,
+}
+
+/// Returns the tree-sitter grammar and the symbol query for a language.
+fn grammar(language: &str) -> Option<(Language, &'static str)> {
+    let grammar = match language {
+        "C" => (tree_sitter_c::LANGUAGE.into(), C_QUERY),
+        "C++" => (tree_sitter_cpp::LANGUAGE.into(), CPP_QUERY),
+        "Python" => (tree_sitter_python::LANGUAGE.into(), PYTHON_QUERY),
+        "Go" => (tree_sitter_go::LANGUAGE.into(), GO_QUERY),
+        "Java" => (tree_sitter_java::LANGUAGE.into(), JAVA_QUERY),
+        "JavaScript" => (tree_sitter_javascript::LANGUAGE.into(), JAVASCRIPT_QUERY),
+        _ => return None,
+    };
+    Some(grammar)
+}
+
+/// Lists the symbols defined in a source file, or `None` if the language isn't supported.
+pub fn file_symbols(path: &Path, source: &str) -> Option<Vec<Symbol>> {
+    let (language, query) = grammar(detect_language(path)?)?;
+    let mut parser = Parser::new();
+    parser.set_language(&language).ok()?;
+    let tree = parser.parse(source, None)?;
+    // The queries are constant, so an error here is a bug.
+    let query = Query::new(&language, query).expect("invalid symbol query");
+
+    let mut symbols = vec![];
+    let mut cursor = QueryCursor::new();
+    let mut matches = cursor.matches(&query, tree.root_node(), source.as_bytes());
+    while let Some(m) = matches.next() {
+        for capture in m.captures {
+            let Ok(name) = capture.node.utf8_text(source.as_bytes()) else {
+                continue;
+            };
+            let symbol = Symbol {
+                kind: query.capture_names()[capture.index as usize].to_string(),
+                name: name.to_string(),
+                line: capture.node.start_position().row + 1,
+            };
+            if !symbols.contains(&symbol) {
+                symbols.push(symbol);
+            }
+        }
+    }
+    symbols.sort_by_key(|symbol| symbol.line);
+    Some(symbols)
+}
+
+/// Indexes the symbols defined in the files of a project.
+///
+/// `files` are paths relative to `root`. Files in unsupported languages are skipped.
+pub fn symbols(root: &Path, files: &[String], args: &SymbolsArgs) -> SymbolsResult {
+    let include = match args.include.as_deref().map(build_globs).transpose() {
+        Ok(include) => include,
+        Err(e) => {
+            return SymbolsResult {
+                error: Some(e),
+                files: vec![],
+            };
+        }
+    };
+
+    let mut result = SymbolsResult {
+        error: None,
+        files: vec![],
+    };
+    for file in files {
+        if include.as_ref().is_some_and(|globs| !globs.is_match(file)) {
+            continue;
+        }
+        let Ok(data) = std::fs::read(root.join(file)) else {
+            continue;
+        };
+        if is_binary(&data) {
+            continue;
+        }
+        let source = decode_text(data).contents;
+        if let Some(symbols) = file_symbols(Path::new(file), &source) {
+            result.files.push(FileSymbols {
+                path: file.clone(),
+                symbols,
+            });
+        }
+    }
+    result
+}
+
+/// Finds the definitions and usages of an identifier in the files of a project.
+///
+/// Definitions are found with tree-sitter in the supported languages, usages are the other
+/// occurrences of the identifier as a whole word, so they may include comments and strings.
+pub fn find_references(
+    root: &Path,
+    files: &[String],
+    args: &FindReferencesArgs,
+) -> FindReferencesResult {
+    let mut result = FindReferencesResult {
+        error: None,
+        definitions: vec![],
+        references: vec![],
+        truncated: false,
+    };
+    let include = match args.include.as_deref().map(build_globs).transpose() {
+        Ok(include) => include,
+        Err(e) => {
+            result.error = Some(e);
+            return result;
+        }
+    };
+    let symbol = args.symbol.trim();
+    if symbol.is_empty() {
+        result.error = Some("The symbol is empty.".to_string());
+        return result;
+    }
+    let regex = Regex::new(&format!(r"\b{}\b", regex::escape(symbol))).unwrap();
+    let max_matches = args.max_matches.unwrap_or(DEFAULT_MAX_REFERENCES);
+
+    for file in files {
+        if include.as_ref().is_some_and(|globs| !globs.is_match(file)) {
+            continue;
+        }
+        let Ok(data) = std::fs::read(root.join(file)) else {
+            continue;
+        };
+        if is_binary(&data) {
+            continue;
+        }
+        let source = decode_text(data).contents;
+        if !regex.is_match(&source) {
+            continue;
+        }
+
+        let definition_lines: Vec<usize> = file_symbols(Path::new(file), &source)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|s| s.name == symbol || s.name.ends_with(&format!("::{symbol}")))
+            .map(|s| s.line)
+            .collect();
+        for (i, line) in source.lines().enumerate() {
+            if !regex.is_match(line) {
+                continue;
+            }
+            if result.definitions.len() + result.references.len() == max_matches {
+                result.truncated = true;
+                return result;
+            }
+            let occurrence = Occurrence {
+                path: file.clone(),
+                line: i + 1,
+                text: line.trim().to_string(),
+            };
+            if definition_lines.contains(&(i + 1)) {
+                result.definitions.push(occurrence);
+            } else {
+                result.references.push(occurrence);
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn names(path: &str, source: &str) -> Vec<String> {
+        file_symbols(Path::new(path), source)
+            .unwrap()
+            .into_iter()
+            .map(|s| format!("{} {}:{}", s.kind, s.name, s.line))
+            .collect()
+    }
+
+    #[test]
+    fn c_symbols() {
+        let source = "#define MAX 10\n\
+            struct point { int x; };\n\
+            typedef struct point point_t;\n\
+            static int counter = 0;\n\
+            int add(int a, int b);\n\
+            char *name(void) { return 0; }\n\
+            int main(void) { int local = 1; return local; }\n";
+        assert_eq!(
+            names("main.c", source),
+            [
+                "macro MAX:1",
+                "struct point:2",
+                "type point_t:3",
+                "global counter:4",
+                "function name:6",
+                "function main:7",
+            ]
+        );
+    }
+
+    #[test]
+    fn other_languages() {
+        assert_eq!(
+            names("a.py", "X = 1\nclass A:\n    def f(self): pass\n"),
+            ["global X:1", "class A:2", "function f:3"]
+        );
+        assert_eq!(
+            names(
+                "a.go",
+                "package a\ntype T struct{}\nfunc (t T) M() {}\nfunc F() {}\n"
+            ),
+            ["type T:2", "method M:3", "function F:4"]
+        );
+        assert_eq!(
+            names("A.java", "class A { int x; void f() {} }\n"),
+            ["class A:1", "field x:1", "method f:1"]
+        );
+        assert_eq!(
+            names(
+                "a.js",
+                "const x = 1;\nfunction f() {}\nclass C { m() {} }\n"
+            ),
+            ["global x:1", "function f:2", "class C:3", "method m:3"]
+        );
+        assert_eq!(
+            names(
+                "a.cpp",
+                "namespace ns { class C { void m(); }; }\nvoid ns::C::m() {}\n"
+            ),
+            ["namespace ns:1", "class C:1", "function ns::C::m:2"]
+        );
+        assert!(file_symbols(Path::new("README"), "text").is_none());
+    }
+
+    #[test]
+    fn references() {
+        let dir = TempDir::new("refs");
+        std::fs::write(dir.join("util.h"), "int add(int a, int b);\n").unwrap();
+        std::fs::write(
+            dir.join("util.c"),
+            "int add(int a, int b) {\n    return a + b;\n}\nint adder;\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("main.c"), "int main(void) { return add(1, 2); }\n").unwrap();
+        let files = ["main.c", "util.c", "util.h"].map(String::from);
+
+        let args = FindReferencesArgs {
+            symbol: "add".to_string(),
+            include: None,
+            max_matches: None,
+        };
+        let result = find_references(&dir, &files, &args);
+        let found = |occurrences: &[Occurrence]| -> Vec<String> {
+            occurrences
+                .iter()
+                .map(|o| format!("{}:{}", o.path, o.line))
+                .collect()
+        };
+        assert_eq!(found(&result.definitions), ["util.c:1"]);
+        assert_eq!(found(&result.references), ["main.c:1", "util.h:1"]);
+        assert_eq!(
+            result.references[0].text,
+            "int main(void) { return add(1, 2); }"
+        );
+    }
+}
@@ -0,0 +1,268 @@
+use crate::cargo::CargoOutput;
+use crate::language::detect_language;
+use regex::Regex;
+use std::fmt;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// `def test_x(` of pytest and unittest.
+static PYTHON_TEST: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^\s*(?:async\s+)?def\s+(test\w*)\s*\(").unwrap());
+/// `func TestX(t *testing.T)` of Go.
+static GO_TEST: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^func\s+(Test\w*)\s*\(\s*\w+\s+\*testing\.T\s*\)").unwrap());
+/// `it("does x", ...)` and `test("x", ...)` of Jest, Mocha and the like.
+static JS_TEST: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?m)\b(?:it|test)\s*\(\s*["'`]([^"'`]+)["'`]"#).unwrap());
+/// A method annotated with `@Test` in Java and Kotlin.
+static JVM_TEST: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"@Test\b[^\n]*\n(?:\s*@\w+[^\n]*\n)*\s*(?:[\w<>\[\],]+\s+)*(?:`([^`]+)`|(\w+))\s*\(",
+    )
+    .unwrap()
+});
+/// `TEST(Suite, Name)` of GoogleTest and `TEST_CASE("name")` of Catch2.
+static CPP_TEST: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?m)^\s*(?:TEST|TEST_F|TEST_P)\s*\(\s*\w+\s*,\s*(\w+)\s*\)|^\s*TEST_CASE\s*\(\s*"([^"]+)""#)
+        .unwrap()
+});
+/// `void test_x(void)` in C files of a test directory.
+static C_TEST: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^(?:static\s+)?(?:void|int)\s+(test_?\w+)\s*\(").unwrap());
+
+/// A test of the source project.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceTest {
+    pub file: String,
+    pub name: String,
+}
+
+/// Finds the tests of the source project by the conventions of the test frameworks of each
+/// language. `files` are paths relative to `root`.
+pub fn find_tests(root: &Path, files: &[String]) -> Vec<SourceTest> {
+    let mut tests = vec![];
+    for file in files {
+        let Some(pattern) = test_pattern(file) else {
+            continue;
+        };
+        let Ok(contents) = std::fs::read_to_string(root.join(file)) else {
+            continue;
+        };
+        for captures in pattern.captures_iter(&contents) {
+            let Some(name) = captures.iter().skip(1).flatten().next() else {
+                continue;
+            };
+            tests.push(SourceTest {
+                file: file.clone(),
+                name: name.as_str().to_string(),
+            });
+        }
+    }
+    tests
+}
+
+/// Picks the pattern of the tests in a file, if it's a test file at all.
+fn test_pattern(file: &str) -> Option<&'static Regex> {
+    let path = Path::new(file);
+    let name = path.file_name()?.to_str()?;
+    let stem = name.split('.').next().unwrap_or(name);
+    let in_test_dir = path.components().any(|component| {
+        matches!(
+            component.as_os_str().to_str(),
+            Some("test" | "tests" | "spec" | "__tests__")
+        )
+    });
+    let is_test_file = in_test_dir
+        || stem.starts_with("test_")
+        || stem.ends_with("_test")
+        || stem.ends_with("Test")
+        || stem.ends_with("Tests")
+        || name.contains(".test.")
+        || name.contains(".spec.");
+    if !is_test_file {
+        return None;
+    }
+    match detect_language(path)? {
+        "Python" => Some(&PYTHON_TEST),
+        "Go" => Some(&GO_TEST),
+        "JavaScript" | "TypeScript" => Some(&JS_TEST),
+        "Java" | "Kotlin" => Some(&JVM_TEST),
+        "C++" => Some(&CPP_TEST),
+        "C" => Some(&C_TEST),
+        _ => None,
+    }
+}
+
+/// Reduces a test name to lower case letters and digits, without a leading `test`, so that
+/// `TestParseEmpty`, `test_parse_empty` and "parse empty" match.
+pub fn normalize_test_name(name: &str) -> String {
+    let name = name.rsplit("::").next().unwrap_or(name);
+    let name: String = name
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+    match name.strip_prefix("test") {
+        Some(rest) if !rest.is_empty() => rest.to_string(),
+        _ => name,
+    }
+}
+
+/// How many of the source tests have a Rust counterpart and how many of those pass.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestSuiteProgress {
+    pub total: usize,
+    pub ported: usize,
+    pub passed: usize,
+}
+
+impl TestSuiteProgress {
+    /// Matches the tests run by `cargo test` with the source tests by their names.
+    pub fn measure(tests: &[SourceTest], output: &CargoOutput) -> Self {
+        let passed: Vec<String> = output
+            .passed_tests
+            .iter()
+            .map(|name| normalize_test_name(name))
+            .collect();
+        let failed: Vec<String> = output
+            .test_failures
+            .iter()
+            .map(|failure| normalize_test_name(&failure.name))
+            .collect();
+        let mut progress = TestSuiteProgress {
+            total: tests.len(),
+            ported: 0,
+            passed: 0,
+        };
+        for test in tests {
+            let name = normalize_test_name(&test.name);
+            if passed.contains(&name) {
+                progress.ported += 1;
+                progress.passed += 1;
+            } else if failed.contains(&name) {
+                progress.ported += 1;
+            }
+        }
+        progress
+    }
+
+    /// Share of the source tests that pass in Rust, in percent.
+    pub fn pass_rate(&self) -> f64 {
+        if self.total == 0 {
+            return 100.0;
+        }
+        self.passed as f64 * 100.0 / self.total as f64
+    }
+}
+
+impl fmt::Display for TestSuiteProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} tests ported, {} passing ({:.1}%)",
+            self.ported,
+            self.total,
+            self.passed,
+            self.pass_rate()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cargo::TestFailure;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn find() {
+        let dir = TempDir::new("test-suite");
+        std::fs::create_dir_all(dir.join("tests")).unwrap();
+        std::fs::create_dir_all(dir.join("pkg")).unwrap();
+        std::fs::write(
+            dir.join("tests/test_parser.py"),
+            "def test_empty():\n    pass\n\nclass T:\n    def test_nested(self):\n        pass\n\ndef helper():\n    pass\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("pkg/parse_test.go"),
+            "func TestParse(t *testing.T) {}\nfunc helper(t *testing.T) {}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("pkg/sum.test.ts"),
+            "describe('sum', () => {\n  it('adds numbers', () => {});\n});\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("tests/ListTest.java"),
+            "class ListTest {\n    @Test\n    public void addsItems() {}\n}\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("tests/list.cc"), "TEST(List, Grows) {}\n").unwrap();
+        std::fs::write(dir.join("parser.py"), "def test_not_a_test():\n    pass\n").unwrap();
+        let files = [
+            "tests/test_parser.py",
+            "pkg/parse_test.go",
+            "pkg/sum.test.ts",
+            "tests/ListTest.java",
+            "tests/list.cc",
+            "parser.py",
+        ]
+        .map(String::from);
+
+        let names: Vec<String> = find_tests(&dir, &files)
+            .into_iter()
+            .map(|test| test.name)
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "test_empty",
+                "test_nested",
+                "TestParse",
+                "adds numbers",
+                "addsItems",
+                "Grows"
+            ]
+        );
+    }
+
+    #[test]
+    fn progress() {
+        assert_eq!(normalize_test_name("TestParseEmpty"), "parseempty");
+        assert_eq!(normalize_test_name("tests::test_parse_empty"), "parseempty");
+        assert_eq!(normalize_test_name("parse empty"), "parseempty");
+        assert_eq!(normalize_test_name("test"), "test");
+
+        let tests = ["TestParse", "TestEmpty", "TestLarge"].map(|name| SourceTest {
+            file: "parse_test.go".to_string(),
+            name: name.to_string(),
+        });
+        let output = CargoOutput {
+            success: false,
+            diagnostics: vec![],
+            test_failures: vec![TestFailure {
+                name: "tests::empty".to_string(),
+                panic_message: None,
+                output: String::new(),
+            }],
+            passed_tests: vec!["tests::test_parse".to_string(), "tests::other".to_string()],
+            truncated: false,
+            stderr: String::new(),
+        };
+        let progress = TestSuiteProgress::measure(&tests, &output);
+        assert_eq!(
+            progress,
+            TestSuiteProgress {
+                total: 3,
+                ported: 2,
+                passed: 1
+            }
+        );
+        assert_eq!(
+            progress.to_string(),
+            "2 of 3 tests ported, 1 passing (33.3%)"
+        );
+    }
+}
@@ -0,0 +1,36 @@
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// Directory for the files of a test, removed when the test ends, even if it fails.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates an empty directory with the given name, unique to the process.
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("riir-{name}-{}", std::process::id()));
+        // Leftovers of an earlier process with the same id.
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,75 @@
+use chardetng::EncodingDetector;
+
+/// Contents of a text file converted to UTF-8.
+pub struct DecodedText {
+    pub contents: String,
+    /// explanation for the model if the file wasn't valid UTF-8
+    pub note: Option<String>,
+}
+
+/// Checks whether the data is binary, the same way as git does:
+/// by looking for a zero byte in its beginning.
+pub fn is_binary(data: &[u8]) -> bool {
+    data.iter().take(8000).any(|&b| b == 0)
+}
+
+/// Counts lines the same way as `str::lines`, a trailing line without a newline included.
+pub fn count_lines(data: &[u8]) -> usize {
+    let newlines = data.iter().filter(|&&b| b == b'\n').count();
+    newlines + usize::from(data.last().is_some_and(|&b| b != b'\n'))
+}
+
+/// Converts the file contents to UTF-8, guessing the encoding of legacy files.
+///
+/// Bytes that can't be decoded are replaced with U+FFFD.
+pub fn decode_text(data: Vec<u8>) -> DecodedText {
+    let data = match String::from_utf8(data) {
+        Ok(contents) => {
+            return DecodedText {
+                contents,
+                note: None,
+            };
+        }
+        Err(e) => e.into_bytes(),
+    };
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(&data, true);
+    let encoding = detector.guess(None, false);
+    let (contents, _, had_errors) = encoding.decode(&data);
+
+    let mut note = format!(
+        "The file is not valid UTF-8, it was decoded as {}.",
+        encoding.name()
+    );
+    if had_errors {
+        note.push_str(" Some bytes could not be decoded and were replaced with U+FFFD.");
+    }
+    DecodedText {
+        contents: contents.into_owned(),
+        note: Some(note),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_legacy_text() {
+        let text = decode_text(b"int x; // ok\n".to_vec());
+        assert_eq!(text.contents, "int x; // ok\n");
+        assert!(text.note.is_none());
+
+        // "café" in Latin-1
+        let text = decode_text(b"/* caf\xe9 */\n".to_vec());
+        assert_eq!(text.contents, "/* café */\n");
+        assert!(text.note.unwrap().contains("windows-1252"));
+    }
+
+    #[test]
+    fn binary_data() {
+        assert!(is_binary(b"\x89PNG\r\n\x1a\n\0\0"));
+        assert!(!is_binary(b"caf\xe9"));
+    }
+}
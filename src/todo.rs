@@ -0,0 +1,205 @@
+use crate::project::write_atomic;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Task list the model maintains to track the conversion progress.
+pub struct TodoList {
+    path: PathBuf,
+    items: Mutex<Vec<TodoItem>>,
+}
+
+impl TodoList {
+    /// Loads the task list from the file, starting with an empty list if it doesn't exist.
+    pub fn load(path: PathBuf) -> Self {
+        let items = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        TodoList {
+            path,
+            items: Mutex::new(items),
+        }
+    }
+
+    pub fn add(&self, title: &str, member: Option<&str>) -> TodoAddResult {
+        let mut items = self.items.lock().unwrap();
+        let id = items.iter().map(|item| item.id).max().unwrap_or(0) + 1;
+        items.push(TodoItem {
+            id,
+            title: title.to_string(),
+            status: TodoStatus::Pending,
+            member: member.map(str::to_string),
+        });
+        TodoAddResult {
+            id: Some(id),
+            error: self.save(&items),
+        }
+    }
+
+    pub fn update(&self, args: TodoUpdateArgs) -> TodoUpdateResult {
+        let mut items = self.items.lock().unwrap();
+        let Some(item) = items.iter_mut().find(|item| item.id == args.id) else {
+            return TodoUpdateResult {
+                error: Some("No such item.".to_string()),
+            };
+        };
+        if let Some(status) = args.status {
+            item.status = status;
+        }
+        if let Some(title) = args.title {
+            item.title = title;
+        }
+        if let Some(member) = args.member {
+            item.member = Some(member).filter(|member| !member.is_empty());
+        }
+        TodoUpdateResult {
+            error: self.save(&items),
+        }
+    }
+
+    pub fn list(&self) -> TodoListResult {
+        TodoListResult {
+            items: self.items.lock().unwrap().clone(),
+        }
+    }
+
+    /// Returns the items that are not finished yet.
+    pub fn unfinished(&self) -> Vec<TodoItem> {
+        let items = self.items.lock().unwrap();
+        items
+            .iter()
+            .filter(|item| !item.status.is_finished())
+            .cloned()
+            .collect()
+    }
+
+    fn save(&self, items: &[TodoItem]) -> Option<String> {
+        serde_json::to_string_pretty(items)
+            .map_err(std::io::Error::from)
+            .and_then(|contents| write_atomic(&self.path, contents.as_bytes()))
+            .err()
+            .map(|e| format!("Cannot save the task list: {e}"))
+    }
+}
+
+impl fmt::Display for TodoList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for item in self.items.lock().unwrap().iter() {
+            writeln!(f, "{item}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TodoItem {
+    pub id: u32,
+    pub title: String,
+    pub status: TodoStatus,
+    /// the workspace member the task is about, if the destination is a workspace
+    #[serde(default)]
+    pub member: Option<String>,
+}
+
+impl fmt::Display for TodoItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mark = match self.status {
+            TodoStatus::Pending => " ",
+            TodoStatus::InProgress => "~",
+            TodoStatus::Done => "x",
+            TodoStatus::Cancelled => "-",
+        };
+        write!(f, "[{mark}] {}. ", self.id)?;
+        if let Some(member) = &self.member {
+            write!(f, "({member}) ")?;
+        }
+        write!(f, "{}", self.title)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoStatus {
+    Pending,
+    InProgress,
+    Done,
+    Cancelled,
+}
+
+impl TodoStatus {
+    fn is_finished(self) -> bool {
+        matches!(self, TodoStatus::Done | TodoStatus::Cancelled)
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct TodoAddArgs {
+    /// a short description of the task, e.g. "Port src/parser.c"
+    pub title: String,
+    /// the crate of the destination workspace the task is about, e.g. "parser"
+    pub member: Option<String>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct TodoUpdateArgs {
+    /// identifier of the task
+    pub id: u32,
+    /// new status of the task
+    pub status: Option<TodoStatus>,
+    /// new description of the task
+    pub title: Option<String>,
+    /// new workspace member of the task, an empty string removes it
+    pub member: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TodoAddResult {
+    pub id: Option<u32>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TodoUpdateResult {
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TodoListResult {
+    pub items: Vec<TodoItem>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn todo_progress() {
+        let dir = TempDir::new("todo");
+        let path = dir.join("todo.json");
+        let todo = TodoList::load(path.clone());
+        let first = todo.add("Port main.c", None).id.unwrap();
+        let second = todo.add("Port util.c", Some("util")).id.unwrap();
+        assert_eq!(todo.unfinished().len(), 2);
+
+        let done = |id| TodoUpdateArgs {
+            id,
+            status: Some(TodoStatus::Done),
+            title: None,
+            member: None,
+        };
+        assert!(todo.update(done(first)).error.is_none());
+        assert!(todo.update(done(42)).error.is_some());
+
+        let todo = TodoList::load(path.clone());
+        assert_eq!(
+            todo.to_string(),
+            "[x] 1. Port main.c\n[ ] 2. (util) Port util.c\n"
+        );
+        assert!(todo.update(done(second)).error.is_none());
+        assert!(todo.unfinished().is_empty());
+    }
+}
@@ -0,0 +1,139 @@
+use crate::text::{count_lines, is_binary};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+const DEFAULT_MAX_DEPTH: usize = 3;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct TreeArgs {
+    /// how many directory levels to expand, 3 by default; deeper directories are summarized
+    pub max_depth: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TreeResult {
+    /// indented listing of directories and files with their sizes and line counts
+    pub tree: String,
+}
+
+#[derive(Default)]
+struct Directory {
+    dirs: BTreeMap<String, Directory>,
+    files: BTreeMap<String, FileInfo>,
+}
+
+struct FileInfo {
+    size: u64,
+    /// `None` for binary files
+    lines: Option<usize>,
+}
+
+impl Directory {
+    fn file_count(&self) -> usize {
+        self.files.len() + self.dirs.values().map(Directory::file_count).sum::<usize>()
+    }
+
+    fn size(&self) -> u64 {
+        self.files.values().map(|f| f.size).sum::<u64>()
+            + self.dirs.values().map(Directory::size).sum::<u64>()
+    }
+
+    fn render(&self, output: &mut String, depth: usize, max_depth: usize) {
+        let indent = "  ".repeat(depth);
+        for (name, dir) in &self.dirs {
+            if depth + 1 >= max_depth {
+                writeln!(
+                    output,
+                    "{indent}{name}/ ({} files, {} bytes)",
+                    dir.file_count(),
+                    dir.size()
+                )
+                .unwrap();
+            } else {
+                writeln!(output, "{indent}{name}/").unwrap();
+                dir.render(output, depth + 1, max_depth);
+            }
+        }
+        for (name, file) in &self.files {
+            match file.lines {
+                Some(lines) => writeln!(
+                    output,
+                    "{indent}{name} ({} bytes, {lines} lines)",
+                    file.size
+                ),
+                None => writeln!(output, "{indent}{name} ({} bytes, binary)", file.size),
+            }
+            .unwrap();
+        }
+    }
+}
+
+/// Renders the files of a project as an indented tree.
+///
+/// `files` are paths relative to `root`.
+pub fn tree(root: &Path, files: &[String], args: &TreeArgs) -> TreeResult {
+    let mut top = Directory::default();
+    for file in files {
+        let mut components: Vec<&str> = Path::new(file)
+            .iter()
+            .filter_map(|component| component.to_str())
+            .collect();
+        let Some(name) = components.pop() else {
+            continue;
+        };
+        let dir = components.into_iter().fold(&mut top, |dir, component| {
+            dir.dirs.entry(component.to_string()).or_default()
+        });
+
+        let data = std::fs::read(root.join(file)).unwrap_or_default();
+        let lines = (!is_binary(&data)).then(|| count_lines(&data));
+        dir.files.insert(
+            name.to_string(),
+            FileInfo {
+                size: data.len() as u64,
+                lines,
+            },
+        );
+    }
+
+    let mut output = String::new();
+    top.render(
+        &mut output,
+        0,
+        args.max_depth.unwrap_or(DEFAULT_MAX_DEPTH).max(1),
+    );
+    TreeResult { tree: output }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn render_tree() {
+        let dir = TempDir::new("tree");
+        std::fs::create_dir_all(dir.join("src/util")).unwrap();
+        std::fs::write(dir.join("Makefile"), "all:\n\tcc main.c").unwrap();
+        std::fs::write(dir.join("src/main.c"), "int main;\n").unwrap();
+        std::fs::write(dir.join("src/util/a.c"), "\n\n").unwrap();
+        std::fs::write(dir.join("src/util/a.o"), b"\0\0\0").unwrap();
+        let files = ["Makefile", "src/main.c", "src/util/a.c", "src/util/a.o"].map(String::from);
+
+        let result = tree(&dir, &files, &TreeArgs { max_depth: None });
+        assert_eq!(
+            result.tree,
+            "src/\n  util/\n    a.c (2 bytes, 2 lines)\n    a.o (3 bytes, binary)\n  \
+            main.c (10 bytes, 1 lines)\nMakefile (15 bytes, 2 lines)\n"
+        );
+
+        let result = tree(&dir, &files, &TreeArgs { max_depth: Some(1) });
+        assert_eq!(
+            result.tree,
+            "src/ (3 files, 15 bytes)\nMakefile (15 bytes, 2 lines)\n"
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use notify::event::EventKind;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, channel};
+use std::time::Duration;
+
+/// Changes are collected until the files stay untouched for this long, so that e.g. a git
+/// checkout is handled at once.
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// Watches directories recursively for changed, added and removed files.
+pub struct SourceWatcher {
+    // Dropping the watcher stops the notifications.
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+}
+
+impl SourceWatcher {
+    pub fn new(roots: &[&Path]) -> Result<Self, String> {
+        let (sender, events) = channel();
+        let mut watcher =
+            notify::recommended_watcher(sender).map_err(|e| format!("Cannot watch files: {e}"))?;
+        for root in roots {
+            // Paths in the events start with the watched path, so it's canonical like the
+            // project paths.
+            let root = root
+                .canonicalize()
+                .map_err(|e| format!("Cannot watch {}: {e}", root.display()))?;
+            watcher
+                .watch(&root, RecursiveMode::Recursive)
+                .map_err(|e| format!("Cannot watch {}: {e}", root.display()))?;
+        }
+        Ok(SourceWatcher {
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    /// Blocks until some files change and returns their absolute paths.
+    pub fn wait_for_changes(&self) -> Result<BTreeSet<PathBuf>, String> {
+        let mut paths = BTreeSet::new();
+        let event = self
+            .events
+            .recv()
+            .map_err(|_| "The source watcher has stopped.".to_string())?;
+        add_paths(&mut paths, event);
+        loop {
+            match self.events.recv_timeout(DEBOUNCE) {
+                Ok(event) => add_paths(&mut paths, event),
+                Err(RecvTimeoutError::Timeout) if paths.is_empty() => {}
+                Err(RecvTimeoutError::Timeout) => return Ok(paths),
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err("The source watcher has stopped.".to_string());
+                }
+            }
+        }
+    }
+}
+
+fn add_paths(paths: &mut BTreeSet<PathBuf>, event: notify::Result<Event>) {
+    let Ok(event) = event else {
+        return;
+    };
+    if matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+    ) {
+        paths.extend(event.paths.into_iter().filter(|path| !path.is_dir()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn watch_changes() {
+        let dir = TempDir::new("watch");
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        let watcher = SourceWatcher::new(&[&dir]).unwrap();
+
+        std::fs::write(dir.join("src/main.c"), "int main;\n").unwrap();
+        let paths = watcher.wait_for_changes().unwrap();
+        assert!(paths.contains(&dir.canonicalize().unwrap().join("src/main.c")));
+    }
+}
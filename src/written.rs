@@ -0,0 +1,75 @@
+use crate::hash::stable_hash;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Hashes of the destination files as riir last wrote them, persisted in the session directory.
+///
+/// A file whose contents no longer match was changed by someone else, usually fixed by hand.
+pub struct WrittenFiles {
+    path: PathBuf,
+    hashes: Mutex<BTreeMap<PathBuf, u64>>,
+}
+
+impl WrittenFiles {
+    /// Loads the hashes from the file, starting with no files if it doesn't exist.
+    pub fn load(path: PathBuf) -> Self {
+        let hashes = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        WrittenFiles {
+            path,
+            hashes: Mutex::new(hashes),
+        }
+    }
+
+    /// Remembers the current contents of a file riir has just changed. The file is forgotten if
+    /// it doesn't exist anymore.
+    pub fn record(&self, relpath: &Path, path: &Path) {
+        let mut hashes = self.hashes.lock().unwrap();
+        match std::fs::read(path) {
+            Ok(contents) => hashes.insert(relpath.to_path_buf(), stable_hash(&contents)),
+            Err(_) => hashes.remove(relpath),
+        };
+        // Losing the hashes only disables the protection, so errors are ignored.
+        if let Ok(contents) = serde_json::to_string_pretty(&*hashes) {
+            let _ = std::fs::write(&self.path, contents);
+        }
+    }
+
+    /// Checks whether a file riir wrote was changed or deleted since then.
+    pub fn is_changed(&self, relpath: &Path, path: &Path) -> bool {
+        let hashes = self.hashes.lock().unwrap();
+        let Some(&written) = hashes.get(relpath) else {
+            return false;
+        };
+        std::fs::read(path).map_or(true, |contents| stable_hash(&contents) != written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn track_changes() {
+        let dir = TempDir::new("written");
+        let file = dir.join("lib.rs");
+        std::fs::write(&file, "fn a() {}\n").unwrap();
+
+        let written = WrittenFiles::load(dir.join("written.json"));
+        assert!(!written.is_changed(Path::new("lib.rs"), &file));
+        written.record(Path::new("lib.rs"), &file);
+        assert!(!written.is_changed(Path::new("lib.rs"), &file));
+
+        std::fs::write(&file, "fn b() {}\n").unwrap();
+        let written = WrittenFiles::load(dir.join("written.json"));
+        assert!(written.is_changed(Path::new("lib.rs"), &file));
+        std::fs::remove_file(&file).unwrap();
+        assert!(written.is_changed(Path::new("lib.rs"), &file));
+        written.record(Path::new("lib.rs"), &file);
+        assert!(!written.is_changed(Path::new("lib.rs"), &file));
+    }
+}